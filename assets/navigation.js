@@ -0,0 +1,92 @@
+// Keyboard and d-pad navigation of the overlays, for switch access, TV box remotes and external keyboards.
+// The arrow keys move the focus between the controls, Enter activates them, and the focus changes made that way
+// are announced to the screen readers through a live region.
+(() => {
+  const FOCUSABLE =
+    "a[href], button, input, select, textarea, [tabindex]:not([tabindex='-1'])";
+  // The controls that need the arrow keys themselves, to move the caret, pick an option or browse the shell history
+  const KEEPS_ARROWS = "input, select, textarea, .xterm";
+
+  const style = document.createElement("style");
+  style.textContent = `
+    :focus-visible {
+      outline: 3px solid #4da3ff;
+      outline-offset: 2px;
+    }
+  `;
+  document.head.appendChild(style);
+
+  const announcer = document.createElement("div");
+  announcer.setAttribute("role", "status");
+  announcer.setAttribute("aria-live", "polite");
+  announcer.style.cssText =
+    "position: absolute; width: 1px; height: 1px; overflow: hidden; clip: rect(0 0 0 0)";
+  document.addEventListener("DOMContentLoaded", () =>
+    document.body.appendChild(announcer)
+  );
+
+  const focusables = () =>
+    [...document.querySelectorAll(FOCUSABLE)].filter(
+      (element) => !element.disabled && element.getClientRects().length > 0
+    );
+
+  const describe = (element) => {
+    const label =
+      element.getAttribute("aria-label") ||
+      (element.labels && element.labels[0] && element.labels[0].textContent) ||
+      element.textContent ||
+      element.placeholder ||
+      element.title ||
+      element.tagName.toLowerCase();
+    const text = label.replace(/\s+/g, " ").trim();
+    if (element.type === "checkbox") {
+      return `${text}, ${element.checked ? "checked" : "not checked"}`;
+    }
+    return text;
+  };
+
+  // Single line inputs only keep Left and Right, so that Up and Down still leave them
+  const keepsArrow = (element, key) => {
+    if (!element || !element.closest(KEEPS_ARROWS)) {
+      return false;
+    }
+    if (element.type === "checkbox") {
+      return false;
+    }
+    const vertical = key === "ArrowUp" || key === "ArrowDown";
+    return !(vertical && element.tagName === "INPUT" && element.type !== "number");
+  };
+
+  document.addEventListener("keydown", (event) => {
+    if (event.altKey || event.ctrlKey || event.metaKey || event.defaultPrevented) {
+      return;
+    }
+    const active = document.activeElement;
+    // The center button of a remote is Enter, which does not toggle a checkbox on its own
+    if (event.key === "Enter" && active && active.type === "checkbox") {
+      event.preventDefault();
+      active.click();
+      announcer.textContent = describe(active);
+      return;
+    }
+    const step = { ArrowDown: 1, ArrowRight: 1, ArrowUp: -1, ArrowLeft: -1 }[
+      event.key
+    ];
+    if (!step || keepsArrow(active, event.key)) {
+      return;
+    }
+    const all = focusables();
+    if (all.length === 0) {
+      return;
+    }
+    const index = all.indexOf(active);
+    const next =
+      index === -1
+        ? all[step > 0 ? 0 : all.length - 1]
+        : all[(index + step + all.length) % all.length];
+    event.preventDefault();
+    next.focus();
+    next.scrollIntoView({ block: "nearest" });
+    announcer.textContent = describe(next);
+  });
+})();
@@ -0,0 +1,54 @@
+package app.polarbear
+
+import android.app.Notification
+import android.app.NotificationChannel
+import android.app.NotificationManager
+import android.app.PendingIntent
+import android.app.Service
+import android.content.Intent
+import android.os.Build
+import android.os.IBinder
+
+/**
+ * Keeps the process in the foreground while no activity shows it, e.g. once the app is swiped away from the recent
+ * apps, so that the compositor and the sessions it runs are not killed along with the activity. They live on the
+ * native side, where the next activity of the process attaches to them. Started and stopped over JNI, see
+ * `src/android/utils/session_service.rs`.
+ */
+class SessionService : Service() {
+    override fun onBind(intent: Intent?): IBinder? = null
+
+    override fun onStartCommand(intent: Intent?, flags: Int, startId: Int): Int {
+        startForeground(NOTIFICATION_ID, notification())
+        // The sessions are gone along with the process, there is nothing to start the service again for
+        return START_NOT_STICKY
+    }
+
+    private fun notification(): Notification {
+        val builder = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.O) {
+            val channel = NotificationChannel(CHANNEL_ID, CHANNEL_NAME, NotificationManager.IMPORTANCE_LOW)
+            getSystemService(NotificationManager::class.java).createNotificationChannel(channel)
+            Notification.Builder(this, CHANNEL_ID)
+        } else {
+            @Suppress("DEPRECATION")
+            Notification.Builder(this)
+        }
+        // Tapping the notification brings the desktop back
+        val launch = packageManager.getLaunchIntentForPackage(packageName)
+        val open = PendingIntent.getActivity(this, 0, launch, PendingIntent.FLAG_IMMUTABLE)
+        return builder
+            .setSmallIcon(applicationInfo.icon)
+            .setContentTitle("Local Desktop is running")
+            .setContentText("The Linux session keeps running in the background")
+            .setContentIntent(open)
+            .setOngoing(true)
+            .build()
+    }
+
+    companion object {
+        /** The notifications of the sessions are tagged, so their ids never collide with this one */
+        private const val NOTIFICATION_ID = 1
+        private const val CHANNEL_ID = "session-service"
+        private const val CHANNEL_NAME = "Running session"
+    }
+}
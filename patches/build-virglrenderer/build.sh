@@ -0,0 +1,13 @@
+#!/bin/bash
+
+# Step 1: Build the Docker image
+docker build -t polar-bear-virglrenderer --platform "linux/amd64" .
+
+# Step 2: Run the Docker container with the inputs as mounted volumes
+docker run --platform=linux/amd64 --rm polar-bear-virglrenderer tar -cf - -C /output . > output.tar
+
+# Step 3: Extract output
+mkdir -p ./output && tar -xf output.tar -C ./output && rm output.tar
+
+# Step 4: Ship the renderer along with the app, named as a library for Android to extract it next to proot
+cp ./output/bin/virgl_test_server ../../assets/libs/arm64-v8a/libvirgl_test_server.so
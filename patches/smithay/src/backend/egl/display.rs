@@ -723,6 +723,16 @@ impl EGLDisplay {
     #[instrument(level = "trace", skip(self), parent = &self.span, err)]
     #[profiling::function]
     pub fn create_image_from_dmabuf(&self, dmabuf: &Dmabuf) -> Result<EGLImage, Error> {
+        #[cfg(target_os = "android")]
+        if !self
+            .extensions
+            .iter()
+            .any(|s| s == "EGL_EXT_image_dma_buf_import")
+            && super::hardware_buffer::is_supported(&self.extensions)
+        {
+            return super::hardware_buffer::create_image_from_dmabuf(**self.display, dmabuf);
+        }
+
         if !self.extensions.iter().any(|s| s == "EGL_KHR_image_base")
             && !self
                 .extensions
@@ -870,6 +880,11 @@ fn get_dmabuf_formats(
     extensions: &[String],
 ) -> Result<(FormatSet, FormatSet), EGLError> {
     if !extensions.iter().any(|s| s == "EGL_EXT_image_dma_buf_import") {
+        #[cfg(target_os = "android")]
+        if super::hardware_buffer::is_supported(extensions) {
+            info!("Importing dmabufs through AHardwareBuffers");
+            return Ok((super::hardware_buffer::texture_formats(), FormatSet::default()));
+        }
         warn!("Dmabuf import extension not available");
         return Ok((FormatSet::default(), FormatSet::default()));
     }
@@ -0,0 +1,199 @@
+//! Importing dmabufs on Android, whose EGL drivers take `AHardwareBuffer`s rather than dmabufs
+//!
+//! The planes of a dmabuf are wrapped into an `AHardwareBuffer` by gralloc, which the EGL image is then
+//! created from through `EGL_ANDROID_image_native_buffer`, without copying the pixels. Only single plane
+//! RGB formats without modifiers are supported, and the gralloc of the device may still refuse a buffer
+//! it did not allocate, in which case the import fails as it would without the extension.
+
+use std::{ffi::c_void, os::unix::io::AsRawFd, sync::LazyLock};
+
+use libloading::Library;
+use tracing::{debug, warn};
+
+use super::{ffi, Error};
+use crate::backend::allocator::{
+    dmabuf::Dmabuf, format::FormatSet, Buffer, Format as DrmFormat, Fourcc, Modifier,
+};
+
+/// `EGL_NATIVE_BUFFER_ANDROID`, the target of `eglCreateImageKHR` for an `EGLClientBuffer` of an `AHardwareBuffer`
+const NATIVE_BUFFER_ANDROID: ffi::egl::types::EGLenum = 0x3140;
+
+/// `AHARDWAREBUFFER_USAGE_GPU_SAMPLED_IMAGE`
+const USAGE_GPU_SAMPLED_IMAGE: u64 = 1 << 8;
+
+/// `sizeof(native_handle_t)`, which its version is
+const NATIVE_HANDLE_VERSION: i32 = 12;
+
+/// `AHARDWAREBUFFER_CREATE_FROM_HANDLE_METHOD_CLONE`: the buffer gets its own copies of the file descriptors
+const CREATE_FROM_HANDLE_METHOD_CLONE: i32 = 1;
+
+/// The formats of gralloc for the formats of DRM, along with their size in bytes per pixel.
+/// `HAL_PIXEL_FORMAT_BGRA_8888` (5) is not part of the public `AHardwareBuffer` formats, but every gralloc knows it.
+const FORMATS: [(Fourcc, u32, u32); 5] = [
+    (Fourcc::Abgr8888, 1, 4), // R8G8B8A8_UNORM
+    (Fourcc::Xbgr8888, 2, 4), // R8G8B8X8_UNORM
+    (Fourcc::Argb8888, 5, 4), // BGRA_8888
+    (Fourcc::Xrgb8888, 5, 4), // BGRA_8888, whose alpha is ignored as the format has none
+    (Fourcc::Rgb565, 4, 2),   // R5G6B5_UNORM
+];
+
+/// `AHardwareBuffer_Desc`
+#[repr(C)]
+struct HardwareBufferDesc {
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: u32,
+    usage: u64,
+    /// In pixels
+    stride: u32,
+    rfu0: u32,
+    rfu1: u64,
+}
+
+/// `native_handle_t` with room for a single file descriptor
+#[repr(C)]
+struct NativeHandle {
+    version: i32,
+    num_fds: i32,
+    num_ints: i32,
+    data: [i32; 1],
+}
+
+type CreateFromHandle =
+    unsafe extern "C" fn(*const HardwareBufferDesc, *const NativeHandle, i32, *mut *mut c_void) -> i32;
+type Release = unsafe extern "C" fn(*mut c_void);
+type GetNativeClientBuffer = unsafe extern "C" fn(*const c_void) -> ffi::egl::types::EGLClientBuffer;
+
+struct HardwareBufferApi {
+    /// Keeps the symbols below loaded
+    _lib: Library,
+    /// Part of the LL-NDK since Android 10, so it is looked up rather than linked
+    create_from_handle: CreateFromHandle,
+    release: Release,
+}
+
+static API: LazyLock<Option<HardwareBufferApi>> = LazyLock::new(|| unsafe {
+    let lib = match Library::new("libnativewindow.so") {
+        Ok(lib) => lib,
+        Err(err) => {
+            warn!(?err, "Failed to load libnativewindow.so");
+            return None;
+        }
+    };
+    let create_from_handle = *lib
+        .get::<CreateFromHandle>(b"AHardwareBuffer_createFromHandle\0")
+        .ok()?;
+    let release = *lib.get::<Release>(b"AHardwareBuffer_release\0").ok()?;
+    Some(HardwareBufferApi {
+        _lib: lib,
+        create_from_handle,
+        release,
+    })
+});
+
+/// Whether dmabufs can be imported through `AHardwareBuffer`s on that display
+pub(super) fn is_supported(extensions: &[String]) -> bool {
+    [
+        "EGL_KHR_image_base",
+        "EGL_ANDROID_image_native_buffer",
+        "EGL_ANDROID_get_native_client_buffer",
+    ]
+    .iter()
+    .all(|required| extensions.iter().any(|s| s == required))
+        && API.is_some()
+}
+
+/// The formats that may be imported, which are sampled as external textures
+pub(super) fn texture_formats() -> FormatSet {
+    FormatSet::from_formats(
+        FORMATS
+            .iter()
+            .flat_map(|(code, ..)| {
+                [Modifier::Linear, Modifier::Invalid].map(|modifier| DrmFormat {
+                    code: *code,
+                    modifier,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Create an EGL image of a dmabuf, wrapped into an `AHardwareBuffer`
+pub(super) fn create_image_from_dmabuf(
+    display: ffi::egl::types::EGLDisplay,
+    dmabuf: &Dmabuf,
+) -> Result<ffi::egl::types::EGLImageKHR, Error> {
+    let api = API.as_ref().ok_or(Error::EGLImageCreationFailed)?;
+    let format = dmabuf.format();
+    let Some((_, hardware_format, bytes_per_pixel)) = FORMATS.iter().find(|(code, ..)| *code == format.code)
+    else {
+        debug!(?format, "Unsupported format for an AHardwareBuffer");
+        return Err(Error::EGLImageCreationFailed);
+    };
+    let (Some(fd), Some(stride), 1) = (
+        dmabuf.handles().next(),
+        dmabuf.strides().next(),
+        dmabuf.num_planes(),
+    ) else {
+        return Err(Error::EGLImageCreationFailed);
+    };
+    if !matches!(format.modifier, Modifier::Linear | Modifier::Invalid) || dmabuf.offsets().next() != Some(0)
+    {
+        return Err(Error::EGLImageCreationFailed);
+    }
+
+    let desc = HardwareBufferDesc {
+        width: dmabuf.width(),
+        height: dmabuf.height(),
+        layers: 1,
+        format: *hardware_format,
+        usage: USAGE_GPU_SAMPLED_IMAGE,
+        stride: stride / bytes_per_pixel,
+        rfu0: 0,
+        rfu1: 0,
+    };
+    let handle = NativeHandle {
+        version: NATIVE_HANDLE_VERSION,
+        num_fds: 1,
+        num_ints: 0,
+        data: [fd.as_raw_fd()],
+    };
+    let mut buffer = std::ptr::null_mut();
+    let status =
+        unsafe { (api.create_from_handle)(&desc, &handle, CREATE_FROM_HANDLE_METHOD_CLONE, &mut buffer) };
+    if status != 0 || buffer.is_null() {
+        debug!(status, "Gralloc refused the dmabuf");
+        return Err(Error::EGLImageCreationFailed);
+    }
+
+    let image = unsafe {
+        let get_native_client_buffer = super::get_proc_address("eglGetNativeClientBufferANDROID");
+        let image = if get_native_client_buffer.is_null() {
+            ffi::egl::NO_IMAGE_KHR
+        } else {
+            let get_native_client_buffer: GetNativeClientBuffer =
+                std::mem::transmute(get_native_client_buffer);
+            let attributes = [
+                ffi::egl::IMAGE_PRESERVED_KHR as i32,
+                ffi::egl::TRUE as i32,
+                ffi::egl::NONE as i32,
+            ];
+            ffi::egl::CreateImageKHR(
+                display,
+                ffi::egl::NO_CONTEXT,
+                NATIVE_BUFFER_ANDROID,
+                get_native_client_buffer(buffer),
+                attributes.as_ptr(),
+            )
+        };
+        // The image holds a reference of its own
+        (api.release)(buffer);
+        image
+    };
+    if image == ffi::egl::NO_IMAGE_KHR {
+        Err(Error::EGLImageCreationFailed)
+    } else {
+        Ok(image)
+    }
+}
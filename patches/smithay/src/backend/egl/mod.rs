@@ -33,6 +33,8 @@ use std::fmt;
 pub mod context;
 pub use self::context::EGLContext;
 mod device;
+#[cfg(target_os = "android")]
+mod hardware_buffer;
 mod error;
 pub use self::error::*;
 use crate::backend::SwapBuffersError as GraphicsSwapBuffersError;
@@ -179,6 +179,40 @@ pub struct WaylandSurfaceRenderElement<R: Renderer> {
     texture: WaylandSurfaceTexture<R>,
 }
 
+impl<R: Renderer> Clone for WaylandSurfaceTexture<R>
+where
+    R::TextureId: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Texture(texture) => Self::Texture(texture.clone()),
+            Self::SolidColor(color) => Self::SolidColor(*color),
+        }
+    }
+}
+
+impl<R: Renderer> Clone for WaylandSurfaceRenderElement<R>
+where
+    R::TextureId: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            location: self.location,
+            alpha: self.alpha,
+            kind: self.kind,
+            view: self.view,
+            buffer: self.buffer.clone(),
+            buffer_scale: self.buffer_scale,
+            buffer_transform: self.buffer_transform,
+            buffer_dimensions: self.buffer_dimensions,
+            damage: self.damage.clone(),
+            opaque_regions: self.opaque_regions.clone(),
+            texture: self.texture.clone(),
+        }
+    }
+}
+
 impl<R: Renderer> fmt::Debug for WaylandSurfaceRenderElement<R> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("WaylandSurfaceRenderElement")
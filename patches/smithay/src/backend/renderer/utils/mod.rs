@@ -414,6 +414,15 @@ pub struct OpaqueRegions<N, Kind> {
     regions: smallvec::SmallVec<[Rectangle<N, Kind>; MAX_OPAQUE_REGIONS]>,
 }
 
+impl<N: Clone, Kind> Clone for OpaqueRegions<N, Kind> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            regions: self.regions.clone(),
+        }
+    }
+}
+
 impl<N, Kind> Default for OpaqueRegions<N, Kind>
 where
     N: Default,
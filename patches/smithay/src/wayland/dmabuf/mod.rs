@@ -770,6 +770,11 @@ impl DmabufState {
         *current_feedback = default_feedback.clone();
     }
 
+    /// Get the id of the ZwpLinuxDmabufV1 global of a dmabuf global
+    pub fn global_id(&self, global: &DmabufGlobal) -> Option<GlobalId> {
+        self.globals.get(&global.id).map(|it| it.id.clone())
+    }
+
     /// Disables a dmabuf global.
     ///
     /// This operation is permanent and there is no way to re-enable a global.
@@ -192,7 +192,8 @@ impl XWayland {
         let wrapper = unsafe { calloop::generic::FdWrapper::new(displayfd_recv.as_raw_fd()) };
         let source = calloop::generic::Generic::new(wrapper, calloop::Interest::READ, calloop::Mode::Level);
         let inner = Instance {
-            display_lock: lock,
+            display_number,
+            _display_lock: Some(lock),
             display_fd: displayfd_recv,
             x11_socket: Some(x_wm_me),
         };
@@ -226,10 +227,102 @@ impl XWayland {
         ))
     }
 
+    /// Spawns an XWayland server instance through a command built by the caller, for
+    /// `Xwayland` binaries that can not be executed directly (e.g. living in a container).
+    ///
+    /// Unlike [`spawn`](Self::spawn), no X11 socket is prepared: the server is expected to
+    /// create (and lock) the socket of the given display number on its own.
+    ///
+    /// ## Arguments
+    ///
+    /// - `display_number` - the display number the XWayland server is started on.
+    /// - `command` - builds the command to run, given the arguments that must be passed through
+    ///   to `Xwayland` and the file descriptor to export as `WAYLAND_SOCKET`. The file descriptors
+    ///   are inherited by the spawned process.
+    /// - `user_data` - see [`spawn`](Self::spawn).
+    pub fn spawn_with_command<C, F>(
+        dh: &DisplayHandle,
+        display_number: u32,
+        command: C,
+        user_data: F,
+    ) -> std::io::Result<(Self, Client)>
+    where
+        C: FnOnce(Vec<String>, RawFd) -> Command,
+        F: FnOnce(&UserDataMap),
+    {
+        let (x_wm_x11, x_wm_me) = UnixStream::pair()?;
+        let (wl_x11, wl_me) = UnixStream::pair()?;
+
+        let (displayfd_recv, displayfd_send) =
+            rustix::pipe::pipe_with(rustix::pipe::PipeFlags::NONBLOCK | rustix::pipe::PipeFlags::CLOEXEC)?;
+
+        let args = vec![
+            format!(":{}", display_number),
+            "-rootless".to_string(),
+            "-wm".to_string(),
+            x_wm_x11.as_raw_fd().to_string(),
+            "-displayfd".to_string(),
+            displayfd_send.as_raw_fd().to_string(),
+        ];
+        let mut command = command(args, wl_x11.as_raw_fd());
+
+        unsafe {
+            let wayland_socket_fd = wl_x11.as_raw_fd();
+            let wm_socket_fd = x_wm_x11.as_raw_fd();
+            let pipe_fd = displayfd_send.as_raw_fd();
+
+            command.pre_exec(move || {
+                unset_cloexec(wayland_socket_fd)?;
+                unset_cloexec(wm_socket_fd)?;
+                unset_cloexec(pipe_fd)?;
+                Ok(())
+            });
+        }
+
+        info!(display_number, "spawning XWayland instance through a custom command");
+
+        let child = command.spawn()?;
+
+        // SAFETY: RawFd's AsRawFd impl is infallible.
+        let wrapper = unsafe { calloop::generic::FdWrapper::new(displayfd_recv.as_raw_fd()) };
+        let source = calloop::generic::Generic::new(wrapper, calloop::Interest::READ, calloop::Mode::Level);
+        let inner = Instance {
+            _display_lock: None,
+            display_number,
+            display_fd: displayfd_recv,
+            x11_socket: Some(x_wm_me),
+        };
+
+        let data_map = UserDataMap::new();
+        user_data(&data_map);
+
+        let inner = Arc::new(Mutex::new(inner));
+        let mut dh = dh.clone();
+        let client = dh.insert_client(
+            wl_me,
+            Arc::new(XWaylandClientData {
+                #[cfg(feature = "wayland_frontend")]
+                compositor_state: CompositorClientState::default(),
+                data_map,
+                child: Mutex::new(Some(child)),
+            }),
+        )?;
+
+        Ok((
+            Self {
+                inner,
+                source,
+                dh,
+                client: client.clone(),
+            },
+            client,
+        ))
+    }
+
     /// Returns the X11 display used by the instance, suitable for setting the
     /// `DISPLAY` environment variable.
     pub fn display_number(&self) -> u32 {
-        self.inner.lock().unwrap().display_lock.display_number()
+        self.inner.lock().unwrap().display_number
     }
 
     /// Returns a file descriptor which can be polled for readiness. When the fd
@@ -261,7 +354,9 @@ impl XWayland {
 
 #[derive(Debug)]
 struct Instance {
-    display_lock: X11Lock,
+    /// Only held when the X11 sockets were prepared by smithay
+    _display_lock: Option<X11Lock>,
+    display_number: u32,
     x11_socket: Option<UnixStream>,
     display_fd: OwnedFd,
 }
@@ -297,7 +392,7 @@ impl calloop::EventSource for XWayland {
             callback(
                 XWaylandEvent::Ready {
                     x11_socket,
-                    display_number: guard.display_lock.display_number(),
+                    display_number: guard.display_number,
                 },
                 &mut (),
             );
@@ -91,6 +91,52 @@ impl<T> EventLoopExtAndroid for EventLoop<T> {
 pub trait ActiveEventLoopExtAndroid {
     /// Get the [`AndroidApp`] which was used to create this event loop.
     fn android_app(&self) -> &AndroidApp;
+
+    /// The time the Android input event currently being dispatched happened, on the
+    /// `CLOCK_MONOTONIC` timeline (same as `SystemClock.uptimeMillis()`).
+    ///
+    /// Returns `None` outside of input event dispatching.
+    fn input_event_time(&self) -> Option<std::time::Duration>;
+
+    /// The stylus of the Android motion event currently being dispatched, if it came from a stylus
+    /// or an eraser rather than a finger or a mouse.
+    ///
+    /// Returns `None` outside of input event dispatching.
+    fn stylus(&self) -> Option<Stylus>;
+
+    /// The state the application saved with [`Self::set_saved_state`] before Android destroyed
+    /// the activity, e.g. when it killed the process in the background, if it was recreated since.
+    fn saved_state(&self) -> Option<Vec<u8>>;
+
+    /// Replace the state handed to Android whenever it asks the activity to save its state, to be
+    /// restored with [`Self::saved_state`] once the activity is recreated.
+    fn set_saved_state(&self, state: Vec<u8>);
+}
+
+/// The axes of a stylus, as Android reports them along with the cursor events.
+///
+/// A stylus moves the cursor with [`WindowEvent::CursorMoved`], presses its tip or its buttons
+/// with [`WindowEvent::MouseInput`], and comes within or out of hover range with
+/// [`WindowEvent::CursorEntered`] and [`WindowEvent::CursorLeft`].
+///
+/// [`WindowEvent::CursorMoved`]: crate::event::WindowEvent::CursorMoved
+/// [`WindowEvent::MouseInput`]: crate::event::WindowEvent::MouseInput
+/// [`WindowEvent::CursorEntered`]: crate::event::WindowEvent::CursorEntered
+/// [`WindowEvent::CursorLeft`]: crate::event::WindowEvent::CursorLeft
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stylus {
+    /// The eraser end of the stylus is used
+    pub eraser: bool,
+    /// Normalized, from 0 to 1, although some devices go beyond 1
+    pub pressure: f32,
+    /// The angle (in radians) between the stylus and the normal of the screen, 0 when upright
+    pub tilt: f32,
+    /// The direction (in radians) the stylus leans towards, clockwise from up, from -π to π
+    pub orientation: f32,
+    /// The [`WindowEvent::MouseInput`] is about a button of the barrel, rather than the tip
+    ///
+    /// [`WindowEvent::MouseInput`]: crate::event::WindowEvent::MouseInput
+    pub barrel_button: bool,
 }
 
 /// Additional methods on [`Window`] that are specific to Android.
@@ -114,6 +160,25 @@ impl ActiveEventLoopExtAndroid for ActiveEventLoop {
     fn android_app(&self) -> &AndroidApp {
         &self.p.app
     }
+
+    fn input_event_time(&self) -> Option<std::time::Duration> {
+        self.p
+            .input_event_time
+            .get()
+            .map(|nanos| std::time::Duration::from_nanos(nanos.max(0) as u64))
+    }
+
+    fn stylus(&self) -> Option<Stylus> {
+        self.p.stylus.get()
+    }
+
+    fn saved_state(&self) -> Option<Vec<u8>> {
+        self.p.loaded_state.borrow().clone()
+    }
+
+    fn set_saved_state(&self, state: Vec<u8>) {
+        self.p.saved_state.replace(state);
+    }
 }
 
 /// Additional methods on [`WindowAttributes`] that are specific to Android.
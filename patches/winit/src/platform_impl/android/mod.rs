@@ -1,4 +1,4 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::hash::Hash;
 use std::marker::PhantomData;
@@ -12,6 +12,7 @@ use android_activity::input::{
 use android_activity::{
     AndroidApp, AndroidAppWaker, ConfigurationRef, InputStatus, MainEvent, Rect,
 };
+use jni::objects::JObject;
 use tracing::{debug, trace, warn};
 
 use crate::cursor::Cursor;
@@ -20,6 +21,7 @@ use crate::error;
 use crate::error::EventLoopError;
 use crate::event::{self, Event, Force, InnerSizeWriter, MouseButton, StartCause, WindowEvent};
 use crate::event_loop::{self, ActiveEventLoop as RootAEL, ControlFlow, DeviceEvents};
+use crate::platform::android::Stylus;
 use crate::platform::pump_events::PumpStatus;
 use crate::platform_impl::Fullscreen;
 use crate::window::{
@@ -36,6 +38,31 @@ pub(crate) use crate::icon::NoIcon as PlatformIcon;
 
 static HAS_FOCUS: AtomicBool = AtomicBool::new(true);
 
+/// `InputDevice.SOURCE_CLASS_JOYSTICK`
+const SOURCE_CLASS_JOYSTICK: u32 = 0x10;
+
+/// The axes of a gamepad reported as [`event::DeviceEvent::Motion`], by their Android id. Some
+/// gamepads report their triggers as the brake and the gas instead.
+fn joystick_axes(pointer: &input::Pointer<'_>) -> [(u32, f64); 8] {
+    let value = |axis| pointer.axis_value(axis) as f64;
+    [
+        (input::Axis::X.into(), value(input::Axis::X)),
+        (input::Axis::Y.into(), value(input::Axis::Y)),
+        (input::Axis::Z.into(), value(input::Axis::Z)),
+        (input::Axis::Rz.into(), value(input::Axis::Rz)),
+        (input::Axis::HatX.into(), value(input::Axis::HatX)),
+        (input::Axis::HatY.into(), value(input::Axis::HatY)),
+        (
+            input::Axis::Ltrigger.into(),
+            value(input::Axis::Ltrigger).max(value(input::Axis::Brake)),
+        ),
+        (
+            input::Axis::Rtrigger.into(),
+            value(input::Axis::Rtrigger).max(value(input::Axis::Gas)),
+        ),
+    ]
+}
+
 /// Returns the minimum `Option<Duration>`, taking into account that `None`
 /// equates to an infinite timeout, not a zero timeout (so can't just use
 /// `Option::min`)
@@ -176,6 +203,10 @@ impl<T: 'static> EventLoop<T> {
             window_target: event_loop::ActiveEventLoop {
                 p: ActiveEventLoop {
                     app: android_app.clone(),
+                    input_event_time: Cell::new(None),
+                    stylus: Cell::new(None),
+                    loaded_state: RefCell::new(None),
+                    saved_state: RefCell::new(Vec::new()),
                     control_flow: Cell::new(ControlFlow::default()),
                     exit: Cell::new(false),
                     redraw_requester: RedrawRequester::new(
@@ -271,14 +302,13 @@ impl<T: 'static> EventLoop<T> {
                     // XXX: how to forward this state to applications?
                     warn!("TODO: forward onStart notification to application");
                 },
-                MainEvent::Resume { .. } => {
+                MainEvent::Resume { loader, .. } => {
                     debug!("App Resumed - is running");
                     self.running = true;
+                    self.window_target.p.loaded_state.replace(loader.load());
                 },
-                MainEvent::SaveState { .. } => {
-                    // XXX: how to forward this state to applications?
-                    // XXX: also how do we expose state restoration to apps?
-                    warn!("TODO: forward saveState notification to application");
+                MainEvent::SaveState { saver, .. } => {
+                    saver.store(&self.window_target.p.saved_state.borrow());
                 },
                 MainEvent::Pause => {
                     debug!("App Paused - stopped running");
@@ -289,9 +319,10 @@ impl<T: 'static> EventLoop<T> {
                     warn!("TODO: forward onStop notification to application");
                 },
                 MainEvent::Destroy => {
-                    // XXX: maybe exit mainloop to drop things before being
-                    // killed by the OS?
-                    warn!("TODO: forward onDestroy notification to application");
+                    // The activity waits for `android_main` to return before it is gone, and the
+                    // process may go on with another activity, which builds an event loop again
+                    debug!("App Destroyed - exiting the loop");
+                    self.window_target.p.exit();
                 },
                 MainEvent::InsetsChanged { .. } => {
                     // XXX: how to forward this state to applications?
@@ -374,6 +405,12 @@ impl<T: 'static> EventLoop<T> {
         F: FnMut(Event<T>, &RootAEL),
     {
         let mut input_status = InputStatus::Handled;
+        let event_time = match event {
+            InputEvent::MotionEvent(motion_event) => Some(motion_event.event_time()),
+            InputEvent::KeyEvent(key) => Some(key.event_time()),
+            _ => None,
+        };
+        self.window_target.p.input_event_time.set(event_time);
         match event {
             InputEvent::MotionEvent(motion_event) => {
                 // Get the tool type of the primary pointer
@@ -385,16 +422,78 @@ impl<T: 'static> EventLoop<T> {
                 // So we also check for `source()`, as it correctly reports `Mouse` (although other devices such as Desktop AVDs report `Unknown``)
                 let source = motion_event.source();
 
-                if tool_type != ToolType::Finger
+                if u32::from(source) & SOURCE_CLASS_JOYSTICK != 0 {
+                    // The sticks, the triggers and the hat of a gamepad are raw axes, rather
+                    // than a cursor
+                    let device_id = event::DeviceId(DeviceId(motion_event.device_id()));
+                    for (axis, value) in joystick_axes(&pointer) {
+                        callback(
+                            Event::DeviceEvent {
+                                device_id,
+                                event: event::DeviceEvent::Motion { axis, value },
+                            },
+                            self.window_target(),
+                        );
+                    }
+                } else if tool_type != ToolType::Finger
                     || source == Source::Mouse
+                    || source == Source::MouseRelative
                     || source == Source::Touchpad
                 {
                     let window_id = window::WindowId(WindowId);
                     let device_id = event::DeviceId(DeviceId(motion_event.device_id()));
                     let button = motion_event.action_button();
 
+                    let stylus = matches!(tool_type, ToolType::Stylus | ToolType::Eraser).then(|| {
+                        Stylus {
+                            eraser: tool_type == ToolType::Eraser,
+                            pressure: pointer.pressure(),
+                            tilt: pointer.axis_value(input::Axis::Tilt),
+                            orientation: pointer.orientation(),
+                            barrel_button: matches!(
+                                action,
+                                MotionAction::ButtonPress | MotionAction::ButtonRelease
+                            ),
+                        }
+                    });
+                    self.window_target.p.stylus.set(stylus);
+
                     // Mouse move (hover or drag)
                     match action {
+                        // A stylus comes within or out of hover range, e.g. before touching the
+                        // screen and after leaving it
+                        MotionAction::HoverEnter if stylus.is_some() => {
+                            callback(
+                                Event::WindowEvent {
+                                    window_id,
+                                    event: WindowEvent::CursorEntered { device_id },
+                                },
+                                self.window_target(),
+                            );
+                        },
+                        MotionAction::HoverExit if stylus.is_some() => {
+                            callback(
+                                Event::WindowEvent {
+                                    window_id,
+                                    event: WindowEvent::CursorLeft { device_id },
+                                },
+                                self.window_target(),
+                            );
+                        },
+                        // A captured mouse has no location, only the relative motions it reports
+                        MotionAction::HoverMove | MotionAction::Move
+                            if source == Source::MouseRelative =>
+                        {
+                            callback(
+                                Event::DeviceEvent {
+                                    device_id,
+                                    event: event::DeviceEvent::MouseMotion {
+                                        delta: (pointer.x() as f64, pointer.y() as f64),
+                                    },
+                                },
+                                self.window_target(),
+                            );
+                        },
                         MotionAction::HoverMove | MotionAction::Move => {
                             let location =
                                 PhysicalPosition { x: pointer.x() as _, y: pointer.y() as _ };
@@ -421,7 +520,9 @@ impl<T: 'static> EventLoop<T> {
                             // Mouse button pressed
 
                             // Skip `MotionAction::Down` and `MotionAction::Up` when source is mouse as they already reported on `MotionAction::PointerDown` and `MotionAction::PointerUp`
-                            if (source == Source::Mouse ||  source == Source::Touchpad) && (action == MotionAction::Down || action == MotionAction::Up) {
+                            if (source == Source::Mouse || source == Source::MouseRelative || source == Source::Touchpad) && (action == MotionAction::Down || action == MotionAction::Up) {
+                                self.window_target.p.input_event_time.set(None);
+                                self.window_target.p.stylus.set(None);
                                 return input_status;
                             }
 
@@ -452,6 +553,24 @@ impl<T: 'static> EventLoop<T> {
                                 _ => event::ElementState::Released,
                             };
 
+                            // A stylus may touch the screen away from where it last hovered, if it
+                            // hovered at all
+                            if stylus.is_some() && action == MotionAction::Down {
+                                callback(
+                                    Event::WindowEvent {
+                                        window_id,
+                                        event: WindowEvent::CursorMoved {
+                                            device_id,
+                                            position: PhysicalPosition {
+                                                x: pointer.x() as _,
+                                                y: pointer.y() as _,
+                                            },
+                                        },
+                                    },
+                                    self.window_target(),
+                                );
+                            }
+
                             callback(
                                 Event::WindowEvent {
                                     window_id,
@@ -586,6 +705,8 @@ impl<T: 'static> EventLoop<T> {
                 warn!("Unknown android_activity input event {event:?}")
             },
         }
+        self.window_target.p.input_event_time.set(None);
+        self.window_target.p.stylus.set(None);
 
         input_status
     }
@@ -763,11 +884,26 @@ impl<T> EventLoopProxy<T> {
 
 pub struct ActiveEventLoop {
     pub(crate) app: AndroidApp,
+    /// Time of the Android input event being dispatched, in nanoseconds on the `CLOCK_MONOTONIC` timeline
+    pub(crate) input_event_time: Cell<Option<i64>>,
+    /// The stylus of the Android motion event being dispatched
+    pub(crate) stylus: Cell<Option<Stylus>>,
+    /// The state Android handed back when resuming the activity
+    pub(crate) loaded_state: RefCell<Option<Vec<u8>>>,
+    /// The state handed to Android when it asks the activity to save it
+    pub(crate) saved_state: RefCell<Vec<u8>>,
     control_flow: Cell<ControlFlow>,
     exit: Cell<bool>,
     redraw_requester: RedrawRequester,
 }
 
+impl<T: 'static> Drop for EventLoop<T> {
+    fn drop(&mut self) {
+        // The next activity of the process builds its own event loop
+        crate::event_loop::EventLoopBuilder::<()>::allow_event_loop_recreation();
+    }
+}
+
 impl ActiveEventLoop {
     pub fn primary_monitor(&self) -> Option<MonitorHandle> {
         Some(MonitorHandle::new(self.app.clone()))
@@ -1044,8 +1180,34 @@ impl Window {
         Err(error::ExternalError::NotSupported(error::NotSupportedError::new()))
     }
 
-    pub fn set_cursor_grab(&self, _: CursorGrabMode) -> Result<(), error::ExternalError> {
-        Err(error::ExternalError::NotSupported(error::NotSupportedError::new()))
+    /// Both grab modes capture the mouse, which then reports relative motions instead of a
+    /// location. Android releases the capture on its own when the window loses the focus.
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), error::ExternalError> {
+        // Pointer capture came with Android 8
+        if self.app.config().sdk_version() < 26 {
+            return Err(error::ExternalError::NotSupported(error::NotSupportedError::new()));
+        }
+        let method = match mode {
+            CursorGrabMode::None => "releasePointerCapture",
+            CursorGrabMode::Confined | CursorGrabMode::Locked => "requestPointerCapture",
+        };
+        let vm = unsafe { jni::JavaVM::from_raw(self.app.vm_as_ptr() as *mut _) }
+            .map_err(|_| error::ExternalError::Os(os_error!(OsError)))?;
+        let mut env =
+            vm.attach_current_thread().map_err(|_| error::ExternalError::Os(os_error!(OsError)))?;
+        let activity = unsafe { JObject::from_raw(self.app.activity_as_ptr() as *mut _) };
+        let result = env
+            .call_method(activity, "getWindow", "()Landroid/view/Window;", &[])
+            .and_then(|window| {
+                env.call_method(window.l()?, "getDecorView", "()Landroid/view/View;", &[])
+            })
+            .and_then(|view| env.call_method(view.l()?, method, "()V", &[]));
+        if let Err(e) = result {
+            warn!("Failed to call {method}: {e}");
+            let _ = env.exception_clear();
+            return Err(error::ExternalError::Os(os_error!(OsError)));
+        }
+        Ok(())
     }
 
     pub fn set_cursor_visible(&self, _: bool) {}
@@ -0,0 +1,76 @@
+use super::build::PolarBearBackend;
+use crate::android::utils::{ndk::run_in_jvm, session_service};
+use jni::objects::JObject;
+use std::sync::{Mutex, RwLock};
+use winit::{event_loop::EventLoopProxy, platform::android::activity::AndroidApp};
+
+/// The activity the process runs for, along with the proxy waking its event loop up. The threads outliving an
+/// activity, e.g. the watchers and the supervisors of the sessions, reach the current one through it.
+static ATTACHED: RwLock<Option<(AndroidApp, EventLoopProxy<()>)>> = RwLock::new(None);
+
+/// The backend of the last activity destroyed, for the next activity of the process to take over
+static DETACHED: Mutex<Option<DetachedBackend>> = Mutex::new(None);
+
+/// The backend is only ever used by one `android_main` thread at a time: the next one takes it over once the
+/// previous one returned
+struct DetachedBackend(PolarBearBackend);
+
+unsafe impl Send for DetachedBackend {}
+
+/// Make `android_app` the activity the process runs for
+pub fn attach(android_app: AndroidApp, proxy: EventLoopProxy<()>) {
+    *ATTACHED.write().unwrap() = Some((android_app, proxy));
+}
+
+/// The activity the process runs for, if any yet
+pub fn attached_app() -> Option<AndroidApp> {
+    ATTACHED
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|(app, _)| app.clone())
+}
+
+/// Wake the event loop of the current activity up, e.g. for it to handle what another thread did
+pub fn wake_event_loop() {
+    if let Some((_, proxy)) = ATTACHED.read().unwrap().as_ref() {
+        let _ = proxy.send_event(());
+    }
+}
+
+/// Keep the backend of the activity being destroyed, so that the compositor and the session outlive it, e.g. as
+/// the system recreates the activity, or as the app is swiped away while the session service keeps the process.
+/// Nothing is kept when the user quit, which stops the service.
+pub fn detach(mut backend: PolarBearBackend, android_app: &AndroidApp) {
+    if is_finishing(android_app) && !session_service::is_running() {
+        log::info!("The activity finished, ending the session");
+        return;
+    }
+    if let PolarBearBackend::Wayland(backend) = &mut backend {
+        backend.release_graphics();
+    }
+    log::info!("The activity was destroyed, keeping the session for the next one");
+    *DETACHED.lock().unwrap() = Some(DetachedBackend(backend));
+}
+
+/// The backend the last activity destroyed left, if the process outlived it
+pub fn take_detached() -> Option<PolarBearBackend> {
+    DETACHED.lock().unwrap().take().map(|it| it.0)
+}
+
+fn is_finishing(android_app: &AndroidApp) -> bool {
+    let mut finishing = true;
+    run_in_jvm(
+        |env, app| {
+            let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as *mut _) };
+            match env.call_method(&activity, "isFinishing", "()Z", &[]) {
+                Ok(it) => finishing = it.z().unwrap_or(true),
+                Err(_) => {
+                    let _ = env.exception_clear();
+                }
+            }
+        },
+        android_app.clone(),
+    );
+    finishing
+}
@@ -1,8 +1,9 @@
 use winit::platform::android::activity::AndroidApp;
 
 use crate::android::{
-    backend::{wayland::WaylandBackend, webview::WebviewBackend},
-    proot::setup::setup,
+    app::attach::take_detached,
+    backend::{recovery::RecoveryBackend, wayland::WaylandBackend, webview::WebviewBackend},
+    proot::{setup::setup, share::open_shared_files},
 };
 
 pub struct PolarBearApp {
@@ -21,12 +22,23 @@ pub enum PolarBearBackend {
 
     /// Use a wayland compositor to render Linux GUI applications back to the Android Native Activity
     Wayland(WaylandBackend),
+
+    /// Use a webview to let the user recover from the crash of the last launch, instead of crashing again
+    Recovery(RecoveryBackend),
 }
 
 impl PolarBearApp {
+    /// Take over the backend the previous activity of the process left, or set it up, unless the last launch
+    /// crashed. What the activity was started to open is handed over to the desktop, once it is installed.
     pub fn build(android_app: AndroidApp) -> Self {
+        let backend = take_detached()
+            .or_else(|| RecoveryBackend::build(&android_app).map(PolarBearBackend::Recovery))
+            .unwrap_or_else(|| setup(android_app.clone()));
+        if let PolarBearBackend::Wayland(_) = backend {
+            open_shared_files(&android_app);
+        }
         Self {
-            backend: setup(android_app.clone()),
+            backend,
             frontend: PolarBearFrontend { android_app },
         }
     }
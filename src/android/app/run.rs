@@ -1,24 +1,62 @@
 use super::build::{PolarBearApp, PolarBearBackend};
+use super::saved_state::{restore_saved_state, save_state};
 use crate::android::{
-    backend::wayland::{bind, centralize, handle, State},
-    proot::launch::launch,
+    backend::wayland::{
+        bind, centralize, centralize_device_event, centralize_injected, handle, idle,
+        publish_globals, serve_remote_clients, sync_screen_power, watch_external_displays, State,
+        SCREEN_OUTPUT,
+    },
+    proot::{
+        gamepad::serve_gamepads, health::schedule_health_checks, launch::launch,
+        packages::watch_package_changes,
+    },
+    utils::application_context::get_application_context,
+    utils::memory::{report_low_memory, take_memory_pressure, watch_memory_pressure},
     utils::ndk::run_in_jvm,
-    utils::webview::show_webview_popup,
+    utils::power::hold_wake_lock_for_pacman,
+    utils::session_service,
+    utils::storage::refresh_shared_storage,
+    utils::telemetry::report_usage,
+    utils::webview::{page_token, show_webview_popup},
+};
+use crate::core::{
+    config,
+    layout::{compact_scale, physical_size_mm},
+    logging::panel_log,
 };
-use crate::core::config;
 use smithay::output::{Mode, Output, PhysicalProperties, Scale, Subpixel};
 use smithay::utils::Transform;
+use std::time::Instant;
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
-use winit::event_loop::ActiveEventLoop;
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow};
 use winit::window::WindowId;
 
 impl ApplicationHandler for PolarBearApp {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // The storage access may have been granted from the system settings meanwhile
+        run_in_jvm(refresh_shared_storage, self.frontend.android_app.clone());
         match self.backend {
             PolarBearBackend::WebView(ref mut backend) => {
                 let port = backend.socket_port;
-                let url = format!("file:///android_asset/setup-progress.html?port={}", port);
+                let url = format!(
+                    "file:///android_asset/setup-progress.html?port={}&token={}",
+                    port,
+                    page_token()
+                );
+                run_in_jvm(
+                    move |env, app| {
+                        show_webview_popup(env, app, &url);
+                    },
+                    self.frontend.android_app.clone(),
+                );
+            }
+            PolarBearBackend::Recovery(ref backend) => {
+                let url = format!(
+                    "file:///android_asset/recovery.html?port={}&token={}",
+                    backend.socket_port,
+                    page_token()
+                );
                 run_in_jvm(
                     move |env, app| {
                         show_webview_popup(env, app, &url);
@@ -28,38 +66,69 @@ impl ApplicationHandler for PolarBearApp {
             }
             PolarBearBackend::Wayland(ref mut backend) => {
                 // Initialize the Wayland backend
-                let winit = bind(&event_loop);
+                let winit = match bind(event_loop) {
+                    Ok(winit) => winit,
+                    Err(e) => {
+                        // The clients live on, and the window is bound again on the next resume
+                        panel_log(format!("Failed to draw on the window: {}", e), true);
+                        return;
+                    }
+                };
                 let window_size = winit.window_size();
-                let scale_factor = winit.scale_factor();
+                let density = winit.scale_factor();
+                let scale_factor = get_application_context()
+                    .local_config
+                    .display
+                    .scale(density);
                 let size = (window_size.w, window_size.h);
+                let state = &mut backend.compositor.state;
+                if state.dmabuf_global.is_none() {
+                    // Advertise the formats the EGL context can import, so that clients can share their GPU buffers without copies
+                    let formats = winit.dmabuf_formats();
+                    log::info!("Advertising {} dmabuf formats", formats.iter().count());
+                    let dh = state.display_handle.clone();
+                    let global = state.dmabuf_state.create_global::<State>(&dh, formats);
+                    state.globals.extend(state.dmabuf_state.global_id(&global));
+                    state.dmabuf_global = Some(global);
+                }
                 backend.graphic_renderer = Some(winit);
+                backend.scale_factor = scale_factor;
+                backend
+                    .compositor
+                    .state
+                    .output_power_state
+                    .set_screen_on(true);
+                // Android hid the soft keyboard along with the app
+                backend.soft_keyboard.reset();
                 backend.compositor.state.size = size.into();
 
                 // Create the Output with given name and physical properties.
                 let output = Output::new(
-                    "Local Desktop Wayland Compositor".into(), // the name of this output,
+                    SCREEN_OUTPUT.into(), // the name of this output,
                     PhysicalProperties {
-                        size: size.into(),                 // dimensions (width, height) in mm
-                        subpixel: Subpixel::HorizontalRgb, // subpixel information
-                        make: "Local Desktop".into(),      // make of the monitor
-                        model: config::VERSION.into(),     // model of the monitor
+                        size: physical_size_mm(size, density).into(), // dimensions (width, height) in mm
+                        subpixel: Subpixel::HorizontalRgb,            // subpixel information
+                        make: "Local Desktop".into(),                 // make of the monitor
+                        model: config::VERSION.into(),                // model of the monitor
                     },
                 );
 
-                let dh = backend.compositor.display.handle();
+                let dh = backend.compositor.state.display_handle.clone();
                 // create a global, if you want to advertise it to clients
-                let _global = output.create_global::<State>(
+                let global = output.create_global::<State>(
                     &dh, // the display
                 ); // you can drop the global, if you never intend to destroy it.
-                   // Now you can configure it
+                backend.compositor.state.globals.push(global);
+                publish_globals(&backend.compositor.state);
+                // Now you can configure it
                 output.change_current_state(
                     Some(Mode {
                         size: size.into(),
                         refresh: 60000,
                     }), // the resolution mode,
                     Some(Transform::Normal), // global screen transformation
-                    Some(Scale::Fractional(scale_factor)), // global screen scaling factor
-                    Some((0, 0).into()),     // output position
+                    Some(Scale::Fractional(compact_scale(size, scale_factor))), // global screen scaling factor
+                    Some((0, 0).into()),                                        // output position
                 );
                 // set the preferred mode
                 output.set_preferred(Mode {
@@ -70,18 +139,110 @@ impl ApplicationHandler for PolarBearApp {
                 backend.compositor.state.space.map_output(&output, (0, 0));
                 backend.compositor.output.replace(output);
 
-                launch();
+                // The desktop session waits for the X server, which the compositor supervises
+                backend.compositor.state.xwayland.start();
+                restore_saved_state(event_loop, backend);
+                // The sessions added to the config since the last launch need a socket to connect to
+                if let Err(e) = backend.compositor.bind_session_sockets() {
+                    log::error!("Failed to bind the socket of a session: {}", e);
+                }
+                launch(self.frontend.android_app.clone());
+                run_in_jvm(
+                    |env, app| {
+                        if let Err(e) = session_service::start(env, app) {
+                            let _ = env.exception_clear();
+                            log::error!("Failed to start the session service: {:?}", e);
+                        }
+                    },
+                    self.frontend.android_app.clone(),
+                );
+                watch_package_changes();
+                schedule_health_checks();
+                serve_remote_clients();
+                serve_gamepads();
+                watch_external_displays();
+                watch_memory_pressure();
+                hold_wake_lock_for_pacman();
+
+                // Serve the clients as soon as they talk, rather than waiting for another event
+                if let Err(e) = backend.compositor.wake_on_activity() {
+                    log::error!("Failed to watch the clients: {}", e);
+                }
             }
         }
     }
 
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        // The app may be killed once in the background
+        report_usage();
+        if let PolarBearBackend::Wayland(backend) = &mut self.backend {
+            // Android took the window away, e.g. as the screen turned off or the Home button was pressed: its EGL
+            // surface cannot be drawn on anymore, and is created again on the next resume
+            backend.release_graphics();
+            backend
+                .compositor
+                .state
+                .output_power_state
+                .set_screen_on(false);
+        }
+    }
+
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         if let PolarBearBackend::Wayland(backend) = &mut self.backend {
             // Map raw events to our own events
-            let event = centralize(event, backend);
+            let event = centralize(event, backend, event_loop);
 
             // Handle the centralized events
-            handle(event, backend, event_loop);
+            handle(event, backend, &self.frontend.android_app, event_loop);
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let PolarBearBackend::Wayland(backend) = &mut self.backend {
+            let event = centralize_device_event(event, backend, event_loop);
+            handle(event, backend, &self.frontend.android_app, event_loop);
+        }
+    }
+
+    fn memory_warning(&mut self, _event_loop: &ActiveEventLoop) {
+        report_low_memory();
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let PolarBearBackend::Wayland(backend) = &mut self.backend {
+            if let Some(pressure) = take_memory_pressure() {
+                if pressure.drops_render_cache() {
+                    backend.compositor.state.render_cache.clear();
+                }
+            }
+            for input in backend.injected_inputs.take() {
+                let event = centralize_injected(input, backend, event_loop);
+                handle(event, backend, &self.frontend.android_app, event_loop);
+            }
+            idle(backend);
+            sync_screen_power(backend, &self.frontend.android_app);
+            save_state(event_loop, backend);
+            // Client activity wakes the event loop up on its own, only the Xwayland restarts, long presses, frames
+            // not on screen yet and held keys are scheduled
+            let deadline = [
+                backend.compositor.state.xwayland.next_start(),
+                backend.gestures.long_press_deadline(),
+                backend.presentation_feedbacks.next_poll(),
+                backend.key_repeat.next_repeat(),
+                backend.screen_power.idle_deadline(Instant::now()),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            event_loop.set_control_flow(match deadline {
+                Some(at) => ControlFlow::WaitUntil(at),
+                None => ControlFlow::Wait,
+            });
         }
     }
 
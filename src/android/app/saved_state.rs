@@ -0,0 +1,68 @@
+use crate::android::{
+    backend::wayland::{WaylandBackend, Zoom},
+    utils::application_context::get_application_context,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use winit::{event_loop::ActiveEventLoop, platform::android::ActiveEventLoopExtAndroid};
+
+/// What the user was doing, which Android hands back when it recreates the activity after killing the
+/// process in the background, so that the desktop comes back as it was left rather than with the defaults
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedState {
+    /// The session the state belongs to
+    username: String,
+    zoom_scale: f64,
+    zoom_origin: (f64, f64),
+    soft_keyboard: bool,
+}
+
+/// The zoom and the soft keyboard visibility last handed over, so that the state is only serialized once they change
+static LAST_SAVED: Mutex<Option<(f64, (f64, f64), bool)>> = Mutex::new(None);
+
+/// Restore the state saved before the activity was last destroyed, if it belongs to the session being started
+pub fn restore_saved_state(event_loop: &ActiveEventLoop, backend: &mut WaylandBackend) {
+    // The event loop of a new activity has nothing to hand over yet
+    *LAST_SAVED.lock().unwrap() = None;
+    let Some(bytes) = event_loop.saved_state() else {
+        return;
+    };
+    let state = match serde_json::from_slice::<SavedState>(&bytes) {
+        Ok(state) => state,
+        Err(e) => {
+            log::warn!("Ignoring the saved state: {}", e);
+            return;
+        }
+    };
+    if state.username != get_application_context().local_config.user.username {
+        log::info!("Ignoring the saved state of {}'s session", state.username);
+        return;
+    }
+    log::info!("Restoring {:?}", state);
+    backend.zoom = Zoom::new(state.zoom_scale, state.zoom_origin.into());
+    backend.soft_keyboard.set_visible(state.soft_keyboard);
+}
+
+/// Hand the current state over to Android, for whenever it asks the activity to save it, if it changed since
+pub fn save_state(event_loop: &ActiveEventLoop, backend: &WaylandBackend) {
+    let origin = backend.zoom.origin();
+    let current = (
+        backend.zoom.scale(),
+        (origin.x, origin.y),
+        backend.soft_keyboard.visible(),
+    );
+    if LAST_SAVED.lock().unwrap().replace(current) == Some(current) {
+        return;
+    }
+    let (zoom_scale, zoom_origin, soft_keyboard) = current;
+    let state = SavedState {
+        username: get_application_context().local_config.user.username,
+        zoom_scale,
+        zoom_origin,
+        soft_keyboard,
+    };
+    match serde_json::to_vec(&state) {
+        Ok(bytes) => event_loop.set_saved_state(bytes),
+        Err(e) => log::warn!("Failed to save the state: {}", e),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    android::{
+        proot::maintenance::delete_rootfs,
+        utils::{
+            application_context::get_application_context,
+            media_store::{self, Collection},
+            ndk::run_in_jvm,
+            restart::restart_app,
+            webview::accept_page,
+        },
+    },
+    core::{
+        diagnostics,
+        logging::{acknowledge_crash, pending_crash},
+        screenshot::file_name,
+    },
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    thread,
+    time::SystemTime,
+};
+use websocket::sync::Server;
+use websocket::OwnedMessage;
+use winit::platform::android::activity::AndroidApp;
+
+/// Where the last panic is recorded, in the app data outside the Arch FS
+const CRASH_LOG: &str = "last-crash.json";
+
+pub fn crash_log() -> PathBuf {
+    get_application_context().data_dir.join(CRASH_LOG)
+}
+
+/// What the recovery page asks for, e.g. `"resetRootfs"`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Request {
+    Retry,
+    ResetRootfs,
+    ExportLogs,
+}
+
+/// Shown instead of the desktop on the launch after the app crashed: the recovery page tells what happened, and
+/// lets the user start again, reset the Arch FS, or export the logs to report the crash
+pub struct RecoveryBackend {
+    pub socket_port: u16,
+}
+
+impl RecoveryBackend {
+    /// The recovery screen, if the last launch crashed and the user has not been through it yet
+    pub fn build(android_app: &AndroidApp) -> Option<Self> {
+        let crash = pending_crash(&crash_log())?;
+        log::warn!("The app crashed on its last launch, showing the recovery screen");
+        let socket = match Server::bind("127.0.0.1:0") {
+            Ok(it) => it,
+            Err(e) => {
+                log::error!(
+                    "Failed to bind the recovery socket, starting normally: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        let socket_port = socket.local_addr().ok()?.port();
+
+        let android_app = android_app.clone();
+        thread::spawn(move || {
+            for request in socket.filter_map(Result::ok) {
+                let Some(mut client) = accept_page(request) else {
+                    continue;
+                };
+                let crash = crash.clone();
+                let android_app = android_app.clone();
+                thread::spawn(move || {
+                    let message = OwnedMessage::Text(json!({ "crash": crash }).to_string());
+                    if client.send_message(&message).is_err() {
+                        return;
+                    }
+                    while let Ok(message) = client.recv_message() {
+                        match message {
+                            OwnedMessage::Text(text) => {
+                                let status = handle_request(&text, &crash, &android_app);
+                                if client.send_message(&status).is_err() {
+                                    break;
+                                }
+                            }
+                            OwnedMessage::Close(_) => break,
+                            _ => {}
+                        }
+                    }
+                });
+            }
+        });
+
+        Some(Self { socket_port })
+    }
+}
+
+fn status(message: &str, is_error: bool) -> OwnedMessage {
+    OwnedMessage::Text(json!({ "message": message, "isError": is_error }).to_string())
+}
+
+/// Start normally from now on, the crash staying on the diagnostics page
+fn forget_crash() {
+    if let Err(e) = acknowledge_crash(&crash_log()) {
+        log::warn!("Failed to acknowledge the crash: {}", e);
+    }
+}
+
+/// Carry a request of the recovery page out, telling the page how it went unless the app restarts
+fn handle_request(text: &str, crash: &Value, android_app: &AndroidApp) -> OwnedMessage {
+    match serde_json::from_str::<Request>(text) {
+        Ok(Request::Retry) => {
+            forget_crash();
+            restart_app(android_app)
+        }
+        Ok(Request::ResetRootfs) => match delete_rootfs() {
+            Ok(()) => {
+                log::info!("The Arch FS has been reset, restarting the app");
+                forget_crash();
+                restart_app(android_app)
+            }
+            Err(e) => status(&e, true),
+        },
+        Ok(Request::ExportLogs) => match export_logs(crash, android_app) {
+            Ok(location) => status(&format!("Logs exported to {}", location), false),
+            Err(e) => status(&format!("Failed to export the logs: {}", e), true),
+        },
+        Err(e) => {
+            log::warn!("Invalid recovery request {:?}: {}", text, e);
+            status("Unknown request", true)
+        }
+    }
+}
+
+/// Save the crash, along with the diagnostics, into `Download/Local Desktop` for the user to send it
+fn export_logs(crash: &Value, android_app: &AndroidApp) -> Result<String, String> {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs());
+    let name = file_name("Crash report", secs, "json");
+    let report = json!({
+        "crash": crash,
+        "diagnostics": diagnostics::snapshot(),
+    });
+    let mut result = Err("the MediaStore was not reached".to_string());
+    run_in_jvm(
+        |env, app| {
+            result = media_store::save(
+                env,
+                app,
+                Collection::Downloads,
+                &name,
+                "application/json",
+                |file| {
+                    let report = serde_json::to_vec_pretty(&report).map_err(io::Error::other)?;
+                    file.write_all(&report)
+                },
+            );
+        },
+        android_app.clone(),
+    );
+    result
+}
@@ -0,0 +1,92 @@
+use crate::{
+    android::utils::{
+        media_store::{self, Collection},
+        ndk::run_in_jvm,
+        toast::show_toast,
+    },
+    core::screenshot::{file_name, flip_rows},
+};
+use image::{ImageFormat, RgbaImage};
+use smithay::backend::{
+    allocator::Fourcc,
+    renderer::{
+        gles::{GlesRenderer, GlesTarget},
+        ExportMem,
+    },
+};
+use smithay::utils::{Physical, Rectangle, Size};
+use std::{
+    io::{BufWriter, Write},
+    thread,
+    time::SystemTime,
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// Read the frame just rendered into the window, before it is swapped, and save it as a PNG into the Pictures of the
+/// phone. The pixels are encoded and saved on a thread of their own, which shows a toast once done.
+pub fn take_screenshot(
+    renderer: &mut GlesRenderer,
+    framebuffer: &GlesTarget,
+    size: Size<i32, Physical>,
+    android_app: &AndroidApp,
+) {
+    let region = Rectangle::from_size((size.w, size.h).into());
+    let pixels = renderer
+        .copy_framebuffer(framebuffer, region, Fourcc::Abgr8888)
+        .and_then(|mapping| renderer.map_texture(&mapping).map(<[u8]>::to_vec));
+    let mut pixels = match pixels {
+        Ok(pixels) => pixels,
+        Err(e) => {
+            log::error!("Failed to read the frame for a screenshot: {:?}", e);
+            show_toast(android_app, "Failed to take a screenshot".to_string());
+            return;
+        }
+    };
+    flip_rows(&mut pixels, size.w as usize * 4);
+    // The frame is opaque on screen, whatever the alpha left by the clients
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+    let Some(image) = RgbaImage::from_raw(size.w as u32, size.h as u32, pixels) else {
+        return;
+    };
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs());
+    let name = file_name("Screenshot", secs, "png");
+
+    thread::spawn({
+        let android_app = android_app.clone();
+        move || {
+            run_in_jvm(
+                |env, app| {
+                    let saved = media_store::save(
+                        env,
+                        app,
+                        Collection::Pictures,
+                        &name,
+                        "image/png",
+                        |file| {
+                            let mut writer = BufWriter::new(file);
+                            image
+                                .write_to(&mut writer, ImageFormat::Png)
+                                .map_err(std::io::Error::other)?;
+                            writer.flush()
+                        },
+                    );
+                    match saved {
+                        Ok(location) => {
+                            log::info!("Saved a screenshot to {}", location);
+                            show_toast(app, format!("Screenshot saved to {}", location));
+                        }
+                        Err(e) => {
+                            log::error!("Failed to save the screenshot: {}", e);
+                            show_toast(app, "Failed to save the screenshot".to_string());
+                        }
+                    }
+                },
+                android_app,
+            );
+        }
+    });
+}
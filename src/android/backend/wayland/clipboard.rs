@@ -0,0 +1,274 @@
+use super::compositor::State;
+use crate::android::utils::ndk::{get_clipboard_text, run_in_jvm, set_clipboard_text};
+use smithay::input::Seat;
+use smithay::reexports::{
+    calloop::{
+        channel::{self, Event, Sender},
+        LoopHandle,
+    },
+    wayland_server::DisplayHandle,
+};
+use smithay::wayland::selection::{
+    data_device::{
+        clear_data_device_selection, current_data_device_selection_userdata,
+        request_data_device_client_selection, set_data_device_selection,
+    },
+    SelectionTarget,
+};
+use smithay::xwayland::X11Wm;
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::io::OwnedFd,
+    thread,
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// The mime types under which plain text is offered to, and accepted from, the Wayland clients
+pub const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+/// Anything bigger is not worth putting on the Android clipboard
+const MAX_CLIPBOARD_BYTES: u64 = 1024 * 1024;
+
+/// Where the clipboard selection set by the compositor itself, rather than by a Wayland client, comes from
+#[derive(Debug, Clone)]
+pub enum CompositorSelection {
+    /// The text of the Android clipboard
+    Android(String),
+    /// The selection of an X11 client, which the X11 window manager reads from Xwayland
+    Xwayland,
+}
+
+/// Keeps the Android clipboard, the Wayland clipboard selection and the X11 clipboard in sync, for plain text only
+pub struct ClipboardBridge {
+    android_app: AndroidApp,
+    seat: Seat<State>,
+    loop_handle: LoopHandle<'static, State>,
+    /// The text both sides agreed on the last time they were synced, so that a copy is not bounced back to its origin
+    last_text: Option<String>,
+    /// Hands the text read from a Wayland or X11 client over to the event loop, which puts it on the Android clipboard
+    incoming: Sender<String>,
+}
+
+impl ClipboardBridge {
+    pub fn new(
+        android_app: AndroidApp,
+        seat: Seat<State>,
+        loop_handle: &LoopHandle<'static, State>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (incoming, receiver) = channel::channel();
+        loop_handle
+            .insert_source(receiver, |event, _, state| {
+                if let Event::Msg(text) = event {
+                    state.clipboard.push_to_android(text);
+                }
+            })
+            .map_err(|e| e.error)?;
+        Ok(Self {
+            android_app,
+            seat,
+            loop_handle: loop_handle.clone(),
+            last_text: None,
+            incoming,
+        })
+    }
+
+    /// A Wayland client set the clipboard selection: read it in the background, as the client only writes it once dispatched
+    pub fn receive_from_client(&self, mime_types: &[String]) {
+        let seat = self.seat.clone();
+        self.receive(mime_types, move |mime_type, fd| {
+            request_data_device_client_selection::<State>(&seat, mime_type, fd)
+                .map_err(|e| format!("{:?}", e))
+        });
+    }
+
+    /// An X11 client set the clipboard: take it over as the Wayland selection, and read it for Android
+    pub fn receive_from_x11(
+        &self,
+        dh: &DisplayHandle,
+        xwm: Option<&mut X11Wm>,
+        mime_types: Vec<String>,
+    ) {
+        let Some(xwm) = xwm else {
+            return;
+        };
+        self.receive(&mime_types, |mime_type, fd| {
+            xwm.send_selection(
+                SelectionTarget::Clipboard,
+                mime_type,
+                fd,
+                self.loop_handle.clone(),
+            )
+            .map_err(|e| e.to_string())
+        });
+        set_data_device_selection(dh, &self.seat, mime_types, CompositorSelection::Xwayland);
+    }
+
+    /// An X11 client cleared the clipboard it owned
+    pub fn cleared_by_x11(&self, dh: &DisplayHandle) {
+        if matches!(
+            current_data_device_selection_userdata(&self.seat).as_deref(),
+            Some(CompositorSelection::Xwayland)
+        ) {
+            clear_data_device_selection(dh, &self.seat);
+        }
+    }
+
+    /// Read the text offered under `mime_types` from a pipe, which `request` asks its owner to write to
+    fn receive(
+        &self,
+        mime_types: &[String],
+        request: impl FnOnce(String, OwnedFd) -> Result<(), String>,
+    ) {
+        let Some(mime_type) = TEXT_MIME_TYPES
+            .iter()
+            .find(|it| mime_types.iter().any(|offered| offered == *it))
+        else {
+            log::debug!("Ignoring non-text selection: {:?}", mime_types);
+            return;
+        };
+
+        let (reader, writer) = match io::pipe() {
+            Ok(it) => it,
+            Err(e) => {
+                log::error!("Failed to create the clipboard pipe: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = request(mime_type.to_string(), OwnedFd::from(writer)) {
+            log::error!("Failed to request the selection: {}", e);
+            return;
+        }
+
+        let incoming = self.incoming.clone();
+        thread::spawn(move || {
+            let mut text = String::new();
+            match reader.take(MAX_CLIPBOARD_BYTES).read_to_string(&mut text) {
+                Ok(_) => {
+                    let _ = incoming.send(text);
+                }
+                Err(e) => log::warn!("Failed to read the selection: {}", e),
+            }
+        });
+    }
+
+    /// A Wayland client asked for the selection the compositor set
+    pub fn send_to_client(
+        &self,
+        selection: &CompositorSelection,
+        mime_type: String,
+        fd: OwnedFd,
+        xwm: Option<&mut X11Wm>,
+    ) {
+        match selection {
+            CompositorSelection::Android(text) => self.write(text.clone(), fd),
+            CompositorSelection::Xwayland => {
+                let Some(xwm) = xwm else {
+                    return;
+                };
+                if let Err(e) = xwm.send_selection(
+                    SelectionTarget::Clipboard,
+                    mime_type,
+                    fd,
+                    self.loop_handle.clone(),
+                ) {
+                    log::warn!("Failed to request the X11 selection: {}", e);
+                }
+            }
+        }
+    }
+
+    /// An X11 client asked for the clipboard, which is either the Android text or a Wayland client's selection
+    pub fn send_to_x11(&self, mime_type: String, fd: OwnedFd) {
+        match current_data_device_selection_userdata(&self.seat).as_deref() {
+            Some(CompositorSelection::Android(text)) => self.write(text.clone(), fd),
+            // Xwayland does not ask for its own selection
+            Some(CompositorSelection::Xwayland) => {}
+            None => {
+                if let Err(e) =
+                    request_data_device_client_selection::<State>(&self.seat, mime_type, fd)
+                {
+                    log::warn!("Failed to request the client selection for X11: {:?}", e);
+                }
+            }
+        }
+    }
+
+    fn write(&self, text: String, fd: OwnedFd) {
+        thread::spawn(move || {
+            if let Err(e) = File::from(fd).write_all(text.as_bytes()) {
+                log::warn!("Failed to send the selection to the client: {}", e);
+            }
+        });
+    }
+
+    /// Put the text copied from a Wayland or X11 client on the Android clipboard. Must run on the main thread.
+    fn push_to_android(&mut self, text: String) {
+        if self.last_text.as_ref() == Some(&text) {
+            return;
+        }
+        run_in_jvm(
+            |env, android_app| {
+                if let Err(e) = set_clipboard_text(env, android_app, &text) {
+                    let _ = env.exception_clear();
+                    log::error!("Failed to set the Android clipboard: {:?}", e);
+                }
+            },
+            self.android_app.clone(),
+        );
+        self.last_text = Some(text);
+    }
+
+    /// Offer the Android clipboard to the Wayland and X11 clients, if it changed since the last sync
+    pub fn pull_from_android(&mut self, dh: &DisplayHandle, xwm: Option<&mut X11Wm>) {
+        let mut android_text = None;
+        run_in_jvm(
+            |env, android_app| match get_clipboard_text(env, android_app) {
+                Ok(text) => android_text = text,
+                Err(e) => {
+                    let _ = env.exception_clear();
+                    log::error!("Failed to read the Android clipboard: {:?}", e);
+                }
+            },
+            self.android_app.clone(),
+        );
+        let Some(text) = android_text else {
+            return;
+        };
+        if self.last_text.as_ref() == Some(&text) {
+            return;
+        }
+        set_data_device_selection(
+            dh,
+            &self.seat,
+            TEXT_MIME_TYPES.iter().map(|it| it.to_string()).collect(),
+            CompositorSelection::Android(text.clone()),
+        );
+        self.offer_to_x11(xwm);
+        self.last_text = Some(text);
+    }
+
+    /// Have the X11 window manager own the clipboard when it holds the Android text, e.g. once Xwayland (re)started
+    pub fn offer_to_x11(&self, xwm: Option<&mut X11Wm>) {
+        let Some(xwm) = xwm else {
+            return;
+        };
+        if !matches!(
+            current_data_device_selection_userdata(&self.seat).as_deref(),
+            Some(CompositorSelection::Android(_))
+        ) {
+            return;
+        }
+        let mime_types = TEXT_MIME_TYPES.iter().map(|it| it.to_string()).collect();
+        if let Err(e) = xwm.new_selection(SelectionTarget::Clipboard, Some(mime_types)) {
+            log::warn!("Failed to offer the clipboard to X11: {}", e);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use smithay::{
+    backend::input::TouchSlot,
+    utils::{Logical, Point},
+};
+
+/// The latest position of a finger that moved since the last frame
+#[derive(Debug, Clone, Copy)]
+pub struct TouchMotion {
+    pub slot: TouchSlot,
+    pub location: Point<f64, Logical>,
+    pub time: u32,
+}
+
+/// Touchscreens report at 240-480 Hz, much faster than the display refreshes.
+/// Only the latest sample of each finger is forwarded to the clients once per frame, instead of flooding them with motions.
+#[derive(Debug)]
+pub struct MotionCoalescer {
+    enabled: bool,
+    pending: Vec<TouchMotion>,
+}
+
+impl MotionCoalescer {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Record a sample, replacing the previous one of the same finger.
+    /// Returns `true` if it should be forwarded right away, as coalescing is disabled.
+    pub fn push(&mut self, motion: TouchMotion) -> bool {
+        match self.pending.iter_mut().find(|it| it.slot == motion.slot) {
+            Some(pending) => *pending = motion,
+            None => self.pending.push(motion),
+        }
+        !self.enabled
+    }
+
+    /// The motions to forward now, e.g. before a frame is rendered or a finger goes down or up
+    pub fn take(&mut self) -> Vec<TouchMotion> {
+        std::mem::take(&mut self.pending)
+    }
+}
@@ -1,59 +1,128 @@
-use super::bind::bind_socket;
+use super::{
+    bind::bind_socket,
+    clipboard::{ClipboardBridge, CompositorSelection},
+    cursor::Cursor,
+    dnd::DndIcon,
+    output_power::OutputPowerState,
+    render_cache::RenderCache,
+    screencopy::ScreencopyState,
+    stats::ClientStats,
+    text_input::TextInputState,
+    virtual_pointer::VirtualPointerState,
+    xwayland::XwaylandSupervisor,
+};
 use crate::{
-    android::backend::wayland::element::WindowElement, core::logging::PolarBearExpectation,
+    android::{
+        backend::wayland::element::WindowElement,
+        proot::priority::prioritize,
+        utils::{application_context::get_application_context, bell},
+    },
+    core::{config, error::PolarBearError, key_repeat::REPEAT_DELAY, logging::panel_log},
 };
 use smithay::{
-    backend::renderer::utils::on_commit_buffer_handler,
-    delegate_compositor, delegate_data_device, delegate_output, delegate_seat, delegate_shm,
-    delegate_xdg_shell,
-    desktop::Space,
-    input::{self, keyboard::KeyboardHandle, touch::TouchHandle, Seat, SeatHandler, SeatState},
-    output::Output,
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        renderer::utils::{on_commit_buffer_handler, with_renderer_surface_state},
+    },
+    delegate_compositor, delegate_data_device, delegate_dmabuf, delegate_fractional_scale,
+    delegate_output, delegate_pointer_constraints, delegate_presentation,
+    delegate_relative_pointer, delegate_seat, delegate_shm, delegate_tablet_manager,
+    delegate_viewporter, delegate_xdg_shell, delegate_xdg_system_bell, delegate_xwayland_shell,
+    desktop::{
+        find_popup_root_surface, get_popup_toplevel_coords, PopupKeyboardGrab, PopupKind,
+        PopupManager, PopupPointerGrab, PopupUngrabStrategy, Space, Window,
+    },
+    input::{
+        self,
+        keyboard::{KeyboardHandle, XkbConfig},
+        pointer::Focus,
+        touch::TouchHandle,
+        Seat, SeatHandler, SeatState,
+    },
+    output::{self, Output},
     reexports::{
-        wayland_protocols::xdg::shell::server::xdg_toplevel,
-        wayland_server::{protocol::wl_seat, Display},
+        calloop::{generic::Generic, EventLoop, Interest, LoopHandle, Mode, PostAction},
+        wayland_server::{backend::GlobalId, protocol::wl_seat, Display, DisplayHandle},
     },
-    utils::{Logical, Serial, Size},
+    utils::{Clock, Logical, Monotonic, Point, Rectangle, Serial, Size},
     wayland::{
         buffer::BufferHandler,
         compositor::{
-            with_surface_tree_downward, CompositorClientState, CompositorHandler, CompositorState,
-            SurfaceAttributes, TraversalAction,
+            add_destruction_hook, with_states, with_surface_tree_downward, CompositorClientState,
+            CompositorHandler, CompositorState, SurfaceAttributes, TraversalAction,
+        },
+        dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
+        fractional_scale::{
+            with_fractional_scale, FractionalScaleHandler, FractionalScaleManagerState,
         },
-        output::OutputHandler,
+        output::{OutputHandler, OutputManagerState},
+        pointer_constraints::{
+            with_pointer_constraint, PointerConstraintsHandler, PointerConstraintsState,
+        },
+        presentation::PresentationState,
+        relative_pointer::RelativePointerManagerState,
         selection::{
             data_device::{
-                ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler,
+                set_data_device_focus, ClientDndGrabHandler, DataDeviceHandler, DataDeviceState,
+                ServerDndGrabHandler,
             },
-            SelectionHandler,
+            SelectionHandler, SelectionSource, SelectionTarget,
         },
         shell::xdg::{
             PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState,
         },
         shm::{ShmHandler, ShmState},
+        tablet_manager::{TabletManagerState, TabletSeatHandler},
+        viewporter::ViewporterState,
+        xdg_system_bell::{XdgSystemBellHandler, XdgSystemBellState},
+        xwayland_shell::XWaylandShellState,
     },
+    xwayland::{X11Wm, XWaylandClientData},
 };
 use smithay::{
     input::pointer::PointerHandle,
     reexports::wayland_server::{
         backend::{ClientData, ClientId, DisconnectReason},
-        protocol::{wl_buffer, wl_surface::WlSurface},
-        Client, ListeningSocket,
+        protocol::{wl_buffer, wl_data_source::WlDataSource, wl_surface::WlSurface},
+        Client, ListeningSocket, Resource,
     },
 };
-use std::{error::Error, os::unix::io::OwnedFd, time::Instant};
+use std::{
+    error::Error,
+    os::unix::io::OwnedFd,
+    sync::{mpsc::SyncSender, Arc, Mutex},
+    time::Instant,
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// Offset between the successive windows opened by the clients
+const CASCADE_STEP: i32 = 32;
+
+/// The cascade starts over from the top-left corner after that many windows
+const MAX_CASCADED_WINDOWS: usize = 10;
+
+/// The names of the outputs, which the clients tell them apart with as the connectors of a desktop monitor
+pub const SCREEN_OUTPUT: &str = "SCREEN-1";
+pub const EXTERNAL_OUTPUT: &str = "EXTERNAL-1";
 
 pub struct Compositor {
     pub state: State,
-    pub display: Display<State>,
-    pub listener: ListeningSocket,
-    pub clients: Vec<Client>,
-    pub start_time: Instant,
+    /// Drives the Wayland socket, the clients and the other event sources, such as the X11 window manager
+    pub event_loop: EventLoop<'static, State>,
+    /// Tells the thread that wakes the main thread up on client activity that the event loop was dispatched
+    pub dispatched: Option<SyncSender<()>>,
+    /// The single time source of the compositor: frame callbacks and input events are all stamped with it
+    pub clock: Clock<Monotonic>,
     pub seat: Seat<State>,
     pub keyboard: KeyboardHandle<State>,
     pub touch: TouchHandle<State>,
     pub pointer: PointerHandle<State>,
     pub output: Option<Output>,
+    /// The second output of a display plugged into the device, which the desktop spans onto
+    pub external_output: Option<(Output, GlobalId)>,
+    pub stats_published_at: Instant,
+    /// How many sessions have their Wayland socket bound, the session at index N listening on `wayland-N`
+    pub session_sockets: usize,
 }
 
 pub struct State {
@@ -64,6 +133,45 @@ pub struct State {
     pub seat_state: SeatState<Self>,
     pub size: Size<i32, Logical>,
     pub space: Space<WindowElement>,
+    /// The menus and tooltips of the Wayland windows, drawn above them
+    pub popups: PopupManager,
+    pub dmabuf_state: DmabufState,
+    /// Only created once the renderer is up, as the supported formats come from its EGL context
+    pub dmabuf_global: Option<DmabufGlobal>,
+    /// Dmabufs waiting for the renderer to check that they can be imported
+    pub pending_dmabufs: Vec<(Dmabuf, ImportNotifier)>,
+    pub cursor: Cursor,
+    /// The render elements of the windows that did not change since the last frame
+    pub render_cache: RenderCache,
+    pub clipboard: ClipboardBridge,
+    pub dnd_icon: Option<DndIcon>,
+    pub display_handle: DisplayHandle,
+    /// Every client that connected, kept for a while after they disconnect for the statistics
+    pub clients: Vec<Client>,
+    /// Something changed on screen since the last frame
+    pub needs_redraw: bool,
+    pub xwayland_shell_state: XWaylandShellState,
+    /// The X11 window manager, while Xwayland is running
+    pub xwm: Option<X11Wm>,
+    pub xwayland: XwaylandSupervisor,
+    pub text_input_state: TextInputState,
+    pub virtual_pointer_state: VirtualPointerState,
+    pub output_power_state: OutputPowerState,
+    pub screencopy_state: ScreencopyState,
+    pub relative_pointer_state: RelativePointerManagerState,
+    pub pointer_constraints_state: PointerConstraintsState,
+    pub system_bell_state: XdgSystemBellState,
+    pub viewporter_state: ViewporterState,
+    pub fractional_scale_state: FractionalScaleManagerState,
+    pub presentation_state: PresentationState,
+    /// Tells the clients where the outputs are in the desktop and how large they are in logical pixels
+    pub output_manager_state: OutputManagerState,
+    /// Gives the drawing apps the pressure and the tilt of the stylus
+    pub tablet_manager_state: TabletManagerState,
+    /// For the Android services the protocols end up in, such as the bell
+    pub android_app: AndroidApp,
+    /// Every global advertised to the clients, for the diagnostics page
+    pub globals: Vec<GlobalId>,
 }
 
 impl BufferHandler for State {
@@ -75,34 +183,129 @@ impl XdgShellHandler for State {
         &mut self.xdg_shell_state
     }
 
+    /// New windows pick their own size within the output, and are cascaded from its top-left corner
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
         surface.with_pending_state(|state| {
-            state.size.replace(self.size);
-            state.states.set(xdg_toplevel::State::Activated);
+            state.bounds = Some(self.size);
         });
         surface.send_configure();
+
+        let cascade = (self.space.elements().count() % MAX_CASCADED_WINDOWS) as i32;
+        let location = (CASCADE_STEP * cascade, CASCADE_STEP * cascade);
+        let window = WindowElement(Window::new_wayland_window(surface));
+        self.space.map_element(window, location, true);
+    }
+
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        let window = self
+            .space
+            .elements()
+            .find(|it| it.0.toplevel() == Some(&surface))
+            .cloned();
+        if let Some(window) = window {
+            self.space.unmap_elem(&window);
+            self.needs_redraw = true;
+        }
     }
 
-    fn new_popup(&mut self, _surface: PopupSurface, _positioner: PositionerState) {
-        // Handle popup creation here
+    /// The popups are configured on their first commit, once kept within the desktop
+    fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {
+        self.unconstrain_popup(&surface);
+        if let Err(e) = self.popups.track_popup(PopupKind::Xdg(surface)) {
+            log::warn!("Failed to track a popup: {:?}", e);
+        }
     }
 
-    fn grab(&mut self, _surface: PopupSurface, _seat: wl_seat::WlSeat, _serial: Serial) {
-        // Handle popup grab here
+    /// A menu takes the keyboard and the pointer until it is dismissed, by a click outside of it or by Escape
+    fn grab(&mut self, surface: PopupSurface, seat: wl_seat::WlSeat, serial: Serial) {
+        let Some(seat) = Seat::<Self>::from_resource(&seat) else {
+            return;
+        };
+        let popup = PopupKind::Xdg(surface);
+        let Ok(root) = find_popup_root_surface(&popup) else {
+            return;
+        };
+        let mut grab = match self.popups.grab_popup(root, popup, &seat, serial) {
+            Ok(grab) => grab,
+            Err(e) => {
+                log::debug!("Refused a popup grab: {:?}", e);
+                return;
+            }
+        };
+        // Another grab than that of the parent popup, e.g. a drag, wins
+        let previous = grab.previous_serial().unwrap_or(serial);
+        if let Some(keyboard) = seat.get_keyboard() {
+            if keyboard.is_grabbed() && !(keyboard.has_grab(serial) || keyboard.has_grab(previous))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(self, PopupKeyboardGrab::new(&grab), serial);
+        }
+        if let Some(pointer) = seat.get_pointer() {
+            if pointer.is_grabbed() && !(pointer.has_grab(serial) || pointer.has_grab(previous)) {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
+    }
+
+    fn popup_destroyed(&mut self, _surface: PopupSurface) {
+        self.needs_redraw = true;
     }
 
     fn reposition_request(
         &mut self,
-        _surface: PopupSurface,
-        _positioner: PositionerState,
-        _token: u32,
+        surface: PopupSurface,
+        positioner: PositionerState,
+        token: u32,
     ) {
-        // Handle popup reposition here
+        surface.with_pending_state(|state| {
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+        self.unconstrain_popup(&surface);
+        surface.send_repositioned(token);
     }
 }
 
 impl SelectionHandler for State {
-    type SelectionUserData = ();
+    type SelectionUserData = CompositorSelection;
+
+    fn new_selection(
+        &mut self,
+        ty: SelectionTarget,
+        source: Option<SelectionSource>,
+        _seat: Seat<Self>,
+    ) {
+        if ty != SelectionTarget::Clipboard {
+            return;
+        }
+        let mime_types = source.map(|it| it.mime_types());
+        if let Some(mime_types) = &mime_types {
+            self.clipboard.receive_from_client(mime_types);
+        }
+        // The X11 window manager takes the clipboard over on behalf of the Wayland client
+        if let Some(xwm) = self.xwm.as_mut() {
+            if let Err(e) = xwm.new_selection(ty, mime_types) {
+                log::warn!("Failed to offer the clipboard to X11: {}", e);
+            }
+        }
+    }
+
+    fn send_selection(
+        &mut self,
+        _ty: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        user_data: &Self::SelectionUserData,
+    ) {
+        self.clipboard
+            .send_to_client(user_data, mime_type, fd, self.xwm.as_mut());
+    }
 }
 
 impl DataDeviceHandler for State {
@@ -111,22 +314,161 @@ impl DataDeviceHandler for State {
     }
 }
 
-impl ClientDndGrabHandler for State {}
+impl ClientDndGrabHandler for State {
+    fn started(
+        &mut self,
+        _source: Option<WlDataSource>,
+        icon: Option<WlSurface>,
+        _seat: Seat<Self>,
+    ) {
+        // The icon follows the cursor image, not the raw pointer location
+        let hotspot = self.cursor.hotspot();
+        self.dnd_icon = icon.map(|surface| DndIcon {
+            surface,
+            offset: (-hotspot.x, -hotspot.y).into(),
+        });
+    }
+
+    fn dropped(&mut self, _target: Option<WlSurface>, _validated: bool, _seat: Seat<Self>) {
+        self.dnd_icon = None;
+        self.needs_redraw = true;
+    }
+}
+
+/// The compositor never starts a drag on its own, so there is nothing to send
 impl ServerDndGrabHandler for State {
     fn send(&mut self, _mime_type: String, _fd: OwnedFd, _seat: Seat<Self>) {}
 }
 
+impl State {
+    /// Keep a popup within the room of the windows, flipping or sliding it as its positioner allows, e.g. a menu
+    /// opened at the bottom of the screen goes up instead
+    fn unconstrain_popup(&self, popup: &PopupSurface) {
+        let kind = PopupKind::Xdg(popup.clone());
+        let Ok(root) = find_popup_root_surface(&kind) else {
+            return;
+        };
+        let Some(window_location) = self
+            .space
+            .elements()
+            .find(|it| it.wl_surface().as_deref() == Some(&root))
+            .and_then(|it| self.space.element_location(it))
+        else {
+            return;
+        };
+        // Relative to the parent, as the positioner is
+        let mut target = Rectangle::from_size(self.size);
+        target.loc -= get_popup_toplevel_coords(&kind) + window_location;
+        popup.with_pending_state(|state| {
+            state.geometry = state.positioner.get_unconstrained_geometry(target);
+        });
+    }
+
+    fn client_pid(&self, surface: &WlSurface) -> Option<u32> {
+        let credentials = surface
+            .client()?
+            .get_credentials(&self.display_handle)
+            .ok()?;
+        Some(credentials.pid as u32)
+    }
+
+    /// Give the processes of the focused window more CPU and I/O than those of the other windows
+    fn prioritize_focused(&self, focused: Option<&WlSurface>) {
+        let mut focused_pids = Vec::new();
+        let mut others = Vec::new();
+        for window in self.space.elements() {
+            let Some(surface) = window.wl_surface() else {
+                continue;
+            };
+            // The X11 clients draw through Xwayland, which tells their pid
+            let pid = match window.0.x11_surface() {
+                Some(x11) => x11.pid(),
+                None => self.client_pid(&surface),
+            };
+            let Some(pid) = pid else {
+                continue;
+            };
+            if Some(&*surface) == focused {
+                focused_pids.push(pid);
+            } else {
+                others.push(pid);
+            }
+        }
+        // That is Xwayland itself when an X11 window is focused, which has to keep up with it
+        focused_pids.extend(focused.and_then(|surface| self.client_pid(surface)));
+        prioritize(focused_pids, others);
+    }
+}
+
 impl CompositorHandler for State {
     fn compositor_state(&mut self) -> &mut CompositorState {
         &mut self.compositor_state
     }
 
     fn client_compositor_state<'a>(&self, client: &'a Client) -> &'a CompositorClientState {
+        if let Some(state) = client.get_data::<XWaylandClientData>() {
+            return &state.compositor_state;
+        }
         &client.get_data::<ClientState>().unwrap().compositor_state
     }
 
+    fn new_surface(&mut self, surface: &WlSurface) {
+        if let Some(stats) = client_stats(surface) {
+            stats.lock().unwrap().surfaces += 1;
+            add_destruction_hook::<Self, _>(surface, move |_, _| {
+                let mut stats = stats.lock().unwrap();
+                stats.surfaces = stats.surfaces.saturating_sub(1);
+            });
+        }
+    }
+
     fn commit(&mut self, surface: &WlSurface) {
         on_commit_buffer_handler::<Self>(surface);
+        self.needs_redraw = true;
+        self.render_cache.invalidate(surface);
+
+        if let Some(window) = self
+            .space
+            .elements()
+            .find(|it| it.wl_surface().as_deref() == Some(surface))
+        {
+            window.0.on_commit();
+        }
+
+        self.popups.commit(surface);
+        if let Some(PopupKind::Xdg(popup)) = self.popups.find_popup(surface) {
+            if !popup.is_initial_configure_sent() {
+                if let Err(e) = popup.send_configure() {
+                    log::warn!("Failed to configure a popup: {:?}", e);
+                }
+            }
+        }
+
+        if let Some(icon) = self.dnd_icon.as_mut().filter(|it| &it.surface == surface) {
+            icon.apply_buffer_delta();
+        }
+
+        if let Some(stats) = client_stats(surface) {
+            let buffer_size =
+                with_renderer_surface_state(surface, |state| state.buffer_size()).flatten();
+            stats.lock().unwrap().record_commit(buffer_size);
+        }
+    }
+}
+
+impl DmabufHandler for State {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.dmabuf_state
+    }
+
+    fn dmabuf_imported(
+        &mut self,
+        _global: &DmabufGlobal,
+        dmabuf: Dmabuf,
+        notifier: ImportNotifier,
+    ) {
+        // The renderer is not part of the state, so the import is checked on the next redraw
+        self.pending_dmabufs.push((dmabuf, notifier));
     }
 }
 
@@ -145,8 +487,17 @@ impl SeatHandler for State {
         &mut self.seat_state
     }
 
-    fn focus_changed(&mut self, _seat: &Seat<Self>, _focused: Option<&WlSurface>) {}
-    fn cursor_image(&mut self, _seat: &Seat<Self>, _image: input::pointer::CursorImageStatus) {}
+    fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
+        // Only the focused client receives the clipboard selection
+        let client = focused.and_then(|surface| surface.client());
+        set_data_device_focus(&self.display_handle, seat, client);
+        self.text_input_state.set_focus(focused);
+        self.prioritize_focused(focused);
+    }
+    fn cursor_image(&mut self, _seat: &Seat<Self>, image: input::pointer::CursorImageStatus) {
+        self.cursor.set_status(image);
+        self.needs_redraw = true;
+    }
 }
 
 pub fn send_frames_surface_tree(surface: &WlSurface, time: u32) {
@@ -174,6 +525,14 @@ pub fn send_frames_surface_tree(surface: &WlSurface, time: u32) {
 #[derive(Default)]
 pub struct ClientState {
     compositor_state: CompositorClientState,
+    pub stats: Arc<Mutex<ClientStats>>,
+}
+
+/// Statistics of the client owning the given surface
+fn client_stats(surface: &WlSurface) -> Option<Arc<Mutex<ClientStats>>> {
+    surface
+        .client()
+        .and_then(|client| client.get_data::<ClientState>().map(|it| it.stats.clone()))
 }
 
 impl ClientData for ClientState {
@@ -181,42 +540,115 @@ impl ClientData for ClientState {
         log::info!("initialized");
     }
 
-    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {
-        log::info!("disconnected");
+    fn disconnected(&self, client_id: ClientId, reason: DisconnectReason) {
+        let (reason, is_protocol_error) = match reason {
+            DisconnectReason::ConnectionClosed => ("Connection closed".to_string(), false),
+            DisconnectReason::ProtocolError(error) => (error.to_string(), true),
+        };
+        if is_protocol_error {
+            panel_log(
+                format!("Wayland client {:?} disconnected: {}", client_id, reason),
+                true,
+            );
+        } else {
+            log::info!("disconnected: {:?}", client_id);
+        }
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.record_disconnect(reason, is_protocol_error);
+        }
     }
 }
 
 impl OutputHandler for State {}
 
+/// The clients draw at the scale of the output rather than at 1x, and the viewport gives the size their buffers
+/// show at
+impl FractionalScaleHandler for State {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        let scale = self.output_scale();
+        with_states(&surface, |states| {
+            with_fractional_scale(states, |it| it.set_preferred_scale(scale));
+        });
+    }
+}
+
+impl State {
+    /// The scale of the screen, which the external display does not have a say in
+    pub fn output_scale(&self) -> f64 {
+        self.space
+            .outputs()
+            .next()
+            .map_or(1.0, |it| it.current_scale().fractional_scale())
+    }
+}
+
+/// Xwayland forwards the X11 bell here too
+impl XdgSystemBellHandler for State {
+    fn ring(&mut self, _surface: Option<WlSurface>) {
+        bell::ring(&self.android_app, "an app");
+    }
+}
+
+impl PointerConstraintsHandler for State {
+    /// Games lock the pointer as soon as they get it, the constraints with a region wait for the pointer to enter it
+    fn new_constraint(&mut self, surface: &WlSurface, pointer: &PointerHandle<Self>) {
+        if pointer.current_focus().as_ref() != Some(surface) {
+            return;
+        }
+        with_pointer_constraint(surface, pointer, |constraint| {
+            if let Some(constraint) = constraint.filter(|it| it.region().is_none()) {
+                constraint.activate();
+            }
+        });
+    }
+
+    /// The cursor stays where it was locked, which is where it shows up again once unlocked
+    fn cursor_position_hint(
+        &mut self,
+        _surface: &WlSurface,
+        _pointer: &PointerHandle<Self>,
+        _location: Point<f64, Logical>,
+    ) {
+    }
+}
+
+/// The cursors the apps set for the stylus are not drawn, as with the touches
+impl TabletSeatHandler for State {}
+
 // Macros used to delegate protocol handling to types in the app state.
 delegate_xdg_shell!(State);
 delegate_compositor!(State);
 delegate_shm!(State);
+delegate_dmabuf!(State);
 delegate_seat!(State);
 delegate_data_device!(State);
 delegate_output!(State);
+delegate_xwayland_shell!(State);
+delegate_relative_pointer!(State);
+delegate_pointer_constraints!(State);
+delegate_xdg_system_bell!(State);
+delegate_viewporter!(State);
+delegate_fractional_scale!(State);
+delegate_presentation!(State);
+delegate_tablet_manager!(State);
 
 impl Compositor {
-    pub fn build() -> Result<Compositor, Box<dyn Error>> {
-        let display = Display::new()?;
+    pub fn build(android_app: AndroidApp) -> Result<Compositor, PolarBearError> {
+        let display = Display::new().map_err(failed)?;
         let dh = display.handle();
 
         let mut seat_state = SeatState::new();
         let mut seat = seat_state.new_wl_seat(&dh, "Local Desktop");
 
-        let listener = bind_socket()?;
-        let clients = Vec::new();
+        let event_loop = EventLoop::try_new().map_err(failed)?;
+        insert_display_source(&event_loop.handle(), display).map_err(failed)?;
 
-        let start_time = Instant::now();
-
-        // Key repeat rate and delay are in milliseconds: https://wayland-book.com/seat/keyboard.html
-        let keyboard = seat
-            .add_keyboard(Default::default(), 1000, 200)
-            .pb_expect("Failed to add keyboard");
+        let keyboard = add_keyboard(&mut seat)?;
         let touch = seat.add_touch();
         let pointer = seat.add_pointer();
 
-        let state = State {
+        let clock = Clock::new();
+        let mut state = State {
             compositor_state: CompositorState::new::<State>(&dh),
             xdg_shell_state: XdgShellState::new::<State>(&dh),
             shm_state: ShmState::new::<State>(&dh, vec![]),
@@ -224,19 +656,220 @@ impl Compositor {
             seat_state,
             size: (1920, 1080).into(),
             space: Space::default(),
+            popups: PopupManager::default(),
+            dmabuf_state: DmabufState::new(),
+            dmabuf_global: None,
+            pending_dmabufs: Vec::new(),
+            cursor: Cursor::default(),
+            render_cache: RenderCache::default(),
+            clipboard: ClipboardBridge::new(
+                android_app.clone(),
+                seat.clone(),
+                &event_loop.handle(),
+            )
+            .map_err(failed)?,
+            dnd_icon: None,
+            display_handle: dh.clone(),
+            clients: Vec::new(),
+            needs_redraw: true,
+            xwayland_shell_state: XWaylandShellState::new::<State>(&dh),
+            xwm: None,
+            xwayland: XwaylandSupervisor::new(event_loop.handle()),
+            text_input_state: TextInputState::new(&dh),
+            virtual_pointer_state: VirtualPointerState::new(&dh),
+            output_power_state: OutputPowerState::new(&dh),
+            screencopy_state: ScreencopyState::new(&dh),
+            relative_pointer_state: RelativePointerManagerState::new::<State>(&dh),
+            pointer_constraints_state: PointerConstraintsState::new::<State>(&dh),
+            system_bell_state: XdgSystemBellState::new::<State>(&dh),
+            viewporter_state: ViewporterState::new::<State>(&dh),
+            fractional_scale_state: FractionalScaleManagerState::new::<State>(&dh),
+            presentation_state: PresentationState::new::<State>(&dh, clock.id() as u32),
+            output_manager_state: OutputManagerState::new_with_xdg_output::<State>(&dh),
+            tablet_manager_state: TabletManagerState::new::<State>(&dh),
+            android_app,
+            globals: Vec::new(),
         };
+        state.globals = [
+            Some(state.compositor_state.compositor_global()),
+            Some(state.compositor_state.subcompositor_global()),
+            Some(state.xdg_shell_state.global()),
+            Some(state.shm_state.global()),
+            Some(state.data_device_state.global()),
+            seat.global(),
+            Some(state.xwayland_shell_state.global()),
+            Some(state.text_input_state.global()),
+            Some(state.virtual_pointer_state.global()),
+            Some(state.output_power_state.global()),
+            Some(state.screencopy_state.global()),
+            Some(state.relative_pointer_state.global()),
+            Some(state.pointer_constraints_state.global()),
+            Some(state.system_bell_state.global()),
+            Some(state.viewporter_state.global()),
+            Some(state.fractional_scale_state.global()),
+            Some(state.presentation_state.global()),
+            state.output_manager_state.xdg_output_manager_global(),
+            Some(state.tablet_manager_state.global()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
 
-        Ok(Compositor {
+        let mut compositor = Compositor {
             state,
-            listener,
-            clients,
-            start_time,
-            display,
+            event_loop,
+            dispatched: None,
+            clock,
             seat,
             keyboard,
             touch,
             pointer,
             output: None,
-        })
+            external_output: None,
+            stats_published_at: Instant::now(),
+            session_sockets: 0,
+        };
+        compositor.bind_session_sockets()?;
+        Ok(compositor)
     }
+
+    /// Bind the sockets of the sessions that have none yet: every session gets a socket of its own, their clients
+    /// share the output. Sessions can be added to the config while the app runs, so this is done before every launch.
+    pub fn bind_session_sockets(&mut self) -> Result<(), PolarBearError> {
+        let sessions = get_application_context().local_config.session_users().len();
+        while self.session_sockets < sessions {
+            let listener =
+                bind_socket(&config::wayland_socket_name(self.session_sockets)).map_err(failed)?;
+            insert_listener_source(&self.event_loop.handle(), listener).map_err(failed)?;
+            self.session_sockets += 1;
+        }
+        Ok(())
+    }
+
+    /// Change the size the clients see for the output, e.g. to keep the windows above the soft keyboard
+    pub fn resize_output(&mut self, size: Size<i32, Logical>) {
+        log::debug!("Resizing the output to {:?}", size);
+        self.state.size = size;
+        if let Some(output) = &self.output {
+            let mode = output::Mode {
+                size: (size.w, size.h).into(),
+                refresh: 60000,
+            };
+            output.change_current_state(Some(mode), None, None, None);
+        }
+        for toplevel in self.state.xdg_shell_state.toplevel_surfaces() {
+            toplevel.with_pending_state(|state| state.bounds = Some(size));
+            toplevel.send_pending_configure();
+        }
+        self.place_external_output();
+        self.state.needs_redraw = true;
+    }
+
+    /// Put the output of the external display on the right of the screen
+    pub fn place_external_output(&mut self) {
+        if let Some((output, _)) = &self.external_output {
+            let location = (self.state.size.w, 0);
+            self.state.space.map_output(output, location);
+            // The position xdg-output tells the clients
+            output.change_current_state(None, None, None, Some(location.into()));
+        }
+    }
+
+    /// Change the scale the clients render at, e.g. once the window moved to a screen of another density
+    pub fn rescale_output(&mut self, scale: f64) {
+        let Some(output) = &self.output else {
+            return;
+        };
+        if output.current_scale().fractional_scale() != scale {
+            log::debug!("Rescaling the output to {}", scale);
+            output.change_current_state(None, None, Some(output::Scale::Fractional(scale)), None);
+            for window in self.state.space.elements() {
+                window.0.with_surfaces(|_, states| {
+                    with_fractional_scale(states, |it| it.set_preferred_scale(scale));
+                });
+            }
+        }
+    }
+}
+
+fn failed(e: impl std::fmt::Display) -> PolarBearError {
+    PolarBearError::Compositor(e.to_string())
+}
+
+/// The keyboard with the keymap of `[keyboard]`, or the US one if XKB does not know it
+fn add_keyboard(seat: &mut Seat<State>) -> Result<KeyboardHandle<State>, PolarBearError> {
+    let config = get_application_context().local_config.keyboard;
+    // XKB takes the lists without spaces
+    let [model, layout, variant, options] =
+        [config.model, config.layout, config.variant, config.options]
+            .map(|it| it.split_whitespace().collect::<String>());
+    let xkb_config = XkbConfig {
+        rules: "",
+        model: &model,
+        layout: &layout,
+        variant: &variant,
+        options: (!options.is_empty()).then_some(options),
+    };
+    // The compositor repeats the held keys itself, so the clients are told not to: https://wayland-book.com/seat/keyboard.html
+    let delay = REPEAT_DELAY.as_millis() as i32;
+    match seat.add_keyboard(xkb_config, delay, 0) {
+        Ok(keyboard) => Ok(keyboard),
+        Err(e) => {
+            panel_log(
+                format!(
+                    "Invalid [keyboard] keymap, falling back to the US layout: {}",
+                    e
+                ),
+                true,
+            );
+            seat.add_keyboard(Default::default(), delay, 0)
+                .map_err(failed)
+        }
+    }
+}
+
+/// Accept the clients of a session socket from the event loop
+fn insert_listener_source(
+    loop_handle: &LoopHandle<'static, State>,
+    listener: ListeningSocket,
+) -> Result<(), Box<dyn Error>> {
+    loop_handle
+        .insert_source(
+            Generic::new(listener, Interest::READ, Mode::Level),
+            |_, listener, state| {
+                while let Some(stream) = listener.accept()? {
+                    log::info!("Got a client: {:?}", stream);
+                    match state
+                        .display_handle
+                        .insert_client(stream, Arc::new(ClientState::default()))
+                    {
+                        Ok(client) => state.clients.push(client),
+                        Err(e) => log::error!("Failed to insert client: {}", e),
+                    }
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Dispatch the clients from the event loop, whenever their sockets are readable
+fn insert_display_source(
+    loop_handle: &LoopHandle<'static, State>,
+    display: Display<State>,
+) -> Result<(), Box<dyn Error>> {
+    loop_handle
+        .insert_source(
+            Generic::new(display, Interest::READ, Mode::Level),
+            |_, display, state| {
+                // SAFETY: the display is never dropped while its source is registered
+                if let Err(e) = unsafe { display.get_mut().dispatch_clients(state) } {
+                    log::error!("Failed to dispatch clients: {}", e);
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|e| e.error)?;
+    Ok(())
 }
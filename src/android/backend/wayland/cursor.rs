@@ -0,0 +1,174 @@
+use super::element::WindowRenderElement;
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::{
+    element::{
+        surface::render_elements_from_surface_tree,
+        texture::{TextureBuffer, TextureRenderElement},
+        Kind,
+    },
+    gles::{GlesRenderer, GlesTexture},
+    utils::import_surface_tree,
+};
+use smithay::input::pointer::{CursorImageStatus, CursorImageSurfaceData};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{IsAlive, Logical, Point, Transform};
+use smithay::wayland::compositor::with_states;
+
+/// Size (in pixels) of the arrow drawn when the client asks for a named cursor
+const DEFAULT_CURSOR_WIDTH: i32 = 12;
+const DEFAULT_CURSOR_HEIGHT: i32 = 18;
+
+/// The pointer image, as requested by the focused client through `wl_pointer.set_cursor`
+pub struct Cursor {
+    status: CursorImageStatus,
+    /// Only external mice move the pointer, so the cursor stays hidden while the user is touching the screen
+    visible: bool,
+    default_texture: Option<TextureBuffer<GlesTexture>>,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            status: CursorImageStatus::default_named(),
+            visible: false,
+            default_texture: None,
+        }
+    }
+}
+
+impl Cursor {
+    pub fn set_status(&mut self, status: CursorImageStatus) {
+        self.status = status;
+    }
+
+    /// Forget the arrow uploaded with a renderer that is gone
+    pub fn release_textures(&mut self) {
+        self.default_texture = None;
+    }
+
+    pub fn show(&mut self) {
+        self.visible = true;
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    /// The client surface used as the cursor image, which needs its frame callbacks like any other surface
+    pub fn surface(&self) -> Option<&WlSurface> {
+        match &self.status {
+            CursorImageStatus::Surface(surface) => Some(surface),
+            _ => None,
+        }
+    }
+
+    /// Offset of the cursor image relative to the pointer location
+    pub fn hotspot(&self) -> Point<i32, Logical> {
+        match &self.status {
+            CursorImageStatus::Surface(surface) => with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<CursorImageSurfaceData>()
+                    .and_then(|it| it.lock().ok().map(|attributes| attributes.hotspot))
+                    .unwrap_or_default()
+            }),
+            _ => (0, 0).into(),
+        }
+    }
+
+    /// The cursor render elements at the given pointer location, in front-to-back order
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        location: Point<f64, Logical>,
+    ) -> Vec<WindowRenderElement<GlesRenderer>> {
+        if let CursorImageStatus::Surface(surface) = &self.status {
+            if !surface.alive() {
+                self.status = CursorImageStatus::default_named();
+            }
+        }
+        if !self.visible {
+            return vec![];
+        }
+
+        match &self.status {
+            CursorImageStatus::Hidden => vec![],
+            CursorImageStatus::Surface(surface) => {
+                let hotspot = self.hotspot();
+                if let Err(e) = import_surface_tree(renderer, surface) {
+                    log::warn!("Failed to import the cursor surface: {:?}", e);
+                    return vec![];
+                }
+                let position = (location - hotspot.to_f64())
+                    .to_physical(1.0)
+                    .to_i32_round();
+                render_elements_from_surface_tree(
+                    renderer,
+                    surface,
+                    position,
+                    1.0,
+                    1.0,
+                    Kind::Cursor,
+                )
+            }
+            CursorImageStatus::Named(_) => {
+                if self.default_texture.is_none() {
+                    match TextureBuffer::from_memory(
+                        renderer,
+                        &default_cursor_pixels(),
+                        Fourcc::Abgr8888,
+                        (DEFAULT_CURSOR_WIDTH, DEFAULT_CURSOR_HEIGHT),
+                        false,
+                        1,
+                        Transform::Normal,
+                        None,
+                    ) {
+                        Ok(texture) => self.default_texture = Some(texture),
+                        Err(e) => {
+                            log::warn!("Failed to upload the default cursor: {:?}", e);
+                            return vec![];
+                        }
+                    }
+                }
+                self.default_texture
+                    .iter()
+                    .map(|texture| {
+                        TextureRenderElement::from_texture_buffer(
+                            location.to_physical(1.0),
+                            texture,
+                            None,
+                            None,
+                            None,
+                            Kind::Cursor,
+                        )
+                        .into()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A white arrow with a black outline, its hotspot being the top-left pixel
+fn default_cursor_pixels() -> Vec<u8> {
+    let inside = |x: i32, y: i32| {
+        (0..DEFAULT_CURSOR_WIDTH).contains(&x)
+            && (0..DEFAULT_CURSOR_HEIGHT).contains(&y)
+            && x * 3 <= y * 2
+    };
+    let mut pixels =
+        Vec::with_capacity((DEFAULT_CURSOR_WIDTH * DEFAULT_CURSOR_HEIGHT * 4) as usize);
+    for y in 0..DEFAULT_CURSOR_HEIGHT {
+        for x in 0..DEFAULT_CURSOR_WIDTH {
+            let pixel = if !inside(x, y) {
+                [0, 0, 0, 0]
+            } else if x == 0 || !inside(x + 1, y) || !inside(x, y + 1) {
+                [0, 0, 0, 255]
+            } else {
+                [255, 255, 255, 255]
+            };
+            pixels.extend_from_slice(&pixel);
+        }
+    }
+    pixels
+}
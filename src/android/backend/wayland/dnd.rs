@@ -0,0 +1,58 @@
+use super::element::WindowRenderElement;
+use smithay::backend::renderer::{
+    element::{surface::render_elements_from_surface_tree, Kind},
+    gles::GlesRenderer,
+    utils::import_surface_tree,
+};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{IsAlive, Logical, Point};
+use smithay::wayland::compositor::{with_states, SurfaceAttributes};
+
+/// The surface dragged along with the pointer during a client drag-and-drop
+#[derive(Debug)]
+pub struct DndIcon {
+    pub surface: WlSurface,
+    /// Relative to the pointer location
+    pub offset: Point<i32, Logical>,
+}
+
+impl DndIcon {
+    /// Clients move the icon relative to the pointer by attaching buffers with an offset
+    pub fn apply_buffer_delta(&mut self) {
+        with_states(&self.surface, |states| {
+            let buffer_delta = states
+                .cached_state
+                .get::<SurfaceAttributes>()
+                .current()
+                .buffer_delta
+                .take()
+                .unwrap_or_default();
+            self.offset += buffer_delta;
+        });
+    }
+
+    pub fn render_elements(
+        &self,
+        renderer: &mut GlesRenderer,
+        pointer_location: Point<f64, Logical>,
+    ) -> Vec<WindowRenderElement<GlesRenderer>> {
+        if !self.surface.alive() {
+            return vec![];
+        }
+        if let Err(e) = import_surface_tree(renderer, &self.surface) {
+            log::warn!("Failed to import the drag-and-drop icon: {:?}", e);
+            return vec![];
+        }
+        let position = (pointer_location + self.offset.to_f64())
+            .to_physical(1.0)
+            .to_i32_round();
+        render_elements_from_surface_tree(
+            renderer,
+            &self.surface,
+            position,
+            1.0,
+            1.0,
+            Kind::Unspecified,
+        )
+    }
+}
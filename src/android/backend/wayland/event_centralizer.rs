@@ -1,19 +1,32 @@
 use crate::android::backend::wayland::{
+    gestures::{Gesture, TouchOutcome},
+    injector::InjectedInput,
     input::{
         RelativePosition, WinitInput, WinitKeyboardInputEvent, WinitMouseInputEvent,
-        WinitMouseMovedEvent, WinitMouseWheelEvent, WinitTouchCancelledEvent, WinitTouchEndedEvent,
-        WinitTouchMovedEvent, WinitTouchStartedEvent,
+        WinitMouseMotionEvent, WinitMouseMovedEvent, WinitMouseWheelEvent,
+        WinitTouchCancelledEvent, WinitTouchEndedEvent, WinitTouchMovedEvent,
+        WinitTouchStartedEvent,
     },
     keymap::physicalkey_to_scancode,
+    tablet::{StylusAction, StylusEvent, BTN_STYLUS, BTN_STYLUS2},
     WaylandBackend,
 };
+use crate::android::utils::application_context::get_application_context;
+use crate::core::gamepad::button_code;
 use smithay::backend::input::InputEvent;
 use smithay::utils::{Physical, Size};
+use std::time::Duration;
 use winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, Touch, TouchPhase, WindowEvent},
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, Touch, TouchPhase, WindowEvent},
+    event_loop::ActiveEventLoop,
+    keyboard::{Key, NativeKeyCode, PhysicalKey},
+    platform::android::{ActiveEventLoopExtAndroid, Stylus},
 };
 
+/// `KeyEvent.KEYCODE_BACK`, which the Back gesture sends too
+const KEYCODE_BACK: u32 = 4;
+
 /// Specific events generated by Winit
 #[derive(Debug)]
 pub enum CentralizedEvent {
@@ -28,9 +41,31 @@ pub enum CentralizedEvent {
     /// The focus state of the window changed
     Focus(bool),
 
-    /// An input event occurred.
+    /// An input event occurred, stamped (in microseconds) with the time Android recorded it, not the time it is handled
     Input(InputEvent<WinitInput>),
 
+    /// Text typed on the soft keyboard that no key maps to, e.g. accented letters or emojis
+    Text(String),
+
+    /// A multi-touch gesture, whose touches the clients don't get
+    Gesture(Gesture),
+
+    /// The stylus hovered, drew or pressed a button, with `[touch] stylus_tablet` on
+    Stylus(StylusEvent),
+
+    /// A button of a gamepad, by its Android key code, which goes to the Arch FS rather than the clients
+    GamepadButton {
+        keycode: u32,
+        pressed: bool,
+        time: u64,
+    },
+
+    /// An axis of a gamepad, by its Android id
+    GamepadAxis { axis: u32, value: f64, time: u64 },
+
+    /// The Back button or gesture of Android, which does what `[gestures] back` says rather than closing the app
+    Back,
+
     /// The user requested to close the window.
     CloseRequested,
 
@@ -41,8 +76,107 @@ pub enum CentralizedEvent {
     Unsupported,
 }
 
-pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> CentralizedEvent {
-    let time = backend.clock.now().as_millis() as u64;
+/// The touch events that are part of a gesture don't reach the clients
+fn gesture_event(outcome: TouchOutcome) -> CentralizedEvent {
+    match outcome {
+        TouchOutcome::Gesture(gesture) => CentralizedEvent::Gesture(gesture),
+        _ => CentralizedEvent::Unsupported,
+    }
+}
+
+/// The cursor events of a stylus, which draws on the tablet rather than moving the mouse
+fn centralize_stylus(event: WindowEvent, stylus: Stylus, time: u64) -> CentralizedEvent {
+    let action = match event {
+        WindowEvent::CursorMoved { position, .. } => {
+            StylusAction::Motion((position.x, position.y).into())
+        }
+        WindowEvent::MouseInput { state, .. } if !stylus.barrel_button => StylusAction::Tip(state),
+        WindowEvent::MouseInput {
+            state,
+            button: MouseButton::Right,
+            ..
+        } => StylusAction::Button(BTN_STYLUS2, state),
+        WindowEvent::MouseInput { state, .. } => StylusAction::Button(BTN_STYLUS, state),
+        WindowEvent::CursorLeft { .. } => StylusAction::Leave,
+        // The stylus is in hover range once it moves
+        _ => return CentralizedEvent::Unsupported,
+    };
+    CentralizedEvent::Stylus(StylusEvent {
+        action,
+        stylus,
+        time,
+    })
+}
+
+/// Both the Android event time and the compositor clock are on the CLOCK_MONOTONIC timeline
+fn event_time(backend: &WaylandBackend, event_loop: &ActiveEventLoop) -> u64 {
+    event_loop
+        .input_event_time()
+        .unwrap_or_else(|| Duration::from(backend.compositor.clock.now()))
+        .as_micros() as u64
+}
+
+/// Map the events of the devices rather than the window, i.e. the motions of a captured mouse
+pub fn centralize_device_event(
+    event: DeviceEvent,
+    backend: &mut WaylandBackend,
+    event_loop: &ActiveEventLoop,
+) -> CentralizedEvent {
+    match event {
+        DeviceEvent::MouseMotion { delta } => CentralizedEvent::Input(InputEvent::PointerMotion {
+            event: WinitMouseMotionEvent {
+                time: event_time(backend, event_loop),
+                delta,
+            },
+        }),
+        DeviceEvent::Motion { axis, value } => CentralizedEvent::GamepadAxis {
+            axis,
+            value,
+            time: event_time(backend, event_loop),
+        },
+        _ => CentralizedEvent::Unsupported,
+    }
+}
+
+/// Map the injected inputs the same way as the real ones
+pub fn centralize_injected(
+    input: InjectedInput,
+    backend: &mut WaylandBackend,
+    event_loop: &ActiveEventLoop,
+) -> CentralizedEvent {
+    match input {
+        InjectedInput::Key { scancode, state } => {
+            match state {
+                ElementState::Pressed => backend.key_counter += 1,
+                ElementState::Released => {
+                    backend.key_counter = backend.key_counter.saturating_sub(1);
+                }
+            };
+            CentralizedEvent::Input(InputEvent::Keyboard {
+                event: WinitKeyboardInputEvent {
+                    time: event_time(backend, event_loop),
+                    key: scancode,
+                    count: backend.key_counter,
+                    state,
+                },
+            })
+        }
+        InjectedInput::Window(event) => centralize(event, backend, event_loop),
+    }
+}
+
+pub fn centralize(
+    event: WindowEvent,
+    backend: &mut WaylandBackend,
+    event_loop: &ActiveEventLoop,
+) -> CentralizedEvent {
+    let time = event_time(backend, event_loop);
+    let stylus = event_loop
+        .stylus()
+        .filter(|_| get_application_context().local_config.touch.stylus_tablet);
+    if let Some(stylus) = stylus {
+        return centralize_stylus(event, stylus, time);
+    }
     return match event {
         WindowEvent::Resized(size) => {
             let (w, h): (i32, i32) = size.into();
@@ -56,7 +190,10 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
             scale_factor: new_scale_factor,
             ..
         } => {
-            backend.scale_factor = new_scale_factor;
+            backend.scale_factor = get_application_context()
+                .local_config
+                .display
+                .scale(new_scale_factor);
             let (w, h): (i32, i32) = backend
                 .graphic_renderer
                 .as_ref()
@@ -72,11 +209,53 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
         WindowEvent::RedrawRequested => CentralizedEvent::Redraw,
         WindowEvent::CloseRequested => CentralizedEvent::CloseRequested,
         WindowEvent::Focused(focused) => CentralizedEvent::Focus(focused),
+        WindowEvent::KeyboardInput {
+            event,
+            is_synthetic,
+            ..
+        } if event.physical_key
+            == PhysicalKey::Unidentified(NativeKeyCode::Android(KEYCODE_BACK)) =>
+        {
+            // The action runs once, on press
+            if !is_synthetic && !event.repeat && event.state == ElementState::Pressed {
+                CentralizedEvent::Back
+            } else {
+                CentralizedEvent::Unsupported
+            }
+        }
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Unidentified(NativeKeyCode::Android(keycode)),
+                    state,
+                    repeat,
+                    ..
+                },
+            is_synthetic,
+            ..
+        } if button_code(keycode).is_some() => {
+            if is_synthetic || repeat {
+                return CentralizedEvent::Unsupported;
+            }
+            CentralizedEvent::GamepadButton {
+                keycode,
+                pressed: state == ElementState::Pressed,
+                time,
+            }
+        }
         WindowEvent::KeyboardInput {
             event,
             is_synthetic,
             ..
         } if !is_synthetic && !event.repeat => {
+            let Some(scancode) = physicalkey_to_scancode(event.physical_key) else {
+                return match event.logical_key {
+                    Key::Character(text) if event.state == ElementState::Pressed => {
+                        CentralizedEvent::Text(text.to_string())
+                    }
+                    _ => CentralizedEvent::Unsupported,
+                };
+            };
             match event.state {
                 ElementState::Pressed => backend.key_counter += 1,
                 ElementState::Released => {
@@ -84,7 +263,6 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
                 }
             };
 
-            let scancode = physicalkey_to_scancode(event.physical_key).unwrap_or(0);
             let event = InputEvent::Keyboard {
                 event: WinitKeyboardInputEvent {
                     time,
@@ -136,14 +314,28 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
             id,
             ..
         }) => {
+            backend.soft_keyboard.touch_down();
             let size = backend
                 .graphic_renderer
                 .as_ref()
                 .unwrap()
                 .window()
                 .inner_size();
+            let screen = (size.width as i32, size.height as i32).into();
+            match backend
+                .gestures
+                .touch_down(id, (location.x, location.y).into(), screen)
+            {
+                TouchOutcome::Forward => {}
+                TouchOutcome::CancelClients => {
+                    return CentralizedEvent::Input(InputEvent::TouchCancel {
+                        event: WinitTouchCancelledEvent { time, id },
+                    });
+                }
+                outcome => return gesture_event(outcome),
+            }
             let x = location.x / size.width as f64;
-            let y = location.y / size.width as f64;
+            let y = location.y / size.height as f64;
             let event = InputEvent::TouchDown {
                 event: WinitTouchStartedEvent {
                     time,
@@ -161,6 +353,13 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
             id,
             ..
         }) => {
+            match backend
+                .gestures
+                .touch_motion(id, (location.x, location.y).into())
+            {
+                TouchOutcome::Forward => {}
+                outcome => return gesture_event(outcome),
+            }
             let size = backend
                 .graphic_renderer
                 .as_ref()
@@ -168,7 +367,7 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
                 .window()
                 .inner_size();
             let x = location.x / size.width as f64;
-            let y = location.y / size.width as f64;
+            let y = location.y / size.height as f64;
             let event = InputEvent::TouchMotion {
                 event: WinitTouchMovedEvent {
                     time,
@@ -187,6 +386,11 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
             id,
             ..
         }) => {
+            backend.soft_keyboard.touch_up();
+            match backend.gestures.touch_up(id) {
+                TouchOutcome::Forward => {}
+                outcome => return gesture_event(outcome),
+            }
             let size = backend
                 .graphic_renderer
                 .as_ref()
@@ -194,7 +398,7 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
                 .window()
                 .inner_size();
             let x = location.x / size.width as f64;
-            let y = location.y / size.width as f64;
+            let y = location.y / size.height as f64;
             let event = InputEvent::TouchMotion {
                 event: WinitTouchMovedEvent {
                     time,
@@ -216,6 +420,8 @@ pub fn centralize(event: WindowEvent, backend: &mut WaylandBackend) -> Centraliz
             id,
             ..
         }) => {
+            backend.soft_keyboard.touch_cancel();
+            backend.gestures.touch_cancel();
             let event = InputEvent::TouchCancel {
                 event: WinitTouchCancelledEvent { time, id },
             };
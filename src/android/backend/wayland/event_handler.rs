@@ -1,215 +1,1414 @@
-use crate::{
-    android::backend::wayland::{
-        compositor::{send_frames_surface_tree, ClientState, State},
-        element::WindowElement,
-        CentralizedEvent, WaylandBackend,
+use crate::android::{
+    backend::wayland::{
+        capture::take_screenshot,
+        coalescer::TouchMotion,
+        compositor::{send_frames_surface_tree, Compositor, State},
+        element::{WindowElement, WindowRenderElement},
+        external_display::sync_external_display,
+        gestures::{Gesture, Zoom},
+        keymap::char_to_scancode,
+        recorder::Recording,
+        screencopy::ScreencopyFrame,
+        shortcuts::{match_shortcut, CompositorAction},
+        stats::publish_client_stats,
+        tablet::{StylusAction, StylusEvent},
+        virtual_pointer::VirtualPointerEvent,
+        CentralizedEvent, KeyboardOwner, WaylandBackend, WinitGraphicsBackend,
     },
-    core::logging::PolarBearExpectation,
+    proot::gamepad,
+    utils::{
+        application_context::get_application_context, diagnostics::show_diagnostics,
+        quit_dialog::confirm_quit, settings::show_settings, terminal::show_terminal,
+        toast::show_toast, webview::close_webview_overlay,
+    },
+};
+use crate::core::{
+    config::KeyboardInset,
+    layout, startup, telemetry,
+    transform::{clamp_to_outputs, OutputArea},
 };
+use smithay::backend::allocator::Fourcc;
 use smithay::backend::input::{
-    AbsolutePositionEvent, Axis, Event, InputEvent, KeyboardKeyEvent, PointerAxisEvent,
-    PointerButtonEvent, TouchEvent,
+    AbsolutePositionEvent, Axis, AxisSource, ButtonState as PointerButtonState, Event, InputEvent,
+    KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
+    TouchEvent, TouchSlot,
 };
-use smithay::backend::renderer::element::surface::{
-    render_elements_from_surface_tree, WaylandSurfaceRenderElement,
+use smithay::backend::renderer::damage::OutputDamageTracker;
+use smithay::backend::renderer::element::{surface::render_elements_from_surface_tree, Kind};
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::backend::renderer::utils::{import_surface_tree, with_renderer_surface_state};
+use smithay::backend::renderer::{Bind, Color32F, ExportMem, ImportDma, Offscreen};
+use smithay::desktop::{
+    space::SpaceElement,
+    utils::{take_presentation_feedback_surface_tree, OutputPresentationFeedback},
+    PopupManager, Space, WindowSurfaceType,
 };
-use smithay::backend::renderer::element::Kind;
-use smithay::backend::renderer::gles::GlesRenderer;
-use smithay::backend::renderer::utils::draw_render_elements;
-use smithay::backend::renderer::{Color32F, Frame, Renderer};
-use smithay::desktop::Space;
 use smithay::input::keyboard::FilterResult;
 use smithay::input::{pointer, touch};
-use smithay::reexports::wayland_server::protocol::wl_pointer::ButtonState;
-use smithay::utils::{Logical, Point, Rectangle, Transform, SERIAL_COUNTER};
-use smithay::wayland::shell::xdg::ToplevelSurface;
-use std::sync::Arc;
+use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
+use smithay::reexports::wayland_server::{
+    backend::protocol::ProtocolError,
+    protocol::{wl_pointer::ButtonState, wl_surface::WlSurface},
+    Resource,
+};
+use smithay::utils::{
+    Buffer as BufferCoord, Logical, Point, Rectangle, Size, Transform, SERIAL_COUNTER,
+};
+use smithay::wayland::pointer_constraints::{with_pointer_constraint, PointerConstraint};
+use std::time::{Duration, Instant};
+use winit::event::ElementState;
 use winit::event_loop::ActiveEventLoop;
+use winit::platform::android::{
+    activity::{AndroidApp, WindowManagerFlags},
+    WindowExtAndroid,
+};
+use winit::window::CursorGrabMode;
+
+/// Linux input event codes of the mouse buttons
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
 
-/**
- * As we currently use Xwayland, there is only 1 surface
- */
-fn get_surface(state: &State) -> Option<ToplevelSurface> {
+/// The evdev scancode of Esc, which the Back button presses by default
+const KEY_ESC: u32 = 1;
+
+/// The space kept between the text cursor and the soft keyboard, in output pixels
+const KEYBOARD_MARGIN: f64 = 16.0;
+
+/// The root surfaces of the client windows with their render location, from the bottom to the top
+fn window_surfaces(state: &State) -> Vec<(WlSurface, Point<i32, Logical>)> {
     state
-        .xdg_shell_state
-        .toplevel_surfaces()
-        .iter()
-        .next()
-        .cloned()
+        .space
+        .elements()
+        .filter_map(|window| {
+            // The geometry excludes the client-side shadows, which are drawn around the window location
+            let location = state.space.element_location(window)? - window.geometry().loc;
+            Some((window.wl_surface()?.into_owned(), location))
+        })
+        .collect()
 }
 
-fn clamp_coords(space: &Space<WindowElement>, pos: Point<f64, Logical>) -> Point<f64, Logical> {
-    if space.outputs().next().is_none() {
-        return pos;
+/// The root surfaces drawn on the desktop with their render location, from the bottom to the top: each window
+/// followed by its popups, the nested ones last
+fn drawn_surfaces(state: &State) -> Vec<(WlSurface, Point<i32, Logical>)> {
+    let mut surfaces = Vec::new();
+    for window in state.space.elements() {
+        let (Some(surface), Some(origin)) =
+            (window.wl_surface(), state.space.element_location(window))
+        else {
+            continue;
+        };
+        // The popups are placed relative to the geometry of their parent, without its shadows
+        let popups = PopupManager::popups_for_surface(&surface)
+            .map(|(popup, offset)| {
+                let location = origin + offset - popup.geometry().loc;
+                (popup.wl_surface().clone(), location)
+            })
+            .collect::<Vec<_>>();
+        surfaces.push((surface.into_owned(), origin - window.geometry().loc));
+        surfaces.extend(popups);
     }
+    surfaces
+}
 
-    let (pos_x, pos_y) = pos.into();
-    let max_x = space
-        .outputs()
-        .fold(0, |acc, o| acc + space.output_geometry(o).unwrap().size.w);
-    let clamped_x = pos_x.clamp(0.0, max_x as f64);
-    let max_y = space
+/// The `wp_presentation` feedbacks the clients asked for along with the frame about to be swapped
+fn take_presentation_feedback(compositor: &Compositor) -> Option<OutputPresentationFeedback> {
+    let output = compositor.output.as_ref()?;
+    let mut feedback = OutputPresentationFeedback::new(output);
+    for (surface, _) in drawn_surfaces(&compositor.state) {
+        take_presentation_feedback_surface_tree(
+            &surface,
+            &mut feedback,
+            |_, _| Some(output.clone()),
+            |_, _| wp_presentation_feedback::Kind::empty(),
+        );
+    }
+    Some(feedback)
+}
+
+/// The topmost window, which gets the keyboard by default
+fn get_surface(state: &State) -> Option<WlSurface> {
+    window_surfaces(state).pop().map(|(surface, _)| surface)
+}
+
+/// The window at the given location, along with its surface receiving input there. The input regions set by the
+/// clients are honored, so that the transparent margins (e.g. shadows) of client-side decorated windows do not catch clicks.
+fn window_under(
+    state: &State,
+    location: Point<f64, Logical>,
+) -> Option<(WindowElement, (WlSurface, Point<f64, Logical>))> {
+    let (window, render_location) = state.space.element_under(location)?;
+    let (surface, origin) = window
+        .0
+        .surface_under(location - render_location.to_f64(), WindowSurfaceType::ALL)?;
+    Some((
+        window.clone(),
+        (surface, (origin + render_location).to_f64()),
+    ))
+}
+
+fn surface_under(
+    state: &State,
+    location: Point<f64, Logical>,
+) -> Option<(WlSurface, Point<f64, Logical>)> {
+    window_under(state, location).map(|(_, focus)| focus)
+}
+
+/// Bring a window to the front, and give it the keyboard
+fn focus_window(compositor: &mut Compositor, window: &WindowElement) {
+    let state = &mut compositor.state;
+    state.space.raise_element(window, true);
+    if let (Some(xwm), Some(surface)) = (state.xwm.as_mut(), window.0.x11_surface()) {
+        if let Err(e) = xwm.raise_window(surface) {
+            log::warn!("Failed to raise X11 window {}: {}", surface.window_id(), e);
+        }
+    }
+    // Raising the window changed which toplevel is activated
+    for toplevel in state.space.elements().filter_map(|it| it.0.toplevel()) {
+        toplevel.send_pending_configure();
+    }
+    let surface = window.wl_surface().map(|it| it.into_owned());
+    compositor
+        .keyboard
+        .set_focus(state, surface, SERIAL_COUNTER.next_serial());
+}
+
+/// Keep a location of the desktop on the outputs
+fn clamp_coords(space: &Space<WindowElement>, pos: Point<f64, Logical>) -> Point<f64, Logical> {
+    let outputs = space
         .outputs()
-        .find(|o| {
-            let geo = space.output_geometry(o).unwrap();
-            geo.contains((clamped_x as i32, 0))
+        .filter_map(|output| {
+            let mode = output.current_mode()?;
+            Some(OutputArea {
+                location: space.output_geometry(output)?.loc.into(),
+                size: output.current_transform().transform_size(mode.size).into(),
+            })
+        })
+        .collect::<Vec<_>>();
+    clamp_to_outputs(pos.into(), &outputs).into()
+}
+
+/// Where a location of the window, as the touch, mouse and stylus events report it, is on the desktop
+fn desktop_location(
+    backend: &WaylandBackend,
+    location: Point<f64, Logical>,
+) -> Point<f64, Logical> {
+    clamp_coords(
+        &backend.compositor.state.space,
+        backend.zoom.to_desktop(location),
+    )
+}
+
+/// Disconnect the clients whose buffers cannot be imported, instead of letting them fail the whole frame
+fn quarantine_unimportable_clients(renderer: &mut GlesRenderer, compositor: &Compositor) {
+    let dh = &compositor.state.display_handle;
+    for surface in compositor.state.xdg_shell_state.toplevel_surfaces() {
+        let wl_surface = surface.wl_surface();
+        if let Err(e) = import_surface_tree(renderer, wl_surface) {
+            if let Some(client) = wl_surface.client() {
+                client.kill(
+                    dh,
+                    ProtocolError {
+                        code: 0,
+                        object_id: wl_surface.id().protocol_id(),
+                        object_interface: WlSurface::interface().name.to_string(),
+                        message: format!("Failed to import buffer: {:?}", e),
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Tell the clients whether the dmabufs they created can be used by the renderer
+fn import_pending_dmabufs(renderer: &mut GlesRenderer, state: &mut State) {
+    for (dmabuf, notifier) in state.pending_dmabufs.drain(..) {
+        match renderer.import_dmabuf(&dmabuf, None) {
+            Ok(_) => {
+                let _ = notifier.successful::<State>();
+            }
+            Err(e) => {
+                log::warn!("Failed to import dmabuf: {:?}", e);
+                notifier.failed();
+            }
+        }
+    }
+}
+
+/// Type keys on the compositor keyboard, e.g. for a chord of the extra keys: they are pressed in order,
+/// then released in reverse order
+fn type_keys(compositor: &mut Compositor, scancodes: &[u32]) {
+    let time = compositor.clock.now().as_millis();
+    let presses = scancodes.iter().map(|it| (it, KeyState::Pressed));
+    let releases = scancodes.iter().rev().map(|it| (it, KeyState::Released));
+    for (scancode, key_state) in presses.chain(releases) {
+        compositor.keyboard.input::<(), _>(
+            &mut compositor.state,
+            (scancode + 8).into(),
+            key_state,
+            SERIAL_COUNTER.next_serial(),
+            time,
+            |_, _, _| FilterResult::Forward,
+        );
+    }
+}
+
+/// Move the pointer under the fingers, at a location of the output, for the gestures acting as a mouse.
+/// The cursor stays hidden, as with any touch.
+fn move_pointer(backend: &mut WaylandBackend, location: Point<f64, Logical>) {
+    let location = desktop_location(backend, location);
+    warp_pointer(&mut backend.compositor, location);
+}
+
+/// Put the pointer at a location of the desktop, whatever the zoom, e.g. for the remote control and accessibility tools
+pub fn warp_pointer(compositor: &mut Compositor, location: Point<f64, Logical>) {
+    let location = clamp_coords(&compositor.state.space, location);
+    let pointer = compositor.pointer.clone();
+    let focus = surface_under(&compositor.state, location);
+    pointer.motion(
+        &mut compositor.state,
+        focus,
+        &pointer::MotionEvent {
+            location,
+            serial: SERIAL_COUNTER.next_serial(),
+            time: compositor.clock.now().as_millis(),
+        },
+    );
+    pointer.frame(&mut compositor.state);
+    activate_pointer_constraint(compositor);
+}
+
+/// Lock or confine the pointer for the surface under it, once it is within the region of the constraint
+fn activate_pointer_constraint(compositor: &mut Compositor) {
+    let pointer = compositor.pointer.clone();
+    let location = pointer.current_location();
+    let Some((surface, surface_location)) = surface_under(&compositor.state, location) else {
+        return;
+    };
+    let point = (location - surface_location).to_i32_round();
+    with_pointer_constraint(&surface, &pointer, |constraint| {
+        if let Some(constraint) = constraint
+            .filter(|it| !it.is_active() && it.region().is_none_or(|region| region.contains(point)))
+        {
+            constraint.activate();
+        }
+    });
+}
+
+/// Whether a client locked or confined the pointer on its surface
+fn pointer_constrained(compositor: &Compositor) -> bool {
+    let pointer = &compositor.pointer;
+    pointer.current_focus().is_some_and(|surface| {
+        with_pointer_constraint(&surface, pointer, |constraint| {
+            constraint.is_some_and(|it| it.is_active())
         })
-        .map(|o| space.output_geometry(o).unwrap().size.h);
+    })
+}
+
+/// Move the pointer by the motion of a captured mouse, unless a client locked it in place or confined it to a region
+fn relative_motion(backend: &mut WaylandBackend, delta: Point<f64, Logical>, utime: u64) {
+    // The mouse moves on the screen, which may be zoomed in
+    let delta = delta.downscale(backend.zoom.scale());
+    let compositor = &mut backend.compositor;
+    let pointer = compositor.pointer.clone();
+    let location = pointer.current_location();
+    let under = surface_under(&compositor.state, location);
 
-    if let Some(max_y) = max_y {
-        let clamped_y = pos_y.clamp(0.0, max_y as f64);
-        (clamped_x, clamped_y).into()
+    let mut locked = false;
+    let mut confined = None;
+    if let Some((surface, surface_location)) = &under {
+        with_pointer_constraint(surface, &pointer, |constraint| match constraint {
+            Some(constraint) if constraint.is_active() => match &*constraint {
+                PointerConstraint::Locked(_) => locked = true,
+                PointerConstraint::Confined(it) => {
+                    confined = Some((surface.clone(), *surface_location, it.region().cloned()))
+                }
+            },
+            _ => {}
+        });
+    }
+
+    // Games and VMs read the motions of the mouse, whether the pointer moves or not
+    pointer.relative_motion(
+        &mut compositor.state,
+        under,
+        &pointer::RelativeMotionEvent {
+            delta,
+            delta_unaccel: delta,
+            utime,
+        },
+    );
+    if !locked {
+        let location = clamp_coords(&compositor.state.space, location + delta);
+        let focus = surface_under(&compositor.state, location);
+        let allowed = confined.is_none_or(|(surface, surface_location, region)| {
+            focus.as_ref().is_some_and(|(it, _)| *it == surface)
+                && region.is_none_or(|it| it.contains((location - surface_location).to_i32_round()))
+        });
+        if allowed {
+            compositor.state.cursor.show();
+            pointer.motion(
+                &mut compositor.state,
+                focus,
+                &pointer::MotionEvent {
+                    location,
+                    serial: SERIAL_COUNTER.next_serial(),
+                    time: (utime / 1000) as u32,
+                },
+            );
+        }
+    }
+    pointer.frame(&mut compositor.state);
+    activate_pointer_constraint(compositor);
+}
+
+fn press_button(compositor: &mut Compositor, button: u32, state: PointerButtonState) {
+    let pointer = compositor.pointer.clone();
+    if state == PointerButtonState::Pressed {
+        if let Some((window, _)) = window_under(&compositor.state, pointer.current_location()) {
+            focus_window(compositor, &window);
+        }
+    }
+    pointer.button(
+        &mut compositor.state,
+        &pointer::ButtonEvent {
+            button,
+            state,
+            serial: SERIAL_COUNTER.next_serial(),
+            time: compositor.clock.now().as_millis(),
+        },
+    );
+    pointer.frame(&mut compositor.state);
+}
+
+/// Tap the extra key under a touch point starting at `location`, if any
+fn tap_extra_key(
+    backend: &mut WaylandBackend,
+    slot: TouchSlot,
+    location: Point<f64, Logical>,
+) -> bool {
+    let output_size = backend.compositor.state.size.to_physical(1);
+    let Some(key) = backend.extra_keys.touch_down(slot, location, output_size) else {
+        return false;
+    };
+    if let Some(scancodes) = backend.extra_keys.tap(key) {
+        backend.injected_inputs.injector().type_keys(&scancodes);
+    }
+    true
+}
+
+/// The windows of the desktop, and its cursor if `cursor`, scaled by `scale` and moved by `offset`, without what the
+/// phone draws over them. Nothing is drawn while the output is off.
+fn desktop_elements(
+    renderer: &mut GlesRenderer,
+    compositor: &mut Compositor,
+    scale: f64,
+    offset: (f64, f64),
+    cursor: bool,
+) -> Vec<WindowRenderElement<GlesRenderer>> {
+    let pointer_location = compositor.pointer.current_location();
+    let state = &mut compositor.state;
+    if !state.output_power_state.is_on() {
+        return vec![];
+    }
+    let place = |location: Point<f64, Logical>| -> Point<f64, Logical> {
+        (location.x * scale + offset.0, location.y * scale + offset.1).into()
+    };
+    let mut elements = match cursor {
+        true => state
+            .cursor
+            .render_elements(renderer, place(pointer_location)),
+        false => vec![],
+    };
+    for (surface, location) in drawn_surfaces(state).iter().rev() {
+        let location = place(location.to_f64()).to_physical(1.0).to_i32_round();
+        elements.extend(render_elements_from_surface_tree::<
+            _,
+            WindowRenderElement<GlesRenderer>,
+        >(
+            renderer, surface, location, scale, 1.0, Kind::Unspecified
+        ));
+    }
+    elements
+}
+
+/// Draw the desktop on the display plugged into the device: all of it, fitted within that display, when mirrored,
+/// or the part on the right of the screen when spanned
+fn render_external(
+    winit: &mut WinitGraphicsBackend<GlesRenderer>,
+    compositor: &mut Compositor,
+    clear_color: Color32F,
+) {
+    let Some(size) = winit.external.as_ref().map(|it| it.size) else {
+        return;
+    };
+    let state = &mut compositor.state;
+    let (scale, offset) = match &compositor.external_output {
+        Some(_) => (1.0, (-state.size.w as f64, 0.0)),
+        None => {
+            let fit = layout::fit((state.size.w, state.size.h), (size.w, size.h));
+            (fit.scale, fit.offset)
+        }
+    };
+    let (renderer, mut framebuffer) = match winit.bind_external() {
+        Some(Ok(bound)) => bound,
+        Some(Err(e)) => {
+            log::warn!("Failed to bind the external display: {:?}", e);
+            return;
+        }
+        None => return,
+    };
+    let elements = desktop_elements(renderer, compositor, scale, offset, true);
+    // Every frame is drawn whole, as the external display is not swapped when the screen has nothing new
+    let mut damage_tracker = OutputDamageTracker::new(size, 1.0, Transform::Flipped180);
+    let rendered =
+        damage_tracker.render_output(renderer, &mut framebuffer, 0, &elements, clear_color);
+    drop(framebuffer);
+    match rendered {
+        Ok(_) => {
+            if let Err(e) = winit.submit_external() {
+                log::warn!(
+                    "Failed to submit the frame of the external display: {:?}",
+                    e
+                );
+            }
+        }
+        Err(e) => log::warn!("Failed to render the external display: {:?}", e),
+    }
+}
+
+/// Draw the desktop into the recording, if one runs and a frame is due. The frames not due are drawn on a later
+/// redraw, so that the recording does not miss the last change on the desktop.
+fn render_recording(
+    winit: &mut WinitGraphicsBackend<GlesRenderer>,
+    compositor: &mut Compositor,
+    clear_color: Color32F,
+) {
+    let Some(recording) = winit.recording.as_mut() else {
+        return;
+    };
+    if !recording.frame_due() {
+        compositor.state.needs_redraw = true;
+        return;
+    }
+    let size = recording.size;
+    let desktop = compositor.state.size;
+    let fit = layout::fit((desktop.w, desktop.h), (size.w, size.h));
+    let (renderer, mut framebuffer) = match winit.bind_recording() {
+        Some(Ok(bound)) => bound,
+        Some(Err(e)) => {
+            log::warn!("Failed to bind the recording: {:?}", e);
+            return;
+        }
+        None => return,
+    };
+    let elements = desktop_elements(renderer, compositor, fit.scale, fit.offset, true);
+    let mut damage_tracker = OutputDamageTracker::new(size, 1.0, Transform::Flipped180);
+    let rendered =
+        damage_tracker.render_output(renderer, &mut framebuffer, 0, &elements, clear_color);
+    drop(framebuffer);
+    match rendered {
+        Ok(_) => {
+            if let Err(e) = winit.submit_recording() {
+                log::warn!("Failed to submit the frame of the recording: {:?}", e);
+            }
+        }
+        Err(e) => log::warn!("Failed to render the recording: {:?}", e),
+    }
+}
+
+/// Copy the desktop into the buffers of the clients capturing it through `zwlr_screencopy_manager_v1`
+fn serve_screencopies(
+    winit: &mut WinitGraphicsBackend<GlesRenderer>,
+    compositor: &mut Compositor,
+    clear_color: Color32F,
+) {
+    let frames = compositor.state.screencopy_state.take();
+    if frames.is_empty() {
+        return;
+    }
+    let time = Duration::from(compositor.clock.now());
+    let renderer = winit.renderer();
+    for frame in frames {
+        match read_screencopy(renderer, compositor, &frame, clear_color) {
+            Ok(pixels) => frame.complete(&pixels, time),
+            Err(e) => {
+                log::warn!("Failed to copy the screen for a client: {}", e);
+                frame.fail();
+            }
+        }
+    }
+}
+
+/// Draw the region of the output a client captures into a texture, and read it back top row first
+fn read_screencopy(
+    renderer: &mut GlesRenderer,
+    compositor: &mut Compositor,
+    frame: &ScreencopyFrame,
+    clear_color: Color32F,
+) -> Result<Vec<u8>, String> {
+    let output = compositor
+        .state
+        .space
+        .output_geometry(frame.output())
+        .map(|it| it.loc)
+        .unwrap_or_default();
+    let region = frame.region();
+    let offset = (
+        -(output.x + region.loc.x) as f64,
+        -(output.y + region.loc.y) as f64,
+    );
+    let elements = desktop_elements(renderer, compositor, 1.0, offset, frame.overlay_cursor());
+    let size = Size::<i32, BufferCoord>::from((region.size.w, region.size.h));
+    let mut texture: GlesTexture = renderer
+        .create_buffer(Fourcc::Abgr8888, size)
+        .map_err(|e| format!("{:?}", e))?;
+    let mut target = renderer
+        .bind(&mut texture)
+        .map_err(|e| format!("{:?}", e))?;
+    // Drawn without the flip of the screen, so that the rows are read top first
+    let mut damage_tracker = OutputDamageTracker::new(region.size, 1.0, Transform::Normal);
+    damage_tracker
+        .render_output(renderer, &mut target, 0, &elements, clear_color)
+        .map_err(|e| format!("{:?}", e))?;
+    let mapping = renderer
+        .copy_framebuffer(&target, Rectangle::from_size(size), Fourcc::Abgr8888)
+        .map_err(|e| format!("{:?}", e))?;
+    let pixels = renderer
+        .map_texture(&mapping)
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(pixels.to_vec())
+}
+
+/// Start recording the desktop, or stop the recording that runs
+fn toggle_recording(backend: &mut WaylandBackend, android_app: &AndroidApp) {
+    let Some(winit) = backend.graphic_renderer.as_mut() else {
+        return;
+    };
+    // Dropping the recording ends it, and it is saved once the encoders are done
+    if winit.recording.take().is_some() {
+        show_toast(android_app, "Saving the recording".to_string());
+        return;
+    }
+    let desktop = backend.compositor.state.size;
+    match Recording::start(winit, (desktop.w, desktop.h), android_app) {
+        Ok(recording) => {
+            winit.recording = Some(recording);
+            backend.compositor.state.needs_redraw = true;
+            telemetry::record("capture", "recording");
+            show_toast(android_app, "Recording the desktop".to_string());
+        }
+        Err(e) => {
+            log::error!("Failed to start recording: {}", e);
+            show_toast(android_app, "Failed to start recording".to_string());
+        }
+    }
+}
+
+/// Whether the topmost window covers the whole desktop, e.g. a video or a game in fullscreen
+fn fullscreen_window_shown(state: &State) -> bool {
+    let desktop = Rectangle::from_size(state.size);
+    state
+        .space
+        .elements()
+        .filter(|it| {
+            it.0.x11_surface()
+                .is_none_or(|it| !it.is_override_redirect())
+        })
+        .last()
+        .and_then(|it| state.space.element_geometry(it))
+        .is_some_and(|geometry| geometry.contains_rect(desktop))
+}
+
+/// Keep the screen on, or let it turn off, as `[power]` wants it now
+pub fn sync_screen_power(backend: &mut WaylandBackend, android_app: &AndroidApp) {
+    let fullscreen = fullscreen_window_shown(&backend.compositor.state);
+    let keep_on = backend.screen_power.keep_on(Instant::now(), fullscreen);
+    if backend.screen_kept_on == Some(keep_on) {
+        return;
+    }
+    log::info!("Keeping the screen on: {}", keep_on);
+    backend.screen_kept_on = Some(keep_on);
+    if keep_on {
+        android_app.set_window_flags(
+            WindowManagerFlags::KEEP_SCREEN_ON,
+            WindowManagerFlags::empty(),
+        );
     } else {
-        (clamped_x, pos_y).into()
+        android_app.set_window_flags(
+            WindowManagerFlags::empty(),
+            WindowManagerFlags::KEEP_SCREEN_ON,
+        );
+    }
+}
+
+/// Handle a gesture, returning the action it is bound to in `[gestures]` if any
+fn handle_gesture(backend: &mut WaylandBackend, gesture: Gesture) -> Option<CompositorAction> {
+    backend.compositor.state.needs_redraw = true;
+    let gestures = || get_application_context().local_config.gestures;
+    match gesture {
+        Gesture::Tap { fingers } => {
+            return CompositorAction::of_gesture(gestures().tap_action(fingers));
+        }
+        Gesture::EdgeSwipe { edge } => {
+            return CompositorAction::of_gesture(gestures().swipe_action(edge));
+        }
+        Gesture::Scroll { location, delta } => scroll(backend, location, Some(delta)),
+        Gesture::ScrollStop { location } => scroll(backend, location, None),
+        Gesture::Pinch {
+            previous_center,
+            center,
+            scale,
+        } => {
+            let output_size = backend.compositor.state.size;
+            backend
+                .zoom
+                .pinch(previous_center, center, scale, output_size);
+        }
+        Gesture::LongPress { id, location } => {
+            if backend.extra_keys.owns_touch(touch_slot(id)) {
+                return None;
+            }
+            let compositor = &mut backend.compositor;
+            compositor.touch.cancel(&mut compositor.state);
+            move_pointer(backend, location);
+            press_button(
+                &mut backend.compositor,
+                BTN_RIGHT,
+                PointerButtonState::Pressed,
+            );
+            press_button(
+                &mut backend.compositor,
+                BTN_RIGHT,
+                PointerButtonState::Released,
+            );
+        }
+        Gesture::DragStart { id, location } => {
+            if tap_extra_key(backend, touch_slot(id), location) {
+                return None;
+            }
+            move_pointer(backend, location);
+            press_button(
+                &mut backend.compositor,
+                BTN_LEFT,
+                PointerButtonState::Pressed,
+            );
+        }
+        Gesture::DragMotion { id, location } => {
+            if !backend.extra_keys.owns_touch(touch_slot(id)) {
+                move_pointer(backend, location);
+            }
+        }
+        Gesture::DragEnd { id, location } => {
+            if backend.extra_keys.touch_up(touch_slot(id)) {
+                return None;
+            }
+            move_pointer(backend, location);
+            press_button(
+                &mut backend.compositor,
+                BTN_LEFT,
+                PointerButtonState::Released,
+            );
+        }
     }
+    None
 }
 
-pub fn handle(event: CentralizedEvent, backend: &mut WaylandBackend, event_loop: &ActiveEventLoop) {
+/// Draw on the tablet with the stylus, which focuses the window it touches as a click would
+fn handle_stylus(backend: &mut WaylandBackend, event: StylusEvent) {
+    telemetry::record("input", "stylus");
+    let time = (event.time / 1000) as u32;
+    let location = match event.action {
+        StylusAction::Leave => {
+            backend.tablet.leave(time);
+            return;
+        }
+        StylusAction::Motion(location) => {
+            let location = desktop_location(backend, location);
+            backend.tablet.location = location;
+            location
+        }
+        _ => backend.tablet.location,
+    };
+    let compositor = &mut backend.compositor;
+    let (tablet, tool) =
+        backend
+            .tablet
+            .tool(&compositor.seat, &mut compositor.state, &event.stylus, time);
+    let serial = SERIAL_COUNTER.next_serial();
+    if let StylusAction::Tip(ElementState::Pressed) = event.action {
+        if let Some((window, _)) = window_under(&compositor.state, location) {
+            focus_window(compositor, &window);
+        }
+    }
+    // The pressure goes along with a motion, including the one of the tip touching the screen
+    let focus = surface_under(&compositor.state, location);
+    match event.action {
+        StylusAction::Motion(_) => tool.motion(location, focus, &tablet, serial, time),
+        StylusAction::Tip(state) => {
+            tool.motion(location, focus, &tablet, serial, time);
+            match state {
+                ElementState::Pressed => tool.tip_down(serial, time),
+                ElementState::Released => tool.tip_up(time),
+            }
+        }
+        StylusAction::Button(button, state) => {
+            let state = match state {
+                ElementState::Pressed => PointerButtonState::Pressed,
+                ElementState::Released => PointerButtonState::Released,
+            };
+            tool.button(button, state, serial, time);
+        }
+        StylusAction::Leave => {}
+    }
+}
+
+fn run_action(backend: &mut WaylandBackend, android_app: &AndroidApp, action: CompositorAction) {
+    match action {
+        CompositorAction::ShowDiagnostics => show_diagnostics(android_app.clone()),
+        CompositorAction::ShowSettings => show_settings(android_app.clone()),
+        CompositorAction::ShowTerminal => show_terminal(android_app.clone()),
+        CompositorAction::ToggleSoftKeyboard => backend.soft_keyboard.toggle(),
+        CompositorAction::Screenshot => {
+            backend.screenshot_requested = true;
+            backend.compositor.state.needs_redraw = true;
+        }
+        CompositorAction::ToggleRecording => toggle_recording(backend, android_app),
+        CompositorAction::ToggleKeepScreenOn => {
+            let fullscreen = fullscreen_window_shown(&backend.compositor.state);
+            let message = if backend.screen_power.toggle(Instant::now(), fullscreen) {
+                "The screen stays on"
+            } else {
+                "The screen may turn off"
+            };
+            show_toast(android_app, message.to_string());
+        }
+        CompositorAction::NextLayout => next_layout(&mut backend.compositor),
+        CompositorAction::Escape if backend.focus.clients_own_keyboard() => {
+            type_keys(&mut backend.compositor, &[KEY_ESC]);
+            backend.compositor.state.needs_redraw = true;
+        }
+        CompositorAction::Escape => {}
+        CompositorAction::ConfirmQuit => confirm_quit(android_app.clone()),
+    }
+}
+
+/// Switch the keyboard to the next layout of `[keyboard]`, which the focused client is told along with the modifiers
+fn next_layout(compositor: &mut Compositor) {
+    let layout = compositor
+        .keyboard
+        .with_xkb_state(&mut compositor.state, |mut context| {
+            context.cycle_next_layout();
+            let xkb = context.xkb().lock().unwrap();
+            xkb.layout_name(xkb.active_layout()).to_string()
+        });
+    log::info!("Switched the keyboard layout to {}", layout);
+}
+
+/// Forward the latest position of the fingers that moved, as a single frame
+fn flush_touch_motions(backend: &mut WaylandBackend) {
+    let motions = backend.touch_motions.take();
+    if motions.is_empty() {
+        return;
+    }
+    let compositor = &mut backend.compositor;
+    for motion in motions {
+        let focus = surface_under(&compositor.state, motion.location);
+        compositor.touch.motion(
+            &mut compositor.state,
+            focus,
+            &touch::MotionEvent {
+                slot: motion.slot,
+                location: motion.location,
+                time: motion.time,
+            },
+        );
+    }
+    compositor.touch.frame(&mut compositor.state);
+}
+
+fn touch_slot(id: u64) -> TouchSlot {
+    Some(id as u32).into()
+}
+
+/// Scroll the window under the fingers by `delta` (in pixels of the output), or stop scrolling it
+fn scroll(
+    backend: &mut WaylandBackend,
+    location: Point<f64, Logical>,
+    delta: Option<Point<f64, Logical>>,
+) {
+    move_pointer(backend, location);
+    let scale = backend.zoom.scale();
+    let compositor = &mut backend.compositor;
+    let pointer = compositor.pointer.clone();
+    let time = compositor.clock.now().as_millis();
+
+    // The content follows the fingers, as in Android apps
+    let mut frame = pointer::AxisFrame::new(time).source(AxisSource::Finger);
+    match delta {
+        Some(delta) => {
+            if delta.x != 0.0 {
+                frame = frame.value(Axis::Horizontal, -delta.x / scale);
+            }
+            if delta.y != 0.0 {
+                frame = frame.value(Axis::Vertical, -delta.y / scale);
+            }
+        }
+        None => frame = frame.stop(Axis::Horizontal).stop(Axis::Vertical),
+    }
+    pointer.axis(&mut compositor.state, frame);
+    pointer.frame(&mut compositor.state);
+}
+
+/// Whether a client has attached a buffer to its toplevel, meaning the desktop is now visible
+fn has_client_frame(compositor: &Compositor) -> bool {
+    window_surfaces(&compositor.state)
+        .iter()
+        .any(|(surface, _)| {
+            with_renderer_surface_state(surface, |state| state.buffer().is_some()).unwrap_or(false)
+        })
+}
+
+/// Accept new clients and dispatch the requests of the existing ones.
+/// Failures are logged, so that a misbehaving client never takes the whole session down.
+fn service_clients(compositor: &mut Compositor) {
+    compositor
+        .state
+        .xwayland
+        .poll(&compositor.state.display_handle.clone());
+    // Accepts and dispatches the clients, among the other sources
+    if let Err(e) = compositor
+        .event_loop
+        .dispatch(Some(Duration::ZERO), &mut compositor.state)
+    {
+        log::error!("Failed to dispatch the event loop: {}", e);
+    }
+    compositor.notify_dispatched();
+
+    // Drop the windows and the popups of the clients that went away
+    let window_count = compositor.state.space.elements().count();
+    compositor.state.space.refresh();
+    compositor.state.popups.cleanup();
+    if compositor.state.space.elements().count() != window_count {
+        compositor.state.needs_redraw = true;
+    }
+    if let Err(e) = compositor.state.display_handle.flush_clients() {
+        log::error!("Failed to flush clients: {}", e);
+    }
+}
+
+/// Press the held key again for the focused client, once it is due
+fn repeat_key(backend: &mut WaylandBackend) {
+    let Some(key) = backend.key_repeat.due(Instant::now()) else {
+        return;
+    };
+    let compositor = &mut backend.compositor;
+    let time = compositor.clock.now().as_millis();
+    compositor.keyboard.input::<(), _>(
+        &mut compositor.state,
+        key.into(),
+        KeyState::Pressed,
+        SERIAL_COUNTER.next_serial(),
+        time,
+        |_, _, _| FilterResult::Forward,
+    );
+}
+
+/// Replay what the virtual pointers of the clients did, as if it came from a mouse
+fn replay_virtual_pointers(backend: &mut WaylandBackend) {
+    for event in backend.compositor.state.virtual_pointer_state.take_events() {
+        let compositor = &mut backend.compositor;
+        match event {
+            VirtualPointerEvent::Motion { time, delta } => {
+                relative_motion(backend, delta, time as u64 * 1000)
+            }
+            VirtualPointerEvent::Warp(location) => {
+                compositor.state.cursor.show();
+                warp_pointer(compositor, location);
+            }
+            VirtualPointerEvent::Button { button, state } => {
+                press_button(compositor, button, state)
+            }
+            VirtualPointerEvent::Axis(frame) => {
+                let pointer = compositor.pointer.clone();
+                pointer.axis(&mut compositor.state, frame);
+                pointer.frame(&mut compositor.state);
+            }
+        }
+    }
+}
+
+/// Keep what is typed visible above the soft keyboard, which covers the bottom of the window that Android no
+/// longer counts as content
+fn make_room_for_keyboard(backend: &mut WaylandBackend) {
+    let Some(winit) = backend.graphic_renderer.as_ref() else {
+        return;
+    };
+    let window_height = winit.window_size().h;
+    let covered = if backend.soft_keyboard.visible() {
+        (window_height - winit.window().content_rect().bottom).clamp(0, window_height)
+    } else {
+        0
+    };
+    let compositor = &mut backend.compositor;
+    match backend.keyboard_inset {
+        KeyboardInset::Pan => {
+            let pan = if covered == 0 {
+                0.0
+            } else {
+                // Where the text cursor is, or else the pointer, which the user likely just clicked the field with
+                let zoom = backend.zoom;
+                let typed_at = text_cursor_location(&compositor.state)
+                    .unwrap_or_else(|| compositor.pointer.current_location());
+                let bottom = zoom.to_screen(typed_at).y + zoom.pan();
+                (bottom + KEYBOARD_MARGIN - (window_height - covered) as f64)
+                    .clamp(0.0, covered as f64)
+            };
+            if backend.zoom.set_pan(pan) {
+                compositor.state.needs_redraw = true;
+            }
+        }
+        KeyboardInset::Shrink => {
+            let height = window_height - covered;
+            if compositor.state.size.h != height {
+                let size = (compositor.state.size.w, height).into();
+                compositor.resize_output(size);
+            }
+        }
+        KeyboardInset::Overlap => {}
+    }
+}
+
+/// The bottom of the text cursor of the focused client, on the desktop
+fn text_cursor_location(state: &State) -> Option<Point<f64, Logical>> {
+    let (surface, cursor) = state.text_input_state.cursor_rectangle()?;
+    let (_, location) = drawn_surfaces(state)
+        .into_iter()
+        .find(|(it, _)| *it == surface)?;
+    let bottom: Point<i32, Logical> = (0, cursor.size.h).into();
+    Some((location + cursor.loc + bottom).to_f64())
+}
+
+/// Serve the clients while the event loop is idle, and only schedule a redraw when something changed on screen
+pub fn idle(backend: &mut WaylandBackend) {
+    if backend.graphic_renderer.is_none() {
+        return;
+    }
+    sync_external_display(backend);
+    service_clients(&mut backend.compositor);
+    replay_virtual_pointers(backend);
+    let Some(winit) = backend.graphic_renderer.as_ref() else {
+        return;
+    };
+    let compositor = &mut backend.compositor;
+    publish_client_stats(compositor);
+    backend.soft_keyboard.sync(
+        winit.window(),
+        compositor.state.text_input_state.wants_keyboard(),
+    );
+    // The mouse only reports relative motions while captured, which is what the locked pointers want
+    let captured = pointer_constrained(compositor);
+    if captured != backend.pointer_captured {
+        backend.pointer_captured = captured;
+        let mode = if captured {
+            CursorGrabMode::Locked
+        } else {
+            CursorGrabMode::None
+        };
+        if let Err(e) = winit.window().set_cursor_grab(mode) {
+            log::warn!("Failed to set the mouse capture to {:?}: {}", mode, e);
+        }
+    }
+    if backend
+        .extra_keys
+        .set_visible(backend.soft_keyboard.visible())
+    {
+        compositor.state.needs_redraw = true;
+    }
+    make_room_for_keyboard(backend);
+    repeat_key(backend);
+    if let Some(winit) = backend.graphic_renderer.as_ref() {
+        backend
+            .presentation_feedbacks
+            .present(winit, &backend.compositor.clock);
+    }
+    if let Some(gesture) = backend.gestures.poll_long_press(Instant::now()) {
+        handle_gesture(backend, gesture);
+    }
+
+    // The splash follows the launch stages on its own. Whatever changed meanwhile is drawn on the next vsync, at most
+    // once per refresh of the display.
+    if (backend.compositor.state.needs_redraw || backend.splash.visible())
+        && backend.choreographer.frame_due()
+    {
+        if let Some(winit) = backend.graphic_renderer.as_ref() {
+            winit.window().request_redraw();
+        }
+    }
+}
+
+pub fn handle(
+    event: CentralizedEvent,
+    backend: &mut WaylandBackend,
+    android_app: &AndroidApp,
+    event_loop: &ActiveEventLoop,
+) {
+    if matches!(
+        event,
+        CentralizedEvent::Input(_)
+            | CentralizedEvent::Text(_)
+            | CentralizedEvent::Gesture(_)
+            | CentralizedEvent::Stylus(_)
+            | CentralizedEvent::GamepadButton { .. }
+            | CentralizedEvent::GamepadAxis { .. }
+    ) {
+        backend.screen_power.input(Instant::now());
+    }
     match event {
         CentralizedEvent::CloseRequested => {
             log::info!("The close button was pressed; stopping");
             event_loop.exit();
         }
+        CentralizedEvent::Resized { size, scale_factor } => {
+            // The damage of the previous frames does not apply to buffers of another size
+            backend.damage_tracker = None;
+            // Rotating or unfolding the device resizes the window, which the desktop follows rather than being
+            // letterboxed at its old size. The EGL surface follows it on the next bind.
+            let size = size.to_logical(1);
+            let compositor = &mut backend.compositor;
+            if compositor.state.size != size {
+                log::info!("The window was resized to {:?}", size);
+                // The zoomed in part may be past the edges of the new output
+                backend.zoom = Zoom::default();
+                compositor.resize_output(size);
+            }
+            // Split screen, picture-in-picture and freeform windows shrink the desktop rather than its room
+            let scale = layout::compact_scale((size.w, size.h), scale_factor);
+            if scale < scale_factor {
+                log::info!(
+                    "The window is small, the apps render at {:.2} rather than {:.2}",
+                    scale,
+                    scale_factor
+                );
+            }
+            compositor.rescale_output(scale);
+            compositor.state.needs_redraw = true;
+        }
         CentralizedEvent::Redraw => {
+            flush_touch_motions(backend);
             if let Some(winit) = backend.graphic_renderer.as_mut() {
                 let size = winit.window_size();
-                let damage = Rectangle::from_size(size);
-                {
-                    let (renderer, mut framebuffer) = winit.bind().unwrap();
+                let compositor = &mut backend.compositor;
+                compositor.state.needs_redraw = false;
+                // A screenshot needs the whole frame, not only what changed since the buffer was last drawn
+                let screenshot = std::mem::take(&mut backend.screenshot_requested);
+                let age = if screenshot {
+                    0
+                } else {
+                    winit.buffer_age().unwrap_or(0)
+                };
+                let mut frame_damage = None;
+                let mut clear_color = backend.wallpaper.color();
 
-                    let compositor = &mut backend.compositor;
+                match winit.bind() {
+                    Ok((renderer, mut framebuffer)) => {
+                        import_pending_dmabufs(renderer, &mut compositor.state);
+                        quarantine_unimportable_clients(renderer, compositor);
 
-                    let elements = compositor
-                        .state
-                        .xdg_shell_state
-                        .toplevel_surfaces()
-                        .iter()
-                        .flat_map(|surface| {
-                            render_elements_from_surface_tree(
-                                renderer,
-                                surface.wl_surface(),
-                                (0, 0),
-                                1.0,
-                                1.0,
-                                Kind::Unspecified,
-                            )
-                        })
-                        .collect::<Vec<WaylandSurfaceRenderElement<GlesRenderer>>>();
-
-                    let mut frame = renderer
-                        .render(&mut framebuffer, size, Transform::Flipped180)
-                        .unwrap();
-                    frame
-                        .clear(Color32F::new(0.1, 0.0, 0.0, 1.0), &[damage])
-                        .unwrap();
-                    draw_render_elements(&mut frame, 1.0, &elements, &[damage]).unwrap();
-                    // We rely on the nested compositor to do the sync for us
-                    let _ = frame.finish().unwrap();
-
-                    for surface in compositor.state.xdg_shell_state.toplevel_surfaces() {
-                        send_frames_surface_tree(
-                            surface.wl_surface(),
-                            compositor.start_time.elapsed().as_millis() as u32,
+                        // Elements are ordered front to back, so the keyboard owner indicator and the cursor go first, and the wallpaper last
+                        let mut elements = backend
+                            .focus
+                            .indicator(size)
+                            .into_iter()
+                            .map(WindowRenderElement::from)
+                            .collect::<Vec<WindowRenderElement<GlesRenderer>>>();
+                        // The cursor keeps its size when zoomed in
+                        let zoom = backend.zoom;
+                        let pointer_location =
+                            zoom.to_screen(compositor.pointer.current_location());
+                        elements.extend(
+                            compositor
+                                .state
+                                .cursor
+                                .render_elements(renderer, pointer_location),
+                        );
+                        if let Some(icon) = &compositor.state.dnd_icon {
+                            elements.extend(icon.render_elements(renderer, pointer_location));
+                        }
+                        elements.extend(backend.extra_keys.render_elements(renderer, size));
+                        elements.extend(backend.splash.render_elements(renderer, size));
+                        let windows = drawn_surfaces(&compositor.state);
+                        let render_cache = &mut compositor.state.render_cache;
+                        for (surface, location) in windows.iter().rev() {
+                            let location = zoom
+                                .to_screen(location.to_f64())
+                                .to_physical(1.0)
+                                .to_i32_round();
+                            elements.extend(
+                                render_cache
+                                    .elements(renderer, surface, location, zoom.scale())
+                                    .into_iter()
+                                    .map(WindowRenderElement::from),
+                            );
+                        }
+                        render_cache.retain(
+                            &windows
+                                .into_iter()
+                                .map(|(surface, _)| surface)
+                                .collect::<Vec<_>>(),
+                        );
+                        elements.extend(
+                            backend
+                                .wallpaper
+                                .element(renderer, size)
+                                .map(WindowRenderElement::from),
                         );
-                    }
 
-                    if let Some(stream) = compositor
-                        .listener
-                        .accept()
-                        .pb_expect("Failed to accept listener")
-                    {
-                        log::info!("Got a client: {:?}", stream);
-
-                        let client = compositor
-                            .display
-                            .handle()
-                            .insert_client(stream, Arc::new(ClientState::default()))
-                            .unwrap();
-                        compositor.clients.push(client);
+                        // A blanked output shows nothing at all, as a monitor in power saving
+                        let blank = !compositor.state.output_power_state.is_on();
+                        if blank {
+                            elements.clear();
+                        }
+                        if blank {
+                            clear_color = Color32F::BLACK;
+                        }
+                        let damage_tracker = backend.damage_tracker.get_or_insert_with(|| {
+                            OutputDamageTracker::new(size, 1.0, Transform::Flipped180)
+                        });
+                        match damage_tracker.render_output(
+                            renderer,
+                            &mut framebuffer,
+                            age,
+                            &elements,
+                            clear_color,
+                        ) {
+                            Ok(result) => {
+                                startup::mark("first frame");
+                                frame_damage = result.damage.cloned()
+                            }
+                            Err(e) => log::error!("Failed to render frame: {:?}", e),
+                        }
+                        if screenshot {
+                            take_screenshot(renderer, &framebuffer, size, android_app);
+                        }
                     }
+                    Err(e) => log::error!("Failed to bind the window for rendering: {:?}", e),
+                }
 
-                    compositor
-                        .display
-                        .dispatch_clients(&mut compositor.state)
-                        .pb_expect("Failed to dispatch clients");
-                    compositor
-                        .display
-                        .flush_clients()
-                        .pb_expect("Failed to flush clients");
+                if has_client_frame(compositor) {
+                    startup::mark("first client frame");
+                    backend.splash.finish();
                 }
 
+                // The clients stop drawing while nobody sees them
+                if compositor.state.output_power_state.is_on() {
+                    let time = compositor.clock.now().as_millis();
+                    for (surface, _) in drawn_surfaces(&compositor.state) {
+                        send_frames_surface_tree(&surface, time);
+                    }
+                    if let Some(surface) = compositor.state.cursor.surface() {
+                        send_frames_surface_tree(surface, time);
+                    }
+                    if let Some(icon) = &compositor.state.dnd_icon {
+                        send_frames_surface_tree(&icon.surface, time);
+                    }
+                }
+
+                service_clients(compositor);
+
                 // It is important that all events on the display have been dispatched and flushed to clients before
                 // swapping buffers because this operation may block.
-                winit.submit(Some(&[damage])).unwrap();
+                // Nothing is swapped when nothing changed on screen.
+                if let Some(damage) = frame_damage {
+                    let feedback = take_presentation_feedback(compositor);
+                    let frame = winit.next_frame_id();
+                    match winit.submit(Some(&damage)) {
+                        Ok(()) => {
+                            if let Some(feedback) = feedback {
+                                backend.presentation_feedbacks.submitted(frame, feedback);
+                            }
+                        }
+                        Err(e) => log::error!("Failed to submit frame: {:?}", e),
+                    }
+                }
+                backend
+                    .presentation_feedbacks
+                    .present(winit, &compositor.clock);
+                render_external(winit, compositor, clear_color);
+                render_recording(winit, compositor, clear_color);
+                serve_screencopies(winit, compositor, clear_color);
             }
-
-            // Redraw the application.
-            //
-            // It's preferable for applications that do not render continuously to render in
-            // this event rather than in AboutToWait, since rendering in here allows
-            // the program to gracefully handle redraws requested by the OS.
-
-            // Draw.
-
-            // Queue a RedrawRequested event.
-            //
-            // You only need to call this if you've determined that you need to redraw in
-            // applications which do not always need to. Applications that redraw continuously
-            // can render here instead.
-            backend
-                .graphic_renderer
-                .as_ref()
-                .unwrap()
-                .window()
-                .request_redraw();
-        }
-        CentralizedEvent::Input(event) => match event {
-            InputEvent::Keyboard { event } => {
-                let compositor = &mut backend.compositor;
-                let state = &mut compositor.state;
-                let serial = SERIAL_COUNTER.next_serial();
-                let time = compositor.start_time.elapsed().as_millis() as u32;
-                compositor.keyboard.input::<(), _>(
-                    state,
-                    event.key_code(),
-                    event.state(),
-                    serial,
-                    time,
-                    |_, _, _| {
-                        //
-                        FilterResult::Forward
-                    },
-                );
+        }
+        CentralizedEvent::Focus(focused) => {
+            backend.compositor.state.needs_redraw = true;
+            if !focused {
+                // Android releases the mouse capture with the focus, it is requested again when back
+                backend.pointer_captured = false;
             }
-            InputEvent::TouchDown { event } => {
+            // Losing the window focus means a host UI overlay (or another Android app) took over the keyboard
+            let owner = if focused {
+                KeyboardOwner::Clients
+            } else {
+                KeyboardOwner::HostOverlay
+            };
+            if backend.focus.hand_over(owner) {
                 let compositor = &mut backend.compositor;
                 let state = &mut compositor.state;
-                if let Some(surface) = get_surface(state) {
-                    compositor.keyboard.set_focus(
-                        state,
-                        Some(surface.wl_surface().clone()),
-                        0.into(),
-                    );
+                // Leaving the client surface releases its pressed keys, so nothing stays stuck while the overlay is in front
+                let focus = match owner {
+                    KeyboardOwner::Clients => get_surface(state),
+                    KeyboardOwner::HostOverlay => None,
+                };
+                compositor
+                    .keyboard
+                    .set_focus(state, focus, SERIAL_COUNTER.next_serial());
+                backend.key_counter = 0;
+                backend.key_repeat.cancel();
+
+                if owner == KeyboardOwner::Clients {
+                    // Back to the desktop, so any overlay has been dismissed
+                    close_webview_overlay(android_app.clone());
+                }
+            }
+            if focused {
+                // Something may have been copied in another Android app meanwhile
+                let state = &mut backend.compositor.state;
+                let dh = state.display_handle.clone();
+                state.clipboard.pull_from_android(&dh, state.xwm.as_mut());
+            }
+        }
+        CentralizedEvent::Text(text) => {
+            telemetry::record("input", "soft keyboard");
+            if !backend.focus.clients_own_keyboard() {
+                return;
+            }
+            // With Ctrl or Alt latched on the extra keys, a letter typed on the soft keyboard becomes a shortcut
+            let mut chars = text.chars();
+            let scancode = match (chars.next(), chars.next()) {
+                (Some(c), None) if backend.extra_keys.has_latched_modifiers() => {
+                    char_to_scancode(c)
+                }
+                _ => None,
+            };
+            if let Some(scancode) = scancode {
+                let scancodes = backend.extra_keys.chord(scancode);
+                type_keys(&mut backend.compositor, &scancodes);
+                backend.compositor.state.needs_redraw = true;
+            } else if !backend.compositor.state.text_input_state.commit_text(&text) {
+                log::debug!("Dropping {:?}, the focused client has no text input", text);
+            }
+        }
+        CentralizedEvent::Back => {
+            let action = CompositorAction::of_back(
+                get_application_context()
+                    .local_config
+                    .gestures
+                    .back_action(),
+            );
+            run_action(backend, android_app, action);
+        }
+        CentralizedEvent::Stylus(event) => {
+            backend.compositor.state.needs_redraw = true;
+            backend.compositor.state.output_power_state.wake();
+            handle_stylus(backend, event);
+        }
+        CentralizedEvent::GamepadButton {
+            keycode,
+            pressed,
+            time,
+        } => {
+            telemetry::record("input", "gamepad");
+            gamepad::press(keycode, pressed, Duration::from_micros(time));
+        }
+        CentralizedEvent::GamepadAxis { axis, value, time } => {
+            gamepad::move_axis(axis, value, Duration::from_micros(time));
+        }
+        CentralizedEvent::Gesture(gesture) => {
+            if let Some(action) = handle_gesture(backend, gesture) {
+                run_action(backend, android_app, action);
+            }
+        }
+        CentralizedEvent::Input(event) => {
+            // The cursor and the clients may respond to any input
+            backend.compositor.state.needs_redraw = true;
+            backend.compositor.state.output_power_state.wake();
+            match event {
+                InputEvent::Keyboard { .. } if !backend.focus.clients_own_keyboard() => {
+                    log::trace!("Dropping key event, the keyboard is owned by a host overlay");
+                }
+                InputEvent::Keyboard { event } => {
+                    telemetry::record("input", "keyboard");
+                    let compositor = &mut backend.compositor;
+                    let state = &mut compositor.state;
                     let serial = SERIAL_COUNTER.next_serial();
-                    let time = compositor.start_time.elapsed().as_millis() as u32;
-                    compositor.touch.down(
+                    let time = event.time_msec();
+                    let key_state = event.state();
+                    let key_code = event.key_code();
+                    let mut repeats = false;
+                    let action = compositor.keyboard.input::<CompositorAction, _>(
                         state,
-                        Some((surface.wl_surface().clone(), (0f64, 0f64).into())),
-                        &touch::DownEvent {
-                            slot: event.slot(),
-                            location: (event.x(), event.y()).into(),
-                            serial,
-                            time,
+                        key_code,
+                        key_state,
+                        serial,
+                        time,
+                        |_, modifiers, keysym| {
+                            if key_state == KeyState::Pressed {
+                                if let Some(action) = match_shortcut(modifiers, &keysym) {
+                                    return FilterResult::Intercept(action);
+                                }
+                                repeats = !keysym.modified_sym().is_modifier_key();
+                            }
+                            FilterResult::Forward
                         },
                     );
-                };
-            }
-            InputEvent::TouchUp { event } => {
-                let compositor = &mut backend.compositor;
-                let state = &mut compositor.state;
-                if let Some(_surface) = get_surface(state) {
+                    match key_state {
+                        KeyState::Pressed if repeats => {
+                            backend.key_repeat.press(key_code.raw(), Instant::now())
+                        }
+                        KeyState::Pressed => {}
+                        KeyState::Released => backend.key_repeat.release(key_code.raw()),
+                    }
+                    if let Some(action) = action {
+                        run_action(backend, android_app, action);
+                    }
+                }
+                InputEvent::TouchDown { event } => {
+                    telemetry::record("input", "touch");
+                    // The fingers that moved meanwhile are reported first, as they happened before
+                    flush_touch_motions(backend);
+                    if tap_extra_key(backend, event.slot(), (event.x(), event.y()).into()) {
+                        return;
+                    }
+                    let location = desktop_location(backend, (event.x(), event.y()).into());
+                    let compositor = &mut backend.compositor;
+                    let state = &mut compositor.state;
+                    state.cursor.hide();
+                    if let Some((window, focus)) = window_under(state, location) {
+                        focus_window(compositor, &window);
+                        let state = &mut compositor.state;
+                        let serial = SERIAL_COUNTER.next_serial();
+                        let time = event.time_msec();
+                        compositor.touch.down(
+                            state,
+                            Some(focus),
+                            &touch::DownEvent {
+                                slot: event.slot(),
+                                location,
+                                serial,
+                                time,
+                            },
+                        );
+                        compositor.touch.frame(&mut compositor.state);
+                    };
+                }
+                InputEvent::TouchUp { event } => {
+                    flush_touch_motions(backend);
+                    if backend.extra_keys.touch_up(event.slot()) {
+                        return;
+                    }
+                    let compositor = &mut backend.compositor;
+                    let state = &mut compositor.state;
                     let serial = SERIAL_COUNTER.next_serial();
-                    let time = compositor.start_time.elapsed().as_millis() as u32;
+                    let time = event.time_msec();
                     compositor.touch.up(
                         state,
                         &touch::UpEvent {
@@ -218,134 +1417,126 @@ pub fn handle(event: CentralizedEvent, backend: &mut WaylandBackend, event_loop:
                             time,
                         },
                     );
-                };
-            }
-            InputEvent::TouchMotion { event } => {
-                let compositor = &mut backend.compositor;
-                let state = &mut compositor.state;
-                if let Some(surface) = get_surface(state) {
-                    let time = compositor.start_time.elapsed().as_millis() as u32;
-                    compositor.touch.motion(
-                        state,
-                        Some((surface.wl_surface().clone(), (0f64, 0f64).into())),
-                        &touch::MotionEvent {
-                            slot: event.slot(),
-                            location: (event.x(), event.y()).into(),
-                            time,
-                        },
-                    );
-                };
-            }
-            InputEvent::PointerMotionAbsolute { event, .. } => {
-                let compositor = &mut backend.compositor;
-                let pointer = compositor.pointer.clone();
-                let space = &compositor.state.space;
-                let serial = SERIAL_COUNTER.next_serial();
-
-                let max_x = space
-                    .outputs()
-                    .fold(0, |acc, o| acc + space.output_geometry(o).unwrap().size.w);
-
-                let max_h_output = space
-                    .outputs()
-                    .max_by_key(|o| space.output_geometry(o).unwrap().size.h)
-                    .unwrap();
-
-                let max_y = space.output_geometry(max_h_output).unwrap().size.h;
-
-                let mut pointer_location =
-                    (event.x_transformed(max_x), event.y_transformed(max_y)).into();
-
-                // clamp to screen limits
-                pointer_location = clamp_coords(space, pointer_location);
+                    compositor.touch.frame(&mut compositor.state);
+                }
+                InputEvent::TouchCancel { .. } => {
+                    // Whatever the fingers did is void
+                    backend.touch_motions.take();
+                    backend.extra_keys.touch_cancel();
+                    let compositor = &mut backend.compositor;
+                    compositor.touch.cancel(&mut compositor.state);
+                }
+                InputEvent::TouchMotion { event }
+                    if backend.extra_keys.owns_touch(event.slot()) => {}
+                InputEvent::TouchMotion { event } => {
+                    let motion = TouchMotion {
+                        slot: event.slot(),
+                        location: desktop_location(backend, (event.x(), event.y()).into()),
+                        time: event.time_msec(),
+                    };
+                    if backend.touch_motions.push(motion) {
+                        flush_touch_motions(backend);
+                    }
+                }
+                InputEvent::PointerMotionAbsolute { event, .. } => {
+                    let pointer_location = desktop_location(backend, (event.x(), event.y()).into());
+                    let compositor = &mut backend.compositor;
+                    let pointer = compositor.pointer.clone();
+                    let serial = SERIAL_COUNTER.next_serial();
+                    compositor.state.cursor.show();
 
-                if let Some(surface) = get_surface(&compositor.state) {
+                    let focus = surface_under(&compositor.state, pointer_location);
                     pointer.motion(
                         &mut compositor.state,
-                        Some((surface.wl_surface().clone(), (0f64, 0f64).into())),
+                        focus,
                         &pointer::MotionEvent {
                             location: pointer_location,
                             serial,
                             time: event.time_msec(),
                         },
                     );
+                    pointer.frame(&mut compositor.state);
+                    activate_pointer_constraint(compositor);
                 }
-                pointer.frame(&mut compositor.state);
-            }
-            InputEvent::PointerButton { event, .. } => {
-                let serial = SERIAL_COUNTER.next_serial();
-                let button = event.button_code();
+                InputEvent::PointerMotion { event } => {
+                    relative_motion(backend, event.delta(), event.time());
+                }
+                InputEvent::PointerButton { event, .. } => {
+                    telemetry::record("input", "mouse");
+                    let serial = SERIAL_COUNTER.next_serial();
+                    let button = event.button_code();
 
-                let state = ButtonState::from(event.state());
+                    let state = ButtonState::from(event.state());
 
-                let compositor = &mut backend.compositor;
-                let pointer = compositor.pointer.clone();
+                    let compositor = &mut backend.compositor;
+                    let pointer = compositor.pointer.clone();
 
-                if let Some(surface) = get_surface(&compositor.state) {
-                    compositor.keyboard.set_focus(
+                    if state == ButtonState::Pressed {
+                        if let Some((window, _)) =
+                            window_under(&compositor.state, pointer.current_location())
+                        {
+                            focus_window(compositor, &window);
+                        }
+                    }
+                    pointer.button(
                         &mut compositor.state,
-                        Some(surface.wl_surface().clone()),
-                        0.into(),
+                        &pointer::ButtonEvent {
+                            button,
+                            state: state.try_into().unwrap(),
+                            serial,
+                            time: event.time_msec(),
+                        },
                     );
+                    pointer.frame(&mut compositor.state);
                 }
-                pointer.button(
-                    &mut compositor.state,
-                    &pointer::ButtonEvent {
-                        button,
-                        state: state.try_into().unwrap(),
-                        serial,
-                        time: event.time_msec(),
-                    },
-                );
-                pointer.frame(&mut compositor.state);
-            }
-            InputEvent::PointerAxis { event } => {
-                let horizontal_amount = event
-                    .amount(Axis::Horizontal)
-                    .unwrap_or_else(|| event.amount_v120(Axis::Horizontal).unwrap_or(0.0) / 120.);
-                let vertical_amount = event
-                    .amount(Axis::Vertical)
-                    .unwrap_or_else(|| event.amount_v120(Axis::Vertical).unwrap_or(0.0) / 120.);
-                let horizontal_amount_discrete = event.amount_v120(Axis::Horizontal);
-                let vertical_amount_discrete = event.amount_v120(Axis::Vertical);
-
-                {
-                    let mut frame =
-                        pointer::AxisFrame::new(event.time_msec()).source(event.source());
-                    if horizontal_amount != 0.0 {
-                        frame = frame.relative_direction(
-                            Axis::Horizontal,
-                            event.relative_direction(Axis::Horizontal),
-                        );
-                        frame = frame.value(Axis::Horizontal, horizontal_amount);
-                        if let Some(discrete) = horizontal_amount_discrete {
-                            frame = frame.v120(Axis::Horizontal, discrete as i32);
+                InputEvent::PointerAxis { event } => {
+                    let horizontal_amount = event.amount(Axis::Horizontal).unwrap_or_else(|| {
+                        event.amount_v120(Axis::Horizontal).unwrap_or(0.0) / 120.
+                    });
+                    let vertical_amount = event
+                        .amount(Axis::Vertical)
+                        .unwrap_or_else(|| event.amount_v120(Axis::Vertical).unwrap_or(0.0) / 120.);
+                    let horizontal_amount_discrete = event.amount_v120(Axis::Horizontal);
+                    let vertical_amount_discrete = event.amount_v120(Axis::Vertical);
+
+                    {
+                        let mut frame =
+                            pointer::AxisFrame::new(event.time_msec()).source(event.source());
+                        if horizontal_amount != 0.0 {
+                            frame = frame.relative_direction(
+                                Axis::Horizontal,
+                                event.relative_direction(Axis::Horizontal),
+                            );
+                            frame = frame.value(Axis::Horizontal, horizontal_amount);
+                            if let Some(discrete) = horizontal_amount_discrete {
+                                frame = frame.v120(Axis::Horizontal, discrete as i32);
+                            }
                         }
-                    }
-                    if vertical_amount != 0.0 {
-                        frame = frame.relative_direction(
-                            Axis::Vertical,
-                            event.relative_direction(Axis::Vertical),
-                        );
-                        frame = frame.value(Axis::Vertical, vertical_amount);
-                        if let Some(discrete) = vertical_amount_discrete {
-                            frame = frame.v120(Axis::Vertical, discrete as i32);
+                        if vertical_amount != 0.0 {
+                            frame = frame.relative_direction(
+                                Axis::Vertical,
+                                event.relative_direction(Axis::Vertical),
+                            );
+                            frame = frame.value(Axis::Vertical, vertical_amount);
+                            if let Some(discrete) = vertical_amount_discrete {
+                                frame = frame.v120(Axis::Vertical, discrete as i32);
+                            }
                         }
+                        if event.amount(Axis::Horizontal) == Some(0.0) {
+                            frame = frame.stop(Axis::Horizontal);
+                        }
+                        if event.amount(Axis::Vertical) == Some(0.0) {
+                            frame = frame.stop(Axis::Vertical);
+                        }
+                        let compositor = &mut backend.compositor;
+                        let pointer = compositor.pointer.clone();
+                        pointer.axis(&mut compositor.state, frame);
+                        pointer.frame(&mut compositor.state);
                     }
-                    if event.amount(Axis::Horizontal) == Some(0.0) {
-                        frame = frame.stop(Axis::Horizontal);
-                    }
-                    if event.amount(Axis::Vertical) == Some(0.0) {
-                        frame = frame.stop(Axis::Vertical);
-                    }
-                    let compositor = &mut backend.compositor;
-                    let pointer = compositor.pointer.clone();
-                    pointer.axis(&mut compositor.state, frame);
-                    pointer.frame(&mut compositor.state);
                 }
+                _ => {}
             }
-            _ => {}
-        },
+        }
         _ => (),
     }
 }
@@ -0,0 +1,286 @@
+use super::{
+    compositor::EXTERNAL_OUTPUT,
+    presentation::{show_view, ViewSurface},
+    State, WaylandBackend,
+};
+use crate::android::{
+    app::attach::{attached_app, wake_event_loop},
+    utils::{application_context::get_application_context, ndk::run_in_jvm},
+};
+use crate::core::{
+    config::{ExternalDisplay, Presentation, VERSION},
+    diagnostics,
+};
+use jni::objects::{GlobalRef, JObject, JObjectArray, JValue};
+use jni::JNIEnv;
+use smithay::backend::egl::EGLSurface;
+use smithay::output::{Mode, Output, PhysicalProperties, Subpixel};
+use smithay::reexports::wayland_server::backend::GlobalId;
+use smithay::utils::{Physical, Size};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+    thread,
+    time::Duration,
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// How often the displays are listed, as there is no listener for them without a Java class of our own
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// `DisplayManager.DISPLAY_CATEGORY_PRESENTATION`: the displays an app can show content on, besides its own
+const PRESENTATION_CATEGORY: &str = "android.hardware.display.category.PRESENTATION";
+
+enum Change {
+    Connected(ViewSurface),
+    Disconnected,
+}
+
+/// The change the watcher noticed, until the main thread applies it
+static CHANGE: Mutex<Option<Change>> = Mutex::new(None);
+
+/// Whether the desktop is drawn on an external display, cleared as its target is dropped
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+static WATCH: Once = Once::new();
+
+/// Where the desktop is drawn on a display plugged into the device, with the EGL context of the window
+#[derive(Debug)]
+pub struct ExternalTarget {
+    pub surface: EGLSurface,
+    pub size: Size<i32, Physical>,
+    /// Applied once the surface is first bound, so that it never waits for the vsync of the other display
+    pub swap_interval: Option<u32>,
+    /// Dismissed after the EGL surface is gone
+    _view: ViewSurface,
+}
+
+impl ExternalTarget {
+    pub fn new(surface: EGLSurface, view: ViewSurface) -> Self {
+        SHOWN.store(true, Ordering::Relaxed);
+        Self {
+            surface,
+            size: view.size(),
+            swap_interval: Some(0),
+            _view: view,
+        }
+    }
+}
+
+impl Drop for ExternalTarget {
+    fn drop(&mut self) {
+        SHOWN.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Watch (once) for the displays plugged in or out, waking the event loop up for the main thread to draw on them.
+/// A display is shown as soon as the previous one is gone, e.g. after the window was recreated.
+pub fn watch_external_displays() {
+    if get_application_context()
+        .local_config
+        .display
+        .external_display()
+        == ExternalDisplay::Off
+    {
+        return;
+    }
+    WATCH.call_once(|| {
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            // The presentations belong to the activity, which may have been recreated meanwhile
+            let Some(android_app) = attached_app() else {
+                continue;
+            };
+            let pending = CHANGE.lock().unwrap().is_some();
+            let plugged = !pending && has_presentation_display(&android_app);
+            let change = match (plugged, SHOWN.load(Ordering::Relaxed)) {
+                _ if pending => None,
+                (true, false) => {
+                    log::info!("An external display was plugged in");
+                    show_view(&android_app, Presentation::SurfaceView, create_presentation)
+                        .map(Change::Connected)
+                }
+                (false, true) => {
+                    log::info!("The external display was unplugged");
+                    Some(Change::Disconnected)
+                }
+                _ => None,
+            };
+            if let Some(change) = change {
+                CHANGE.lock().unwrap().replace(change);
+                wake_event_loop();
+            }
+        });
+    });
+}
+
+/// Draw on the display plugged in, or stop drawing on the one unplugged, since the last call
+pub fn sync_external_display(backend: &mut WaylandBackend) {
+    let Some(change) = CHANGE.lock().unwrap().take() else {
+        return;
+    };
+    let Some(winit) = backend.graphic_renderer.as_mut() else {
+        return;
+    };
+    let mode = get_application_context()
+        .local_config
+        .display
+        .external_display();
+    let compositor = &mut backend.compositor;
+    match change {
+        Change::Connected(view) => match winit.create_surface(view.handle()) {
+            Ok(surface) => {
+                let target = ExternalTarget::new(surface, view);
+                log::info!("Drawing on an external display of {:?}", target.size);
+                if mode == ExternalDisplay::Span {
+                    let output = span_output(&mut compositor.state, target.size);
+                    compositor.external_output = Some(output);
+                    compositor.place_external_output();
+                }
+                diagnostics::publish(
+                    "external display",
+                    serde_json::json!({
+                        "size": format!("{}x{}", target.size.w, target.size.h),
+                        "mode": format!("{:?}", mode),
+                    }),
+                );
+                winit.external = Some(target);
+            }
+            Err(e) => log::warn!("Failed to draw on the external display: {:?}", e),
+        },
+        Change::Disconnected => {
+            winit.external = None;
+            if let Some((output, global)) = compositor.external_output.take() {
+                let state = &mut compositor.state;
+                state.space.unmap_output(&output);
+                state.globals.retain(|it| *it != global);
+                state.display_handle.remove_global::<State>(global);
+            }
+            diagnostics::publish("external display", serde_json::Value::Null);
+        }
+    }
+    compositor.state.needs_redraw = true;
+}
+
+/// A second output for the clients, which the desktop spans onto
+fn span_output(state: &mut State, size: Size<i32, Physical>) -> (Output, GlobalId) {
+    let output = Output::new(
+        EXTERNAL_OUTPUT.into(),
+        PhysicalProperties {
+            // Android does not tell how large the display is
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "Local Desktop".into(),
+            model: VERSION.into(),
+        },
+    );
+    let mode = Mode {
+        size,
+        refresh: 60000,
+    };
+    output.change_current_state(Some(mode), None, None, None);
+    output.set_preferred(mode);
+    let global = output.create_global::<State>(&state.display_handle);
+    state.globals.push(global.clone());
+    (output, global)
+}
+
+fn has_presentation_display(android_app: &AndroidApp) -> bool {
+    let mut found = false;
+    run_in_jvm(
+        |env, app| match presentation_display(env, app) {
+            Ok(display) => found = display.is_some(),
+            Err(e) => {
+                let _ = env.exception_clear();
+                log::warn!("Failed to list the displays: {:?}", e);
+            }
+        },
+        android_app.clone(),
+    );
+    found
+}
+
+/// The first display the app can show a presentation on, if any
+fn presentation_display<'local>(
+    env: &mut JNIEnv<'local>,
+    android_app: &AndroidApp,
+) -> jni::errors::Result<Option<JObject<'local>>> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let service = env.new_string("display")?;
+    let manager = env
+        .call_method(
+            &activity,
+            "getSystemService",
+            "(Ljava/lang/String;)Ljava/lang/Object;",
+            &[JValue::Object(&service)],
+        )?
+        .l()?;
+    let category = env.new_string(PRESENTATION_CATEGORY)?;
+    let displays = JObjectArray::from(
+        env.call_method(
+            &manager,
+            "getDisplays",
+            "(Ljava/lang/String;)[Landroid/view/Display;",
+            &[JValue::Object(&category)],
+        )?
+        .l()?,
+    );
+    if env.get_array_length(&displays)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(env.get_object_array_element(&displays, 0)?))
+}
+
+/// A presentation showing a full screen `SurfaceView` on the external display
+fn create_presentation<'local>(
+    env: &mut JNIEnv<'local>,
+    android_app: &AndroidApp,
+    _: Presentation,
+) -> jni::errors::Result<(JObject<'local>, GlobalRef, GlobalRef)> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let Some(display) = presentation_display(env, android_app)? else {
+        return Err(jni::errors::Error::NullPtr("no external display"));
+    };
+    env.call_static_method("android/os/Looper", "prepare", "()V", &[])?;
+    let presentation = env.new_object(
+        "android/app/Presentation",
+        "(Landroid/content/Context;Landroid/view/Display;)V",
+        &[JValue::Object(&activity), JValue::Object(&display)],
+    )?;
+    // The context of the presentation has the metrics of the external display
+    let context = env
+        .call_method(
+            &presentation,
+            "getContext",
+            "()Landroid/content/Context;",
+            &[],
+        )?
+        .l()?;
+    let view = env.new_object(
+        "android/view/SurfaceView",
+        "(Landroid/content/Context;)V",
+        &[JValue::Object(&context)],
+    )?;
+    env.call_method(
+        &presentation,
+        "setContentView",
+        "(Landroid/view/View;)V",
+        &[JValue::Object(&view)],
+    )?;
+    env.call_method(&presentation, "show", "()V", &[])?;
+    let looper = env
+        .call_static_method(
+            "android/os/Looper",
+            "myLooper",
+            "()Landroid/os/Looper;",
+            &[],
+        )?
+        .l()?;
+    Ok((
+        presentation,
+        env.new_global_ref(looper)?,
+        env.new_global_ref(view)?,
+    ))
+}
@@ -0,0 +1,298 @@
+use super::element::WindowRenderElement;
+use crate::android::utils::{ndk::run_in_jvm, text::rasterize_text};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::input::TouchSlot;
+use smithay::backend::renderer::{
+    element::{
+        solid::SolidColorRenderElement,
+        texture::{TextureBuffer, TextureRenderElement},
+        Id, Kind,
+    },
+    gles::{GlesRenderer, GlesTexture},
+    utils::CommitCounter,
+    Color32F,
+};
+use smithay::utils::{Logical, Physical, Point, Rectangle, Size, Transform};
+use winit::platform::android::activity::AndroidApp;
+
+/// Height (in pixels) of the toolbar
+const BAR_HEIGHT: i32 = 96;
+
+const TEXT_SIZE: f32 = 36.0;
+
+const TEXT_COLOR: u32 = 0xFFEEEEEE;
+
+const BAR_COLOR: Color32F = Color32F::new(0.1, 0.1, 0.1, 0.9);
+
+/// Behind the modifiers applying to the next key
+const LATCHED_COLOR: Color32F = Color32F::new(0.2, 0.45, 0.8, 1.0);
+
+/// The keys Android soft keyboards cannot type, from left to right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtraKey {
+    Esc,
+    Ctrl,
+    Alt,
+    Tab,
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+const KEYS: [ExtraKey; 8] = [
+    ExtraKey::Esc,
+    ExtraKey::Ctrl,
+    ExtraKey::Alt,
+    ExtraKey::Tab,
+    ExtraKey::Left,
+    ExtraKey::Down,
+    ExtraKey::Up,
+    ExtraKey::Right,
+];
+
+impl ExtraKey {
+    fn label(&self) -> &'static str {
+        match self {
+            ExtraKey::Esc => "ESC",
+            ExtraKey::Ctrl => "CTRL",
+            ExtraKey::Alt => "ALT",
+            ExtraKey::Tab => "TAB",
+            ExtraKey::Left => "←",
+            ExtraKey::Down => "↓",
+            ExtraKey::Up => "↑",
+            ExtraKey::Right => "→",
+        }
+    }
+
+    /// The evdev scancode of the key
+    pub fn scancode(&self) -> u32 {
+        match self {
+            ExtraKey::Esc => 1,
+            ExtraKey::Ctrl => 29,
+            ExtraKey::Alt => 56,
+            ExtraKey::Tab => 15,
+            ExtraKey::Left => 105,
+            ExtraKey::Down => 108,
+            ExtraKey::Up => 103,
+            ExtraKey::Right => 106,
+        }
+    }
+
+    fn is_modifier(&self) -> bool {
+        matches!(self, ExtraKey::Ctrl | ExtraKey::Alt)
+    }
+}
+
+/// A Termux-style row of Esc, Ctrl, Alt, Tab and arrow keys, shown along with the soft keyboard.
+/// It is drawn along the top edge of the output, as the soft keyboard covers the bottom of it.
+/// Ctrl and Alt are sticky: they apply to the next key, typed on the toolbar or on the soft keyboard.
+pub struct ExtraKeys {
+    android_app: AndroidApp,
+    visible: bool,
+    latched: Vec<ExtraKey>,
+    /// The touch points that started on the toolbar, kept away from the clients until lifted
+    touches: Vec<TouchSlot>,
+    labels: Vec<Option<(TextureBuffer<GlesTexture>, Size<i32, Physical>)>>,
+    bar_id: Id,
+    key_ids: Vec<Id>,
+    commit: CommitCounter,
+}
+
+impl ExtraKeys {
+    pub fn new(android_app: AndroidApp) -> Self {
+        Self {
+            android_app,
+            visible: false,
+            latched: vec![],
+            touches: vec![],
+            labels: vec![],
+            bar_id: Id::new(),
+            key_ids: KEYS.iter().map(|_| Id::new()).collect(),
+            commit: CommitCounter::default(),
+        }
+    }
+
+    /// Forget the labels uploaded with a renderer that is gone
+    pub fn release_textures(&mut self) {
+        self.labels.clear();
+    }
+
+    /// Show or hide the toolbar. Returns `true` if that changed, so that the caller redraws.
+    pub fn set_visible(&mut self, visible: bool) -> bool {
+        if self.visible == visible {
+            return false;
+        }
+        self.visible = visible;
+        if !visible {
+            self.latched.clear();
+        }
+        true
+    }
+
+    /// The key under a touch point starting at `location`, if any. The rest of that touch is then owned by the toolbar.
+    pub fn touch_down(
+        &mut self,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        output_size: Size<i32, Physical>,
+    ) -> Option<ExtraKey> {
+        if !self.visible || location.y >= BAR_HEIGHT as f64 || output_size.w <= 0 {
+            return None;
+        }
+        self.touches.push(slot);
+        let index = (location.x.max(0.0) as usize * KEYS.len()) / output_size.w as usize;
+        KEYS.get(index).copied()
+    }
+
+    /// Whether that touch point started on the toolbar
+    pub fn owns_touch(&self, slot: TouchSlot) -> bool {
+        self.touches.contains(&slot)
+    }
+
+    /// Returns `true` if the lifted touch point started on the toolbar
+    pub fn touch_up(&mut self, slot: TouchSlot) -> bool {
+        let owned = self.owns_touch(slot);
+        self.touches.retain(|it| *it != slot);
+        owned
+    }
+
+    pub fn touch_cancel(&mut self) {
+        self.touches.clear();
+    }
+
+    /// Tapping a modifier latches it (or releases it). Tapping another key returns the scancodes to type,
+    /// starting with the latched modifiers.
+    pub fn tap(&mut self, key: ExtraKey) -> Option<Vec<u32>> {
+        if key.is_modifier() {
+            if self.latched.contains(&key) {
+                self.latched.retain(|it| *it != key);
+            } else {
+                self.latched.push(key);
+            }
+            self.commit.increment();
+            return None;
+        }
+        Some(self.chord(key.scancode()))
+    }
+
+    pub fn has_latched_modifiers(&self) -> bool {
+        !self.latched.is_empty()
+    }
+
+    /// The scancodes to type for a key with the latched modifiers, which are then released
+    pub fn chord(&mut self, scancode: u32) -> Vec<u32> {
+        let mut scancodes = self
+            .latched
+            .drain(..)
+            .map(|it| it.scancode())
+            .collect::<Vec<_>>();
+        scancodes.push(scancode);
+        self.commit.increment();
+        scancodes
+    }
+
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output_size: Size<i32, Physical>,
+    ) -> Vec<WindowRenderElement<GlesRenderer>> {
+        if !self.visible {
+            return vec![];
+        }
+        self.load_labels(renderer);
+
+        let key_width = output_size.w / KEYS.len() as i32;
+        let mut labels = vec![];
+        let mut highlights = vec![];
+        for (index, key) in KEYS.iter().enumerate() {
+            let x = index as i32 * key_width;
+            if let Some(Some((texture, size))) = self.labels.get(index) {
+                labels.push(
+                    TextureRenderElement::from_texture_buffer(
+                        (
+                            (x + (key_width - size.w) / 2) as f64,
+                            ((BAR_HEIGHT - size.h) / 2) as f64,
+                        ),
+                        texture,
+                        None,
+                        None,
+                        None,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+            if self.latched.contains(key) {
+                highlights.push(
+                    SolidColorRenderElement::new(
+                        self.key_ids[index].clone(),
+                        Rectangle::new((x, 0).into(), (key_width, BAR_HEIGHT).into()),
+                        self.commit,
+                        LATCHED_COLOR,
+                        Kind::Unspecified,
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        // Front to back: the labels, the latched modifiers, then the bar itself
+        let mut elements = labels;
+        elements.extend(highlights);
+        elements.push(
+            SolidColorRenderElement::new(
+                self.bar_id.clone(),
+                Rectangle::new((0, 0).into(), (output_size.w, BAR_HEIGHT).into()),
+                CommitCounter::default(),
+                BAR_COLOR,
+                Kind::Unspecified,
+            )
+            .into(),
+        );
+        elements
+    }
+
+    fn load_labels(&mut self, renderer: &mut GlesRenderer) {
+        if !self.labels.is_empty() {
+            return;
+        }
+        let mut bitmaps = vec![];
+        run_in_jvm(
+            |env, _| {
+                for key in KEYS {
+                    match rasterize_text(env, key.label(), TEXT_SIZE, TEXT_COLOR) {
+                        Ok(it) => bitmaps.push(Some(it)),
+                        Err(e) => {
+                            log::warn!("Failed to rasterize the {:?} key: {:?}", key, e);
+                            bitmaps.push(None);
+                        }
+                    }
+                }
+            },
+            self.android_app.clone(),
+        );
+        self.labels = bitmaps
+            .into_iter()
+            .map(|bitmap| {
+                let bitmap = bitmap?;
+                match TextureBuffer::from_memory(
+                    renderer,
+                    &bitmap.pixels,
+                    Fourcc::Abgr8888,
+                    (bitmap.width, bitmap.height),
+                    false,
+                    1,
+                    Transform::Normal,
+                    None,
+                ) {
+                    Ok(texture) => Some((texture, (bitmap.width, bitmap.height).into())),
+                    Err(e) => {
+                        log::warn!("Failed to upload an extra key label: {:?}", e);
+                        None
+                    }
+                }
+            })
+            .collect();
+    }
+}
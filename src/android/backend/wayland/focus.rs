@@ -0,0 +1,72 @@
+use smithay::backend::renderer::{
+    element::{solid::SolidColorRenderElement, Id, Kind},
+    utils::CommitCounter,
+    Color32F,
+};
+use smithay::utils::{Physical, Rectangle, Size};
+
+/// Height (in pixels) of the bar shown on top of the desktop while a host overlay owns the keyboard
+const INDICATOR_HEIGHT: i32 = 6;
+
+/// Which layer currently receives the key events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardOwner {
+    /// Key events are forwarded to the focused Wayland client
+    Clients,
+    /// A host UI overlay (e.g. a WebView popup) sits on top of the compositor and consumes the key events
+    HostOverlay,
+}
+
+/// Decides whether key events are routed to the Wayland clients or kept by the host UI overlays,
+/// so that typing into an overlay never leaks into the Linux desktop behind it.
+pub struct FocusArbiter {
+    owner: KeyboardOwner,
+    indicator_id: Id,
+    indicator_commit: CommitCounter,
+}
+
+impl Default for FocusArbiter {
+    fn default() -> Self {
+        Self {
+            owner: KeyboardOwner::Clients,
+            indicator_id: Id::new(),
+            indicator_commit: CommitCounter::default(),
+        }
+    }
+}
+
+impl FocusArbiter {
+    pub fn owner(&self) -> KeyboardOwner {
+        self.owner
+    }
+
+    pub fn clients_own_keyboard(&self) -> bool {
+        self.owner == KeyboardOwner::Clients
+    }
+
+    /// Give the keyboard to another layer. Returns `true` if the owner actually changed,
+    /// so that the caller knows it has to move the Wayland keyboard focus accordingly.
+    pub fn hand_over(&mut self, owner: KeyboardOwner) -> bool {
+        if self.owner == owner {
+            return false;
+        }
+        log::info!("Keyboard owner changed: {:?} -> {:?}", self.owner, owner);
+        self.owner = owner;
+        self.indicator_commit.increment();
+        true
+    }
+
+    /// A thin bar across the top of the output, visible only while a host overlay owns the keyboard
+    pub fn indicator(&self, output_size: Size<i32, Physical>) -> Option<SolidColorRenderElement> {
+        if self.clients_own_keyboard() {
+            return None;
+        }
+        Some(SolidColorRenderElement::new(
+            self.indicator_id.clone(),
+            Rectangle::new((0, 0).into(), (output_size.w, INDICATOR_HEIGHT).into()),
+            self.indicator_commit,
+            Color32F::new(1.0, 0.6, 0.0, 1.0),
+            Kind::Unspecified,
+        ))
+    }
+}
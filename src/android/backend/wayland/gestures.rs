@@ -0,0 +1,507 @@
+use crate::android::utils::application_context::get_application_context;
+use crate::core::config::{Edge, GestureAction, GesturesConfig, TouchConfig};
+use crate::core::transform::View;
+use smithay::utils::{Logical, Point, Size};
+use std::time::{Duration, Instant};
+
+/// How far (in pixels) the fingers move apart or together before two fingers are told to pinch
+const PINCH_THRESHOLD: f64 = 48.0;
+
+/// How far (in pixels) the fingers move along before two fingers are told to scroll
+const SCROLL_THRESHOLD: f64 = 16.0;
+
+const MAX_ZOOM: f64 = 4.0;
+
+/// How far (in pixels) a finger may wander and still be held still, or tap
+pub const TAP_SLOP: f64 = 16.0;
+
+/// A tap is shorter than that
+const TAP_DURATION: Duration = Duration::from_millis(250);
+
+/// Touching again within that delay after a tap, close to it, starts a drag
+const DOUBLE_TAP_INTERVAL: Duration = Duration::from_millis(300);
+const DOUBLE_TAP_SLOP: f64 = 48.0;
+
+/// Fingers tapping at once are all lifted within that delay
+pub const MULTI_TAP_DURATION: Duration = Duration::from_millis(400);
+
+/// How close (in pixels) to an edge of the screen a swipe from it starts, and how far towards the middle it goes
+pub const EDGE_WIDTH: f64 = 32.0;
+pub const EDGE_SWIPE_DISTANCE: f64 = 96.0;
+
+/// Multi-touch gestures, handled by the compositor instead of the clients
+#[derive(Debug)]
+pub enum Gesture {
+    /// Two fingers dragged along, by `delta` (in pixels) since the last event
+    Scroll {
+        location: Point<f64, Logical>,
+        delta: Point<f64, Logical>,
+    },
+    /// The fingers scrolling have been lifted
+    ScrollStop { location: Point<f64, Logical> },
+    /// Two fingers moved apart (`scale > 1`) or together since the last event
+    Pinch {
+        previous_center: Point<f64, Logical>,
+        center: Point<f64, Logical>,
+        scale: f64,
+    },
+    /// A finger has been held still: the clients must forget its touch sequence, which becomes a right click
+    LongPress {
+        id: u64,
+        location: Point<f64, Logical>,
+    },
+    /// A finger touched again right after a tap: it drags with the left button until lifted
+    DragStart {
+        id: u64,
+        location: Point<f64, Logical>,
+    },
+    DragMotion {
+        id: u64,
+        location: Point<f64, Logical>,
+    },
+    DragEnd {
+        id: u64,
+        location: Point<f64, Logical>,
+    },
+    /// Three fingers or more tapped at once
+    Tap { fingers: usize },
+    /// A finger swiped from an edge of the screen towards its middle
+    EdgeSwipe { edge: Edge },
+}
+
+/// What becomes of a touch event once gestures are recognized
+#[derive(Debug)]
+pub enum TouchOutcome {
+    /// A single finger, which the clients get as usual
+    Forward,
+    /// A second finger started a gesture: the clients must forget the touch sequence they got so far
+    CancelClients,
+    Gesture(Gesture),
+    /// Part of a gesture, with nothing to do yet
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Undecided,
+    Scroll,
+    Pinch,
+    /// The gesture is over, but some fingers are still on the screen
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PressKind {
+    /// Forwarded to the clients, and may still turn into a long press
+    Touch {
+        still: bool,
+    },
+    /// Turned into a right click, the rest of the touch sequence is dropped
+    RightClicked,
+    Drag,
+    /// Started along an edge with a swipe action, kept away from the clients
+    Edge(Edge),
+    /// Swiped from the edge, the rest of the touch sequence is dropped
+    Swiped,
+}
+
+/// The single finger on the screen, outside of multi-touch gestures
+#[derive(Debug)]
+struct Press {
+    id: u64,
+    kind: PressKind,
+    start: Point<f64, Logical>,
+    location: Point<f64, Logical>,
+    started: Instant,
+}
+
+#[derive(Debug)]
+struct TwoFingers {
+    mode: Mode,
+    start_center: Point<f64, Logical>,
+    start_distance: f64,
+    center: Point<f64, Logical>,
+    distance: f64,
+    started: Instant,
+    /// Where each finger of the gesture touched first, to tell taps apart
+    starts: Vec<(u64, Point<f64, Logical>)>,
+    /// Whether every finger stayed where it touched first, so far
+    still: bool,
+}
+
+/// Tells multi-touch gestures apart from the touches of a single finger, which go to the clients.
+/// A gesture starts with the second finger, and lasts until every finger is lifted.
+/// A single finger may also be translated into pointer buttons, for the apps made for a mouse:
+/// holding it still right-clicks, and double-tapping then dragging drags with the left button.
+/// Tapping with more fingers, or swiping from an edge, runs the actions of `[gestures]`.
+#[derive(Debug)]
+pub struct GestureRecognizer {
+    points: Vec<(u64, Point<f64, Logical>)>,
+    gesture: Option<TwoFingers>,
+    press: Option<Press>,
+    /// Where and when the last tap was lifted
+    last_tap: Option<(Point<f64, Logical>, Instant)>,
+    long_press_delay: Option<Duration>,
+    double_tap_drag: bool,
+    /// Read again as each gesture starts, so that the changes made on the settings page apply right away
+    config: GesturesConfig,
+}
+
+fn distance(a: Point<f64, Logical>, b: Point<f64, Logical>) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+impl GestureRecognizer {
+    pub fn new(config: &TouchConfig) -> Self {
+        Self {
+            points: vec![],
+            gesture: None,
+            press: None,
+            last_tap: None,
+            long_press_delay: config
+                .long_press_right_click
+                .then(|| Duration::from_millis(config.long_press_delay)),
+            double_tap_drag: config.double_tap_drag,
+            config: GesturesConfig::default(),
+        }
+    }
+
+    /// The edge of the screen a touch at `location` starts a swipe from, if that edge has an action
+    fn edge_at(&self, location: Point<f64, Logical>, screen: Size<i32, Logical>) -> Option<Edge> {
+        [
+            (Edge::Left, location.x),
+            (Edge::Right, screen.w as f64 - location.x),
+            (Edge::Top, location.y),
+            (Edge::Bottom, screen.h as f64 - location.y),
+        ]
+        .into_iter()
+        .find(|(edge, distance)| {
+            *distance < EDGE_WIDTH && self.config.swipe_action(*edge) != GestureAction::None
+        })
+        .map(|(edge, _)| edge)
+    }
+
+    /// The center and the spread of the first two fingers
+    fn measure(&self) -> Option<(Point<f64, Logical>, f64)> {
+        match self.points.as_slice() {
+            [(_, a), (_, b), ..] => Some((
+                ((a.x + b.x) / 2.0, (a.y + b.y) / 2.0).into(),
+                distance(*a, *b),
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn touch_down(
+        &mut self,
+        id: u64,
+        location: Point<f64, Logical>,
+        screen: Size<i32, Logical>,
+    ) -> TouchOutcome {
+        self.points.push((id, location));
+        if self.points.len() == 1 && self.gesture.is_none() {
+            let now = Instant::now();
+            self.config = get_application_context().local_config.gestures;
+            if let Some(edge) = self.edge_at(location, screen) {
+                self.press = Some(Press {
+                    id,
+                    kind: PressKind::Edge(edge),
+                    start: location,
+                    location,
+                    started: now,
+                });
+                return TouchOutcome::Ignore;
+            }
+            let double_tap = self.double_tap_drag
+                && self.last_tap.take().is_some_and(|(tap, at)| {
+                    now.duration_since(at) < DOUBLE_TAP_INTERVAL
+                        && distance(tap, location) < DOUBLE_TAP_SLOP
+                });
+            let kind = if double_tap {
+                PressKind::Drag
+            } else {
+                PressKind::Touch { still: true }
+            };
+            self.press = Some(Press {
+                id,
+                kind,
+                start: location,
+                location,
+                started: now,
+            });
+            return if double_tap {
+                TouchOutcome::Gesture(Gesture::DragStart { id, location })
+            } else {
+                TouchOutcome::Forward
+            };
+        }
+        let Some((center, distance)) = self.measure() else {
+            return TouchOutcome::Ignore;
+        };
+        match &mut self.gesture {
+            None => {
+                self.gesture = Some(TwoFingers {
+                    mode: Mode::Undecided,
+                    start_center: center,
+                    start_distance: distance,
+                    center,
+                    distance,
+                    started: Instant::now(),
+                    starts: self.points.clone(),
+                    still: true,
+                });
+                // Whatever the first finger was doing is over
+                match self.press.take() {
+                    Some(Press {
+                        kind: PressKind::Drag,
+                        id,
+                        location,
+                        ..
+                    }) => TouchOutcome::Gesture(Gesture::DragEnd { id, location }),
+                    Some(Press {
+                        kind: PressKind::RightClicked | PressKind::Edge(_) | PressKind::Swiped,
+                        ..
+                    }) => TouchOutcome::Ignore,
+                    _ => TouchOutcome::CancelClients,
+                }
+            }
+            Some(gesture) => {
+                gesture.starts.push((id, location));
+                // Back to two fingers, which carry on from where they are
+                if self.points.len() == 2 {
+                    gesture.center = center;
+                    gesture.distance = distance;
+                }
+                TouchOutcome::Ignore
+            }
+        }
+    }
+
+    pub fn touch_motion(&mut self, id: u64, location: Point<f64, Logical>) -> TouchOutcome {
+        if let Some(point) = self.points.iter_mut().find(|(it, _)| *it == id) {
+            point.1 = location;
+        }
+        if self.gesture.is_none() {
+            let Some(press) = self.press.as_mut().filter(|it| it.id == id) else {
+                return TouchOutcome::Forward;
+            };
+            press.location = location;
+            return match &mut press.kind {
+                PressKind::Touch { still } => {
+                    *still = *still && distance(press.start, location) < TAP_SLOP;
+                    TouchOutcome::Forward
+                }
+                PressKind::RightClicked | PressKind::Swiped => TouchOutcome::Ignore,
+                PressKind::Drag => TouchOutcome::Gesture(Gesture::DragMotion { id, location }),
+                PressKind::Edge(edge) => {
+                    let edge = *edge;
+                    let inwards = match edge {
+                        Edge::Left => location.x - press.start.x,
+                        Edge::Right => press.start.x - location.x,
+                        Edge::Top => location.y - press.start.y,
+                        Edge::Bottom => press.start.y - location.y,
+                    };
+                    if inwards > EDGE_SWIPE_DISTANCE {
+                        press.kind = PressKind::Swiped;
+                        TouchOutcome::Gesture(Gesture::EdgeSwipe { edge })
+                    } else {
+                        TouchOutcome::Ignore
+                    }
+                }
+            };
+        }
+        let Some((center, distance)) = self.measure() else {
+            return TouchOutcome::Ignore;
+        };
+        let gesture = self.gesture.as_mut().unwrap();
+        if let Some((_, start)) = gesture.starts.iter().find(|(it, _)| *it == id) {
+            gesture.still = gesture.still && self::distance(*start, location) < TAP_SLOP;
+        }
+        if gesture.mode == Mode::Undecided {
+            if self.config.pinch_zoom && (distance - gesture.start_distance).abs() > PINCH_THRESHOLD
+            {
+                gesture.mode = Mode::Pinch;
+            } else if self.config.two_finger_scroll
+                && self::distance(center, gesture.start_center) > SCROLL_THRESHOLD
+            {
+                gesture.mode = Mode::Scroll;
+            }
+        }
+        let outcome = match gesture.mode {
+            Mode::Scroll => TouchOutcome::Gesture(Gesture::Scroll {
+                location: center,
+                delta: center - gesture.center,
+            }),
+            Mode::Pinch if gesture.distance > 0.0 => TouchOutcome::Gesture(Gesture::Pinch {
+                previous_center: gesture.center,
+                center,
+                scale: distance / gesture.distance,
+            }),
+            _ => TouchOutcome::Ignore,
+        };
+        gesture.center = center;
+        gesture.distance = distance;
+        outcome
+    }
+
+    pub fn touch_up(&mut self, id: u64) -> TouchOutcome {
+        self.points.retain(|(it, _)| *it != id);
+        let Some(gesture) = &mut self.gesture else {
+            let Some(press) = self.press.take_if(|it| it.id == id) else {
+                return TouchOutcome::Forward;
+            };
+            return match press.kind {
+                PressKind::Touch { still } => {
+                    if still && press.started.elapsed() < TAP_DURATION {
+                        self.last_tap = Some((press.location, Instant::now()));
+                    }
+                    TouchOutcome::Forward
+                }
+                PressKind::RightClicked | PressKind::Edge(_) | PressKind::Swiped => {
+                    TouchOutcome::Ignore
+                }
+                PressKind::Drag => TouchOutcome::Gesture(Gesture::DragEnd {
+                    id,
+                    location: press.location,
+                }),
+            };
+        };
+        let mut outcome = TouchOutcome::Ignore;
+        if self.points.len() < 2 {
+            if gesture.mode == Mode::Scroll {
+                outcome = TouchOutcome::Gesture(Gesture::ScrollStop {
+                    location: gesture.center,
+                });
+            }
+            gesture.mode = Mode::Done;
+        }
+        if self.points.is_empty() {
+            let fingers = gesture.starts.len();
+            if gesture.still && fingers >= 3 && gesture.started.elapsed() < MULTI_TAP_DURATION {
+                outcome = TouchOutcome::Gesture(Gesture::Tap { fingers });
+            }
+            self.gesture = None;
+        }
+        outcome
+    }
+
+    pub fn touch_cancel(&mut self) {
+        self.points.clear();
+        self.gesture = None;
+        self.press = None;
+        self.last_tap = None;
+    }
+
+    /// When the finger held still turns into a long press, for the event loop to wake up by then
+    pub fn long_press_deadline(&self) -> Option<Instant> {
+        let delay = self.long_press_delay?;
+        match self.press.as_ref()? {
+            Press {
+                kind: PressKind::Touch { still: true },
+                started,
+                ..
+            } => Some(*started + delay),
+            _ => None,
+        }
+    }
+
+    /// The long press of the finger held still, once its deadline passed
+    pub fn poll_long_press(&mut self, now: Instant) -> Option<Gesture> {
+        if self.long_press_deadline()? > now {
+            return None;
+        }
+        let press = self.press.as_mut()?;
+        press.kind = PressKind::RightClicked;
+        Some(Gesture::LongPress {
+            id: press.id,
+            location: press.location,
+        })
+    }
+}
+
+/// The part of the desktop shown on the output, once zoomed in by pinching
+#[derive(Debug, Clone, Copy)]
+pub struct Zoom {
+    scale: f64,
+    /// The desktop location shown at the top left corner of the output
+    origin: Point<f64, Logical>,
+    /// How far up the output is moved, in output pixels, for the soft keyboard not to cover what is typed
+    pan: f64,
+}
+
+impl Default for Zoom {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            origin: (0.0, 0.0).into(),
+            pan: 0.0,
+        }
+    }
+}
+
+impl Zoom {
+    pub fn new(scale: f64, origin: Point<f64, Logical>) -> Self {
+        Self {
+            scale: scale.clamp(1.0, MAX_ZOOM),
+            origin,
+            pan: 0.0,
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn origin(&self) -> Point<f64, Logical> {
+        self.origin
+    }
+
+    pub fn pan(&self) -> f64 {
+        self.pan
+    }
+
+    /// Returns whether the pan changed
+    pub fn set_pan(&mut self, pan: f64) -> bool {
+        let changed = pan != self.pan;
+        self.pan = pan;
+        changed
+    }
+
+    fn view(&self) -> View {
+        View {
+            scale: self.scale,
+            origin: self.origin.into(),
+            pan: self.pan,
+        }
+    }
+
+    /// Where a location of the output is on the desktop
+    pub fn to_desktop(&self, location: Point<f64, Logical>) -> Point<f64, Logical> {
+        self.view().to_desktop(location.into()).into()
+    }
+
+    /// Where a location of the desktop is on the output
+    pub fn to_screen(&self, location: Point<f64, Logical>) -> Point<f64, Logical> {
+        self.view().to_window(location.into()).into()
+    }
+
+    /// Zoom by `factor`, the desktop location under the fingers following them
+    pub fn pinch(
+        &mut self,
+        previous_center: Point<f64, Logical>,
+        center: Point<f64, Logical>,
+        factor: f64,
+        output_size: Size<i32, Logical>,
+    ) {
+        let anchor = self.to_desktop(previous_center);
+        self.scale = (self.scale * factor).clamp(1.0, MAX_ZOOM);
+        // The zoomed in output never goes past the edges of the desktop
+        let max_x = output_size.w as f64 * (1.0 - 1.0 / self.scale);
+        let max_y = output_size.h as f64 * (1.0 - 1.0 / self.scale);
+        self.origin = (
+            (anchor.x - center.x / self.scale).clamp(0.0, max_x),
+            (anchor.y - (center.y + self.pan) / self.scale).clamp(0.0, max_y),
+        )
+            .into();
+    }
+}
@@ -0,0 +1,110 @@
+use crate::android::app::attach::wake_event_loop;
+use std::sync::mpsc::{self, Receiver, Sender};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{
+        DeviceId, ElementState, MouseButton, MouseScrollDelta, Touch, TouchPhase, WindowEvent,
+    },
+};
+
+/// A synthetic input, as a device would report it
+#[derive(Debug)]
+pub enum InjectedInput {
+    /// A key, by its Linux scancode
+    Key { scancode: u32, state: ElementState },
+    /// A pointer, wheel or touch event of the window
+    Window(WindowEvent),
+}
+
+/// Injects synthetic input from anywhere, e.g. the extra keys, macros or automation.
+/// The inputs are handled on the main thread like the real ones, from the centralizer on, so that they get the same
+/// gestures, focus and serials.
+#[derive(Clone)]
+pub struct InputInjector {
+    sender: Sender<InjectedInput>,
+}
+
+/// The inputs injected since the main thread last handled them
+pub struct InjectedInputs {
+    receiver: Receiver<InjectedInput>,
+    injector: InputInjector,
+}
+
+impl Default for InjectedInputs {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            receiver,
+            injector: InputInjector { sender },
+        }
+    }
+}
+
+impl InjectedInputs {
+    pub fn injector(&self) -> InputInjector {
+        self.injector.clone()
+    }
+
+    pub fn take(&self) -> Vec<InjectedInput> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+impl InputInjector {
+    /// Queue an input for the main thread, waking its event loop up
+    pub fn inject(&self, input: InjectedInput) {
+        if self.sender.send(input).is_ok() {
+            wake_event_loop();
+        }
+    }
+
+    pub fn key(&self, scancode: u32, state: ElementState) {
+        self.inject(InjectedInput::Key { scancode, state });
+    }
+
+    /// Press the keys in order, then release them in reverse, e.g. for a shortcut
+    pub fn type_keys(&self, scancodes: &[u32]) {
+        for scancode in scancodes {
+            self.key(*scancode, ElementState::Pressed);
+        }
+        for scancode in scancodes.iter().rev() {
+            self.key(*scancode, ElementState::Released);
+        }
+    }
+
+    /// Move the pointer to a location of the window, in physical pixels
+    pub fn pointer_move(&self, x: f64, y: f64) {
+        self.inject(InjectedInput::Window(WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(x, y),
+        }));
+    }
+
+    pub fn pointer_button(&self, button: MouseButton, state: ElementState) {
+        self.inject(InjectedInput::Window(WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button,
+        }));
+    }
+
+    /// Scroll by some pixels
+    pub fn scroll(&self, dx: f64, dy: f64) {
+        self.inject(InjectedInput::Window(WindowEvent::MouseWheel {
+            device_id: DeviceId::dummy(),
+            delta: MouseScrollDelta::PixelDelta(PhysicalPosition::new(dx, dy)),
+            phase: TouchPhase::Moved,
+        }));
+    }
+
+    /// A finger touching, moving on, or leaving a location of the window, in physical pixels
+    pub fn touch(&self, id: u64, phase: TouchPhase, x: f64, y: f64) {
+        self.inject(InjectedInput::Window(WindowEvent::Touch(Touch {
+            device_id: DeviceId::dummy(),
+            phase,
+            location: PhysicalPosition::new(x, y),
+            force: None,
+            id,
+        })));
+    }
+}
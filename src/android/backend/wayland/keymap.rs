@@ -7,6 +7,7 @@ pub fn physicalkey_to_scancode(key: PhysicalKey) -> Option<u32> {
             return match code {
                 NativeKeyCode::Unidentified => Some(240),
                 NativeKeyCode::Xkb(raw) => Some(raw),
+                NativeKeyCode::Android(code) => android_keycode_to_scancode(code),
                 _ => None,
             };
         }
@@ -124,6 +125,7 @@ pub fn physicalkey_to_scancode(key: PhysicalKey) -> Option<u32> {
         KeyCode::AudioVolumeMute => Some(113),
         KeyCode::AudioVolumeDown => Some(114),
         KeyCode::AudioVolumeUp => Some(115),
+        KeyCode::Power => Some(116),
         KeyCode::NumpadEqual => Some(117),
         KeyCode::Pause => Some(119),
         KeyCode::NumpadComma => Some(121),
@@ -133,6 +135,11 @@ pub fn physicalkey_to_scancode(key: PhysicalKey) -> Option<u32> {
         KeyCode::SuperLeft => Some(125),
         KeyCode::SuperRight => Some(126),
         KeyCode::ContextMenu => Some(127),
+        KeyCode::Copy => Some(133),
+        KeyCode::Paste => Some(135),
+        KeyCode::Cut => Some(137),
+        KeyCode::Sleep => Some(142),
+        KeyCode::WakeUp => Some(143),
         KeyCode::MediaTrackNext => Some(163),
         KeyCode::MediaPlayPause => Some(164),
         KeyCode::MediaTrackPrevious => Some(165),
@@ -152,3 +159,45 @@ pub fn physicalkey_to_scancode(key: PhysicalKey) -> Option<u32> {
         _ => None,
     }
 }
+
+/// The scancode of the Android keys winit does not name, mostly the media and function keys of the keyboards,
+/// which the keymap turns into XF86 keysyms (e.g. `XF86MonBrightnessUp`)
+fn android_keycode_to_scancode(code: u32) -> Option<u32> {
+    match code {
+        64 => Some(150),  // KEYCODE_EXPLORER: XF86WWW
+        65 => Some(155),  // KEYCODE_ENVELOPE: XF86Mail
+        84 => Some(217),  // KEYCODE_SEARCH: XF86Search
+        89 => Some(168),  // KEYCODE_MEDIA_REWIND: XF86AudioRewind
+        90 => Some(208),  // KEYCODE_MEDIA_FAST_FORWARD: XF86AudioForward
+        91 => Some(248),  // KEYCODE_MUTE, of the microphone: XF86AudioMicMute
+        125 => Some(159), // KEYCODE_FORWARD: XF86Forward
+        126 => Some(200), // KEYCODE_MEDIA_PLAY: XF86AudioPlay
+        127 => Some(201), // KEYCODE_MEDIA_PAUSE: XF86AudioPause
+        129 => Some(161), // KEYCODE_MEDIA_EJECT: XF86Eject
+        130 => Some(167), // KEYCODE_MEDIA_RECORD: XF86AudioRecord
+        174 => Some(156), // KEYCODE_BOOKMARK: XF86Favorites
+        209 => Some(226), // KEYCODE_MUSIC: XF86AudioMedia
+        210 => Some(140), // KEYCODE_CALCULATOR: XF86Calculator
+        220 => Some(224), // KEYCODE_BRIGHTNESS_DOWN: XF86MonBrightnessDown
+        221 => Some(225), // KEYCODE_BRIGHTNESS_UP: XF86MonBrightnessUp
+        259 => Some(138), // KEYCODE_HELP: Help
+        285 => Some(173), // KEYCODE_REFRESH: XF86Reload
+        305 => Some(229), // KEYCODE_KEYBOARD_BACKLIGHT_DOWN: XF86KbdBrightnessDown
+        306 => Some(230), // KEYCODE_KEYBOARD_BACKLIGHT_UP: XF86KbdBrightnessUp
+        307 => Some(228), // KEYCODE_KEYBOARD_BACKLIGHT_TOGGLE: XF86KbdLightOnOff
+        _ => None,
+    }
+}
+
+/// The scancode of a letter or digit typed on the soft keyboard, for the US layout of the compositor keyboard
+pub fn char_to_scancode(c: char) -> Option<u32> {
+    const ROWS: [(&str, u32); 4] = [
+        ("1234567890", 2),
+        ("qwertyuiop", 16),
+        ("asdfghjkl", 30),
+        ("zxcvbnm", 44),
+    ];
+    let c = c.to_ascii_lowercase();
+    ROWS.iter()
+        .find_map(|(row, first)| row.find(c).map(|index| first + index as u32))
+}
@@ -1,26 +1,117 @@
 pub mod bind;
+mod capture;
+mod clipboard;
+mod coalescer;
 mod compositor;
+mod cursor;
+mod dnd;
 mod element;
 mod event_centralizer;
 mod event_handler;
+mod external_display;
+mod extra_keys;
+mod focus;
+mod gestures;
+mod injector;
 mod input;
 mod keymap;
+mod output_power;
+mod presentation;
+mod presentation_time;
+mod recorder;
+mod remote;
+mod render_cache;
+mod screencopy;
+mod shortcuts;
+mod soft_keyboard;
+mod splash;
+mod stats;
+mod tablet;
+mod text_input;
+mod virtual_pointer;
+mod wakeup;
+mod wallpaper;
 mod winit_backend;
+mod xwayland;
 
-pub use compositor::{Compositor, State};
-pub use event_centralizer::{centralize, CentralizedEvent};
-pub use event_handler::handle;
+pub use coalescer::MotionCoalescer;
+pub use compositor::{Compositor, State, SCREEN_OUTPUT};
+pub use event_centralizer::{
+    centralize, centralize_device_event, centralize_injected, CentralizedEvent,
+};
+pub use event_handler::{handle, idle, sync_screen_power, warp_pointer};
+pub use external_display::watch_external_displays;
+pub use extra_keys::ExtraKeys;
+pub use focus::{FocusArbiter, KeyboardOwner};
+pub use gestures::{
+    GestureRecognizer, Zoom, EDGE_SWIPE_DISTANCE, EDGE_WIDTH, MULTI_TAP_DURATION, TAP_SLOP,
+};
+pub use injector::{InjectedInputs, InputInjector};
+pub use presentation_time::PresentationFeedbacks;
+pub use remote::serve_remote_clients;
+pub use soft_keyboard::SoftKeyboard;
+pub use splash::Splash;
+pub use stats::publish_globals;
+pub use tablet::Tablet;
+pub use wallpaper::Wallpaper;
 pub use winit_backend::{bind, WinitGraphicsBackend};
 
-use smithay::{
-    backend::renderer::gles::GlesRenderer,
-    utils::{Clock, Monotonic},
-};
+use crate::android::utils::choreographer::Choreographer;
+use crate::core::{config::KeyboardInset, key_repeat::KeyRepeat, power::ScreenPower};
+use smithay::backend::renderer::{damage::OutputDamageTracker, gles::GlesRenderer};
 
 pub struct WaylandBackend {
     pub compositor: Compositor,
     pub graphic_renderer: Option<WinitGraphicsBackend<GlesRenderer>>,
-    pub clock: Clock<Monotonic>,
+    /// Created on the first frame after each resize
+    pub damage_tracker: Option<OutputDamageTracker>,
+    pub focus: FocusArbiter,
+    pub wallpaper: Wallpaper,
+    pub splash: Splash,
+    pub soft_keyboard: SoftKeyboard,
+    pub extra_keys: ExtraKeys,
+    pub gestures: GestureRecognizer,
+    /// The synthetic inputs waiting to be handled
+    pub injected_inputs: InjectedInputs,
+    /// The finger motions waiting for the next frame
+    pub touch_motions: MotionCoalescer,
+    /// The stylus, once used
+    pub tablet: Tablet,
+    /// Applied to the windows when rendering, and reverted on the input locations
+    pub zoom: Zoom,
+    /// How the desktop makes room for the soft keyboard
+    pub keyboard_inset: KeyboardInset,
+    /// The mouse is captured for a client that locked or confined the pointer
+    pub pointer_captured: bool,
     pub key_counter: u32,
+    /// The key held on the keyboard, pressed again for the focused client until it is released
+    pub key_repeat: KeyRepeat,
     pub scale_factor: f64,
+    /// The `wp_presentation` feedbacks of the frames not on screen yet
+    pub presentation_feedbacks: PresentationFeedbacks,
+    /// When the next frame is due
+    pub choreographer: Choreographer,
+    /// The next frame is saved as a screenshot
+    pub screenshot_requested: bool,
+    /// Whether the screen is to be kept on, from `[power]` and the input
+    pub screen_power: ScreenPower,
+    /// Whether the window keeps the screen on, once set
+    pub screen_kept_on: Option<bool>,
+}
+
+impl WaylandBackend {
+    /// Drop the window, its EGL context and every texture uploaded with it, e.g. as Android took the window away.
+    /// The clients and their buffers are kept, and drawn again once the window is bound on the next resume.
+    pub fn release_graphics(&mut self) {
+        self.graphic_renderer = None;
+        self.damage_tracker = None;
+        self.wallpaper.release_textures();
+        self.splash.release_textures();
+        self.extra_keys.release_textures();
+        let state = &mut self.compositor.state;
+        state.cursor.release_textures();
+        state.render_cache.clear();
+        // The next activity has a window of its own, whose flags are set again
+        self.screen_kept_on = None;
+    }
 }
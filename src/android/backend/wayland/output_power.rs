@@ -0,0 +1,154 @@
+use super::compositor::State;
+use smithay::reexports::{
+    wayland_protocols_wlr::output_power_management::v1::server::{
+        zwlr_output_power_manager_v1::{self, ZwlrOutputPowerManagerV1},
+        zwlr_output_power_v1::{self, Mode, ZwlrOutputPowerV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    },
+};
+
+/// The power of the output through `zwlr_output_power_management_v1`, so that power tools such as `swayidle` or
+/// `wlopm` can blank it, and so that they know when Android turned the screen off.
+/// The output is on only while both the clients and Android want it on.
+pub struct OutputPowerState {
+    global: GlobalId,
+    controls: Vec<ZwlrOutputPowerV1>,
+    /// What the clients last asked for
+    requested: Mode,
+    /// Whether Android shows the window
+    screen_on: bool,
+}
+
+impl OutputPowerState {
+    pub fn new(dh: &DisplayHandle) -> Self {
+        let global = dh.create_global::<State, ZwlrOutputPowerManagerV1, _>(1, ());
+        Self {
+            global,
+            controls: Vec::new(),
+            requested: Mode::On,
+            screen_on: true,
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.mode() == Mode::On
+    }
+
+    fn mode(&self) -> Mode {
+        if self.requested == Mode::On && self.screen_on {
+            Mode::On
+        } else {
+            Mode::Off
+        }
+    }
+
+    /// Follow the Android screen, which the window goes away with
+    pub fn set_screen_on(&mut self, on: bool) {
+        let was_on = self.is_on();
+        self.screen_on = on;
+        if self.is_on() != was_on {
+            self.broadcast();
+        }
+    }
+
+    /// Turn the output back on as the user touches it, as a monitor does.
+    /// Returns whether it was blanked by a client.
+    pub fn wake(&mut self) -> bool {
+        if self.requested == Mode::On {
+            return false;
+        }
+        log::info!("Turning the output back on, as the user is active");
+        self.requested = Mode::On;
+        self.broadcast();
+        true
+    }
+
+    fn request(&mut self, mode: Mode) {
+        let was_on = self.is_on();
+        self.requested = mode;
+        log::info!("A client turned the output {:?}", mode);
+        if self.is_on() != was_on {
+            self.broadcast();
+        }
+    }
+
+    fn broadcast(&mut self) {
+        let mode = self.mode();
+        self.controls.retain(|it| it.is_alive());
+        for control in &self.controls {
+            control.mode(mode);
+        }
+    }
+}
+
+impl GlobalDispatch<ZwlrOutputPowerManagerV1, ()> for State {
+    fn bind(
+        _state: &mut Self,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrOutputPowerManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerManagerV1, ()> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrOutputPowerManagerV1,
+        request: zwlr_output_power_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        // There is a single output, whichever the client asks for
+        if let zwlr_output_power_manager_v1::Request::GetOutputPower { id, .. } = request {
+            let control = data_init.init(id, ());
+            let power = &mut state.output_power_state;
+            control.mode(power.mode());
+            power.controls.push(control);
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, ()> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ZwlrOutputPowerV1,
+        request: zwlr_output_power_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_output_power_v1::Request::SetMode { mode } = request {
+            match mode {
+                WEnum::Value(mode) => {
+                    state.output_power_state.request(mode);
+                    state.needs_redraw = true;
+                }
+                WEnum::Unknown(value) => resource.post_error(
+                    zwlr_output_power_v1::Error::InvalidMode,
+                    format!("Unknown power mode {}", value),
+                ),
+            }
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, resource: &ZwlrOutputPowerV1, _data: &()) {
+        state
+            .output_power_state
+            .controls
+            .retain(|it| it.id() != resource.id());
+    }
+}
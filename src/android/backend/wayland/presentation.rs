@@ -0,0 +1,311 @@
+use crate::android::utils::{application_context::get_application_context, ndk::run_in_jvm};
+use crate::core::{config::Presentation, diagnostics};
+use jni::objects::{GlobalRef, JObject, JString, JValue};
+use jni::JNIEnv;
+use smithay::utils::{Physical, Size};
+use std::{
+    ffi::c_void,
+    ptr::NonNull,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+use winit::platform::android::activity::AndroidApp;
+use winit::raw_window_handle::AndroidNdkWindowHandle;
+
+/// How long the view has to get a surface before the window of the activity is used instead
+const SURFACE_TIMEOUT: Duration = Duration::from_secs(2);
+const SURFACE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[link(name = "android")]
+extern "C" {
+    fn ANativeWindow_fromSurface(
+        env: *mut jni::sys::JNIEnv,
+        surface: jni::sys::jobject,
+    ) -> *mut c_void;
+    fn ANativeWindow_release(window: *mut c_void);
+    fn ANativeWindow_getWidth(window: *mut c_void) -> i32;
+    fn ANativeWindow_getHeight(window: *mut c_void) -> i32;
+}
+
+/// Shows a view on a thread that just prepared its Looper, returning what dismisses it (e.g. a popup), the Looper
+/// and the view
+pub type ShowView = for<'local> fn(
+    &mut JNIEnv<'local>,
+    &AndroidApp,
+    Presentation,
+) -> jni::errors::Result<(JObject<'local>, GlobalRef, GlobalRef)>;
+
+/// A view shown over the activity, whose surface the desktop is drawn in rather than the window of the activity.
+/// It lets the touches and keys through to the activity, and goes away when dropped.
+#[derive(Debug)]
+pub struct ViewSurface {
+    android_app: AndroidApp,
+    /// The thread hosting the view, until it quits
+    looper: GlobalRef,
+    /// The Java surface, kept alive along with the native window acquired from it
+    _surface: GlobalRef,
+    window: NonNull<c_void>,
+}
+
+unsafe impl Send for ViewSurface {}
+
+impl ViewSurface {
+    pub fn handle(&self) -> AndroidNdkWindowHandle {
+        AndroidNdkWindowHandle::new(self.window)
+    }
+
+    pub fn size(&self) -> Size<i32, Physical> {
+        let window = self.window.as_ptr();
+        unsafe {
+            (
+                ANativeWindow_getWidth(window),
+                ANativeWindow_getHeight(window),
+            )
+        }
+        .into()
+    }
+}
+
+impl Drop for ViewSurface {
+    fn drop(&mut self) {
+        unsafe { ANativeWindow_release(self.window.as_ptr()) };
+        let looper = self.looper.clone();
+        run_in_jvm(
+            move |env, _| {
+                // The view is dismissed as its thread quits, see `show_view`
+                if let Err(e) = env.call_method(looper.as_obj(), "quit", "()V", &[]) {
+                    log::warn!("Failed to quit the Looper of the view: {:?}", e);
+                }
+            },
+            self.android_app.clone(),
+        );
+    }
+}
+
+/// The view to draw in, if `[display] presentation` picks one. Falls back to the window of the activity, i.e.
+/// `None`, if the view does not get a surface.
+pub fn present_in_view(android_app: &AndroidApp) -> Option<ViewSurface> {
+    // Told along with the presentation, to find out which devices need a view
+    let mut device = (String::new(), String::new());
+    run_in_jvm(
+        |env, _| match (build_field(env, "MANUFACTURER"), build_field(env, "MODEL")) {
+            (Ok(manufacturer), Ok(model)) => device = (manufacturer, model),
+            (Err(e), _) | (_, Err(e)) => {
+                let _ = env.exception_clear();
+                log::warn!("Failed to read the device model: {:?}", e);
+            }
+        },
+        android_app.clone(),
+    );
+    let presentation = get_application_context()
+        .local_config
+        .display
+        .presentation();
+    let view = match presentation {
+        Presentation::NativeWindow => None,
+        _ => show_view(android_app, presentation, create_popup),
+    };
+    let used = if view.is_some() {
+        presentation
+    } else {
+        Presentation::NativeWindow
+    };
+    log::info!(
+        "Drawing in {:?} on {} {} ({:?} picked)",
+        used,
+        device.0,
+        device.1,
+        presentation
+    );
+    diagnostics::publish(
+        "presentation",
+        serde_json::json!({
+            "device": format!("{} {}", device.0, device.1),
+            "picked": format!("{:?}", presentation),
+            "used": format!("{:?}", used),
+        }),
+    );
+    view
+}
+
+fn build_field(env: &mut JNIEnv, name: &str) -> jni::errors::Result<String> {
+    let value = env
+        .get_static_field("android/os/Build", name, "Ljava/lang/String;")?
+        .l()?;
+    Ok(env.get_string(&JString::from(value))?.into())
+}
+
+/// Show the view on a thread of its own, as its popup or dialog needs a Looper, then wait for its surface
+pub fn show_view(
+    android_app: &AndroidApp,
+    presentation: Presentation,
+    show: ShowView,
+) -> Option<ViewSurface> {
+    let (sender, receiver) = mpsc::channel();
+    let app = android_app.clone();
+    thread::spawn(move || {
+        run_in_jvm(
+            move |env, app| {
+                let popup = match show(env, app, presentation) {
+                    Ok((popup, looper, view)) => {
+                        sender.send(Some((looper, view))).unwrap_or(());
+                        popup
+                    }
+                    Err(e) => {
+                        let _ = env.exception_clear();
+                        log::warn!("Failed to show the {:?}: {:?}", presentation, e);
+                        sender.send(None).unwrap_or(());
+                        return;
+                    }
+                };
+                if let Err(e) = env
+                    .call_static_method("android/os/Looper", "loop", "()V", &[])
+                    .and_then(|_| env.call_method(&popup, "dismiss", "()V", &[]))
+                {
+                    let _ = env.exception_clear();
+                    log::warn!("Failed to dismiss the {:?}: {:?}", presentation, e);
+                }
+            },
+            app,
+        );
+    });
+    let (looper, view) = receiver.recv().ok().flatten()?;
+
+    let started_at = Instant::now();
+    while started_at.elapsed() < SURFACE_TIMEOUT {
+        let mut found = None;
+        run_in_jvm(
+            |env, _| match surface_of(env, &view, presentation) {
+                Ok(surface) => found = surface,
+                Err(e) => {
+                    let _ = env.exception_clear();
+                    log::warn!("Failed to get the surface of the view: {:?}", e);
+                }
+            },
+            android_app.clone(),
+        );
+        if let Some((surface, window)) = found {
+            return Some(ViewSurface {
+                android_app: android_app.clone(),
+                looper,
+                _surface: surface,
+                window,
+            });
+        }
+        thread::sleep(SURFACE_POLL_INTERVAL);
+    }
+    log::warn!("The {:?} got no surface", presentation);
+    run_in_jvm(
+        move |env, _| {
+            let _ = env.call_method(looper.as_obj(), "quit", "()V", &[]);
+        },
+        android_app.clone(),
+    );
+    None
+}
+
+/// A full screen popup showing the view, which neither takes the touches nor the focus from the activity
+fn create_popup<'local>(
+    env: &mut JNIEnv<'local>,
+    android_app: &AndroidApp,
+    presentation: Presentation,
+) -> jni::errors::Result<(JObject<'local>, GlobalRef, GlobalRef)> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    env.call_static_method("android/os/Looper", "prepare", "()V", &[])?;
+    let class = match presentation {
+        Presentation::TextureView => "android/view/TextureView",
+        _ => "android/view/SurfaceView",
+    };
+    let view = env.new_object(
+        class,
+        "(Landroid/content/Context;)V",
+        &[JValue::Object(&activity)],
+    )?;
+    let popup = env.new_object(
+        "android/widget/PopupWindow",
+        "(Landroid/view/View;II)V",
+        &[
+            JValue::Object(&view),
+            JValue::Int(-1), // MATCH_PARENT width
+            JValue::Int(-1), // MATCH_PARENT height
+        ],
+    )?;
+    env.call_method(&popup, "setTouchable", "(Z)V", &[JValue::Bool(0)])?;
+    env.call_method(&popup, "setClippingEnabled", "(Z)V", &[JValue::Bool(0)])?;
+    env.call_method(
+        &popup,
+        "showAtLocation",
+        "(Landroid/view/View;III)V",
+        &[
+            JValue::Object(&view),
+            JValue::Int(17), // Gravity.CENTER
+            JValue::Int(0),
+            JValue::Int(0),
+        ],
+    )?;
+    let looper = env
+        .call_static_method(
+            "android/os/Looper",
+            "myLooper",
+            "()Landroid/os/Looper;",
+            &[],
+        )?
+        .l()?;
+    Ok((
+        popup,
+        env.new_global_ref(looper)?,
+        env.new_global_ref(view)?,
+    ))
+}
+
+/// The surface of the view and its native window, once the view has one
+fn surface_of(
+    env: &mut JNIEnv,
+    view: &GlobalRef,
+    presentation: Presentation,
+) -> jni::errors::Result<Option<(GlobalRef, NonNull<c_void>)>> {
+    let surface = match presentation {
+        Presentation::TextureView => {
+            if !env
+                .call_method(view.as_obj(), "isAvailable", "()Z", &[])?
+                .z()?
+            {
+                return Ok(None);
+            }
+            let texture = env
+                .call_method(
+                    view.as_obj(),
+                    "getSurfaceTexture",
+                    "()Landroid/graphics/SurfaceTexture;",
+                    &[],
+                )?
+                .l()?;
+            env.new_object(
+                "android/view/Surface",
+                "(Landroid/graphics/SurfaceTexture;)V",
+                &[JValue::Object(&texture)],
+            )?
+        }
+        _ => {
+            let holder = env
+                .call_method(
+                    view.as_obj(),
+                    "getHolder",
+                    "()Landroid/view/SurfaceHolder;",
+                    &[],
+                )?
+                .l()?;
+            env.call_method(holder, "getSurface", "()Landroid/view/Surface;", &[])?
+                .l()?
+        }
+    };
+    if !env.call_method(&surface, "isValid", "()Z", &[])?.z()? {
+        return Ok(None);
+    }
+    let window = unsafe { ANativeWindow_fromSurface(env.get_raw(), surface.as_raw()) };
+    let Some(window) = NonNull::new(window) else {
+        return Ok(None);
+    };
+    Ok(Some((env.new_global_ref(surface)?, window)))
+}
@@ -0,0 +1,190 @@
+use super::winit_backend::WinitGraphicsBackend;
+use smithay::{
+    backend::{
+        egl::{self, display::EGLDisplay, ffi, EGLSurface},
+        renderer::gles::GlesRenderer,
+    },
+    desktop::utils::OutputPresentationFeedback,
+    output::Output,
+    reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback::Kind,
+    utils::{Clock, Monotonic, Time},
+    wayland::presentation::Refresh,
+};
+use std::{
+    collections::VecDeque,
+    ffi::c_void,
+    time::{Duration, Instant},
+};
+
+const EXTENSION: &str = "EGL_ANDROID_get_frame_timestamps";
+const EGL_TIMESTAMPS_ANDROID: i32 = 0x3430;
+const EGL_DISPLAY_PRESENT_TIME_ANDROID: i32 = 0x343A;
+const EGL_TIMESTAMP_PENDING_ANDROID: i64 = -2;
+
+/// How often the frames not on screen yet are looked at again, while nothing else wakes the compositor up
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+/// A frame not on screen after that long is reported as presented now, e.g. as the window went away meanwhile
+const PRESENT_TIMEOUT: Duration = Duration::from_millis(500);
+
+type GetNextFrameId = unsafe extern "C" fn(*const c_void, *const c_void, *mut u64) -> u32;
+type GetFrameTimestamps =
+    unsafe extern "C" fn(*const c_void, *const c_void, u64, i32, *const i32, *mut i64) -> u32;
+
+/// When the frames of a surface actually show up on screen, through `EGL_ANDROID_get_frame_timestamps`
+#[derive(Debug)]
+pub struct FrameTimestamps {
+    get_next_frame_id: GetNextFrameId,
+    get_frame_timestamps: GetFrameTimestamps,
+}
+
+/// See `FrameTimestamps::present_time`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentTime {
+    /// On the `CLOCK_MONOTONIC` timeline
+    At(Duration),
+    Pending,
+    Unknown,
+}
+
+impl FrameTimestamps {
+    /// Have the driver record the timestamps of the frames of `surface`, if it can
+    pub fn enable(display: &EGLDisplay, surface: &EGLSurface) -> Option<Self> {
+        if !display.extensions().iter().any(|it| it == EXTENSION) {
+            log::info!("No {}, the frames are timed as they are swapped", EXTENSION);
+            return None;
+        }
+        let (next, timestamps) = unsafe {
+            (
+                egl::get_proc_address("eglGetNextFrameIdANDROID"),
+                egl::get_proc_address("eglGetFrameTimestampsANDROID"),
+            )
+        };
+        if next.is_null() || timestamps.is_null() {
+            return None;
+        }
+        let enabled = unsafe {
+            ffi::egl::SurfaceAttrib(
+                display.get_display_handle().handle,
+                surface.get_surface_handle(),
+                EGL_TIMESTAMPS_ANDROID,
+                ffi::egl::TRUE as i32,
+            )
+        };
+        if enabled != ffi::egl::TRUE {
+            log::warn!("Failed to turn the frame timestamps on");
+            return None;
+        }
+        Some(Self {
+            get_next_frame_id: unsafe {
+                std::mem::transmute::<*const c_void, GetNextFrameId>(next)
+            },
+            get_frame_timestamps: unsafe {
+                std::mem::transmute::<*const c_void, GetFrameTimestamps>(timestamps)
+            },
+        })
+    }
+
+    /// The id of the frame the next swap submits
+    pub fn next_frame_id(&self, display: &EGLDisplay, surface: &EGLSurface) -> Option<u64> {
+        let mut id = 0;
+        let found = unsafe {
+            (self.get_next_frame_id)(
+                display.get_display_handle().handle,
+                surface.get_surface_handle(),
+                &mut id,
+            )
+        };
+        (found == ffi::egl::TRUE).then_some(id)
+    }
+
+    /// When the frame showed up on screen
+    pub fn present_time(&self, display: &EGLDisplay, surface: &EGLSurface, id: u64) -> PresentTime {
+        let names = [EGL_DISPLAY_PRESENT_TIME_ANDROID];
+        let mut values = [0i64];
+        let found = unsafe {
+            (self.get_frame_timestamps)(
+                display.get_display_handle().handle,
+                surface.get_surface_handle(),
+                id,
+                1,
+                names.as_ptr(),
+                values.as_mut_ptr(),
+            )
+        };
+        match values[0] {
+            _ if found != ffi::egl::TRUE => PresentTime::Unknown,
+            EGL_TIMESTAMP_PENDING_ANDROID => PresentTime::Pending,
+            nanos if nanos > 0 => PresentTime::At(Duration::from_nanos(nanos as u64)),
+            _ => PresentTime::Unknown,
+        }
+    }
+}
+
+struct Submitted {
+    frame: Option<u64>,
+    feedback: OutputPresentationFeedback,
+    at: Instant,
+}
+
+/// The `wp_presentation` feedbacks of the frames swapped, until they are on screen
+#[derive(Default)]
+pub struct PresentationFeedbacks {
+    submitted: VecDeque<Submitted>,
+    /// The frames presented so far, which the clients tell the skipped ones with
+    sequence: u64,
+}
+
+impl PresentationFeedbacks {
+    /// Track the feedbacks of the frame `frame` (see `FrameTimestamps::next_frame_id`), which was just swapped
+    pub fn submitted(&mut self, frame: Option<u64>, feedback: OutputPresentationFeedback) {
+        self.submitted.push_back(Submitted {
+            frame,
+            feedback,
+            at: Instant::now(),
+        });
+    }
+
+    /// Send the feedbacks of the frames which showed up on screen, in order
+    pub fn present(
+        &mut self,
+        winit: &WinitGraphicsBackend<GlesRenderer>,
+        clock: &Clock<Monotonic>,
+    ) {
+        while let Some(submitted) = self.submitted.front_mut() {
+            let timed = submitted.frame.map(|frame| winit.present_time(frame));
+            let (time, kind) = match timed {
+                Some(PresentTime::At(time)) => (
+                    Time::<Monotonic>::from(time),
+                    Kind::Vsync | Kind::HwClock | Kind::HwCompletion,
+                ),
+                Some(PresentTime::Pending) if submitted.at.elapsed() < PRESENT_TIMEOUT => break,
+                _ => (clock.now(), Kind::Vsync),
+            };
+            let refresh = submitted
+                .feedback
+                .output()
+                .as_ref()
+                .map_or(Refresh::Unknown, refresh);
+            self.sequence += 1;
+            submitted
+                .feedback
+                .presented(time, refresh, self.sequence, kind);
+            self.submitted.pop_front();
+        }
+    }
+
+    /// When the frames not on screen yet are looked at again
+    pub fn next_poll(&self) -> Option<Instant> {
+        (!self.submitted.is_empty()).then(|| Instant::now() + POLL_INTERVAL)
+    }
+}
+
+fn refresh(output: &Output) -> Refresh {
+    match output.current_mode() {
+        Some(mode) if mode.refresh > 0 => Refresh::fixed(Duration::from_nanos(
+            1_000_000_000_000 / mode.refresh as u64,
+        )),
+        _ => Refresh::Unknown,
+    }
+}
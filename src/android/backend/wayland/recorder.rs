@@ -0,0 +1,351 @@
+use super::WinitGraphicsBackend;
+use crate::{
+    android::{
+        proot::audio::{tap_playback, CHANNELS, SAMPLE_RATE},
+        utils::{
+            aaudio::AAudioInput,
+            application_context::get_application_context,
+            media_codec::{Encoder, Muxer, Output},
+            media_store::{self, Collection},
+            ndk::{has_permission, request_permission, run_in_jvm},
+            toast::show_toast,
+        },
+    },
+    core::{
+        recording::{mix, video_size, FramePacer},
+        screenshot::file_name,
+    },
+};
+use smithay::backend::{egl::EGLSurface, renderer::gles::GlesRenderer};
+use smithay::utils::{Physical, Size};
+use std::{
+    collections::VecDeque,
+    ffi::c_void,
+    os::fd::AsRawFd,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use winit::platform::android::activity::AndroidApp;
+use winit::raw_window_handle::AndroidNdkWindowHandle;
+
+#[link(name = "android")]
+extern "C" {
+    fn ANativeWindow_release(window: *mut c_void);
+}
+
+/// The hardware encoders of most phones go up to 1080p, on either side
+const MAX_VIDEO_SIDE: i32 = 1920;
+
+const AUDIO_BITRATE: i32 = 128_000;
+
+/// The sound is encoded 1024 frames at a time, the size of an AAC frame
+const AUDIO_CHUNK_FRAMES: usize = 1024;
+
+/// The sound played and not encoded yet is dropped past that many frames, e.g. as the microphone lags behind
+const MAX_PENDING_FRAMES: usize = SAMPLE_RATE as usize;
+
+/// How long the encoders are waited for at once, so that the threads notice the end of the recording
+const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+const MICROPHONE_PERMISSION: &str = "android.permission.RECORD_AUDIO";
+
+/// The input surface of the video encoder, released after the EGL surface drawing in it
+#[derive(Debug)]
+struct InputWindow(NonNull<c_void>);
+
+impl Drop for InputWindow {
+    fn drop(&mut self) {
+        unsafe { ANativeWindow_release(self.0.as_ptr()) };
+    }
+}
+
+/// A recording of the desktop into an MP4 video, with the sound of the session and optionally the microphone. The
+/// frames are drawn into the input surface of a hardware encoder, while a thread muxes what the encoders output
+/// into the file. Dropping the recording ends it, and the file is saved into `Movies/Local Desktop` once the
+/// encoders are done.
+#[derive(Debug)]
+pub struct Recording {
+    /// Where the desktop is drawn for the encoder
+    pub surface: EGLSurface,
+    pub size: Size<i32, Physical>,
+    /// Applied once the surface is first bound, so that it never waits for the encoder
+    pub swap_interval: Option<u32>,
+    _window: InputWindow,
+    video: Arc<Encoder>,
+    pacer: FramePacer,
+    /// Tells the sound thread to end its input
+    ended: Arc<AtomicBool>,
+}
+
+impl Recording {
+    /// Start recording a desktop of `desktop` physical pixels
+    pub fn start(
+        winit: &WinitGraphicsBackend<GlesRenderer>,
+        desktop: (i32, i32),
+        android_app: &AndroidApp,
+    ) -> Result<Self, String> {
+        let config = get_application_context().local_config.recording;
+        let (width, height) = video_size(desktop, MAX_VIDEO_SIDE);
+        let frame_rate = config.frame_rate.clamp(1, 60);
+        let (video, window) = Encoder::video(
+            width,
+            height,
+            config.video_bitrate.saturating_mul(1000) as i32,
+            frame_rate as i32,
+        )?;
+        let window = InputWindow(window);
+        let surface = winit
+            .create_surface(AndroidNdkWindowHandle::new(window.0))
+            .map_err(|e| format!("Failed to draw into the encoder: {:?}", e))?;
+        let video = Arc::new(video);
+
+        let ended = Arc::new(AtomicBool::new(false));
+        let audio = match Encoder::audio(SAMPLE_RATE, CHANNELS as i32, AUDIO_BITRATE) {
+            Ok(encoder) => {
+                let encoder = Arc::new(encoder);
+                record_sound(
+                    encoder.clone(),
+                    config.microphone,
+                    ended.clone(),
+                    android_app.clone(),
+                );
+                Some(encoder)
+            }
+            Err(e) => {
+                log::warn!("The recording has no sound: {}", e);
+                None
+            }
+        };
+        mux(video.clone(), audio, android_app.clone());
+        log::info!("Recording the desktop at {}x{}", width, height);
+        Ok(Self {
+            surface,
+            size: (width, height).into(),
+            swap_interval: Some(0),
+            _window: window,
+            video,
+            pacer: FramePacer::new(frame_rate),
+            ended,
+        })
+    }
+
+    /// Whether a frame is to be recorded now, within `[recording] frame_rate`
+    pub fn frame_due(&mut self) -> bool {
+        self.pacer.due(Instant::now())
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.ended.store(true, Ordering::SeqCst);
+        if let Err(e) = self.video.end_input_surface() {
+            log::warn!("Failed to end the video: {}", e);
+        }
+    }
+}
+
+/// The time of `CLOCK_MONOTONIC`, which the frames of the input surface are stamped with, in microseconds
+fn monotonic_micros() -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    time.tv_sec as u64 * 1_000_000 + time.tv_nsec as u64 / 1000
+}
+
+/// Encode the sound played by the session, with the microphone mixed in if `microphone`, until `ended`
+fn record_sound(
+    encoder: Arc<Encoder>,
+    microphone: bool,
+    ended: Arc<AtomicBool>,
+    android_app: AndroidApp,
+) {
+    let (sender, playback) = mpsc::sync_channel(MAX_PENDING_FRAMES / AUDIO_CHUNK_FRAMES);
+    tap_playback(Some(sender));
+    thread::spawn(move || {
+        let mut microphone = microphone.then(|| open_microphone(&android_app)).flatten();
+        let chunk_duration =
+            Duration::from_secs(1) * AUDIO_CHUNK_FRAMES as u32 / SAMPLE_RATE as u32;
+        let start = monotonic_micros();
+        let started = Instant::now();
+        let mut pending = VecDeque::new();
+        let mut frames = 0u64;
+        loop {
+            let end = ended.load(Ordering::SeqCst);
+            let mut samples = vec![0i16; AUDIO_CHUNK_FRAMES * CHANNELS];
+            match microphone.as_mut() {
+                Some(input) => {
+                    if let Err(e) = input.read(&mut samples, chunk_duration * 2) {
+                        log::warn!("The microphone was lost: {}", e);
+                        microphone = None;
+                    }
+                }
+                // Paced on the clock instead of the microphone
+                None => {
+                    let due = chunk_duration * (frames / AUDIO_CHUNK_FRAMES as u64 + 1) as u32;
+                    thread::sleep(due.saturating_sub(started.elapsed()));
+                }
+            }
+            take_playback(&playback, &mut pending);
+            let played = pending
+                .drain(..samples.len().min(pending.len()))
+                .collect::<Vec<_>>();
+            mix(&mut samples, &played);
+
+            let bytes = samples
+                .iter()
+                .flat_map(|it| it.to_le_bytes())
+                .collect::<Vec<_>>();
+            let time = start + frames * 1_000_000 / SAMPLE_RATE as u64;
+            let mut queued = 0;
+            while queued < bytes.len() {
+                match encoder.queue_input(&bytes[queued..], time, end, POLL_TIMEOUT) {
+                    Ok(size) => queued += size,
+                    Err(e) => {
+                        log::warn!("Failed to encode the sound: {}", e);
+                        break;
+                    }
+                }
+            }
+            frames += AUDIO_CHUNK_FRAMES as u64;
+            if end {
+                break;
+            }
+        }
+        tap_playback(None);
+    });
+}
+
+/// Move the samples played since the last chunk to `pending`, dropping the oldest ones past the limit
+fn take_playback(playback: &Receiver<Vec<i16>>, pending: &mut VecDeque<i16>) {
+    while let Ok(samples) = playback.try_recv() {
+        pending.extend(samples);
+    }
+    let max = MAX_PENDING_FRAMES * CHANNELS;
+    if pending.len() > max {
+        pending.drain(..pending.len() - max);
+    }
+}
+
+/// The microphone, once the user allowed the app to use it
+fn open_microphone(android_app: &AndroidApp) -> Option<AAudioInput> {
+    let mut granted = false;
+    run_in_jvm(
+        |env, app| match has_permission(env, app, MICROPHONE_PERMISSION) {
+            Ok(true) => granted = true,
+            Ok(false) => {
+                if request_permission(env, app, MICROPHONE_PERMISSION).is_err() {
+                    let _ = env.exception_clear();
+                }
+            }
+            Err(_) => {
+                let _ = env.exception_clear();
+            }
+        },
+        android_app.clone(),
+    );
+    if !granted {
+        show_toast(
+            android_app,
+            "Allow the microphone for the next recordings".to_string(),
+        );
+        return None;
+    }
+    match AAudioInput::open(SAMPLE_RATE, CHANNELS as i32) {
+        Ok(input) => Some(input),
+        Err(e) => {
+            log::warn!("Failed to open the microphone: {}", e);
+            None
+        }
+    }
+}
+
+/// An encoder and the track of the muxer it writes into
+struct Track {
+    encoder: Arc<Encoder>,
+    index: Option<usize>,
+    ended: bool,
+}
+
+/// Write what the encoders output into an MP4 file of `Movies/Local Desktop`, until they are both done
+fn mux(video: Arc<Encoder>, audio: Option<Arc<Encoder>>, android_app: AndroidApp) {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs());
+    let name = file_name("Recording", secs, "mp4");
+    thread::spawn(move || {
+        run_in_jvm(
+            |env, app| {
+                let mut tracks = [Some(video), audio]
+                    .into_iter()
+                    .flatten()
+                    .map(|encoder| Track {
+                        encoder,
+                        index: None,
+                        ended: false,
+                    })
+                    .collect::<Vec<_>>();
+                let saved =
+                    media_store::save(env, app, Collection::Movies, &name, "video/mp4", |file| {
+                        let mut muxer =
+                            Muxer::mp4(file.as_raw_fd()).map_err(std::io::Error::other)?;
+                        write_tracks(&mut muxer, &mut tracks).map_err(std::io::Error::other)?;
+                        // Finishes the file
+                        drop(muxer);
+                        Ok(())
+                    });
+                match saved {
+                    Ok(location) => {
+                        log::info!("Saved a recording to {}", location);
+                        show_toast(app, format!("Recording saved to {}", location));
+                    }
+                    Err(e) => {
+                        log::error!("Failed to save the recording: {}", e);
+                        show_toast(app, "Failed to save the recording".to_string());
+                    }
+                }
+            },
+            android_app,
+        );
+    });
+}
+
+fn write_tracks(muxer: &mut Muxer, tracks: &mut [Track]) -> Result<(), String> {
+    // The muxer starts once it knows every track, and the samples output until then wait for it
+    let mut waiting = Vec::new();
+    let mut unknown_tracks = tracks.len();
+    while tracks.iter().any(|it| !it.ended) {
+        for track in tracks.iter_mut().filter(|it| !it.ended) {
+            match track.encoder.dequeue_output(POLL_TIMEOUT)? {
+                Output::Nothing => {}
+                Output::FormatChanged => {
+                    track.index = Some(muxer.add_track(&track.encoder)?);
+                    unknown_tracks -= 1;
+                    if unknown_tracks == 0 {
+                        muxer.start()?;
+                        for (index, sample) in waiting.drain(..) {
+                            muxer.write_owned(index, &sample)?;
+                        }
+                    }
+                }
+                Output::Sample(sample) => match track.index {
+                    Some(index) if unknown_tracks == 0 => muxer.write(index, &sample)?,
+                    Some(index) => waiting.push((index, sample.keep())),
+                    None => log::warn!("An encoder output a sample before its format"),
+                },
+                Output::End => track.ended = true,
+            };
+        }
+    }
+    if unknown_tracks > 0 {
+        return Err("The recording ended before its first frame".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use crate::{
+    android::{proot::process::ArchProcess, utils::application_context::get_application_context},
+    core::{
+        config::{self, RemoteConfig},
+        logging::panel_log,
+        token::constant_time_eq,
+    },
+};
+use std::{
+    io::{self, Read},
+    net::{Shutdown, TcpListener, TcpStream},
+    os::unix::net::UnixStream,
+    sync::Once,
+    thread,
+    time::Duration,
+};
+
+/// Where `waypipe client` listens inside the Arch FS, turning the forwarded streams back into Wayland connections
+const WAYPIPE_SOCKET: &str = "/tmp/waypipe-remote.sock";
+
+/// The remote side has this long to authenticate
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest token accepted, so that a stray connection cannot make us buffer forever
+const MAX_TOKEN_LENGTH: usize = 256;
+
+static START: Once = Once::new();
+
+/// Expose the display to the Wayland clients of other machines, if enabled in `[remote]`.
+///
+/// Wayland needs to pass file descriptors, which TCP cannot carry, so the remote apps run under
+/// `waypipe server`, whose stream reaches the `waypipe client` running in the Arch FS through this socket:
+/// `waypipe --socket /tmp/wp.sock server -- weston-terminal` on the remote machine, then
+/// `socat UNIX-LISTEN:/tmp/wp.sock,fork SYSTEM:'(echo <token>; exec socat - TCP:<phone>:<port>)'`.
+/// Every connection must start with the configured token followed by a newline.
+pub fn serve_remote_clients() {
+    let remote = get_application_context().local_config.remote;
+    if !remote.enabled {
+        return;
+    }
+    START.call_once(|| {
+        if remote.token.trim().is_empty() {
+            panel_log(
+                "Remote clients are enabled without a token, refusing to expose the display",
+                true,
+            );
+            return;
+        }
+        thread::spawn(move || {
+            if let Err(e) = listen(remote) {
+                panel_log(format!("Remote clients are unavailable: {}", e), true);
+            }
+        });
+    });
+}
+
+fn listen(remote: RemoteConfig) -> io::Result<()> {
+    start_waypipe_client();
+
+    let listener = TcpListener::bind(("0.0.0.0", remote.port))?;
+    panel_log(
+        format!("Accepting remote Wayland clients on port {}", remote.port),
+        false,
+    );
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(it) => it,
+            Err(e) => {
+                log::warn!("Failed to accept a remote client: {}", e);
+                continue;
+            }
+        };
+        let token = remote.token.clone();
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or("unknown peer".to_string(), |it| it.to_string());
+            match authenticate(&stream, &token) {
+                Ok(true) => {
+                    log::info!("Remote client {} authenticated", peer);
+                    if let Err(e) = forward(stream) {
+                        log::warn!("Remote client {} disconnected: {}", peer, e);
+                    }
+                }
+                Ok(false) => panel_log(
+                    format!("Rejected remote client {}: wrong token", peer),
+                    true,
+                ),
+                Err(e) => log::warn!("Remote client {} failed to authenticate: {}", peer, e),
+            }
+        });
+    }
+    Ok(())
+}
+
+fn start_waypipe_client() {
+    let username = get_application_context().local_config.user.username;
+    let command = format!(
+        "rm -f {socket}; XDG_RUNTIME_DIR=/tmp WAYLAND_DISPLAY={display} waypipe --socket {socket} client 2>&1",
+        socket = WAYPIPE_SOCKET,
+        display = config::WAYLAND_SOCKET_NAME,
+    );
+    thread::spawn(move || match ArchProcess::exec_as(&command, &username) {
+        Ok(client) => {
+            client.with_log(|it| log::info!("waypipe: {}", it));
+            panel_log("waypipe client exited, is waypipe installed?", true);
+        }
+        Err(e) => panel_log(e.to_string(), true),
+    });
+}
+
+/// Read the token line byte by byte, as anything after it belongs to waypipe
+fn authenticate(mut stream: &TcpStream, token: &str) -> io::Result<bool> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut received = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        stream.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        if received.len() >= MAX_TOKEN_LENGTH {
+            return Ok(false);
+        }
+        received.push(byte[0]);
+    }
+    stream.set_read_timeout(None)?;
+
+    let received = received.strip_suffix(b"\r").unwrap_or(&received);
+    Ok(constant_time_eq(received, token.trim().as_bytes()))
+}
+
+fn forward(stream: TcpStream) -> io::Result<()> {
+    let socket = format!("{}{}", config::ARCH_FS_ROOT, WAYPIPE_SOCKET);
+    let waypipe = UnixStream::connect(socket)?;
+
+    let (mut tcp_reader, mut tcp_writer) = (stream.try_clone()?, stream);
+    let (mut waypipe_reader, mut waypipe_writer) = (waypipe.try_clone()?, waypipe);
+    let upstream = thread::spawn(move || {
+        let result = io::copy(&mut tcp_reader, &mut waypipe_writer);
+        let _ = waypipe_writer.shutdown(Shutdown::Write);
+        result
+    });
+    let result = io::copy(&mut waypipe_reader, &mut tcp_writer);
+    let _ = tcp_writer.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+    result.map(|_| ())
+}
@@ -0,0 +1,81 @@
+use smithay::{
+    backend::renderer::{
+        element::{
+            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+            Kind,
+        },
+        gles::GlesRenderer,
+    },
+    reexports::wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource},
+    utils::{Physical, Point},
+    wayland::compositor::get_parent,
+};
+use std::collections::HashMap;
+
+/// The render elements of a window, as of the last time one of its surfaces committed
+struct CachedTree {
+    location: Point<i32, Physical>,
+    scale: f64,
+    elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>>,
+}
+
+/// Most windows of a desktop stay still between frames: their render elements are reused until one of their
+/// surfaces commits, rather than walking their surface tree and importing their buffers again on every frame
+#[derive(Default)]
+pub struct RenderCache {
+    trees: HashMap<ObjectId, CachedTree>,
+}
+
+impl RenderCache {
+    /// A surface committed: the window it belongs to is rebuilt on the next frame
+    pub fn invalidate(&mut self, surface: &WlSurface) {
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        self.trees.remove(&root.id());
+    }
+
+    /// The render elements of the window whose root surface is `surface`, drawn at `location`
+    pub fn elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        surface: &WlSurface,
+        location: Point<i32, Physical>,
+        scale: f64,
+    ) -> Vec<WaylandSurfaceRenderElement<GlesRenderer>> {
+        if let Some(tree) = self.trees.get(&surface.id()) {
+            if tree.location == location && tree.scale == scale {
+                return tree.elements.clone();
+            }
+        }
+        let elements = render_elements_from_surface_tree(
+            renderer,
+            surface,
+            location,
+            scale,
+            1.0,
+            Kind::Unspecified,
+        );
+        self.trees.insert(
+            surface.id(),
+            CachedTree {
+                location,
+                scale,
+                elements: elements.clone(),
+            },
+        );
+        elements
+    }
+
+    /// Forget every window, e.g. to give memory back to Android, at the cost of importing them all again
+    pub fn clear(&mut self) {
+        self.trees = HashMap::new();
+    }
+
+    /// Forget the windows that are gone, so that their buffers are released
+    pub fn retain(&mut self, surfaces: &[WlSurface]) {
+        self.trees
+            .retain(|id, _| surfaces.iter().any(|it| it.id() == *id));
+    }
+}
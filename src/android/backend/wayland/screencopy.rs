@@ -0,0 +1,282 @@
+use super::compositor::State;
+use smithay::output::Output;
+use smithay::reexports::{
+    wayland_protocols_wlr::screencopy::v1::server::{
+        zwlr_screencopy_frame_v1::{self, Flags, ZwlrScreencopyFrameV1},
+        zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+    },
+    wayland_server::{
+        backend::GlobalId,
+        protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+use smithay::utils::{Physical, Rectangle, Size};
+use smithay::wayland::shm::{with_buffer_contents, with_buffer_contents_mut};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// The only format the frames are offered in, whose bytes are the ones read from OpenGL: red, green, blue, and the
+/// alpha the clients ignore
+const FORMAT: wl_shm::Format = wl_shm::Format::Xbgr8888;
+
+/// Lets the tools of the session capture the desktop through `zwlr_screencopy_manager_v1`, e.g. `grim` or OBS with
+/// `wlrobs`. The desktop is drawn again for them, without what the phone draws over it, and copied into their shared
+/// memory buffers. Dmabufs are not offered, which the tools do without.
+pub struct ScreencopyState {
+    global: GlobalId,
+    /// The copies asked for, done on the next frame
+    pending: Vec<ScreencopyFrame>,
+}
+
+impl ScreencopyState {
+    pub fn new(dh: &DisplayHandle) -> Self {
+        let global = dh.create_global::<State, ZwlrScreencopyManagerV1, _>(3, ());
+        Self {
+            global,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// The copies to do on this frame, of the frames the clients still wait for
+    pub fn take(&mut self) -> Vec<ScreencopyFrame> {
+        self.pending
+            .drain(..)
+            .filter(|it| it.frame.is_alive())
+            .collect()
+    }
+}
+
+/// What a frame captures
+#[derive(Debug, Clone)]
+struct Capture {
+    output: Output,
+    /// The part of the output, in physical pixels
+    region: Rectangle<i32, Physical>,
+    overlay_cursor: bool,
+}
+
+/// The user data of a frame: what it captures, if the output could be captured at all
+pub struct FrameData {
+    capture: Option<Capture>,
+    copied: AtomicBool,
+}
+
+/// A frame a client waits for, along with the buffer to copy it into
+pub struct ScreencopyFrame {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    capture: Capture,
+    with_damage: bool,
+}
+
+impl ScreencopyFrame {
+    pub fn output(&self) -> &Output {
+        &self.capture.output
+    }
+
+    pub fn region(&self) -> Rectangle<i32, Physical> {
+        self.capture.region
+    }
+
+    pub fn overlay_cursor(&self) -> bool {
+        self.capture.overlay_cursor
+    }
+
+    /// Copy the pixels of the region, top row first, into the buffer of the client, and tell it the frame is ready
+    /// as of `time`, on the monotonic clock
+    pub fn complete(self, pixels: &[u8], time: Duration) {
+        let size = self.capture.region.size;
+        let row = size.w as usize * 4;
+        let copied = with_buffer_contents_mut(&self.buffer, |ptr, len, info| {
+            let stride = info.stride as usize;
+            for (y, source) in pixels.chunks_exact(row).take(size.h as usize).enumerate() {
+                let start = info.offset as usize + y * stride;
+                if start + row > len {
+                    return false;
+                }
+                unsafe { std::ptr::copy_nonoverlapping(source.as_ptr(), ptr.add(start), row) };
+            }
+            true
+        });
+        if !matches!(copied, Ok(true)) {
+            self.frame.failed();
+            return;
+        }
+        self.frame.flags(Flags::empty());
+        if self.with_damage {
+            self.frame.damage(0, 0, size.w as u32, size.h as u32);
+        }
+        let secs = time.as_secs();
+        self.frame
+            .ready((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+    }
+
+    pub fn fail(self) {
+        self.frame.failed();
+    }
+}
+
+/// The part of an output of `size` physical pixels a frame captures, given the region of `capture_output_region` in
+/// the logical pixels of the output
+fn capture_region(
+    output: &Output,
+    size: Size<i32, Physical>,
+    region: Option<(i32, i32, i32, i32)>,
+) -> Rectangle<i32, Physical> {
+    let whole = Rectangle::from_size(size);
+    let Some((x, y, width, height)) = region else {
+        return whole;
+    };
+    let scale = output.current_scale().fractional_scale();
+    let scaled = |value: i32| (value as f64 * scale).round() as i32;
+    Rectangle::new(
+        (scaled(x), scaled(y)).into(),
+        (scaled(width), scaled(height)).into(),
+    )
+    .intersection(whole)
+    .unwrap_or_default()
+}
+
+fn start_capture(
+    data_init: &mut DataInit<'_, State>,
+    frame: New<ZwlrScreencopyFrameV1>,
+    overlay_cursor: i32,
+    output: &WlOutput,
+    region: Option<(i32, i32, i32, i32)>,
+) {
+    let capture = Output::from_resource(output).and_then(|output| {
+        let mode = output.current_mode()?;
+        let size = output.current_transform().transform_size(mode.size);
+        let region = capture_region(&output, size, region);
+        (!region.is_empty()).then_some(Capture {
+            output,
+            region,
+            overlay_cursor: overlay_cursor != 0,
+        })
+    });
+    let frame = data_init.init(
+        frame,
+        FrameData {
+            capture: capture.clone(),
+            copied: AtomicBool::new(false),
+        },
+    );
+    let Some(capture) = capture else {
+        frame.failed();
+        return;
+    };
+    let size = capture.region.size;
+    frame.buffer(FORMAT, size.w as u32, size.h as u32, size.w as u32 * 4);
+    if frame.version() >= 3 {
+        frame.buffer_done();
+    }
+}
+
+impl GlobalDispatch<ZwlrScreencopyManagerV1, ()> for State {
+    fn bind(
+        _state: &mut Self,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for State {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput {
+                frame,
+                overlay_cursor,
+                output,
+            } => start_capture(data_init, frame, overlay_cursor, &output, None),
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => start_capture(
+                data_init,
+                frame,
+                overlay_cursor,
+                &output,
+                Some((x, y, width, height)),
+            ),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, FrameData> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &FrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            _ => return,
+        };
+        if data.copied.swap(true, Ordering::SeqCst) {
+            resource.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "The frame was already copied",
+            );
+            return;
+        }
+        let Some(capture) = data.capture.clone() else {
+            resource.failed();
+            return;
+        };
+        let size = capture.region.size;
+        let fits = with_buffer_contents(&buffer, |_, _, info| {
+            info.format == FORMAT
+                && info.width == size.w
+                && info.height == size.h
+                && info.stride >= size.w * 4
+        });
+        if !matches!(fits, Ok(true)) {
+            resource.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "The buffer does not match the frame",
+            );
+            return;
+        }
+        state.screencopy_state.pending.push(ScreencopyFrame {
+            frame: resource.clone(),
+            buffer,
+            capture,
+            with_damage,
+        });
+        // A copy with damage waits for the desktop to change
+        if !with_damage {
+            state.needs_redraw = true;
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::config::{BackAction, GestureAction},
+};
+use smithay::input::keyboard::{Keysym, KeysymHandle, ModifiersState};
+
+/// Actions handled by the compositor itself, instead of being forwarded to the focused client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositorAction {
+    ShowDiagnostics,
+    ShowSettings,
+    ShowTerminal,
+    ToggleSoftKeyboard,
+    /// Save the next frame into the Pictures of the phone
+    Screenshot,
+    /// Start recording the desktop into the Movies of the phone, or stop
+    ToggleRecording,
+    /// Keep the screen on, or let it turn off, over `[power] keep_screen_on`
+    ToggleKeepScreenOn,
+    NextLayout,
+    /// Press Esc in the focused window
+    Escape,
+    ConfirmQuit,
+}
+
+impl CompositorAction {
+    /// The action a gesture is bound to in `[gestures]`, if any
+    pub fn of_gesture(action: GestureAction) -> Option<Self> {
+        match action {
+            GestureAction::None => None,
+            GestureAction::ShowDiagnostics => Some(Self::ShowDiagnostics),
+            GestureAction::ShowSettings => Some(Self::ShowSettings),
+            GestureAction::ShowTerminal => Some(Self::ShowTerminal),
+            GestureAction::ToggleSoftKeyboard => Some(Self::ToggleSoftKeyboard),
+            GestureAction::Screenshot => Some(Self::Screenshot),
+            GestureAction::ToggleRecording => Some(Self::ToggleRecording),
+        }
+    }
+
+    /// The action the Back button is bound to in `[gestures]`
+    pub fn of_back(action: BackAction) -> Self {
+        match action {
+            BackAction::Escape => Self::Escape,
+            BackAction::ToggleSoftKeyboard => Self::ToggleSoftKeyboard,
+            BackAction::ConfirmQuit => Self::ConfirmQuit,
+        }
+    }
+}
+
+/// Match a key press against the compositor shortcuts.
+/// Shortcuts use Ctrl+Alt+Shift, so that they don't collide with the XFCE defaults. Ctrl+Space switches the keyboard
+/// layout, only with several layouts in `[keyboard]`.
+pub fn match_shortcut(
+    modifiers: &ModifiersState,
+    keysym: &KeysymHandle,
+) -> Option<CompositorAction> {
+    if modifiers.ctrl
+        && !(modifiers.alt || modifiers.shift || modifiers.logo)
+        && keysym.raw_latin_sym_or_raw_current_sym() == Some(Keysym::space)
+        && get_application_context()
+            .local_config
+            .keyboard
+            .has_several_layouts()
+    {
+        return Some(CompositorAction::NextLayout);
+    }
+    if !(modifiers.ctrl && modifiers.alt && modifiers.shift) {
+        return None;
+    }
+    match keysym.raw_latin_sym_or_raw_current_sym()? {
+        Keysym::d => Some(CompositorAction::ShowDiagnostics),
+        Keysym::k => Some(CompositorAction::ToggleSoftKeyboard),
+        Keysym::p => Some(CompositorAction::Screenshot),
+        Keysym::r => Some(CompositorAction::ToggleRecording),
+        Keysym::s => Some(CompositorAction::ShowSettings),
+        Keysym::t => Some(CompositorAction::ShowTerminal),
+        Keysym::w => Some(CompositorAction::ToggleKeepScreenOn),
+        _ => None,
+    }
+}
@@ -0,0 +1,67 @@
+use winit::window::Window;
+
+/// Touching the screen with that many fingers toggles the soft keyboard
+const TOGGLE_TOUCH_POINTS: usize = 3;
+
+/// The Android soft keyboard, shown when the focused client asks for text input, or on demand
+/// for the X11 apps that have no way to ask for it
+#[derive(Default)]
+pub struct SoftKeyboard {
+    /// Whether the keyboard should be visible
+    visible: bool,
+    /// Whether it currently is, as far as we know
+    shown: bool,
+    /// What the focused client asked for on the last sync, so that only its changes show or hide the keyboard
+    client_wants_keyboard: bool,
+    touch_points: usize,
+}
+
+impl SoftKeyboard {
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn touch_down(&mut self) {
+        self.touch_points += 1;
+        if self.touch_points == TOGGLE_TOUCH_POINTS {
+            self.toggle();
+        }
+    }
+
+    pub fn touch_up(&mut self) {
+        self.touch_points = self.touch_points.saturating_sub(1);
+    }
+
+    pub fn touch_cancel(&mut self) {
+        self.touch_points = 0;
+    }
+
+    /// The keyboard is gone along with the window, e.g. once the app went to the background
+    pub fn reset(&mut self) {
+        self.shown = false;
+    }
+
+    /// Show or hide the keyboard, following the text input requests of the focused client
+    pub fn sync(&mut self, window: &Window, client_wants_keyboard: bool) {
+        if client_wants_keyboard != self.client_wants_keyboard {
+            self.client_wants_keyboard = client_wants_keyboard;
+            self.visible = client_wants_keyboard;
+        }
+        if self.visible != self.shown {
+            log::debug!(
+                "{} the soft keyboard",
+                if self.visible { "Showing" } else { "Hiding" }
+            );
+            window.set_ime_allowed(self.visible);
+            self.shown = self.visible;
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use super::element::WindowRenderElement;
+use crate::android::{
+    proot::launch::{launch_stage, LaunchStage},
+    utils::{
+        ndk::{read_asset, run_in_jvm},
+        text::rasterize_text,
+    },
+};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::{
+    element::{
+        texture::{TextureBuffer, TextureRenderElement},
+        Kind,
+    },
+    gles::{GlesRenderer, GlesTexture},
+};
+use smithay::utils::{Physical, Size, Transform};
+use winit::platform::android::activity::AndroidApp;
+
+/// Size (in pixels) of the logo drawn in the middle of the splash
+const LOGO_SIZE: i32 = 192;
+
+/// Gap (in pixels) between the logo and the stage text
+const TEXT_MARGIN: i32 = 32;
+
+const TEXT_SIZE: f32 = 40.0;
+
+const TEXT_COLOR: u32 = 0xFFDDDDDD;
+
+/// Shown in place of the long black screen between the app launch and the first frame of the desktop
+pub struct Splash {
+    android_app: AndroidApp,
+    finished: bool,
+    logo: Option<TextureBuffer<GlesTexture>>,
+    logo_loaded: bool,
+    stage: Option<LaunchStage>,
+    stage_text: Option<(TextureBuffer<GlesTexture>, Size<i32, Physical>)>,
+}
+
+impl Splash {
+    pub fn new(android_app: AndroidApp) -> Self {
+        Self {
+            android_app,
+            finished: false,
+            logo: None,
+            logo_loaded: false,
+            stage: None,
+            stage_text: None,
+        }
+    }
+
+    /// Forget the images uploaded with a renderer that is gone
+    pub fn release_textures(&mut self) {
+        self.logo = None;
+        self.logo_loaded = false;
+        self.stage = None;
+        self.stage_text = None;
+    }
+
+    pub fn visible(&self) -> bool {
+        !self.finished
+    }
+
+    /// Hide the splash for good, once a client frame is on screen. Returns `true` the first time only.
+    pub fn finish(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        log::info!("First client frame presented, hiding the splash");
+        self.finished = true;
+        self.logo = None;
+        self.stage_text = None;
+        true
+    }
+
+    pub fn render_elements(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output_size: Size<i32, Physical>,
+    ) -> Vec<WindowRenderElement<GlesRenderer>> {
+        if self.finished {
+            return vec![];
+        }
+        self.load_logo(renderer);
+        self.update_stage_text(renderer);
+
+        let center_x = output_size.w / 2;
+        let logo_y = output_size.h / 2 - LOGO_SIZE;
+        let mut elements = vec![];
+        if let Some((texture, text_size)) = &self.stage_text {
+            elements.push(
+                TextureRenderElement::from_texture_buffer(
+                    (
+                        (center_x - text_size.w / 2) as f64,
+                        (logo_y + LOGO_SIZE + TEXT_MARGIN) as f64,
+                    ),
+                    texture,
+                    None,
+                    None,
+                    None,
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+        if let Some(texture) = &self.logo {
+            elements.push(
+                TextureRenderElement::from_texture_buffer(
+                    ((center_x - LOGO_SIZE / 2) as f64, logo_y as f64),
+                    texture,
+                    None,
+                    None,
+                    Some((LOGO_SIZE, LOGO_SIZE).into()),
+                    Kind::Unspecified,
+                )
+                .into(),
+            );
+        }
+        elements
+    }
+
+    fn load_logo(&mut self, renderer: &mut GlesRenderer) {
+        if self.logo_loaded {
+            return;
+        }
+        self.logo_loaded = true;
+
+        let image = match read_asset(&self.android_app, "icon.png")
+            .and_then(|bytes| image::load_from_memory(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                log::warn!("Failed to load the splash logo: {}", e);
+                return;
+            }
+        };
+        match TextureBuffer::from_memory(
+            renderer,
+            image.as_raw(),
+            Fourcc::Abgr8888,
+            (image.width() as i32, image.height() as i32),
+            false,
+            1,
+            Transform::Normal,
+            None,
+        ) {
+            Ok(texture) => self.logo = Some(texture),
+            Err(e) => log::warn!("Failed to upload the splash logo: {:?}", e),
+        }
+    }
+
+    fn update_stage_text(&mut self, renderer: &mut GlesRenderer) {
+        let stage = launch_stage();
+        if self.stage == Some(stage) {
+            return;
+        }
+        self.stage = Some(stage);
+        self.stage_text = None;
+
+        let mut bitmap = None;
+        run_in_jvm(
+            |env, _| match rasterize_text(env, stage.label(), TEXT_SIZE, TEXT_COLOR) {
+                Ok(it) => bitmap = Some(it),
+                Err(e) => log::warn!("Failed to rasterize the splash text: {:?}", e),
+            },
+            self.android_app.clone(),
+        );
+        let Some(bitmap) = bitmap else {
+            return;
+        };
+        match TextureBuffer::from_memory(
+            renderer,
+            &bitmap.pixels,
+            Fourcc::Abgr8888,
+            (bitmap.width, bitmap.height),
+            false,
+            1,
+            Transform::Normal,
+            None,
+        ) {
+            Ok(texture) => self.stage_text = Some((texture, (bitmap.width, bitmap.height).into())),
+            Err(e) => log::warn!("Failed to upload the splash text: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use super::compositor::{ClientState, Compositor, State};
+use crate::core::diagnostics;
+use serde_json::{json, Value};
+use smithay::utils::{Logical, Size};
+use std::{
+    collections::VecDeque,
+    fs,
+    time::{Duration, Instant},
+};
+
+/// Commits older than this are not counted in the commit rate anymore
+const COMMIT_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Disconnected clients stay visible on the diagnostics page for a while, so that their disconnect reason can be read
+const DISCONNECTED_CLIENT_RETENTION: Duration = Duration::from_secs(60);
+
+/// How often the client statistics are republished to the diagnostics page
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Per-client metrics, used to find out which app is hammering the compositor when the session feels slow
+#[derive(Debug)]
+pub struct ClientStats {
+    pub connected_at: Instant,
+    pub last_activity: Instant,
+    pub surfaces: usize,
+    pub commits: u64,
+    recent_commits: VecDeque<Instant>,
+    pub buffer_size: Option<Size<i32, Logical>>,
+    pub largest_buffer_size: Option<Size<i32, Logical>>,
+    pub protocol_errors: u32,
+    pub disconnected: Option<(Instant, String)>,
+}
+
+impl Default for ClientStats {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            connected_at: now,
+            last_activity: now,
+            surfaces: 0,
+            commits: 0,
+            recent_commits: VecDeque::new(),
+            buffer_size: None,
+            largest_buffer_size: None,
+            protocol_errors: 0,
+            disconnected: None,
+        }
+    }
+}
+
+impl ClientStats {
+    pub fn record_commit(&mut self, buffer_size: Option<Size<i32, Logical>>) {
+        let now = Instant::now();
+        self.commits += 1;
+        self.last_activity = now;
+        self.recent_commits.push_back(now);
+        self.prune_commits(now);
+
+        if let Some(size) = buffer_size {
+            self.buffer_size = Some(size);
+            let area = |size: Size<i32, Logical>| size.w as i64 * size.h as i64;
+            if self
+                .largest_buffer_size
+                .map_or(true, |it| area(size) > area(it))
+            {
+                self.largest_buffer_size = Some(size);
+            }
+        }
+    }
+
+    pub fn record_disconnect(&mut self, reason: String, is_protocol_error: bool) {
+        if is_protocol_error {
+            self.protocol_errors += 1;
+        }
+        let now = Instant::now();
+        self.last_activity = now;
+        self.disconnected = Some((now, reason));
+    }
+
+    /// Commits per second, over the last `COMMIT_RATE_WINDOW`
+    pub fn commit_rate(&mut self) -> f64 {
+        self.prune_commits(Instant::now());
+        self.recent_commits.len() as f64 / COMMIT_RATE_WINDOW.as_secs_f64()
+    }
+
+    fn prune_commits(&mut self, now: Instant) {
+        while let Some(oldest) = self.recent_commits.front() {
+            if now.duration_since(*oldest) <= COMMIT_RATE_WINDOW {
+                break;
+            }
+            self.recent_commits.pop_front();
+        }
+    }
+}
+
+fn format_size(size: Option<Size<i32, Logical>>) -> Value {
+    size.map_or(Value::Null, |it| json!(format!("{}x{}", it.w, it.h)))
+}
+
+/// The name of the process behind a client, as seen from Android
+fn process_name(pid: i32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/comm", pid))
+        .ok()
+        .map(|it| it.trim().to_string())
+}
+
+/// Forget about clients that have been disconnected for long enough, and publish the statistics of the rest
+pub fn publish_client_stats(compositor: &mut Compositor) {
+    let now = Instant::now();
+    if now.duration_since(compositor.stats_published_at) < PUBLISH_INTERVAL {
+        return;
+    }
+    compositor.stats_published_at = now;
+
+    compositor.state.clients.retain(|client| {
+        client
+            .get_data::<ClientState>()
+            .and_then(|it| {
+                it.stats
+                    .lock()
+                    .ok()
+                    .and_then(|stats| stats.disconnected.clone())
+            })
+            .map_or(true, |(at, _)| {
+                now.duration_since(at) < DISCONNECTED_CLIENT_RETENTION
+            })
+    });
+
+    let dh = &compositor.state.display_handle;
+    let clients = compositor
+        .state
+        .clients
+        .iter()
+        .filter_map(|client| {
+            let credentials = client.get_credentials(dh).ok();
+            let state = client.get_data::<ClientState>()?;
+            let mut stats = state.stats.lock().ok()?;
+            Some(json!({
+                "id": format!("{:?}", client.id()),
+                "pid": credentials.map(|it| it.pid),
+                "process": credentials.and_then(|it| process_name(it.pid)),
+                "surfaces": stats.surfaces,
+                "commits": stats.commits,
+                "commits_per_second": stats.commit_rate(),
+                "buffer_size": format_size(stats.buffer_size),
+                "largest_buffer_size": format_size(stats.largest_buffer_size),
+                "protocol_errors": stats.protocol_errors,
+                "connected_for_seconds": now.duration_since(stats.connected_at).as_secs(),
+                "idle_for_seconds": now.duration_since(stats.last_activity).as_secs(),
+                "disconnect_reason": stats.disconnected.as_ref().map(|(_, reason)| reason.clone()),
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    diagnostics::publish("clients", Value::Array(clients));
+}
+
+/// The globals the clients can bind, to check which protocols (and versions) the session gets
+pub fn publish_globals(state: &State) {
+    let handle = state.display_handle.backend_handle();
+    let globals = state
+        .globals
+        .iter()
+        .filter_map(|id| handle.global_info(id.clone()).ok())
+        .filter(|it| !it.disabled)
+        .map(|it| json!({ "interface": it.interface.name, "version": it.version }))
+        .collect::<Vec<_>>();
+    diagnostics::publish("wayland globals", Value::Array(globals));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_track_largest_buffer() {
+        let mut stats = ClientStats::default();
+        stats.record_commit(Some((800, 600).into()));
+        stats.record_commit(Some((100, 100).into()));
+        assert_eq!(stats.buffer_size, Some((100, 100).into()));
+        assert_eq!(stats.largest_buffer_size, Some((800, 600).into()));
+        assert_eq!(stats.commits, 2);
+    }
+
+    #[test]
+    fn should_count_recent_commits_only() {
+        let mut stats = ClientStats::default();
+        stats
+            .recent_commits
+            .push_back(Instant::now() - COMMIT_RATE_WINDOW * 2);
+        stats.record_commit(None);
+        assert_eq!(stats.commit_rate(), 1.0);
+    }
+}
@@ -0,0 +1,113 @@
+use super::compositor::State;
+use crate::core::stylus::tilt_degrees;
+use smithay::{
+    backend::input::{TabletToolCapabilities, TabletToolDescriptor, TabletToolType},
+    input::Seat,
+    utils::{Logical, Point},
+    wayland::tablet_manager::{TabletDescriptor, TabletHandle, TabletSeatTrait, TabletToolHandle},
+};
+use winit::{event::ElementState, platform::android::Stylus};
+
+/// Linux input event codes of the buttons of the barrel
+pub const BTN_STYLUS: u32 = 0x14b;
+pub const BTN_STYLUS2: u32 = 0x14c;
+
+/// What a stylus did, along with its axes at that time
+#[derive(Debug)]
+pub struct StylusEvent {
+    pub action: StylusAction,
+    pub stylus: Stylus,
+    /// In microseconds, on the `CLOCK_MONOTONIC` timeline
+    pub time: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum StylusAction {
+    /// The stylus hovers, or draws, at a location of the window
+    Motion(Point<f64, Logical>),
+    /// The tip touched the screen, or left it
+    Tip(ElementState),
+    Button(u32, ElementState),
+    /// The stylus went out of hover range
+    Leave,
+}
+
+/// The stylus of the device, as a pen and an eraser on a tablet that covers the screen. It is only advertised once
+/// a stylus is used, so that the drawing apps don't offer it on the devices without one.
+#[derive(Debug, Default)]
+pub struct Tablet {
+    handles: Option<TabletHandles>,
+    /// The end of the stylus in hover range, if any, which is the eraser with `true`
+    active: Option<bool>,
+    /// The last location of the stylus in the desktop, which the tip and the buttons act at
+    pub location: Point<f64, Logical>,
+}
+
+#[derive(Debug)]
+struct TabletHandles {
+    tablet: TabletHandle,
+    pen: TabletToolHandle,
+    eraser: TabletToolHandle,
+}
+
+impl Tablet {
+    /// The tablet, and the tool at the end of the stylus used, with the axes of the stylus queued for its next
+    /// motion. The tool is in hover range from then on, and the other end leaves the range first.
+    pub fn tool(
+        &mut self,
+        seat: &Seat<State>,
+        state: &mut State,
+        stylus: &Stylus,
+        time: u32,
+    ) -> (TabletHandle, TabletToolHandle) {
+        let handles = self.handles.get_or_insert_with(|| add_tablet(seat, state));
+        if self.active.replace(stylus.eraser) == Some(!stylus.eraser) {
+            handles.tool(!stylus.eraser).proximity_out(time);
+        }
+        let tool = handles.tool(stylus.eraser);
+        tool.pressure((stylus.pressure as f64).clamp(0.0, 1.0));
+        tool.tilt(tilt_degrees(stylus.tilt as f64, stylus.orientation as f64));
+        (handles.tablet.clone(), tool)
+    }
+
+    /// The stylus went out of hover range
+    pub fn leave(&mut self, time: u32) {
+        if let (Some(handles), Some(eraser)) = (&self.handles, self.active.take()) {
+            handles.tool(eraser).proximity_out(time);
+        }
+    }
+}
+
+impl TabletHandles {
+    fn tool(&self, eraser: bool) -> TabletToolHandle {
+        match eraser {
+            true => self.eraser.clone(),
+            false => self.pen.clone(),
+        }
+    }
+}
+
+fn add_tablet(seat: &Seat<State>, state: &mut State) -> TabletHandles {
+    log::info!("A stylus is used, advertising a tablet");
+    let dh = state.display_handle.clone();
+    let tablet_seat = seat.tablet_seat();
+    let tablet = tablet_seat.add_tablet::<State>(
+        &dh,
+        &TabletDescriptor {
+            name: "Android stylus".to_string(),
+            usb_id: None,
+            syspath: None,
+        },
+    );
+    let tool = |tool_type| TabletToolDescriptor {
+        tool_type,
+        hardware_serial: 0,
+        hardware_id_wacom: 0,
+        capabilities: TabletToolCapabilities::PRESSURE | TabletToolCapabilities::TILT,
+    };
+    TabletHandles {
+        tablet,
+        pen: tablet_seat.add_tool::<State>(state, &dh, &tool(TabletToolType::Pen)),
+        eraser: tablet_seat.add_tool::<State>(state, &dh, &tool(TabletToolType::Eraser)),
+    }
+}
@@ -0,0 +1,203 @@
+use super::compositor::State;
+use smithay::{
+    input::Seat,
+    reexports::{
+        wayland_protocols::wp::text_input::zv3::server::{
+            zwp_text_input_manager_v3::{self, ZwpTextInputManagerV3},
+            zwp_text_input_v3::{self, ZwpTextInputV3},
+        },
+        wayland_server::{
+            backend::ClientId, backend::GlobalId, protocol::wl_surface::WlSurface, Client,
+            DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Rectangle},
+};
+
+/// A `zwp_text_input_v3` object, following the keyboard focus of its client
+struct TextInput {
+    resource: ZwpTextInputV3,
+    /// The surface `enter` was sent for
+    focus: Option<WlSurface>,
+    /// Whether the client asked for text input on that surface, as of its last commit
+    enabled: bool,
+    pending_enabled: Option<bool>,
+    /// Where the text cursor is, relative to the focused surface, as of the last commit
+    cursor: Option<Rectangle<i32, Logical>>,
+    pending_cursor: Option<Rectangle<i32, Logical>>,
+    /// How many times the client committed, echoed back with `done`
+    serial: u32,
+}
+
+/// The compositor acts as the input method of the Wayland clients: the text typed on the
+/// Android soft keyboard is committed to the text input of the focused client.
+/// Smithay's own implementation only relays text input to an input method client, which we don't have.
+pub struct TextInputState {
+    instances: Vec<TextInput>,
+    global: GlobalId,
+}
+
+impl TextInputState {
+    pub fn new(dh: &DisplayHandle) -> Self {
+        let global = dh.create_global::<State, ZwpTextInputManagerV3, _>(1, ());
+        Self {
+            instances: Vec::new(),
+            global,
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Follow the keyboard focus: text inputs of the focused client enter its surface, the others leave
+    pub fn set_focus(&mut self, focused: Option<&WlSurface>) {
+        for text_input in &mut self.instances {
+            text_input.set_focus(focused);
+        }
+    }
+
+    /// The focused client enabled text input, so the soft keyboard should show up
+    pub fn wants_keyboard(&self) -> bool {
+        self.instances
+            .iter()
+            .any(|it| it.enabled && it.focus.is_some())
+    }
+
+    /// Commit text to the focused client. Returns `false` if it has not enabled text input.
+    pub fn commit_text(&self, text: &str) -> bool {
+        let mut committed = false;
+        for text_input in self
+            .instances
+            .iter()
+            .filter(|it| it.enabled && it.focus.is_some())
+        {
+            text_input.resource.commit_string(Some(text.to_string()));
+            text_input.resource.done(text_input.serial);
+            committed = true;
+        }
+        committed
+    }
+
+    /// Where the focused client types text, relative to its surface, if it told
+    pub fn cursor_rectangle(&self) -> Option<(WlSurface, Rectangle<i32, Logical>)> {
+        self.instances
+            .iter()
+            .filter(|it| it.enabled)
+            .find_map(|it| Some((it.focus.clone()?, it.cursor?)))
+    }
+
+    fn get_mut(&mut self, resource: &ZwpTextInputV3) -> Option<&mut TextInput> {
+        self.instances
+            .iter_mut()
+            .find(|it| it.resource == *resource)
+    }
+}
+
+impl TextInput {
+    fn set_focus(&mut self, focused: Option<&WlSurface>) {
+        let focused = focused.filter(|it| it.id().same_client_as(&self.resource.id()));
+        if self.focus.as_ref() == focused {
+            return;
+        }
+        if let Some(surface) = self.focus.take() {
+            self.resource.leave(&surface);
+            // The client has to enable text input again on the next surface
+            self.enabled = false;
+        }
+        if let Some(surface) = focused {
+            self.resource.enter(surface);
+            self.focus = Some(surface.clone());
+        }
+    }
+}
+
+impl GlobalDispatch<ZwpTextInputManagerV3, ()> for State {
+    fn bind(
+        _state: &mut Self,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpTextInputManagerV3>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwpTextInputManagerV3, ()> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwpTextInputManagerV3,
+        request: zwp_text_input_manager_v3::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwp_text_input_manager_v3::Request::GetTextInput { id, seat } = request {
+            let resource = data_init.init(id, ());
+            let focus = Seat::<State>::from_resource(&seat)
+                .and_then(|seat| seat.get_keyboard())
+                .and_then(|keyboard| keyboard.current_focus());
+            let mut text_input = TextInput {
+                resource,
+                focus: None,
+                enabled: false,
+                pending_enabled: None,
+                cursor: None,
+                pending_cursor: None,
+                serial: 0,
+            };
+            text_input.set_focus(focus.as_ref());
+            state.text_input_state.instances.push(text_input);
+        }
+    }
+}
+
+impl Dispatch<ZwpTextInputV3, ()> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ZwpTextInputV3,
+        request: zwp_text_input_v3::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let Some(text_input) = state.text_input_state.get_mut(resource) else {
+            return;
+        };
+        match request {
+            zwp_text_input_v3::Request::Enable => text_input.pending_enabled = Some(true),
+            zwp_text_input_v3::Request::Disable => text_input.pending_enabled = Some(false),
+            zwp_text_input_v3::Request::SetCursorRectangle {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                text_input.pending_cursor =
+                    Some(Rectangle::new((x, y).into(), (width, height).into()))
+            }
+            zwp_text_input_v3::Request::Commit => {
+                text_input.serial = text_input.serial.wrapping_add(1);
+                if let Some(enabled) = text_input.pending_enabled.take() {
+                    text_input.enabled = enabled && text_input.focus.is_some();
+                }
+                if let Some(cursor) = text_input.pending_cursor.take() {
+                    text_input.cursor = Some(cursor);
+                }
+            }
+            // There is no preedit nor cursor-relative editing, the soft keyboard only commits text
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, resource: &ZwpTextInputV3, _data: &()) {
+        state
+            .text_input_state
+            .instances
+            .retain(|it| it.resource != *resource);
+    }
+}
@@ -0,0 +1,223 @@
+use super::compositor::State;
+use smithay::{
+    backend::input::{Axis, AxisSource, ButtonState},
+    input::pointer::AxisFrame,
+    reexports::{
+        wayland_protocols_wlr::virtual_pointer::v1::server::{
+            zwlr_virtual_pointer_manager_v1::{self, ZwlrVirtualPointerManagerV1},
+            zwlr_virtual_pointer_v1::{self, ZwlrVirtualPointerV1},
+        },
+        wayland_server::{
+            backend::GlobalId, protocol::wl_pointer, Client, DataInit, Dispatch, DisplayHandle,
+            GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    utils::{Logical, Point},
+};
+use std::sync::Mutex;
+
+/// What a virtual pointer did, replayed by the event handler as if it came from a mouse
+pub enum VirtualPointerEvent {
+    /// A relative motion, in screen pixels
+    Motion {
+        time: u32,
+        delta: Point<f64, Logical>,
+    },
+    /// Put the pointer at a location of the desktop
+    Warp(Point<f64, Logical>),
+    Button {
+        button: u32,
+        state: ButtonState,
+    },
+    Axis(AxisFrame),
+}
+
+/// Lets the clients drive the pointer through `zwlr_virtual_pointer_v1`, as VNC servers (e.g. `wayvnc`),
+/// macro and accessibility tools do to click on behalf of the user.
+/// The requests are queued, as the pointer belongs to the compositor rather than to the state the clients see.
+pub struct VirtualPointerState {
+    global: GlobalId,
+    events: Vec<VirtualPointerEvent>,
+}
+
+impl VirtualPointerState {
+    pub fn new(dh: &DisplayHandle) -> Self {
+        let global = dh.create_global::<State, ZwlrVirtualPointerManagerV1, _>(2, ());
+        Self {
+            global,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// What the virtual pointers did since the last call
+    pub fn take_events(&mut self) -> Vec<VirtualPointerEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+fn axis(axis: WEnum<wl_pointer::Axis>) -> Option<Axis> {
+    match axis {
+        WEnum::Value(wl_pointer::Axis::VerticalScroll) => Some(Axis::Vertical),
+        WEnum::Value(wl_pointer::Axis::HorizontalScroll) => Some(Axis::Horizontal),
+        _ => None,
+    }
+}
+
+fn axis_source(source: WEnum<wl_pointer::AxisSource>) -> Option<AxisSource> {
+    match source {
+        WEnum::Value(wl_pointer::AxisSource::Wheel) => Some(AxisSource::Wheel),
+        WEnum::Value(wl_pointer::AxisSource::Finger) => Some(AxisSource::Finger),
+        WEnum::Value(wl_pointer::AxisSource::Continuous) => Some(AxisSource::Continuous),
+        WEnum::Value(wl_pointer::AxisSource::WheelTilt) => Some(AxisSource::WheelTilt),
+        _ => None,
+    }
+}
+
+impl GlobalDispatch<ZwlrVirtualPointerManagerV1, ()> for State {
+    fn bind(
+        _state: &mut Self,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrVirtualPointerManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrVirtualPointerManagerV1, ()> for State {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrVirtualPointerManagerV1,
+        request: zwlr_virtual_pointer_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        // There is a single seat and a single output, whichever the client asks for
+        match request {
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { id, .. }
+            | zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput {
+                id, ..
+            } => {
+                data_init.init(id, Mutex::new(None));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The scroll of a virtual pointer is accumulated until its `frame` request
+impl Dispatch<ZwlrVirtualPointerV1, Mutex<Option<AxisFrame>>> for State {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &ZwlrVirtualPointerV1,
+        request: zwlr_virtual_pointer_v1::Request,
+        data: &Mutex<Option<AxisFrame>>,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let mut pending = data.lock().unwrap();
+        let events = &mut state.virtual_pointer_state.events;
+        match request {
+            zwlr_virtual_pointer_v1::Request::Motion { time, dx, dy } => {
+                events.push(VirtualPointerEvent::Motion {
+                    time,
+                    delta: (dx, dy).into(),
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute {
+                x,
+                y,
+                x_extent,
+                y_extent,
+                ..
+            } => {
+                if x_extent == 0 || y_extent == 0 {
+                    return;
+                }
+                let location = (
+                    x as f64 / x_extent as f64 * state.size.w as f64,
+                    y as f64 / y_extent as f64 * state.size.h as f64,
+                );
+                events.push(VirtualPointerEvent::Warp(location.into()));
+            }
+            zwlr_virtual_pointer_v1::Request::Button {
+                button,
+                state: button_state,
+                ..
+            } => {
+                let state = match button_state {
+                    WEnum::Value(wl_pointer::ButtonState::Pressed) => ButtonState::Pressed,
+                    _ => ButtonState::Released,
+                };
+                events.push(VirtualPointerEvent::Button { button, state });
+            }
+            zwlr_virtual_pointer_v1::Request::Axis {
+                time,
+                axis: wl_axis,
+                value,
+            } => {
+                let Some(axis) = axis(wl_axis) else {
+                    resource
+                        .post_error(zwlr_virtual_pointer_v1::Error::InvalidAxis, "Unknown axis");
+                    return;
+                };
+                let frame = pending.take().unwrap_or_else(|| AxisFrame::new(time));
+                *pending = Some(frame.value(axis, value));
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete {
+                time,
+                axis: wl_axis,
+                value,
+                discrete,
+            } => {
+                let Some(axis) = axis(wl_axis) else {
+                    resource
+                        .post_error(zwlr_virtual_pointer_v1::Error::InvalidAxis, "Unknown axis");
+                    return;
+                };
+                let frame = pending.take().unwrap_or_else(|| AxisFrame::new(time));
+                *pending = Some(frame.value(axis, value).v120(axis, discrete * 120));
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource {
+                axis_source: source,
+            } => {
+                let Some(source) = axis_source(source) else {
+                    resource.post_error(
+                        zwlr_virtual_pointer_v1::Error::InvalidAxisSource,
+                        "Unknown axis source",
+                    );
+                    return;
+                };
+                let frame = pending.take().unwrap_or_else(|| AxisFrame::new(0));
+                *pending = Some(frame.source(source));
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop {
+                time,
+                axis: wl_axis,
+            } => {
+                let Some(axis) = axis(wl_axis) else {
+                    resource
+                        .post_error(zwlr_virtual_pointer_v1::Error::InvalidAxis, "Unknown axis");
+                    return;
+                };
+                let frame = pending.take().unwrap_or_else(|| AxisFrame::new(time));
+                *pending = Some(frame.stop(axis));
+            }
+            zwlr_virtual_pointer_v1::Request::Frame => {
+                if let Some(frame) = pending.take() {
+                    events.push(VirtualPointerEvent::Axis(frame));
+                }
+            }
+            _ => {}
+        }
+    }
+}
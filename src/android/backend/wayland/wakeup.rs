@@ -0,0 +1,66 @@
+use super::compositor::Compositor;
+use crate::android::app::attach::wake_event_loop;
+use smithay::reexports::calloop::{generic::Generic, EventLoop, Interest, Mode, PostAction};
+use std::{
+    error::Error,
+    io,
+    os::fd::{AsFd, OwnedFd},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+impl Compositor {
+    /// Wake the main thread up whenever the event loop of the compositor has something to dispatch,
+    /// so that clients are served as soon as they talk instead of on the next frame or timeout
+    pub fn wake_on_activity(&mut self) -> io::Result<()> {
+        if self.dispatched.is_some() {
+            return Ok(());
+        }
+        let fd = self.event_loop.as_fd().try_clone_to_owned()?;
+        // A single slot is enough: it only has to tell that some dispatch happened since the wake up
+        let (dispatched, on_dispatched) = mpsc::sync_channel(1);
+
+        thread::Builder::new()
+            .name("wayland-wakeup".to_string())
+            .spawn(move || {
+                if let Err(e) = watch(fd, on_dispatched) {
+                    log::error!("Stopped waking up on client activity: {}", e);
+                }
+            })?;
+
+        self.dispatched = Some(dispatched);
+        Ok(())
+    }
+
+    /// Let the wake up thread watch the event loop again
+    pub fn notify_dispatched(&self) {
+        if let Some(dispatched) = &self.dispatched {
+            let _ = dispatched.try_send(());
+        }
+    }
+}
+
+fn watch(fd: OwnedFd, mut on_dispatched: Receiver<()>) -> Result<(), Box<dyn Error>> {
+    let mut watcher = EventLoop::<Receiver<()>>::try_new()?;
+    // Stop watching once the main thread is gone
+    let signal = watcher.get_signal();
+    watcher
+        .handle()
+        .insert_source(
+            Generic::new(fd, Interest::READ, Mode::Level),
+            move |_, _, on_dispatched| {
+                // Forget the dispatches that happened before the event loop became readable again
+                while on_dispatched.try_recv().is_ok() {}
+                // The event loop stays readable until the main thread dispatched it
+                wake_event_loop();
+                if on_dispatched.recv().is_err() {
+                    signal.stop();
+                    return Ok(PostAction::Remove);
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .map_err(|e| e.error)?;
+    watcher.run(None, &mut on_dispatched, |_| {})?;
+    Ok(())
+}
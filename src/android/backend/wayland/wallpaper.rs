@@ -0,0 +1,98 @@
+use crate::{
+    android::utils::ndk::read_asset,
+    core::config::{Background, DisplayConfig, ARCH_FS_ROOT},
+};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::{
+    element::{
+        texture::{TextureBuffer, TextureRenderElement},
+        Kind,
+    },
+    gles::{GlesRenderer, GlesTexture},
+    Color32F,
+};
+use smithay::utils::{Physical, Size, Transform};
+use std::fs;
+use winit::platform::android::activity::AndroidApp;
+
+/// What is painted where no client surface covers the output
+pub struct Wallpaper {
+    color: Color32F,
+    /// Decoded RGBA pixels, uploaded again whenever the renderer changes
+    image: Option<image::RgbaImage>,
+    texture: Option<TextureBuffer<GlesTexture>>,
+}
+
+impl Wallpaper {
+    pub fn new(config: &DisplayConfig, android_app: &AndroidApp) -> Self {
+        let mut wallpaper = Self {
+            color: Color32F::new(0.0, 0.0, 0.0, 1.0),
+            image: None,
+            texture: None,
+        };
+
+        let bytes = match config.background() {
+            Background::Color([r, g, b, a]) => {
+                wallpaper.color = Color32F::new(r, g, b, a);
+                return wallpaper;
+            }
+            Background::File(path) => fs::read(format!("{}{}", ARCH_FS_ROOT, path))
+                .map_err(|e| format!("Failed to read {}: {}", path, e)),
+            Background::Asset(name) => read_asset(android_app, &name),
+        };
+
+        match bytes.and_then(|bytes| image::load_from_memory(&bytes).map_err(|e| e.to_string())) {
+            Ok(image) => wallpaper.image = Some(image.to_rgba8()),
+            Err(e) => log::error!("Failed to load the background image: {}", e),
+        }
+        wallpaper
+    }
+
+    /// Used to clear the frame, visible as-is if there is no background image
+    pub fn color(&self) -> Color32F {
+        self.color
+    }
+
+    /// Forget the image uploaded with a renderer that is gone
+    pub fn release_textures(&mut self) {
+        self.texture = None;
+    }
+
+    /// The background image stretched over the whole output, if any
+    pub fn element(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        output_size: Size<i32, Physical>,
+    ) -> Option<TextureRenderElement<GlesTexture>> {
+        if let Some(image) = self.image.as_ref().filter(|_| self.texture.is_none()) {
+            let size = (image.width() as i32, image.height() as i32);
+            match TextureBuffer::from_memory(
+                renderer,
+                image.as_raw(),
+                Fourcc::Abgr8888,
+                size,
+                false,
+                1,
+                Transform::Normal,
+                None,
+            ) {
+                Ok(texture) => self.texture = Some(texture),
+                Err(e) => {
+                    log::error!("Failed to upload the background image: {:?}", e);
+                    self.image = None;
+                }
+            }
+        }
+
+        self.texture.as_ref().map(|texture| {
+            TextureRenderElement::from_texture_buffer(
+                (0.0, 0.0),
+                texture,
+                None,
+                None,
+                Some((output_size.w, output_size.h).into()),
+                Kind::Unspecified,
+            )
+        })
+    }
+}
@@ -21,14 +21,16 @@
 use khronos_egl::DynamicInstance;
 use smithay::{
     backend::{
+        allocator::format::FormatSet,
         egl::{
             context::{GlAttributes, PixelFormatRequirements},
             display::EGLDisplay,
+            ffi,
             native::EGLNativeSurface,
             EGLContext, EGLSurface, Error as EGLError,
         },
         renderer::{
-            gles::{GlesError, GlesRenderer},
+            gles::{GlesError, GlesRenderer, GlesTarget},
             Bind,
         },
         SwapBuffersError,
@@ -38,10 +40,15 @@ use smithay::{
 use std::ffi::c_void;
 use std::sync::Arc;
 use winit::event_loop::ActiveEventLoop;
+use winit::platform::android::ActiveEventLoopExtAndroid;
 use winit::raw_window_handle::{AndroidNdkWindowHandle, HasWindowHandle, RawWindowHandle};
 use winit::window::{Window as WinitWindow, WindowAttributes};
 
-use crate::core::logging::PolarBearExpectation;
+use super::external_display::ExternalTarget;
+use super::presentation::{present_in_view, ViewSurface};
+use super::presentation_time::{FrameTimestamps, PresentTime};
+use super::recorder::Recording;
+use crate::android::utils::application_context::get_application_context;
 
 pub struct AndroidNativeSurface {
     handle: AndroidNdkWindowHandle,
@@ -55,14 +62,15 @@ unsafe impl EGLNativeSurface for AndroidNativeSurface {
         display: &Arc<smithay::backend::egl::display::EGLDisplayHandle>,
         config_id: smithay::backend::egl::ffi::egl::types::EGLConfig,
     ) -> Result<*const std::os::raw::c_void, smithay::backend::egl::EGLError> {
-        let surface = smithay::backend::egl::ffi::egl::CreateWindowSurface(
-            display.handle,
-            config_id,
-            self.handle.a_native_window.as_ptr(),
-            std::ptr::null(),
-        );
-        assert!(!surface.is_null());
-        Ok(surface)
+        // Fails rather than aborting when the window is already gone, e.g. as the app went to the background
+        smithay::backend::egl::wrap_egl_call_ptr(|| {
+            smithay::backend::egl::ffi::egl::CreateWindowSurface(
+                display.handle,
+                config_id,
+                self.handle.a_native_window.as_ptr(),
+                std::ptr::null(),
+            )
+        })
     }
 }
 
@@ -75,7 +83,7 @@ fn create_egl_display(
 
     // Get the display
     let display = unsafe { egl.get_display(khronos_egl::DEFAULT_DISPLAY) }
-        .pb_expect("Failed to get EGL display");
+        .ok_or("Failed to get EGL display")?;
 
     // Initialize the display
     let (major, minor) = egl.initialize(display)?;
@@ -83,9 +91,8 @@ fn create_egl_display(
     // Choose an EGL configuration
     let config_attribs = [khronos_egl::NONE];
     let config = egl
-        .choose_first_config(display, &config_attribs)
-        .pb_expect("Failed to choose EGL config")
-        .pb_expect("No suitable EGL config found");
+        .choose_first_config(display, &config_attribs)?
+        .ok_or("No suitable EGL config found")?;
 
     // Create the EGLDisplay from raw pointers
     let egl_display = unsafe {
@@ -93,8 +100,7 @@ fn create_egl_display(
             display.as_ptr() as *mut c_void,
             config.as_ptr() as *mut c_void,
         )
-    }
-    .pb_expect("Failed to create EGL display");
+    }?;
 
     Ok(egl_display)
 }
@@ -103,24 +109,25 @@ fn create_egl_display(
 /// trait, from a given [`WindowAttributes`] struct, as well as given
 /// [`GlAttributes`] for further customization of the rendering pipeline and a
 /// corresponding [`WinitEventLoop`].
-pub fn bind(event_loop: &ActiveEventLoop) -> WinitGraphicsBackend<GlesRenderer> {
+///
+/// Fails rather than panicking when the native window cannot be drawn on, e.g. as it went away meanwhile, so that
+/// the compositor and its clients live on until the next resume.
+pub fn bind(event_loop: &ActiveEventLoop) -> Result<WinitGraphicsBackend<GlesRenderer>, String> {
     #[allow(deprecated)]
     let window = Arc::new(
         event_loop
             .create_window(WindowAttributes::default())
-            .pb_expect("Failed to create window"),
+            .map_err(|e| format!("Failed to create window: {}", e))?,
     );
 
     let handle = window.window_handle().map(|handle| handle.as_raw());
-    let (display, context, surface) = match handle {
+    let (display, context, surface, view) = match handle {
         Ok(RawWindowHandle::AndroidNdk(handle)) => {
-            let display = create_egl_display(handle);
-            let display = match display {
-                Ok(display) => display,
-                Err(error) => {
-                    panic!("Failed to create EGLDisplay: {:?}", error)
-                }
-            };
+            // The window still takes the input, but the desktop may be drawn in a view over it
+            let view = present_in_view(event_loop.android_app());
+            let handle = view.as_ref().map_or(handle, ViewSurface::handle);
+            let display = create_egl_display(handle)
+                .map_err(|e| format!("Failed to create EGLDisplay: {:?}", e))?;
 
             let gl_attributes = GlAttributes {
                 version: (3, 0),
@@ -140,38 +147,80 @@ pub fn bind(event_loop: &ActiveEventLoop) -> WinitGraphicsBackend<GlesRenderer>
                     PixelFormatRequirements::_8_bit(),
                 )
             })
-            .pb_expect("Failed to create EGLContext");
-
+            .map_err(|e| format!("Failed to create EGLContext: {:?}", e))?;
+
+            set_buffer_count(
+                handle,
+                get_application_context().local_config.display.buffers,
+            );
+            let pixel_format = context
+                .pixel_format()
+                .ok_or("The EGLContext has no pixel format")?;
             let surface = unsafe {
                 EGLSurface::new(
                     &display,
-                    context.pixel_format().unwrap(),
+                    pixel_format,
                     context.config_id(),
                     AndroidNativeSurface { handle },
                 )
-                .pb_expect("Failed to create EGLSurface")
-            };
+            }
+            .map_err(|e| format!("Failed to create EGLSurface: {:?}", e))?;
 
             let _ = context.unbind();
-            (display, context, surface)
+            (display, context, surface, view)
         }
-        Ok(platform) => panic!("Unsupported platform: {:?}", platform),
-        Err(error) => panic!("Failed to get window handle: {:?}", error),
+        Ok(platform) => return Err(format!("Unsupported platform: {:?}", platform)),
+        Err(error) => return Err(format!("Failed to get window handle: {:?}", error)),
     };
 
-    let renderer =
-        unsafe { GlesRenderer::new(context) }.pb_expect("Failed to create GLES Renderer");
+    let renderer = unsafe { GlesRenderer::new(context) }
+        .map_err(|e| format!("Failed to create GLES Renderer: {:?}", e))?;
     let damage_tracking = display.supports_damage();
+    let timestamps = FrameTimestamps::enable(&display, &surface);
 
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
-    WinitGraphicsBackend {
+    Ok(WinitGraphicsBackend {
         window: window.clone(),
-        _display: display,
+        display,
         egl_surface: surface,
         damage_tracking,
         bind_size: None,
+        swap_interval: Some(get_application_context().local_config.display.swap_interval),
         renderer,
+        external: None,
+        recording: None,
+        timestamps,
+        _view: view,
+    })
+}
+
+type SetBufferCount = unsafe extern "C" fn(*mut c_void, usize) -> i32;
+
+/// Have the window queue that many buffers, before EGL connects to it.
+/// `ANativeWindow_setBufferCount` is not part of the public NDK, so it is looked up at runtime and may be missing.
+fn set_buffer_count(handle: AndroidNdkWindowHandle, buffers: u32) {
+    if buffers == 0 {
+        return;
+    }
+    if !(2..=3).contains(&buffers) {
+        log::warn!(
+            "Ignoring [display] buffers = {}, it must be 2 or 3",
+            buffers
+        );
+        return;
+    }
+    let result = unsafe {
+        libloading::Library::new("libnativewindow.so").and_then(|library| {
+            let set: libloading::Symbol<SetBufferCount> =
+                library.get(b"ANativeWindow_setBufferCount")?;
+            Ok(set(handle.a_native_window.as_ptr(), buffers as usize))
+        })
+    };
+    match result {
+        Ok(0) => log::info!("The window queues {} buffers", buffers),
+        Ok(status) => log::warn!("Failed to set the window buffer count: {}", status),
+        Err(e) => log::warn!("The window buffer count cannot be set: {}", e),
     }
 }
 
@@ -196,12 +245,21 @@ pub enum Error {
 #[derive(Debug)]
 pub struct WinitGraphicsBackend<R> {
     renderer: R,
-    // The display isn't used past this point but must be kept alive.
-    _display: EGLDisplay,
+    display: EGLDisplay,
     egl_surface: EGLSurface,
     window: Arc<WinitWindow>,
     damage_tracking: bool,
     bind_size: Option<Size<i32, Physical>>,
+    /// Applied once the surface is first bound, as it applies to the surface current at the time
+    swap_interval: Option<u32>,
+    /// The display plugged into the device, if the desktop is drawn there as well
+    pub external: Option<ExternalTarget>,
+    /// The recording of the desktop, if one runs
+    pub recording: Option<Recording>,
+    /// When the frames show up on screen, if the driver tells
+    timestamps: Option<FrameTimestamps>,
+    /// The view drawn in instead of the window, released after the EGL surface
+    _view: Option<ViewSurface>,
 }
 
 impl<R> WinitGraphicsBackend<R>
@@ -243,6 +301,14 @@ where
         self.bind_size = Some(window_size);
 
         let fb = self.renderer.bind(&mut self.egl_surface)?;
+        if let Some(interval) = self.swap_interval.take() {
+            let applied = unsafe {
+                ffi::egl::SwapInterval(self.display.get_display_handle().handle, interval as i32)
+            };
+            if applied != ffi::egl::TRUE {
+                log::warn!("Failed to set the swap interval to {}", interval);
+            }
+        }
 
         Ok((&mut self.renderer, fb))
     }
@@ -302,3 +368,94 @@ where
         Ok(())
     }
 }
+
+impl WinitGraphicsBackend<GlesRenderer> {
+    /// Dmabuf formats the EGL context can sample from, to be advertised through `zwp_linux_dmabuf_v1`
+    pub fn dmabuf_formats(&self) -> FormatSet {
+        self.renderer.egl_context().dmabuf_texture_formats().clone()
+    }
+
+    /// Another surface the EGL context can draw in, e.g. on an external display
+    pub fn create_surface(
+        &self,
+        handle: AndroidNdkWindowHandle,
+    ) -> Result<EGLSurface, smithay::backend::egl::EGLError> {
+        let context = self.renderer.egl_context();
+        let pixel_format = context
+            .pixel_format()
+            .ok_or(smithay::backend::egl::EGLError::BadConfig)?;
+        unsafe {
+            EGLSurface::new(
+                &self.display,
+                pixel_format,
+                context.config_id(),
+                AndroidNativeSurface { handle },
+            )
+        }
+    }
+
+    /// Bind the surface of the external display to the renderer, if there is one
+    pub fn bind_external(
+        &mut self,
+    ) -> Option<Result<(&mut GlesRenderer, GlesTarget<'_>), SwapBuffersError>> {
+        let external = self.external.as_mut()?;
+        let framebuffer = match self.renderer.bind(&mut external.surface) {
+            Ok(framebuffer) => framebuffer,
+            Err(e) => return Some(Err(e.into())),
+        };
+        if let Some(interval) = external.swap_interval.take() {
+            unsafe {
+                ffi::egl::SwapInterval(self.display.get_display_handle().handle, interval as i32)
+            };
+        }
+        Some(Ok((&mut self.renderer, framebuffer)))
+    }
+
+    /// Bind the surface of the recording to the renderer, if one runs
+    pub fn bind_recording(
+        &mut self,
+    ) -> Option<Result<(&mut GlesRenderer, GlesTarget<'_>), SwapBuffersError>> {
+        let recording = self.recording.as_mut()?;
+        let framebuffer = match self.renderer.bind(&mut recording.surface) {
+            Ok(framebuffer) => framebuffer,
+            Err(e) => return Some(Err(e.into())),
+        };
+        if let Some(interval) = recording.swap_interval.take() {
+            unsafe {
+                ffi::egl::SwapInterval(self.display.get_display_handle().handle, interval as i32)
+            };
+        }
+        Some(Ok((&mut self.renderer, framebuffer)))
+    }
+
+    /// Submits the back buffer to the encoder of the recording, once bound (see
+    /// [`WinitGraphicsBackend::bind_recording`])
+    pub fn submit_recording(&mut self) -> Result<(), SwapBuffersError> {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.surface.swap_buffers(None)?;
+        }
+        Ok(())
+    }
+
+    /// The id of the frame the next `submit` swaps, to ask when it showed up on screen
+    pub fn next_frame_id(&self) -> Option<u64> {
+        self.timestamps
+            .as_ref()?
+            .next_frame_id(&self.display, &self.egl_surface)
+    }
+
+    /// When a frame swapped by `submit` showed up on screen
+    pub fn present_time(&self, frame: u64) -> PresentTime {
+        self.timestamps.as_ref().map_or(PresentTime::Unknown, |it| {
+            it.present_time(&self.display, &self.egl_surface, frame)
+        })
+    }
+
+    /// Submits the back buffer to the external display, once bound (see [`WinitGraphicsBackend::bind_external`])
+    pub fn submit_external(&mut self) -> Result<(), SwapBuffersError> {
+        if let Some(external) = self.external.as_mut() {
+            external.surface.swap_buffers(None)?;
+        }
+        Ok(())
+    }
+}
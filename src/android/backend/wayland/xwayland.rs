@@ -0,0 +1,304 @@
+use super::{compositor::State, element::WindowElement};
+use crate::{
+    android::{proot::process::ArchProcess, utils::application_context::get_application_context},
+    core::{backoff::Backoff, logging::panel_log, telemetry},
+};
+use smithay::{
+    desktop::Window,
+    reexports::{
+        calloop::{LoopHandle, RegistrationToken},
+        wayland_server::DisplayHandle,
+    },
+    utils::{Logical, Rectangle},
+    wayland::{
+        selection::SelectionTarget,
+        xwayland_shell::{XWaylandShellHandler, XWaylandShellState},
+    },
+    xwayland::{
+        xwm::{Reorder, ResizeEdge, XwmId},
+        X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler,
+    },
+};
+use std::{error::Error, os::unix::io::OwnedFd, process::Stdio, time::Instant};
+
+/// The X display the desktop session connects to
+pub const X_DISPLAY: u32 = 1;
+
+/// Runs Xwayland inside the rootfs, and restarts it whenever it exits
+pub struct XwaylandSupervisor {
+    loop_handle: LoopHandle<'static, State>,
+    /// The running instance, which reports on the event loop once the X server is ready
+    source: Option<RegistrationToken>,
+    /// When to (re)start Xwayland, while it is not running
+    start_at: Option<Instant>,
+    backoff: Backoff,
+}
+
+impl XwaylandSupervisor {
+    pub fn new(loop_handle: LoopHandle<'static, State>) -> Self {
+        Self {
+            loop_handle,
+            source: None,
+            start_at: None,
+            backoff: Backoff::default(),
+        }
+    }
+
+    /// Have Xwayland started on the next poll, unless it is already running
+    pub fn start(&mut self) {
+        if self.source.is_none() && self.start_at.is_none() {
+            self.start_at = Some(Instant::now());
+        }
+    }
+
+    /// When Xwayland is due to be (re)started, if it is not running
+    pub fn next_start(&self) -> Option<Instant> {
+        self.start_at
+    }
+
+    /// Start Xwayland when it is due
+    pub fn poll(&mut self, dh: &DisplayHandle) {
+        if !self.start_at.is_some_and(|at| Instant::now() >= at) {
+            return;
+        }
+        self.start_at = None;
+        if let Err(e) = self.spawn(dh) {
+            panel_log(format!("Failed to start Xwayland: {}", e), true);
+            self.exited();
+        }
+    }
+
+    /// Xwayland is gone or failed to start: schedule a restart, backing off while it keeps crashing
+    pub fn exited(&mut self) {
+        if let Some(token) = self.source.take() {
+            // Dropping the instance disconnects its Wayland client
+            self.loop_handle.remove(token);
+        }
+        let delay = self.backoff.exited();
+        telemetry::record("crash recovery", "xwayland");
+        panel_log(
+            format!("Xwayland exited, restarting in {}s", delay.as_secs()),
+            true,
+        );
+        self.start_at = Some(Instant::now() + delay);
+    }
+
+    fn spawn(&mut self, dh: &DisplayHandle) -> Result<(), Box<dyn Error>> {
+        let config = get_application_context().local_config;
+        let username = config.user.username;
+        let extra_args = config.display.xwayland_args();
+        log::info!("Starting Xwayland with {:?}", extra_args);
+        let (xwayland, client) = XWayland::spawn_with_command(
+            dh,
+            X_DISPLAY,
+            |args, wayland_socket| {
+                // Leftovers of a previous instance would keep Xwayland from taking the display
+                let mut command = ArchProcess {
+                    command: format!(
+                        "rm -f /tmp/.X{0}-lock /tmp/.X11-unix/X{0}; XDG_RUNTIME_DIR=/tmp WAYLAND_SOCKET={1} exec Xwayland \"$@\"",
+                        X_DISPLAY, wayland_socket
+                    ),
+                    user: username,
+                    process: None,
+                }
+                .command();
+                command
+                    .args(args)
+                    .args(extra_args)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::inherit());
+                command
+            },
+            |_| {},
+        )?;
+
+        let loop_handle = self.loop_handle.clone();
+        let token = self
+            .loop_handle
+            .insert_source(xwayland, move |event, _, state| match event {
+                XWaylandEvent::Ready { x11_socket, .. } => {
+                    match X11Wm::start_wm(loop_handle.clone(), x11_socket, client.clone()) {
+                        Ok(wm) => {
+                            log::info!("Xwayland is ready on :{}", X_DISPLAY);
+                            state.xwm = Some(wm);
+                            state.clipboard.offer_to_x11(state.xwm.as_mut());
+                        }
+                        Err(e) => {
+                            panel_log(
+                                format!("Failed to start the X11 window manager: {}", e),
+                                true,
+                            );
+                            state.xwayland.exited();
+                        }
+                    }
+                }
+                XWaylandEvent::Error => state.xwayland.exited(),
+            })
+            .map_err(|e| e.error)?;
+
+        self.source = Some(token);
+        self.backoff.started();
+        Ok(())
+    }
+}
+
+impl State {
+    fn x11_element(&self, window: &X11Surface) -> Option<WindowElement> {
+        self.space
+            .elements()
+            .find(|it| it.0.x11_surface() == Some(window))
+            .cloned()
+    }
+}
+
+impl XWaylandShellHandler for State {
+    fn xwayland_shell_state(&mut self) -> &mut XWaylandShellState {
+        &mut self.xwayland_shell_state
+    }
+}
+
+impl XwmHandler for State {
+    fn xwm_state(&mut self, _xwm: XwmId) -> &mut X11Wm {
+        self.xwm.as_mut().unwrap()
+    }
+
+    fn new_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn new_override_redirect_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    /// There are no server-side decorations nor layout: X11 windows show up where they asked to
+    fn map_window_request(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Err(e) = window.set_mapped(true) {
+            log::warn!("Failed to map X11 window {}: {}", window.window_id(), e);
+            return;
+        }
+        let geometry = window.geometry();
+        let element = WindowElement(Window::new_x11_window(window.clone()));
+        self.space.map_element(element, geometry.loc, true);
+        if let Err(e) = window.configure(geometry) {
+            log::warn!(
+                "Failed to configure X11 window {}: {}",
+                window.window_id(),
+                e
+            );
+        }
+    }
+
+    fn mapped_override_redirect_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        let location = window.geometry().loc;
+        let element = WindowElement(Window::new_x11_window(window));
+        self.space.map_element(element, location, true);
+    }
+
+    fn unmapped_window(&mut self, _xwm: XwmId, window: X11Surface) {
+        if let Some(element) = self.x11_element(&window) {
+            self.space.unmap_elem(&element);
+            self.needs_redraw = true;
+        }
+        if !window.is_override_redirect() {
+            let _ = window.set_mapped(false);
+        }
+    }
+
+    fn destroyed_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn configure_request(
+        &mut self,
+        _xwm: XwmId,
+        window: X11Surface,
+        x: Option<i32>,
+        y: Option<i32>,
+        w: Option<u32>,
+        h: Option<u32>,
+        _reorder: Option<Reorder>,
+    ) {
+        let mut geometry = window.geometry();
+        if let Some(x) = x {
+            geometry.loc.x = x;
+        }
+        if let Some(y) = y {
+            geometry.loc.y = y;
+        }
+        if let Some(w) = w {
+            geometry.size.w = w as i32;
+        }
+        if let Some(h) = h {
+            geometry.size.h = h as i32;
+        }
+        if let Err(e) = window.configure(geometry) {
+            log::warn!(
+                "Failed to configure X11 window {}: {}",
+                window.window_id(),
+                e
+            );
+        }
+    }
+
+    fn configure_notify(
+        &mut self,
+        _xwm: XwmId,
+        window: X11Surface,
+        geometry: Rectangle<i32, Logical>,
+        _above: Option<u32>,
+    ) {
+        if let Some(element) = self.x11_element(&window) {
+            self.space.map_element(element, geometry.loc, false);
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Interactive moves and resizes are left to the X11 clients
+    fn resize_request(
+        &mut self,
+        _xwm: XwmId,
+        _window: X11Surface,
+        _button: u32,
+        _resize_edge: ResizeEdge,
+    ) {
+    }
+
+    fn move_request(&mut self, _xwm: XwmId, _window: X11Surface, _button: u32) {}
+
+    /// The X11 clients share the clipboard with the Wayland clients and Android, the primary selection stays among them
+    fn allow_selection_access(&mut self, _xwm: XwmId, selection: SelectionTarget) -> bool {
+        selection == SelectionTarget::Clipboard
+    }
+
+    fn send_selection(
+        &mut self,
+        _xwm: XwmId,
+        _selection: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+    ) {
+        self.clipboard.send_to_x11(mime_type, fd);
+    }
+
+    fn new_selection(&mut self, _xwm: XwmId, selection: SelectionTarget, mime_types: Vec<String>) {
+        if selection == SelectionTarget::Clipboard {
+            self.clipboard
+                .receive_from_x11(&self.display_handle, self.xwm.as_mut(), mime_types);
+        }
+    }
+
+    fn cleared_selection(&mut self, _xwm: XwmId, selection: SelectionTarget) {
+        if selection == SelectionTarget::Clipboard {
+            self.clipboard.cleared_by_x11(&self.display_handle);
+        }
+    }
+
+    fn disconnected(&mut self, _xwm: XwmId) {
+        self.xwm = None;
+        let x11_windows = self
+            .space
+            .elements()
+            .filter(|it| it.0.is_x11())
+            .cloned()
+            .collect::<Vec<_>>();
+        for element in x11_windows {
+            self.space.unmap_elem(&element);
+        }
+        self.needs_redraw = true;
+        self.xwayland.exited();
+    }
+}
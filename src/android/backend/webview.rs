@@ -1,12 +1,20 @@
-use crate::android::proot::setup::SetupMessage;
+use crate::android::{proot::setup::SetupMessage, utils::webview::accept_page};
 use crate::core::logging::PolarBearExpectation;
+use serde::Deserialize;
 use serde_json::json;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use websocket::sync::Server;
 use websocket::OwnedMessage;
 
+/// What the setup page answers, e.g. `{"username": "alice"}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Answer {
+    Username(String),
+}
+
 pub struct WebviewBackend {
     pub socket_port: u16,
     pub progress: Arc<Mutex<u16>>, // 0-100
@@ -14,7 +22,11 @@ pub struct WebviewBackend {
 
 impl WebviewBackend {
     /// Start accepting connections and listening for messages
-    pub fn build(receiver: Receiver<SetupMessage>, progress: Arc<Mutex<u16>>) -> Self {
+    pub fn build(
+        receiver: Receiver<SetupMessage>,
+        progress: Arc<Mutex<u16>>,
+        usernames: Sender<String>,
+    ) -> Self {
         let socket = Server::bind("127.0.0.1:0").pb_expect("Failed to bind socket");
         let socket_port = socket.local_addr().unwrap().port();
 
@@ -35,17 +47,35 @@ impl WebviewBackend {
                 }
 
                 // Accept the new client
-                if !request.protocols().contains(&"rust-websocket".to_string()) {
-                    request.reject().unwrap();
+                let Some(client) = accept_page(request) else {
                     continue;
-                }
-
-                let client = request.use_protocol("rust-websocket").accept().unwrap();
+                };
                 let ip = client.peer_addr().unwrap();
                 log::info!("Connection from {}", ip);
+                let Ok((mut reader, writer)) = client.split() else {
+                    continue;
+                };
 
                 // Store the new client
-                *active_client = Some(client); // Store the writer part of the connection
+                *active_client = Some(writer); // Store the writer part of the connection
+
+                // The answers of the user go to the setup stage asking
+                let usernames = usernames.clone();
+                thread::spawn(move || {
+                    for message in reader.incoming_messages() {
+                        let text = match message {
+                            Ok(OwnedMessage::Text(text)) => text,
+                            Ok(OwnedMessage::Close(_)) | Err(_) => break,
+                            Ok(_) => continue,
+                        };
+                        match serde_json::from_str::<Answer>(&text) {
+                            Ok(Answer::Username(username)) => {
+                                usernames.send(username).unwrap_or(())
+                            }
+                            Err(e) => log::warn!("Invalid setup answer {:?}: {}", text, e),
+                        }
+                    }
+                });
 
                 // Spawn a thread to handle messages for this client
                 let active_client_clone = active_client_clone.clone();
@@ -64,6 +94,11 @@ impl WebviewBackend {
                                 "message": msg,
                                 "isError": true
                             }),
+                            SetupMessage::AskUsername => json!({
+                                "progress": progress,
+                                "message": "Choose a username",
+                                "askUsername": true
+                            }),
                         };
 
                         let message = OwnedMessage::Text(json_message.to_string());
@@ -1,22 +1,85 @@
 use crate::{
     android::{
-        app::build::PolarBearApp,
+        app::{
+            attach::{attach, detach},
+            build::PolarBearApp,
+        },
+        backend::recovery::crash_log,
         utils::{
-            application_context::ApplicationContext,
-            fullscreen_immersive::{enable_fullscreen_immersive_mode, keep_screen_on},
-            ndk::run_in_jvm,
+            application_context::get_application_context,
+            application_context::{update_local_config, ApplicationContext},
+            fullscreen_immersive::enable_fullscreen_immersive_mode,
+            ndk::{get_intent_string_extra, run_in_jvm},
+            restart::schedule_restart,
+            storage::request_shared_storage,
+            telemetry::anonymize,
+        },
+    },
+    core::{
+        config::{self, is_valid_username},
+        logging::{
+            install_panic_hook, publish_last_crash, set_log_levels, FilteredLogger,
+            PolarBearExpectation,
         },
+        startup,
     },
-    core::{config, logging::PolarBearExpectation},
 };
 use sentry::integrations::log::{LogFilter, SentryLogger};
+use std::{sync::Arc, time::Duration};
 use winit::{
     event_loop::{ControlFlow, EventLoop},
     platform::android::{activity::AndroidApp, EventLoopBuilderExtAndroid},
 };
 
+/// How long Sentry gets to send the report of a crash, which it does in the background, before the app aborts
+const CRASH_REPORT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The `log_level` intent extra takes precedence over `[logging] level`, to debug without editing the config file:
+/// `adb shell am start -n app.polarbear/android.app.NativeActivity --es log_level debug`
+fn apply_log_level(android_app: &AndroidApp) {
+    let mut level = get_application_context().local_config.logging.level;
+    run_in_jvm(
+        |env, app| match get_intent_string_extra(env, app, "log_level") {
+            Ok(Some(extra)) => level = extra,
+            Ok(None) => {}
+            Err(e) => {
+                let _ = env.exception_clear();
+                log::warn!("Failed to read the log_level intent extra: {:?}", e);
+            }
+        },
+        android_app.clone(),
+    );
+    if let Err(e) = set_log_levels(&level) {
+        log::warn!("{}, keeping the default log level", e);
+    }
+}
+
+/// The `user` intent extra picks the session to start for this launch only, without touching `[user] username`
+fn select_user(android_app: &AndroidApp) {
+    let mut user = None;
+    run_in_jvm(
+        |env, app| match get_intent_string_extra(env, app, "user") {
+            Ok(extra) => user = extra,
+            Err(e) => {
+                let _ = env.exception_clear();
+                log::warn!("Failed to read the user intent extra: {:?}", e);
+            }
+        },
+        android_app.clone(),
+    );
+    match user {
+        Some(user) if is_valid_username(&user) => {
+            log::info!("Starting the session of {}", user);
+            update_local_config(|config| config.user.username = user);
+        }
+        Some(user) => log::warn!("Ignoring the invalid user {:?}", user),
+        None => {}
+    }
+}
+
 #[no_mangle]
 fn android_main(android_app: AndroidApp) {
+    startup::mark("android_main");
     std::env::set_var("RUST_BACKTRACE", "full");
     let _guard = sentry::init((
         config::SENTRY_DSN,
@@ -26,6 +89,7 @@ fn android_main(android_app: AndroidApp) {
             // see https://docs.sentry.io/platforms/rust/data-management/data-collected for more info
             send_default_pii: true,
             enable_logs: true,
+            before_send: Some(Arc::new(anonymize)),
             ..Default::default()
         },
     ));
@@ -47,21 +111,51 @@ fn android_main(android_app: AndroidApp) {
     let log_level = log::LevelFilter::Trace;
     #[cfg(not(debug_assertions))]
     let log_level = log::LevelFilter::Info;
-    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+    if log::set_boxed_logger(Box::new(FilteredLogger(logger))).is_ok() {
         log::set_max_level(log_level);
     } else {
         android_logger::init_once(android_logger::Config::default().with_max_level(log_level));
     }
 
     ApplicationContext::build(&android_app);
+    let crash_log = crash_log();
+    publish_last_crash(&crash_log);
+    let crashed_app = android_app.clone();
+    install_panic_hook(crash_log, move |restart| {
+        if let Some(client) = sentry::Hub::current().client() {
+            client.flush(Some(CRASH_REPORT_TIMEOUT));
+        }
+        // The next launch opens the recovery screen
+        if restart {
+            run_in_jvm(
+                |env, app| {
+                    if let Err(e) = schedule_restart(env, app) {
+                        let _ = env.exception_clear();
+                        log::error!("Failed to schedule the restart of the app: {:?}", e);
+                    }
+                },
+                crashed_app.clone(),
+            );
+        }
+    });
+    apply_log_level(&android_app);
+    select_user(&android_app);
+    startup::mark("context built");
 
     run_in_jvm(enable_fullscreen_immersive_mode, android_app.clone());
-    run_in_jvm(keep_screen_on, android_app.clone());
+    run_in_jvm(request_shared_storage, android_app.clone());
 
-    let event_loop = EventLoop::builder()
-        .with_android_app(android_app.clone())
-        .build()
-        .pb_expect("Failed to create event loop");
+    let mut event_loop = EventLoop::builder();
+    event_loop.with_android_app(android_app.clone());
+    if !get_application_context()
+        .local_config
+        .keyboard
+        .android_volume_keys
+    {
+        // The desktop gets them as XF86AudioRaiseVolume... instead
+        event_loop.handle_volume_keys();
+    }
+    let event_loop = event_loop.build().pb_expect("Failed to create event loop");
 
     // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
     // dispatched any events. This is ideal for games and similar applications.
@@ -72,9 +166,14 @@ fn android_main(android_app: AndroidApp) {
     // input, and uses significantly less power/CPU time than ControlFlow::Poll.
     event_loop.set_control_flow(ControlFlow::Wait);
 
+    attach(android_app.clone(), event_loop.create_proxy());
+
     // Phase 1: Setup
-    let mut app = PolarBearApp::build(android_app);
+    let mut app = PolarBearApp::build(android_app.clone());
 
     // Phase 2: Run
     event_loop.run_app(&mut app).pb_expect("Failed to run app");
+
+    // Phase 3: The activity is gone, the next one of the process takes the session over
+    detach(app.backend, &android_app);
 }
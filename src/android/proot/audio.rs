@@ -0,0 +1,150 @@
+use super::process::ArchProcess;
+use crate::{
+    android::utils::aaudio::AAudioOutput,
+    core::{config::ARCH_FS_ROOT, logging::panel_log},
+};
+use std::{
+    fs::{self, File},
+    io::Read,
+    sync::{mpsc::SyncSender, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// `pulseaudio-alsa` routes the ALSA apps to PulseAudio too
+const PACKAGES: &str = "pulseaudio pulseaudio-alsa";
+
+/// The PulseAudio configuration of the session, inside the Arch FS
+const CONFIG: &str = "/etc/pulse/localdesktop.pa";
+
+/// The pipe PulseAudio writes the samples it plays to, inside the Arch FS
+const FIFO: &str = "/tmp/.localdesktop-audio";
+
+/// The socket the apps of the session connect to, inside the Arch FS
+const SOCKET: &str = "/tmp/.localdesktop-pulse";
+
+pub const SAMPLE_RATE: i32 = 48000;
+pub const CHANNELS: usize = 2;
+
+/// The samples are forwarded 10 ms at a time
+const CHUNK_FRAMES: usize = 480;
+
+/// Whether PulseAudio is set up: done once per launch, for every session and restart to share
+static READY: OnceLock<bool> = OnceLock::new();
+
+/// Where the samples played are sent as well, e.g. while the desktop is recorded
+static TAP: Mutex<Option<SyncSender<Vec<i16>>>> = Mutex::new(None);
+
+/// Send a copy of the samples played to `tap` from now on, or stop with `None`. The samples are dropped rather than
+/// waited for when `tap` is full.
+pub fn tap_playback(tap: Option<SyncSender<Vec<i16>>>) {
+    *TAP.lock().unwrap() = tap;
+}
+
+/// The Android stream is closed after that much silence, so that the audio hardware can sleep
+const SILENCE_CHUNKS: usize = 100;
+
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+fn pulseaudio_config() -> String {
+    format!(
+        "load-module module-pipe-sink file={} sink_name=android sink_properties=device.description=Android format=s16le rate={} channels={}\n\
+        set-default-sink android\n\
+        load-module module-native-protocol-unix socket={} auth-anonymous=1\n\
+        load-module module-suspend-on-idle\n",
+        FIFO, SAMPLE_RATE, CHANNELS, SOCKET
+    )
+}
+
+/// Play what PulseAudio writes to the pipe through AAudio, until the app exits.
+/// The pipe is drained even when AAudio is not available (before Android 8), so that the apps never stall.
+fn forward_to_android() {
+    thread::spawn(|| {
+        let path = format!("{}{}", ARCH_FS_ROOT, FIFO);
+        let mut buffer = vec![0u8; CHUNK_FRAMES * CHANNELS * 2];
+        let mut output: Option<AAudioOutput> = None;
+        let mut retry_at = Instant::now();
+        let mut silent_chunks = 0;
+        loop {
+            // Blocks until PulseAudio opens the pipe
+            let Ok(mut fifo) = File::open(&path) else {
+                thread::sleep(RETRY_INTERVAL);
+                continue;
+            };
+            while fifo.read_exact(&mut buffer).is_ok() {
+                let samples = buffer
+                    .chunks_exact(2)
+                    .map(|it| i16::from_le_bytes([it[0], it[1]]))
+                    .collect::<Vec<_>>();
+                if let Some(tap) = TAP.lock().unwrap().as_ref() {
+                    let _ = tap.try_send(samples.clone());
+                }
+                silent_chunks = if samples.iter().all(|it| *it == 0) {
+                    silent_chunks + 1
+                } else {
+                    0
+                };
+                if silent_chunks >= SILENCE_CHUNKS {
+                    output = None;
+                    continue;
+                }
+                if output.is_none() && Instant::now() >= retry_at {
+                    match AAudioOutput::open(SAMPLE_RATE, CHANNELS as i32) {
+                        Ok(it) => output = Some(it),
+                        Err(e) => {
+                            log::warn!("Failed to open the Android audio output: {}", e);
+                            retry_at = Instant::now() + RETRY_INTERVAL;
+                        }
+                    }
+                }
+                if let Some(stream) = output.as_mut() {
+                    if let Err(e) = stream.write(&samples, WRITE_TIMEOUT) {
+                        // Reopened on the next chunk, on the output Android switched to
+                        log::info!("The Android audio output was lost: {}", e);
+                        output = None;
+                    }
+                }
+            }
+            // PulseAudio exited
+            output = None;
+            thread::sleep(RETRY_INTERVAL);
+        }
+    });
+}
+
+/// Install PulseAudio, and start forwarding what it plays to Android
+fn prepare() -> bool {
+    if !ArchProcess::install_packages(PACKAGES) {
+        panel_log(
+            format!("Failed to install {}, the session has no sound", PACKAGES),
+            true,
+        );
+        return false;
+    }
+    if let Err(e) = fs::write(format!("{}{}", ARCH_FS_ROOT, CONFIG), pulseaudio_config()) {
+        panel_log(
+            format!(
+                "Failed to write {}, the session has no sound: {}",
+                CONFIG, e
+            ),
+            true,
+        );
+        return false;
+    }
+    forward_to_android();
+    true
+}
+
+/// Install PulseAudio, and forward what it plays to Android, on the first call only: a single reader drains the
+/// pipe. Returns the shell commands starting it for the session, to prefix the launch command with, or nothing when
+/// the session has no sound.
+pub fn audio_environment() -> String {
+    if !*READY.get_or_init(prepare) {
+        return String::new();
+    }
+    format!(
+        "pulseaudio --daemonize=yes --exit-idle-time=-1 --disable-shm=yes -n -F {} >/dev/null 2>&1; export PULSE_SERVER=unix:{}; ",
+        CONFIG, SOCKET
+    )
+}
@@ -0,0 +1,158 @@
+use super::{maintenance::size_of, setup::SetupMessage};
+use crate::{
+    android::{
+        app::attach::attached_app,
+        utils::{
+            media_store::{self, Collection},
+            ndk::run_in_jvm,
+        },
+    },
+    core::{config::ARCH_FS_ROOT, logging::panel_log},
+};
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    os::unix::fs::FileTypeExt,
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tar::Builder;
+use winit::platform::android::activity::AndroidApp;
+
+/// The folder the setup extracts the Arch FS from, so that a backup restores like any other archive
+const ARCHIVE_ROOT: &str = "archlinux-aarch64";
+
+/// A good tradeoff between the size of the backup and the time it takes on a phone
+const ZSTD_LEVEL: i32 = 3;
+
+/// Adds the Arch FS to a tarball, reporting how much of it was written
+struct Exporter<'a, W: io::Write> {
+    builder: Builder<W>,
+    sender: &'a Sender<SetupMessage>,
+    total: u64,
+    written: u64,
+    last_percent: u64,
+}
+
+impl<W: io::Write> Exporter<'_, W> {
+    fn append(&mut self, path: &Path, name: &Path) -> io::Result<()> {
+        let metadata = fs::symlink_metadata(path)?;
+        let file_type = metadata.file_type();
+        if file_type.is_socket() {
+            // Sockets only mean something to the processes running
+            return Ok(());
+        }
+        self.builder.append_path_with_name(path, name)?;
+        if file_type.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                self.append(&entry.path(), &name.join(entry.file_name()))?;
+            }
+        } else if file_type.is_file() {
+            self.written += metadata.len();
+            self.report();
+        }
+        Ok(())
+    }
+
+    fn report(&mut self) {
+        let percent = (self.written * 100 / self.total.max(1)).min(100);
+        if percent / 10 != self.last_percent / 10 {
+            self.sender
+                .send(SetupMessage::Progress(format!(
+                    "Backing up the Arch FS... {}% ({:.2} MB / {:.2} MB)",
+                    percent,
+                    self.written as f64 / 1024.0 / 1024.0,
+                    self.total as f64 / 1024.0 / 1024.0
+                )))
+                .unwrap_or(());
+        }
+        self.last_percent = percent;
+    }
+}
+
+/// Stream the Arch FS into a zstd compressed tarball of the Downloads collection, where file managers and the share
+/// sheet find it. Opening it with the app on another device sets the Arch FS up from it, instead of downloading a
+/// fresh one. The files changed meanwhile may be saved half-written, so apps are better closed first.
+/// Returns where the backup is, as the user sees it.
+pub fn export_rootfs(
+    android_app: &AndroidApp,
+    sender: &Sender<SetupMessage>,
+) -> Result<String, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs());
+    let name = format!("localdesktop-{}.tar.zst", timestamp);
+
+    sender
+        .send(SetupMessage::Progress(
+            "Measuring the Arch FS...".to_string(),
+        ))
+        .unwrap_or(());
+    let total = size_of(Path::new(ARCH_FS_ROOT));
+
+    // The MediaStore only lists the backup once it is complete, so that a half-written one never looks like one
+    let export = |file: &mut fs::File| -> io::Result<()> {
+        let encoder = zstd::Encoder::new(BufWriter::new(file), ZSTD_LEVEL)?;
+        let mut builder = Builder::new(encoder);
+        builder.follow_symlinks(false);
+        let mut exporter = Exporter {
+            builder,
+            sender,
+            total,
+            written: 0,
+            last_percent: 0,
+        };
+        exporter.append(Path::new(ARCH_FS_ROOT), Path::new(ARCHIVE_ROOT))?;
+        let encoder = exporter.builder.into_inner()?;
+        encoder.finish()?.flush()
+    };
+    let mut result = Err("the MediaStore was not reached".to_string());
+    run_in_jvm(
+        |env, app| {
+            result = media_store::save(
+                env,
+                app,
+                Collection::Downloads,
+                &name,
+                "application/zstd",
+                export,
+            );
+        },
+        android_app.clone(),
+    );
+    result.map_err(|e| format!("Failed to back up the Arch FS: {}", e))
+}
+
+/// Back the Arch FS up from the diagnostics page, reporting the progress on the log panel
+pub fn back_up_rootfs() {
+    thread::spawn(|| {
+        let (sender, receiver) = mpsc::channel();
+        let reporter = thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    SetupMessage::Progress(message) => panel_log(message, false),
+                    SetupMessage::Error(message) => panel_log(message, true),
+                    SetupMessage::AskUsername => {}
+                }
+            }
+        });
+        let result = match attached_app() {
+            Some(android_app) => export_rootfs(&android_app, &sender),
+            None => Err("Failed to back up the Arch FS: the app is not running".to_string()),
+        };
+        match result {
+            Ok(location) => sender
+                .send(SetupMessage::Progress(format!(
+                    "Backed up the Arch FS to {}",
+                    location
+                )))
+                .unwrap_or(()),
+            Err(e) => sender.send(SetupMessage::Error(e)).unwrap_or(()),
+        }
+        drop(sender);
+        let _ = reporter.join();
+    });
+}
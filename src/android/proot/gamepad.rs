@@ -0,0 +1,164 @@
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{
+        config::ARCH_FS_ROOT,
+        gamepad::{capabilities, Gamepad, EV_ABS, EV_KEY, INPUT_EVENT_SIZE},
+        logging::panel_log,
+    },
+};
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::{fs::PermissionsExt, net::UnixListener, net::UnixStream},
+    path::Path,
+    sync::{Mutex, Once},
+    thread,
+    time::Duration,
+};
+
+/// The socket the gamepad events are streamed to, inside the Arch FS
+const SOCKET: &str = "/tmp/.localdesktop-gamepad";
+
+/// Creates a controller through uinput out of the events of the socket
+const BRIDGE_SCRIPT: &str = "/usr/local/bin/localdesktop-gamepad";
+
+static START: Once = Once::new();
+
+/// The gamepad, and the readers of the socket
+static BRIDGE: Mutex<Option<(Gamepad, Vec<UnixStream>)>> = Mutex::new(None);
+
+/// The records written to `/dev/uinput` are the ones of the socket, as is: `struct input_event` on 64-bit Linux.
+/// The controller is set up with the legacy `struct uinput_user_dev`, which every kernel takes.
+fn bridge_script() -> String {
+    let (buttons, axes) = capabilities();
+    let buttons = buttons
+        .iter()
+        .map(|it| it.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let axes = axes
+        .iter()
+        .map(|(code, min, max)| format!("{}: ({}, {})", code, min, max))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        r#"#!/usr/bin/env python3
+# Written by Local Desktop: replays the gamepads of Android on a controller created through /dev/uinput
+import fcntl, os, socket, struct
+
+UI_SET_EVBIT, UI_SET_KEYBIT, UI_SET_ABSBIT, UI_DEV_CREATE = 0x40045564, 0x40045565, 0x40045567, 0x5501
+BUTTONS = [{buttons}]
+AXES = {{{axes}}}
+
+uinput = os.open("/dev/uinput", os.O_WRONLY)
+fcntl.ioctl(uinput, UI_SET_EVBIT, {ev_key})
+fcntl.ioctl(uinput, UI_SET_EVBIT, {ev_abs})
+for button in BUTTONS:
+    fcntl.ioctl(uinput, UI_SET_KEYBIT, button)
+absmax, absmin = [0] * 64, [0] * 64
+for axis, (low, high) in AXES.items():
+    fcntl.ioctl(uinput, UI_SET_ABSBIT, axis)
+    absmin[axis], absmax[axis] = low, high
+# The name, the bus (USB) and the ids of an Xbox 360 controller, which the games know the layout of
+device = struct.pack("80sHHHHi64i64i64i64i", b"Local Desktop gamepad", 0x03, 0x045e, 0x028e, 1, 0,
+                     *absmax, *absmin, *([0] * 64), *([0] * 64))
+os.write(uinput, device)
+fcntl.ioctl(uinput, UI_DEV_CREATE)
+
+stream = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+stream.connect("{socket}")
+pending = b""
+while True:
+    data = stream.recv(4096)
+    if not data:
+        break
+    pending += data
+    whole = len(pending) - len(pending) % {size}
+    os.write(uinput, pending[:whole])
+    pending = pending[whole:]
+"#,
+        buttons = buttons,
+        axes = axes,
+        ev_key = EV_KEY,
+        ev_abs = EV_ABS,
+        socket = SOCKET,
+        size = INPUT_EVENT_SIZE,
+    )
+}
+
+/// Stream the gamepads to the Arch FS, if enabled in `[gamepad]`
+pub fn serve_gamepads() {
+    if !get_application_context()
+        .local_config
+        .gamepad
+        .forward_gamepads
+    {
+        return;
+    }
+    START.call_once(|| {
+        let script = format!("{}{}", ARCH_FS_ROOT, BRIDGE_SCRIPT);
+        let written = Path::new(&script)
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&script, bridge_script()))
+            .and_then(|_| fs::set_permissions(&script, fs::Permissions::from_mode(0o755)));
+        if let Err(e) = written {
+            log::warn!("Failed to write {}: {}", BRIDGE_SCRIPT, e);
+        }
+        *BRIDGE.lock().unwrap() = Some((Gamepad::default(), Vec::new()));
+        thread::spawn(|| {
+            if let Err(e) = listen() {
+                panel_log(format!("Gamepads are not forwarded: {}", e), true);
+            }
+        });
+    });
+}
+
+fn listen() -> io::Result<()> {
+    let path = format!("{}{}", ARCH_FS_ROOT, SOCKET);
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(it) => it,
+            Err(e) => {
+                log::warn!("Failed to accept a gamepad reader: {}", e);
+                continue;
+            }
+        };
+        // A reader that falls behind is dropped rather than stalling the compositor
+        stream.set_nonblocking(true)?;
+        log::info!("Streaming the gamepads to a new reader");
+        if let Some((_, readers)) = BRIDGE.lock().unwrap().as_mut() {
+            readers.push(stream);
+        }
+    }
+    Ok(())
+}
+
+/// A button of a gamepad, by its Android key code
+pub fn press(keycode: u32, pressed: bool, time: Duration) {
+    send(|gamepad| gamepad.button(keycode, pressed, time));
+}
+
+/// An axis of a gamepad, by its Android id
+pub fn move_axis(axis: u32, value: f64, time: Duration) {
+    send(|gamepad| gamepad.motion(&[(axis, value)], time));
+}
+
+fn send(events: impl FnOnce(&mut Gamepad) -> Option<Vec<u8>>) {
+    let mut bridge = BRIDGE.lock().unwrap();
+    let Some((gamepad, readers)) = bridge.as_mut() else {
+        return;
+    };
+    let Some(bytes) = events(gamepad) else {
+        return;
+    };
+    readers.retain_mut(|reader| match reader.write_all(&bytes) {
+        Ok(()) => true,
+        Err(e) => {
+            log::info!("Dropping a gamepad reader: {}", e);
+            false
+        }
+    });
+}
@@ -0,0 +1,105 @@
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{backoff::Backoff, config::GpuRenderer, config::ARCH_FS_ROOT, logging::panel_log},
+};
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    sync::Once,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The VirGL renderer, built for Android by `patches/build-virglrenderer` and shipped along with the app. It draws
+/// the OpenGL commands of the apps with the GLES driver of the phone.
+const VIRGL_SERVER: &str = "libvirgl_test_server.so";
+
+/// The socket the renderer listens on, inside the Arch FS, which is also where Mesa looks for it by default
+const VIRGL_SOCKET: &str = "/tmp/.virgl_test";
+
+/// The renderer is given that long to listen before the session starts
+const VIRGL_STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+const VIRGL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The renderer is left stopped after crashing that many times in a row, the apps falling back to software
+const MAX_CRASHES: u32 = 10;
+
+static START_VIRGL: Once = Once::new();
+
+/// Run the VirGL renderer for as long as the app runs, restarting it whenever it exits
+fn supervise_virgl(server: &Path) {
+    let socket = format!("{}{}", ARCH_FS_ROOT, VIRGL_SOCKET);
+    let server = server.to_path_buf();
+    thread::spawn(move || {
+        let mut backoff = Backoff::default();
+        loop {
+            // A socket left over by a previous renderer makes it fail to listen
+            let _ = fs::remove_file(&socket);
+            let mut command = Command::new(&server);
+            command
+                .arg("--use-egl-surfaceless")
+                .arg("--use-gles")
+                .arg("--multi-clients")
+                .arg(format!("--socket-path={}", socket));
+            match command.spawn() {
+                Ok(mut child) => {
+                    log::info!("The VirGL renderer is running as {}", child.id());
+                    backoff.started();
+                    match child.wait() {
+                        Ok(status) => log::warn!("The VirGL renderer exited with {}", status),
+                        Err(e) => log::warn!("Failed to wait for the VirGL renderer: {}", e),
+                    }
+                }
+                Err(e) => log::warn!("Failed to start the VirGL renderer: {}", e),
+            }
+            let delay = backoff.exited();
+            if backoff.crashes() >= MAX_CRASHES {
+                panel_log(
+                    "The VirGL renderer keeps exiting, the apps render in software".to_string(),
+                    true,
+                );
+                return;
+            }
+            thread::sleep(delay);
+        }
+    });
+}
+
+/// Bridge the GPU of the phone into the Arch FS, as `[gpu] renderer` picks. Returns the shell commands that point
+/// Mesa to it, to prefix the launch command with, or nothing when the apps render in software.
+pub fn gpu_environment() -> String {
+    match get_application_context().local_config.gpu.renderer() {
+        GpuRenderer::Llvmpipe => String::new(),
+        GpuRenderer::Virgl => {
+            let server = get_application_context()
+                .native_library_dir
+                .join(VIRGL_SERVER);
+            if !server.exists() {
+                panel_log(
+                    format!(
+                        "{} is not shipped with this build, the apps render in software",
+                        VIRGL_SERVER
+                    ),
+                    true,
+                );
+                return String::new();
+            }
+            START_VIRGL.call_once(|| supervise_virgl(&server));
+            let socket = format!("{}{}", ARCH_FS_ROOT, VIRGL_SOCKET);
+            let deadline = Instant::now() + VIRGL_STARTUP_TIMEOUT;
+            while !Path::new(&socket).exists() && Instant::now() < deadline {
+                thread::sleep(VIRGL_POLL_INTERVAL);
+            }
+            format!(
+                "export GALLIUM_DRIVER=virpipe VTEST_SOCKET_NAME={}; ",
+                VIRGL_SOCKET
+            )
+        }
+        // Turnip does not pass the Vulkan conformance checks of Zink on every Adreno, which it is told to skip
+        GpuRenderer::Zink => {
+            "export MESA_LOADER_DRIVER_OVERRIDE=zink GALLIUM_DRIVER=zink TU_DEBUG=noconform; "
+                .to_string()
+        }
+    }
+}
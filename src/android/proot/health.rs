@@ -0,0 +1,168 @@
+use super::process::ArchProcess;
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{
+        diagnostics,
+        health::{HealthHistory, HealthRun},
+        logging::panel_log,
+    },
+};
+use serde_json::json;
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Once,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The past checks, in the app data outside the Arch FS so that they survive a broken one
+const HISTORY_FILE: &str = "health-history.json";
+
+/// The first check waits for the session to settle, so that it does not slow the startup down
+const STARTUP_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How often the scheduler looks whether a check is due, and whether `[health] nightly` was turned on
+const POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The headless X server, the terminal, and the tool taking a screenshot of them
+const SMOKE_TEST_PACKAGES: &str = "xorg-server-xvfb xterm xorg-xwd";
+
+/// Start an xterm filled with text in a headless X server, and check that it is still alive and that the bottom of its
+/// window holds more than one color, i.e. the text was drawn. The pixels are at the end of the `xwd` dump, after a
+/// header of its own.
+const SMOKE_TEST: &str = "export DISPLAY=:97; \
+    Xvfb $DISPLAY -screen 0 640x480x24 -nolisten tcp >/dev/null 2>&1 & server=$!; sleep 3; \
+    xterm -T localdesktop-smoke-test -e sh -c 'yes Local Desktop | head -n 1000; sleep 30' & terminal=$!; sleep 5; \
+    kill -0 $terminal && [ \"$(xwd -silent -name localdesktop-smoke-test | tail -c 65536 \
+    | od -An -v -tx4 | tr -s ' ' '\\n' | sort -u | grep -c .)\" -gt 1 ]; result=$?; \
+    kill $terminal $server 2>/dev/null; exit $result";
+
+static SCHEDULER: Once = Once::new();
+
+/// Check the Arch FS once a day while `[health] nightly` is on. There is no job an Android native activity could
+/// schedule without a service class of its own, so the checks only run while the app is open.
+pub fn schedule_health_checks() {
+    SCHEDULER.call_once(|| {
+        thread::spawn(|| {
+            publish(&load());
+            thread::sleep(STARTUP_DELAY);
+            loop {
+                let mut history = load();
+                if get_application_context().local_config.health.nightly && history.is_due(now()) {
+                    let run = run_check();
+                    history.record(run);
+                    save(&history);
+                    publish(&history);
+                    report(&history);
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    });
+}
+
+fn run_check() -> HealthRun {
+    log::info!("Running the health check of the Arch FS");
+    let at = now();
+    let config = get_application_context().local_config;
+    if let Err(failure) = step("check", &config.command.check) {
+        return HealthRun {
+            at,
+            failure: Some(failure),
+            skipped: None,
+        };
+    }
+    // Missing packages, or a mirror failing to serve them, do not mean that the Arch FS broke
+    let ready = step(
+        "smoke test packages",
+        &format!("pacman -Q {} >/dev/null 2>&1", SMOKE_TEST_PACKAGES),
+    )
+    .is_ok()
+        || (config.health.install_smoke_test && ArchProcess::install_packages(SMOKE_TEST_PACKAGES));
+    if !ready {
+        return HealthRun {
+            at,
+            failure: None,
+            skipped: Some(format!(
+                "smoke test skipped as {} are not installed, see [health] install_smoke_test",
+                SMOKE_TEST_PACKAGES
+            )),
+        };
+    }
+    HealthRun {
+        at,
+        failure: step("smoke test", SMOKE_TEST).err(),
+        skipped: None,
+    }
+}
+
+fn step(name: &str, command: &str) -> Result<(), String> {
+    match ArchProcess::exec(command).and_then(ArchProcess::wait) {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{}: {}", name, status)),
+        Err(e) => Err(format!("{}: {}", name, e)),
+    }
+}
+
+fn report(history: &HealthHistory) {
+    let Some(last) = history.runs.last() else {
+        return;
+    };
+    match &last.failure {
+        None => log::info!("The health check {}", last.summary()),
+        Some(failure) if history.just_broke() => panel_log(
+            format!(
+                "The health check failed ({}), an update may have broken the Arch FS",
+                failure
+            ),
+            true,
+        ),
+        Some(failure) => log::warn!("The health check still fails: {}", failure),
+    }
+}
+
+fn publish(history: &HealthHistory) {
+    if history.runs.is_empty() {
+        return;
+    }
+    let runs = history
+        .runs
+        .iter()
+        .rev()
+        .map(|run| {
+            json!({
+                "timestamp": run.at * 1000,
+                "result": run.summary(),
+                "isError": !run.passed(),
+            })
+        })
+        .collect();
+    diagnostics::publish("health check", serde_json::Value::Array(runs));
+}
+
+fn history_file() -> PathBuf {
+    get_application_context().data_dir.join(HISTORY_FILE)
+}
+
+fn load() -> HealthHistory {
+    fs::read_to_string(history_file())
+        .ok()
+        .and_then(|it| serde_json::from_str(&it).ok())
+        .unwrap_or_default()
+}
+
+fn save(history: &HealthHistory) {
+    let result = serde_json::to_string(history)
+        .map_err(|e| e.to_string())
+        .and_then(|it| fs::write(history_file(), it).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::warn!("Failed to save the health check history: {}", e);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs())
+}
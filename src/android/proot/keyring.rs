@@ -0,0 +1,142 @@
+use crate::{
+    android::utils::{
+        application_context::get_application_context, keystore::get_or_create_secret,
+        ndk::run_in_jvm,
+    },
+    core::{config::ARCH_FS_ROOT, logging::panel_log},
+};
+use std::{
+    collections::BTreeSet,
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// Where the secret of each user is kept in the app storage, encrypted by the Android Keystore
+const SECRETS_DIR: &str = "keyring";
+
+/// Started by the desktop session, so that gnome-keyring runs on the session bus
+const AUTOSTART_ENTRY: &str = "/etc/xdg/autostart/localdesktop-keyring.desktop";
+const UNLOCK_SCRIPT: &str = "/usr/local/bin/localdesktop-unlock-keyring";
+
+const AUTOSTART_ENTRY_CONTENT: &str = "[Desktop Entry]
+Type=Application
+Name=Local Desktop keyring
+Comment=Unlock the login keyring with the secret held by the Android Keystore
+Exec=/usr/local/bin/localdesktop-unlock-keyring
+NoDisplay=true
+";
+
+const UNLOCK_SCRIPT_CONTENT: &str = r#"#!/bin/sh
+# Written by Local Desktop: unlocks (or creates) the login keyring of the session with the secret handed over at launch
+secret="/tmp/.localdesktop-keyring-$(id -un)"
+[ -p "$secret" ] || exit 0
+gnome-keyring-daemon --replace --unlock --components=secrets,pkcs11 < "$secret" > /dev/null
+"#;
+
+/// The users whose secret is handed over, by a thread of their own
+static HANDED_OVER: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// The pipe the secret is handed over to the session of `username` through, so that it is never written to a file
+fn handover_path(username: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/tmp/.localdesktop-keyring-{}",
+        ARCH_FS_ROOT, username
+    ))
+}
+
+fn write_file(path: &str, content: &str, mode: u32) -> std::io::Result<()> {
+    let path = format!("{}{}", ARCH_FS_ROOT, path);
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+}
+
+fn create_fifo(path: &Path) -> std::io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => return Ok(()),
+        // The secret itself, as earlier versions handed it over
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    let c_path =
+        CString::new(path.as_os_str().as_encoded_bytes()).map_err(std::io::Error::other)?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Write `secret` to every session of `username` opening the pipe, until the app exits. Opening the pipe blocks
+/// until the unlock script of a session reads it.
+fn hand_over(username: &str, secret: Vec<u8>) -> std::io::Result<()> {
+    let path = handover_path(username);
+    create_fifo(&path)?;
+    if !HANDED_OVER.lock().unwrap().insert(username.to_string()) {
+        return Ok(());
+    }
+    let username = username.to_string();
+    thread::spawn(move || loop {
+        match OpenOptions::new().write(true).open(&path) {
+            Ok(mut pipe) => {
+                if let Err(e) = pipe.write_all(&secret) {
+                    log::warn!("Failed to hand the keyring secret over: {}", e);
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to open {}: {}", path.display(), e);
+                HANDED_OVER.lock().unwrap().remove(&username);
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Have the login keyring of `username` unlocked as the session starts: apps using libsecret
+/// (browsers, mail clients...) then store their passwords encrypted at rest without ever asking for a keyring password.
+/// The keyring password is a random secret that only this app can decrypt, through the Android Keystore.
+pub fn unlock_keyring(android_app: &AndroidApp, username: &str) {
+    let context = get_application_context();
+    if !context.local_config.keyring.unlock {
+        // Nothing would ever write to the pipe, which the unlock script would wait on
+        let _ = fs::remove_file(handover_path(username));
+        return;
+    }
+
+    let secrets_dir = context.data_dir.join(SECRETS_DIR);
+    let secret_path = secrets_dir.join(username);
+    let mut secret = None;
+    run_in_jvm(
+        |env, _| {
+            let result = fs::create_dir_all(&secrets_dir)
+                .map_err(Into::into)
+                .and_then(|_| get_or_create_secret(env, &secret_path));
+            match result {
+                Ok(it) => secret = Some(it),
+                Err(e) => {
+                    let _ = env.exception_clear();
+                    panel_log(format!("Failed to get the keyring secret: {}", e), true);
+                }
+            }
+        },
+        android_app.clone(),
+    );
+    let Some(secret) = secret else {
+        return;
+    };
+
+    let result = write_file(AUTOSTART_ENTRY, AUTOSTART_ENTRY_CONTENT, 0o644)
+        .and_then(|_| write_file(UNLOCK_SCRIPT, UNLOCK_SCRIPT_CONTENT, 0o755))
+        .and_then(|_| hand_over(username, secret.into()));
+    if let Err(e) = result {
+        panel_log(format!("Failed to set up the keyring: {}", e), true);
+    }
+}
@@ -1,20 +1,380 @@
-use super::process::ArchProcess;
-use crate::android::utils::application_context::get_application_context;
-use std::thread;
+use super::{
+    audio::audio_environment,
+    gpu::gpu_environment,
+    keyring::unlock_keyring,
+    limits::enforce_limits,
+    notifications::forward_notifications,
+    process::{ArchProcess, OutputStream},
+    share::share_environment,
+    users::ensure_user,
+    xdg_open::serve_xdg_open,
+};
+use crate::{
+    android::{app::attach::attached_app, utils::application_context::get_application_context},
+    core::{backoff::Backoff, config, diagnostics, logging::panel_log, telemetry},
+};
+use sentry::{Breadcrumb, Level};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// How often the launch progress is probed
+const STAGE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Custom launch commands may never reach some stages, so stop probing after a while
+const STAGE_WATCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The Android density of a screen showing everything at its actual size (mdpi)
+const BASELINE_DENSITY: u32 = 160;
+
+/// The DPI X11 apps are designed for
+const X11_BASELINE_DPI: u32 = 96;
+
+/// The session is left stopped after crashing that many times in a row, until the app is resumed again
+const MAX_CRASHES: u32 = 10;
+
+/// The steps of the default launch command, shown on the boot splash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchStage {
+    StartingXwayland,
+    StartingDbus,
+    StartingDesktop,
+}
+
+impl LaunchStage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LaunchStage::StartingXwayland => "Starting Xwayland...",
+            LaunchStage::StartingDbus => "Starting dbus...",
+            LaunchStage::StartingDesktop => "Starting XFCE...",
+        }
+    }
+}
+
+static LAUNCH_STAGE: Mutex<LaunchStage> = Mutex::new(LaunchStage::StartingXwayland);
+
+pub fn launch_stage() -> LaunchStage {
+    LAUNCH_STAGE
+        .lock()
+        .map(|it| *it)
+        .unwrap_or(LaunchStage::StartingXwayland)
+}
+
+fn set_launch_stage(stage: LaunchStage) {
+    if let Ok(mut current) = LAUNCH_STAGE.lock() {
+        if *current != stage {
+            log::info!("Launch stage: {:?}", stage);
+            *current = stage;
+        }
+    }
+}
+
+/// The session launched by this process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Session {
+    Stopped,
+    Launching,
+    Running {
+        pid: u32,
+    },
+    /// The session exited, and is launched again after a while
+    Restarting {
+        delay: Duration,
+    },
+}
+
+/// The sessions launched by this process, by index in `LocalConfig::session_users`
+static SESSIONS: Mutex<BTreeMap<usize, Session>> = Mutex::new(BTreeMap::new());
+
+/// Record a transition of a session, on the log panel and along the next Sentry reports
+fn set_session(index: usize, session: Session, message: String) {
+    let is_error = matches!(session, Session::Restarting { .. } | Session::Stopped);
+    sentry::add_breadcrumb(Breadcrumb {
+        category: Some("session".to_string()),
+        message: Some(message.clone()),
+        level: if is_error {
+            Level::Warning
+        } else {
+            Level::Info
+        },
+        ..Default::default()
+    });
+    panel_log(message, is_error);
+    SESSIONS.lock().unwrap().insert(index, session);
+}
+
+/// Where the pid of a running session is kept, to find it from the next process of the app
+fn session_pid_file(index: usize) -> PathBuf {
+    get_application_context()
+        .data_dir
+        .join(format!("session-{}.pid", index))
+}
+
+/// A session left over by a previous process of the app, which Android may not have killed along with it.
+/// Its clients were connected to a compositor that is gone, so it cannot be attached to.
+fn stop_stale_session(index: usize) {
+    let Ok(pid) = fs::read_to_string(session_pid_file(index)) else {
+        return;
+    };
+    let pid = pid.trim();
+    let proot = get_application_context()
+        .native_library_dir
+        .join("libproot.so");
+    if fs::read_link(format!("/proc/{}/exe", pid)).is_ok_and(|exe| exe == proot) {
+        log::warn!(
+            "Stopping the session {} left over by a previous launch",
+            pid
+        );
+        if let Err(e) = Command::new("kill").arg(pid).status() {
+            log::warn!("Failed to stop the session {}: {}", pid, e);
+        }
+    }
+    let _ = fs::remove_file(session_pid_file(index));
+}
+
+/// Whether a process with the given name is running, as seen from Android
+fn is_process_running(name: &str) -> bool {
+    fs::read_dir("/proc").is_ok_and(|entries| {
+        entries.filter_map(Result::ok).any(|entry| {
+            fs::read_to_string(entry.path().join("comm")).is_ok_and(|comm| comm.trim() == name)
+        })
+    })
+}
+
+fn wait_until(deadline: Instant, condition: impl Fn() -> bool) -> bool {
+    while !condition() {
+        if Instant::now() > deadline {
+            return false;
+        }
+        thread::sleep(STAGE_POLL_INTERVAL);
+    }
+    true
+}
+
+/// Follow the launch command from the outside: the X socket shows up once the compositor's Xwayland is ready,
+/// then the dbus daemon starts right before the desktop session.
+fn watch_launch_stages() {
+    let x_socket = format!("{}/tmp/.X11-unix/X1", config::ARCH_FS_ROOT);
+    set_launch_stage(LaunchStage::StartingXwayland);
 
-pub fn launch() {
     thread::spawn(move || {
-        // Clean up potential leftover files for display :1
-        ArchProcess::exec("rm -f /tmp/.X1-lock");
-        ArchProcess::exec("rm -f /tmp/.X11-unix/X1");
+        let deadline = Instant::now() + STAGE_WATCH_TIMEOUT;
+        if !wait_until(deadline, || Path::new(&x_socket).exists()) {
+            return;
+        }
+        set_launch_stage(LaunchStage::StartingDbus);
+
+        if !wait_until(deadline, || is_process_running("dbus-daemon")) {
+            return;
+        }
+        set_launch_stage(LaunchStage::StartingDesktop);
+    });
+}
+
+/// The factor scaling the X11 apps, so that they are not microscopic on high density screens: `[display] scale`,
+/// or the density of the screen rounded to an integer
+fn hidpi_scale(android_app: &AndroidApp) -> f64 {
+    let density = android_app.config().density().unwrap_or(BASELINE_DENSITY);
+    let auto = (density as f64 / BASELINE_DENSITY as f64).round().max(1.0);
+    get_application_context().local_config.display.scale(auto)
+}
+
+/// Scale the GTK and Qt apps through their environment. As `Xft.dpi` also scales the fonts of GTK and Qt, they are
+/// told to undo that. GTK only scales by integers, so a fractional scale is rounded for its widgets but not for its
+/// fonts.
+fn scale_environment(scale: f64) -> String {
+    let gdk_scale = scale.round().max(1.0);
+    format!(
+        "export GDK_SCALE={0} GDK_DPI_SCALE={1:.3} QT_SCALE_FACTOR={2} QT_FONT_DPI={3} QT_AUTO_SCREEN_SCALE_FACTOR=0; ",
+        gdk_scale,
+        1.0 / gdk_scale,
+        scale,
+        X11_BASELINE_DPI,
+    )
+}
 
-        let local_config = get_application_context().local_config;
-        let username = local_config.user.username;
+/// Scale the fonts of the other X11 apps through `Xft.dpi`, which is set once Xwayland is up. Only done by the
+/// first session, which owns the X display. Skipped when a custom `[command] install` left `xorg-xrdb` out.
+fn xft_dpi_command(scale: f64) -> String {
+    format!(
+        "if command -v xrdb >/dev/null; then \
+        (while [ ! -e /tmp/.X11-unix/X1 ]; do sleep 0.1; done; echo 'Xft.dpi: {}' | DISPLAY=:1 xrdb -nocpp -merge) & \
+        else echo 'xrdb is missing, install xorg-xrdb to scale the fonts of the X11 apps' >&2; fi; ",
+        (X11_BASELINE_DPI as f64 * scale).round()
+    )
+}
+
+/// Run a session until it exits. Failing to start it counts as a crash for the backoff.
+fn run_session(android_app: &AndroidApp, index: usize, username: &str, backoff: &mut Backoff) {
+    let local_config = get_application_context().local_config;
+    ensure_user(username);
+    unlock_keyring(android_app, username);
+    forward_notifications();
+    serve_xdg_open();
+
+    let scale = hidpi_scale(android_app);
+    log::info!("Scaling the X11 apps by {}", scale);
+    let audio = if local_config.audio.playback {
+        audio_environment()
+    } else {
+        String::new()
+    };
+    let gpu = gpu_environment();
+    // The files shared from Android open in the session shown, the one on the X display
+    let (xft_dpi, share) = if index == 0 {
+        (xft_dpi_command(scale), share_environment())
+    } else {
+        (String::new(), String::new())
+    };
+    let session_command = local_config.session_command(username);
+    telemetry::record("desktop", telemetry::desktop_of(&session_command));
+    telemetry::record("audio bridge", if audio.is_empty() { "off" } else { "on" });
+    telemetry::record("gpu bridge", if gpu.is_empty() { "off" } else { "on" });
+    let socket = config::wayland_socket_name(index);
+    let full_launch_command = format!(
+        "export XDG_RUNTIME_DIR=/tmp WAYLAND_DISPLAY={}; {}{}{}{}{}{}",
+        socket,
+        scale_environment(scale),
+        xft_dpi,
+        audio,
+        gpu,
+        share,
+        session_command
+    );
+
+    let session = ArchProcess {
+        command: full_launch_command,
+        user: username.to_string(),
+        process: None,
+    };
+    diagnostics::publish(&format!("session {}", socket), session.describe());
+
+    // The splash only follows the session of the selected user
+    if index == 0 {
+        watch_launch_stages();
+    }
+    let session = match session.spawn() {
+        Ok(it) => it,
+        Err(e) => {
+            log::error!("Failed to run the session of {}: {}", username, e);
+            return;
+        }
+    };
+    if let Some(pid) = session.process.as_ref().map(|it| it.id()) {
+        backoff.started();
+        set_session(
+            index,
+            Session::Running { pid },
+            format!("The session of {} is running as {}", username, pid),
+        );
+        if let Err(e) = fs::write(session_pid_file(index), pid.to_string()) {
+            log::warn!("Failed to write the session pid: {}", e);
+        }
+        enforce_limits(pid, username.to_string());
+    }
+    let status = session.with_output(|line, stream| match stream {
+        OutputStream::Stdout => log::info!("{}", line),
+        OutputStream::Stderr => log::warn!("{}", line),
+    });
+    let _ = fs::remove_file(session_pid_file(index));
+    match status {
+        Ok(status) => log::info!("The session of {} exited with {}", username, status),
+        Err(e) => log::error!("Failed to run the session of {}: {}", username, e),
+    }
+}
+
+/// Launch the sessions, unless this process already did: Android recreating the activity resumes it again,
+/// and the sessions that are running then simply show up in the new window.
+/// Each session is supervised: it is launched again whenever it exits, backing off while it keeps crashing.
+pub fn launch(android_app: AndroidApp) {
+    let users = get_application_context().local_config.session_users();
+    for (index, username) in users.into_iter().enumerate() {
+        launch_session(android_app.clone(), index, username);
+    }
+}
 
-        let full_launch_command = local_config.command.launch;
+/// Stop the running sessions, which their supervisors launch again, e.g. after an update of the desktop
+pub fn restart_sessions() {
+    let pids = SESSIONS
+        .lock()
+        .unwrap()
+        .values()
+        .filter_map(|session| match session {
+            Session::Running { pid } => Some(pid.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if pids.is_empty() {
+        panel_log("No session is running".to_string(), true);
+        return;
+    }
+    panel_log("Restarting the sessions".to_string(), false);
+    // Proot runs with `--kill-on-exit`, so that the whole session goes along with it
+    if let Err(e) = Command::new("kill").args(&pids).status() {
+        log::warn!("Failed to stop the sessions {:?}: {}", pids, e);
+    }
+}
+
+fn launch_session(android_app: AndroidApp, index: usize, username: String) {
+    let Ok(mut sessions) = SESSIONS.lock() else {
+        return;
+    };
+    let session = sessions.entry(index).or_insert(Session::Stopped);
+    if *session != Session::Stopped {
+        log::info!(
+            "Attaching to the session of {}, which is {:?}",
+            username,
+            *session
+        );
+        return;
+    }
+    // Taken right away, so that a quick second resume does not launch meanwhile
+    *session = Session::Launching;
+    drop(sessions);
 
-        ArchProcess::exec_as(&full_launch_command, &username).with_log(|it| {
-            log::info!("{}", it);
-        });
+    thread::spawn(move || {
+        stop_stale_session(index);
+        let mut backoff = Backoff::default();
+        loop {
+            // The activity the session started with may have been recreated since
+            let android_app = attached_app().unwrap_or_else(|| android_app.clone());
+            run_session(&android_app, index, &username, &mut backoff);
+            let delay = backoff.exited();
+            if backoff.crashes() >= MAX_CRASHES {
+                break;
+            }
+            telemetry::record("crash recovery", "session");
+            set_session(
+                index,
+                Session::Restarting { delay },
+                format!(
+                    "The session of {} exited, restarting in {}s",
+                    username,
+                    delay.as_secs()
+                ),
+            );
+            thread::sleep(delay);
+            set_session(
+                index,
+                Session::Launching,
+                format!("Restarting the session of {}", username),
+            );
+        }
+        // The next resume launches a new session
+        set_session(
+            index,
+            Session::Stopped,
+            format!(
+                "The session of {} keeps exiting, it is launched again when the app is resumed",
+                username
+            ),
+        );
     });
 }
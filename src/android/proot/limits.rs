@@ -0,0 +1,107 @@
+use super::priority::parents;
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{
+        limits::{memory_victim, pause_for},
+        logging::panel_log,
+        priority::process_tree,
+    },
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the usage of a session is measured
+const MEASURE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Keep the session whose Proot runs as `pid` within `[limits]`, until it exits
+pub fn enforce_limits(pid: u32, username: String) {
+    let config = get_application_context().local_config.limits;
+    if config.max_memory_mb == 0 && config.max_cpu_percent == 0 {
+        return;
+    }
+    thread::spawn(move || {
+        let cores = thread::available_parallelism().map_or(1, |it| it.get() as u32);
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+        let max_memory = config.max_memory_mb * 1024 * 1024;
+        let max_share = f64::from(config.max_cpu_percent) / 100.0;
+
+        let mut ticks = cpu_ticks(&process_tree(&parents(), pid));
+        let mut measured_at = Instant::now();
+        while Path::new(&format!("/proc/{}", pid)).exists() {
+            thread::sleep(MEASURE_INTERVAL);
+            let tree = process_tree(&parents(), pid);
+
+            if max_memory > 0 {
+                let resident = tree
+                    .iter()
+                    .filter_map(|&pid| Some((pid, resident_pages(pid)? * page_size)))
+                    .collect::<HashMap<_, _>>();
+                if let Some(victim) = memory_victim(&resident, pid, max_memory) {
+                    panel_log(
+                        format!(
+                            "The session of {} went over {} MiB, killing its process {} ({} MiB)",
+                            username,
+                            config.max_memory_mb,
+                            victim,
+                            resident[&victim] / 1024 / 1024
+                        ),
+                        true,
+                    );
+                    unsafe { libc::kill(victim as libc::pid_t, libc::SIGKILL) };
+                }
+            }
+
+            let current = cpu_ticks(&tree);
+            // The processes started meanwhile count with all their time
+            let used = current
+                .iter()
+                .map(|(pid, &total)| total.saturating_sub(ticks.get(pid).copied().unwrap_or(0)))
+                .sum::<u64>();
+            ticks = current;
+            let elapsed = measured_at.elapsed();
+            measured_at = Instant::now();
+            if max_share > 0.0 {
+                let used = Duration::from_secs_f64(used as f64 / ticks_per_second);
+                let pause = pause_for(used, elapsed, cores, max_share);
+                if !pause.is_zero() {
+                    log::debug!("Pausing the session of {} for {:?}", username, pause);
+                    signal(&tree, libc::SIGSTOP);
+                    thread::sleep(pause);
+                    signal(&tree, libc::SIGCONT);
+                    measured_at = Instant::now();
+                }
+            }
+        }
+    });
+}
+
+fn signal(pids: &[u32], signal: libc::c_int) {
+    for &pid in pids {
+        unsafe { libc::kill(pid as libc::pid_t, signal) };
+    }
+}
+
+/// The CPU time each process used so far, in clock ticks
+fn cpu_ticks(pids: &[u32]) -> HashMap<u32, u64> {
+    pids.iter()
+        .filter_map(|&pid| {
+            let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            // `pid (comm) state ...`, with the user and system times 11 and 12 fields after the state
+            let mut fields = stat.rsplit_once(')')?.1.split_whitespace().skip(11);
+            let user = fields.next()?.parse::<u64>().ok()?;
+            let system = fields.next()?.parse::<u64>().ok()?;
+            Some((pid, user + system))
+        })
+        .collect()
+}
+
+fn resident_pages(pid: u32) -> Option<u64> {
+    let statm = fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    statm.split_whitespace().nth(1)?.parse().ok()
+}
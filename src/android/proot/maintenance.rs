@@ -0,0 +1,167 @@
+use super::process::INSTALL;
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{config::ARCH_FS_ROOT, logging::panel_log},
+};
+use std::{
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long the Proot processes get to exit on their own, before they are killed
+const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+const TEARDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where pacman keeps the packages it downloaded
+const PACKAGE_CACHE: &str = "/var/cache/pacman/pkg";
+
+/// The Proot processes of the app, by pid. Every process of the rootfs runs under one of them.
+fn proot_processes() -> Vec<String> {
+    let proot = get_application_context()
+        .native_library_dir
+        .join("libproot.so");
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| fs::read_link(entry.path().join("exe")).is_ok_and(|exe| exe == proot))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|pid| pid.parse::<u32>().is_ok())
+        .collect()
+}
+
+fn signal(pids: &[String], signal: &str) {
+    if let Err(e) = Command::new("kill")
+        .arg(format!("-{}", signal))
+        .args(pids)
+        .status()
+    {
+        log::warn!("Failed to send SIG{} to {:?}: {}", signal, pids, e);
+    }
+}
+
+fn wait_for_exit(timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !proot_processes().is_empty() {
+        if Instant::now() > deadline {
+            return false;
+        }
+        thread::sleep(TEARDOWN_POLL_INTERVAL);
+    }
+    true
+}
+
+/// Stop every process of the rootfs, politely first. Proot runs with `--kill-on-exit`, so that its processes go
+/// along with it.
+fn stop_proot_processes() -> Result<(), String> {
+    let pids = proot_processes();
+    if pids.is_empty() {
+        return Ok(());
+    }
+    log::info!("Stopping the Proot processes {:?}", pids);
+    signal(&pids, "TERM");
+    if wait_for_exit(TEARDOWN_TIMEOUT) {
+        return Ok(());
+    }
+    signal(&proot_processes(), "KILL");
+    if wait_for_exit(TEARDOWN_TIMEOUT) {
+        Ok(())
+    } else {
+        Err(format!(
+            "The Proot processes {:?} are still running",
+            proot_processes()
+        ))
+    }
+}
+
+/// Give the owner write access to every directory, which `remove_dir_all` needs and which some packages take away
+fn make_writable(path: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(());
+    }
+    let mut permissions = metadata.permissions();
+    permissions.set_mode(permissions.mode() | 0o700);
+    fs::set_permissions(path, permissions)?;
+    for entry in fs::read_dir(path)? {
+        make_writable(&entry?.path())?;
+    }
+    Ok(())
+}
+
+/// Stop the processes of the Arch FS and delete it, so that the next launch sets it up from scratch
+pub fn delete_rootfs() -> Result<(), String> {
+    stop_proot_processes()?;
+    let root = Path::new(ARCH_FS_ROOT);
+    make_writable(root)
+        .and_then(|_| fs::remove_dir_all(root))
+        .map_err(|e| format!("Failed to delete {}: {}", ARCH_FS_ROOT, e))
+}
+
+/// Delete the Arch FS, so that the next launch sets it up from scratch, and close the app, whose session was
+/// running from it
+pub fn reset_rootfs() {
+    thread::spawn(|| {
+        panel_log("Resetting the Arch FS...", false);
+        match delete_rootfs() {
+            Ok(()) => {
+                log::info!("The Arch FS has been reset, closing the app");
+                std::process::exit(0);
+            }
+            Err(e) => panel_log(e, true),
+        }
+    });
+}
+
+pub(super) fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path).map_or(0, |entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| size_of(&entry.path()))
+                .sum()
+        }),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Delete the packages pacman downloaded, which are only needed to downgrade or reinstall offline
+pub fn purge_package_cache() {
+    thread::spawn(|| {
+        // pacman may be filling the cache right now
+        let _guard = INSTALL.lock().unwrap_or_else(|it| it.into_inner());
+        let cache = Path::new(ARCH_FS_ROOT).join(PACKAGE_CACHE.trim_start_matches('/'));
+        let Ok(entries) = fs::read_dir(&cache) else {
+            panel_log("The package cache is already empty", false);
+            return;
+        };
+        let mut freed = 0;
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let size = size_of(&path);
+            let removed = if entry.file_type().is_ok_and(|it| it.is_dir()) {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            match removed {
+                Ok(()) => freed += size,
+                Err(e) => log::warn!("Failed to delete {}: {}", path.display(), e),
+            }
+        }
+        panel_log(
+            format!(
+                "Purged the package cache, freeing {} MB",
+                freed / 1024 / 1024
+            ),
+            false,
+        );
+    });
+}
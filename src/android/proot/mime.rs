@@ -0,0 +1,168 @@
+use super::process::ArchProcess;
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{config::ARCH_FS_ROOT, diagnostics, logging::panel_log},
+};
+use serde_json::json;
+use std::{fs, process::Stdio, thread};
+
+/// `xdg-mime` reads and writes the associations of `~/.config/mimeapps.list`
+const PACKAGES: &str = "xdg-utils";
+
+const APPLICATIONS_DIR: &str = "/usr/share/applications";
+
+/// The defaults one may want to pick, with the MIME types they stand for
+const CATEGORIES: [(&str, &[&str]); 6] = [
+    (
+        "Web browser",
+        &[
+            "x-scheme-handler/http",
+            "x-scheme-handler/https",
+            "text/html",
+        ],
+    ),
+    ("Text editor", &["text/plain"]),
+    ("File manager", &["inode/directory"]),
+    ("Image viewer", &["image/png", "image/jpeg", "image/gif"]),
+    ("PDF viewer", &["application/pdf"]),
+    ("Mail client", &["x-scheme-handler/mailto"]),
+];
+
+/// An installed application, from its desktop entry
+struct Application {
+    /// The name of the desktop entry, e.g. `firefox.desktop`
+    id: String,
+    name: String,
+    mime_types: Vec<String>,
+}
+
+/// The applications shown in the menus, with the MIME types they can open
+fn list_applications() -> Vec<Application> {
+    let Ok(entries) = fs::read_dir(format!("{}{}", ARCH_FS_ROOT, APPLICATIONS_DIR)) else {
+        return vec![];
+    };
+    let mut applications = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let id = entry.file_name().to_string_lossy().to_string();
+            if !id.ends_with(".desktop") {
+                return None;
+            }
+            parse_desktop_entry(id, &fs::read_to_string(entry.path()).ok()?)
+        })
+        .collect::<Vec<_>>();
+    applications.sort_by(|a, b| a.name.cmp(&b.name));
+    applications
+}
+
+fn parse_desktop_entry(id: String, content: &str) -> Option<Application> {
+    let mut in_main_group = false;
+    let mut name = None;
+    let mut mime_types = vec![];
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_main_group = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_main_group {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match (key.trim(), value.trim()) {
+            ("Name", value) => name = Some(value.to_string()),
+            ("MimeType", value) => {
+                mime_types = value
+                    .split(';')
+                    .filter(|it| !it.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            ("NoDisplay" | "Hidden", "true") => return None,
+            _ => {}
+        }
+    }
+    Some(Application {
+        id,
+        name: name?,
+        mime_types,
+    })
+}
+
+/// Run `xdg-mime` as the user of the session, the arguments never being interpreted by the shell
+fn xdg_mime(args: &[&str]) -> Result<String, String> {
+    let output = ArchProcess {
+        command: "xdg-mime \"$@\" 2>&1".to_string(),
+        user: get_application_context().local_config.user.username,
+        process: None,
+    }
+    .command()
+    .args(args)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::inherit())
+    .output()
+    .map_err(|e| e.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() {
+        Ok(stdout)
+    } else {
+        Err(stdout)
+    }
+}
+
+fn publish() {
+    let applications = list_applications();
+    let categories = CATEGORIES
+        .iter()
+        .map(|(category, mime_types)| {
+            let current = xdg_mime(&["query", "default", mime_types[0]]).unwrap_or_default();
+            let candidates = applications
+                .iter()
+                .filter(|app| {
+                    mime_types
+                        .iter()
+                        .any(|it| app.mime_types.iter().any(|m| m == it))
+                })
+                .map(|app| json!({ "id": app.id, "name": app.name }))
+                .collect::<Vec<_>>();
+            json!({ "category": category, "current": current, "candidates": candidates })
+        })
+        .collect::<Vec<_>>();
+    diagnostics::publish("default applications", json!(categories));
+}
+
+/// Publish the default applications for the diagnostics page to change them, installing `xdg-mime` first if needed
+pub fn publish_default_applications() {
+    thread::spawn(|| {
+        if ArchProcess::install_packages(PACKAGES) {
+            publish();
+        } else {
+            panel_log(format!("Failed to install {}", PACKAGES), true);
+        }
+    });
+}
+
+/// Make `application` (a desktop entry name) the default for every MIME type of `category`
+pub fn set_default_application(category: String, application: String) {
+    thread::spawn(move || {
+        let Some((_, mime_types)) = CATEGORIES.iter().find(|(name, _)| *name == category) else {
+            log::warn!("Unknown application category {:?}", category);
+            return;
+        };
+        if !list_applications().iter().any(|it| it.id == application) {
+            panel_log(format!("There is no application {}", application), true);
+            return;
+        }
+        let mut args = vec!["default", application.as_str()];
+        args.extend(mime_types.iter());
+        match xdg_mime(&args) {
+            Ok(_) => log::info!("{} is now the default {}", application, category),
+            Err(e) => panel_log(
+                format!("Failed to set the default {}: {}", category, e),
+                true,
+            ),
+        }
+        publish();
+    });
+}
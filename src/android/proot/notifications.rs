@@ -0,0 +1,209 @@
+use super::process::ArchProcess;
+use crate::{
+    android::{
+        app::attach::attached_app,
+        utils::{
+            application_context::get_application_context,
+            ndk::run_in_jvm,
+            notifications::{cancel, post},
+        },
+    },
+    core::{
+        config::ARCH_FS_ROOT,
+        logging::panel_log,
+        notifications::{parse_message, GuestMessage},
+    },
+};
+use std::{
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    sync::Once,
+    thread,
+};
+
+/// The service is written in Python, over the D-Bus bindings of GLib
+const PACKAGES: &str = "python-dbus python-gobject";
+
+/// Started by the desktop session, so that the service runs on the session bus
+const AUTOSTART_ENTRY: &str = "/etc/xdg/autostart/localdesktop-notifications.desktop";
+const SERVICE_SCRIPT: &str = "/usr/local/bin/localdesktop-notifications";
+
+/// The pipe the service writes the notifications to, inside the Arch FS
+const FIFO: &str = "/tmp/.localdesktop-notifications";
+
+const AUTOSTART_ENTRY_CONTENT: &str = "[Desktop Entry]
+Type=Application
+Name=Local Desktop notifications
+Comment=Show the notifications of the session as Android notifications
+Exec=/usr/local/bin/localdesktop-notifications
+NoDisplay=true
+";
+
+const SERVICE_SCRIPT_CONTENT: &str = r#"#!/usr/bin/env python3
+# Written by Local Desktop: serves org.freedesktop.Notifications on the session bus, and hands the notifications over
+# to the app, which shows them as Android notifications
+import json
+import os
+
+import dbus
+import dbus.service
+from dbus.mainloop.glib import DBusGMainLoop
+from gi.repository import GLib
+
+FIFO = "/tmp/.localdesktop-notifications"
+NAME = "org.freedesktop.Notifications"
+PATH = "/org/freedesktop/Notifications"
+
+
+def forward(message):
+    # Dropped when the app does not read the pipe
+    try:
+        fd = os.open(FIFO, os.O_WRONLY | os.O_NONBLOCK)
+    except OSError:
+        return
+    try:
+        os.write(fd, (json.dumps(message) + "\n").encode())
+    except OSError:
+        pass
+    finally:
+        os.close(fd)
+
+
+class Notifications(dbus.service.Object):
+    def __init__(self, bus):
+        super().__init__(bus, PATH)
+        self.last_id = 0
+
+    @dbus.service.method(NAME, in_signature="susssasa{sv}i", out_signature="u")
+    def Notify(self, app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout):
+        if replaces_id == 0:
+            self.last_id += 1
+            replaces_id = self.last_id
+        forward({"notify": {
+            "id": int(replaces_id),
+            "app_name": str(app_name),
+            "summary": str(summary),
+            "body": str(body),
+            "urgency": int(hints.get("urgency", 1)),
+        }})
+        return replaces_id
+
+    @dbus.service.method(NAME, in_signature="u")
+    def CloseNotification(self, id):
+        forward({"close": int(id)})
+        # Closed by a call to CloseNotification
+        self.NotificationClosed(id, 3)
+
+    @dbus.service.method(NAME, out_signature="as")
+    def GetCapabilities(self):
+        return ["body"]
+
+    @dbus.service.method(NAME, out_signature="ssss")
+    def GetServerInformation(self):
+        return ["Local Desktop", "Local Desktop", "1.0", "1.2"]
+
+    @dbus.service.signal(NAME, signature="uu")
+    def NotificationClosed(self, id, reason):
+        pass
+
+
+DBusGMainLoop(set_as_default=True)
+bus = dbus.SessionBus()
+try:
+    # Left to the notification daemon of the desktop if it runs already
+    name = dbus.service.BusName(NAME, bus, do_not_queue=True)
+except dbus.exceptions.NameExistsException:
+    raise SystemExit(0)
+service = Notifications(bus)
+GLib.MainLoop().run()
+"#;
+
+static FORWARD: Once = Once::new();
+
+fn write_file(path: &str, content: &str, mode: u32) -> std::io::Result<()> {
+    let path = format!("{}{}", ARCH_FS_ROOT, path);
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+}
+
+fn create_fifo(path: &str) -> std::io::Result<()> {
+    if Path::new(path).exists() {
+        return Ok(());
+    }
+    let c_path = CString::new(path).map_err(std::io::Error::other)?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o666) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // Written by every user of the Arch FS, whatever the umask
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))
+}
+
+/// Install the `org.freedesktop.Notifications` service the sessions start, and show what it forwards as Android
+/// notifications, until the app exits. Does nothing without `[notifications] forward_notifications`.
+pub fn forward_notifications() {
+    if !get_application_context()
+        .local_config
+        .notifications
+        .forward_notifications
+    {
+        return;
+    }
+    if !ArchProcess::install_packages(PACKAGES) {
+        panel_log(
+            format!(
+                "Failed to install {}, the notifications of the session stay inside it",
+                PACKAGES
+            ),
+            true,
+        );
+        return;
+    }
+    let fifo = format!("{}{}", ARCH_FS_ROOT, FIFO);
+    let result = write_file(AUTOSTART_ENTRY, AUTOSTART_ENTRY_CONTENT, 0o644)
+        .and_then(|_| write_file(SERVICE_SCRIPT, SERVICE_SCRIPT_CONTENT, 0o755))
+        .and_then(|_| create_fifo(&fifo));
+    if let Err(e) = result {
+        panel_log(
+            format!("Failed to set up the notifications of the session: {}", e),
+            true,
+        );
+        return;
+    }
+    FORWARD.call_once(|| {
+        thread::spawn(move || {
+            // Opened for writing as well, so that the pipe never ends as the services come and go
+            let pipe = match OpenOptions::new().read(true).write(true).open(&fifo) {
+                Ok(it) => it,
+                Err(e) => {
+                    log::error!("Failed to open {}: {}", fifo, e);
+                    return;
+                }
+            };
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                let (Some(message), Some(android_app)) = (parse_message(&line), attached_app())
+                else {
+                    continue;
+                };
+                run_in_jvm(
+                    |env, app| {
+                        let result = match &message {
+                            GuestMessage::Notify(notification) => post(env, app, notification),
+                            GuestMessage::Close(id) => cancel(env, app, *id),
+                        };
+                        if let Err(e) = result {
+                            let _ = env.exception_clear();
+                            log::warn!("Failed to forward the notification {:?}: {}", message, e);
+                        }
+                    },
+                    android_app,
+                );
+            }
+        });
+    });
+}
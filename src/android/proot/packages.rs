@@ -0,0 +1,122 @@
+use super::process::ArchProcess;
+use crate::android::utils::application_context::get_application_context;
+use crate::core::{
+    compat::{self, PACKAGES_CHANGED, SCHEMAS_DIR},
+    config::ARCH_FS_ROOT,
+    logging::panel_log,
+    sanitizer::{sanitize, RULES},
+};
+use std::{
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Read},
+    os::{fd::FromRawFd, unix::ffi::OsStrExt},
+    path::Path,
+    sync::Once,
+    thread,
+};
+
+static WATCHER: Once = Once::new();
+
+/// Apply the compatibility shims and the rootfs fixes again whenever the pacman hook reports a transaction, so
+/// that the applications installed after the setup work too
+pub fn watch_package_changes() {
+    WATCHER.call_once(|| {
+        thread::spawn(|| {
+            if let Err(e) = watch() {
+                log::warn!("Stopped watching the package changes: {}", e);
+            }
+        });
+    });
+}
+
+fn watch() -> io::Result<()> {
+    let stamp = Path::new(ARCH_FS_ROOT).join(PACKAGES_CHANGED.trim_start_matches('/'));
+    let dir = stamp.parent().unwrap_or(Path::new(ARCH_FS_ROOT));
+    fs::create_dir_all(dir)?;
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut inotify = unsafe { File::from_raw_fd(fd) };
+    let path = CString::new(dir.as_os_str().as_bytes())?;
+    if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), libc::IN_ATTRIB | libc::IN_CLOSE_WRITE) }
+        == -1
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    // A transaction that ran while the app was not watching
+    if fs::remove_file(&stamp).is_ok() {
+        reapply();
+    }
+    let mut events = [0; 4096];
+    loop {
+        if inotify.read(&mut events)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // `touch` reports several events, the first one to remove the stamp handles them all
+        if fs::remove_file(&stamp).is_ok() {
+            reapply();
+        }
+    }
+}
+
+/// Apply the compatibility shims picked by `[setup]`, returning how many changes were made
+pub fn apply_shims() -> usize {
+    let setup = get_application_context().local_config.setup;
+    let changes = compat::apply(Path::new(ARCH_FS_ROOT), compat::enabled(&setup));
+    if compat::schemas_changed(&changes) {
+        // Only the compiled schemas are read, and the package providing the tool may not be installed yet
+        let command = format!(
+            "command -v glib-compile-schemas >/dev/null && glib-compile-schemas {}",
+            SCHEMAS_DIR
+        );
+        if let Err(e) = ArchProcess::exec(&command).and_then(ArchProcess::wait) {
+            log::warn!("Failed to compile the GSettings schemas: {}", e);
+        }
+    }
+    changes.len()
+}
+
+fn reapply() {
+    let changes = apply_shims() + sanitize(Path::new(ARCH_FS_ROOT), RULES).len();
+    if changes > 0 {
+        panel_log(
+            format!("Packages changed, applied {} compatibility fixes", changes),
+            false,
+        );
+    }
+}
+
+/// Stop the pacman processes of the Arch FS, or let them go on
+pub fn pause_package_manager(paused: bool) {
+    let signal = if paused { libc::SIGSTOP } else { libc::SIGCONT };
+    for pid in pacman_processes() {
+        unsafe { libc::kill(pid, signal) };
+    }
+}
+
+/// Whether pacman runs in the Arch FS, e.g. to install or upgrade packages
+pub fn package_manager_running() -> bool {
+    !pacman_processes().is_empty()
+}
+
+/// The pacman processes of the Arch FS. Proot keeps the command line of the programs it runs, while their executable
+/// is its loader.
+fn pacman_processes() -> Vec<libc::pid_t> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<libc::pid_t>().ok()?;
+            let cmdline = fs::read(entry.path().join("cmdline")).ok()?;
+            let program = cmdline.split(|&byte| byte == 0).next()?;
+            (Path::new(std::ffi::OsStr::from_bytes(program)).file_name()? == "pacman")
+                .then_some(pid)
+        })
+        .collect()
+}
@@ -0,0 +1,137 @@
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{
+        config::PriorityConfig,
+        priority::{plan, Tier},
+    },
+};
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often the process trees are read again, to catch the processes spawned meanwhile, e.g. by `make -j`
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// See `man ioprio_set`
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_BE: libc::c_int = 2;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+/// The levels within the best-effort I/O class, from 0 (most I/O) to 7, 4 being the default of every process
+const FOCUSED_IO_LEVEL: libc::c_int = 0;
+const BACKGROUND_IO_LEVEL: libc::c_int = 7;
+const DEFAULT_IO_LEVEL: libc::c_int = 4;
+
+/// The processes of the window clients, by pid
+#[derive(Default)]
+struct Windows {
+    focused: Vec<u32>,
+    others: Vec<u32>,
+}
+
+static PRIORITIZER: OnceLock<Option<Sender<Windows>>> = OnceLock::new();
+
+/// Tell which processes back the focused window and which back the others, so that the whole process trees are
+/// reniced accordingly, and kept so as they spawn children. Nothing happens with `[priority] boost_focused` off.
+pub fn prioritize(focused: Vec<u32>, others: Vec<u32>) {
+    let prioritizer = PRIORITIZER.get_or_init(|| {
+        let config = get_application_context().local_config.priority;
+        if !config.boost_focused {
+            return None;
+        }
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || run(receiver, config));
+        Some(sender)
+    });
+    if let Some(sender) = prioritizer {
+        sender.send(Windows { focused, others }).unwrap_or(());
+    }
+}
+
+fn run(receiver: Receiver<Windows>, config: PriorityConfig) {
+    let mut windows = Windows::default();
+    let mut applied = HashMap::new();
+    loop {
+        match receiver.recv_timeout(REFRESH_INTERVAL) {
+            Ok(update) => windows = update,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        // Only the latest focus matters
+        while let Ok(update) = receiver.try_recv() {
+            windows = update;
+        }
+
+        let tiers = plan(&parents(), &windows.focused, &windows.others);
+        for (&pid, &tier) in &tiers {
+            if applied.get(&pid) != Some(&tier) {
+                match tier {
+                    Tier::Focused => set_priority(pid, config.focused_nice, FOCUSED_IO_LEVEL),
+                    Tier::Background => {
+                        set_priority(pid, config.background_nice, BACKGROUND_IO_LEVEL)
+                    }
+                }
+            }
+        }
+        // The processes whose window went away get back to normal
+        for &pid in applied.keys().filter(|pid| !tiers.contains_key(pid)) {
+            set_priority(pid, 0, DEFAULT_IO_LEVEL);
+        }
+        applied = tiers;
+    }
+}
+
+/// The parent of every process the app can see, which on Android are only its own
+pub(super) fn parents() -> HashMap<u32, u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let stat = fs::read_to_string(entry.path().join("stat")).ok()?;
+            // `pid (comm) state ppid ...`, where the command name may contain spaces and parentheses
+            let ppid = stat.rsplit_once(')')?.1.split_whitespace().nth(1)?;
+            Some((pid, ppid.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Set the CPU and I/O priorities of every thread of a process, as Linux applies both per thread
+fn set_priority(pid: u32, nice: i32, io_level: libc::c_int) {
+    let Ok(tasks) = fs::read_dir(format!("/proc/{}/task", pid)) else {
+        // The process already exited
+        return;
+    };
+    let ioprio = (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | io_level;
+    for tid in tasks
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<libc::id_t>().ok())
+    {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } == -1 {
+            log::debug!(
+                "Failed to set the nice value of {} to {}: {}",
+                tid,
+                nice,
+                std::io::Error::last_os_error()
+            );
+        }
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, ioprio) } == -1 {
+            log::debug!(
+                "Failed to set the I/O priority of {} to {}: {}",
+                tid,
+                io_level,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    log::debug!("Set the priority of {} to nice {}", pid, nice);
+}
@@ -1,21 +1,114 @@
 use crate::android::utils::application_context::get_application_context;
-use crate::core::{config, logging::PolarBearExpectation};
+use crate::android::utils::storage::shared_storage_bind;
+use crate::core::{config, error::PolarBearError};
+use serde_json::{json, Value};
+use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::io::Read;
-use std::process::{Child, Command, Stdio};
+use std::io::Write;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{mpsc, Mutex};
+use std::thread;
 
 pub type Log = Box<dyn Fn(String)>;
 
+/// Held while installing, as pacman cannot run twice at once
+pub(super) static INSTALL: Mutex<()> = Mutex::new(());
+
+/// Which output of a process a line comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+fn forward_lines(
+    reader: impl Read + Send + 'static,
+    stream: OutputStream,
+    sender: mpsc::Sender<(String, OutputStream)>,
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            if sender.send((line, stream)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// What the interactive programs expect to draw on
+const PTY_TERM: &str = "xterm-256color";
+
+/// A process running in a pseudo-terminal, as `ArchProcess::spawn_pty` starts it
+pub struct PtyProcess {
+    pub child: Child,
+    /// The terminal side of the pseudo-terminal, where the output is read and the input written
+    master: File,
+}
+
+fn set_pty_size(master: RawFd, rows: u16, cols: u16) -> std::io::Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl PtyProcess {
+    /// Type into the terminal, e.g. a password or `\x03` for Ctrl+C
+    pub fn write(&self, input: &[u8]) -> std::io::Result<()> {
+        (&self.master).write_all(input)
+    }
+
+    /// The output, as it would show up on a terminal, escape sequences included.
+    /// Reading fails once the process and its children closed the terminal.
+    pub fn reader(&self) -> std::io::Result<File> {
+        self.master.try_clone()
+    }
+
+    /// Tell the process that the terminal has this many rows and columns, sending it `SIGWINCH`
+    pub fn resize(&self, rows: u16, cols: u16) -> std::io::Result<()> {
+        set_pty_size(self.master.as_raw_fd(), rows, cols)
+    }
+
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.child.wait()
+    }
+}
+
 pub struct ArchProcess {
     pub command: String,
     pub user: String,
     pub process: Option<Child>,
-    pub panic_on_error: bool,
 }
 
 impl ArchProcess {
-    pub fn spawn(mut self) -> Self {
+    pub fn spawn(mut self) -> Result<Self, PolarBearError> {
+        let child = self
+            .command()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|source| PolarBearError::Spawn {
+                command: self.command.clone(),
+                source,
+            })?;
+
+        self.process.replace(child);
+        Ok(self)
+    }
+
+    /// The command running `self.command` inside Proot, as `self.user`. Extra arguments are
+    /// available to the shell command as `"$@"`.
+    pub fn command(&self) -> Command {
         // Run the command inside Proot
         let context = get_application_context();
 
@@ -52,9 +145,30 @@ impl ArchProcess {
             .arg(format!("--bind={}/proc/.sysctl_entry_cap_last_cap:/proc/sys/kernel/cap_last_cap", config::ARCH_FS_ROOT))
             .arg(format!("--bind={}/proc/.sysctl_inotify_max_user_watches:/proc/sys/fs/inotify/max_user_watches", config::ARCH_FS_ROOT))
             .arg(format!("--bind={}/sys/.empty:/sys/fs/selinux", config::ARCH_FS_ROOT))
+            .args(shared_storage_bind())
+            .args(context.local_config.mount_args())
             .arg("/usr/bin/env")
             .arg("-i");
 
+        let max_open_files = context.local_config.limits.max_open_files;
+        if max_open_files > 0 {
+            unsafe {
+                process.pre_exec(move || {
+                    // The hard limit can only be lowered
+                    let mut limit = std::mem::zeroed::<libc::rlimit>();
+                    if libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    limit.rlim_max = limit.rlim_max.min(max_open_files as libc::rlim_t);
+                    limit.rlim_cur = limit.rlim_max;
+                    if libc::setrlimit(libc::RLIMIT_NOFILE, &limit) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
         let home = if self.user == "root" {
             "HOME=/root".to_string()
         } else {
@@ -67,7 +181,8 @@ impl ArchProcess {
             .arg("PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin:/usr/local/games:/usr/games:/system/bin:/system/xbin")
             .arg("TMPDIR=/tmp")
             .arg(format!("USER={}", self.user))
-            .arg(format!("LOGNAME={}", self.user));
+            .arg(format!("LOGNAME={}", self.user))
+            .args(context.local_config.env_args());
         if self.user == "root" {
             process.arg("sh");
         } else {
@@ -78,94 +193,218 @@ impl ArchProcess {
                 .arg("--")
                 .arg("sh");
         }
-        let child = process
-            .arg("-c")
-            .arg(&self.command)
-            .stdout(Stdio::piped())
-            .stderr(if self.panic_on_error {
-                Stdio::piped()
-            } else {
-                Stdio::inherit()
-            })
-            .spawn()
-            .pb_expect("Failed to run command");
-
-        self.process.replace(child);
-        self
+        // The shell name fills `$0`, so that extra arguments start at `$1`
+        process.arg("-c").arg(&self.command).arg("sh");
+        process
     }
 
-    pub fn exec(command: &str) -> Self {
-        ArchProcess {
-            command: command.to_string(),
-            user: "root".to_string(),
+    /// Run `self.command` in a pseudo-terminal of `rows` by `cols`, for the interactive programs (e.g. `sudo`
+    /// asking for a password, `nano` or ncurses installers) that need a terminal to work
+    pub fn spawn_pty(&self, rows: u16, cols: u16) -> std::io::Result<PtyProcess> {
+        // Both sides are close-on-exec from the start, so that no process spawned meanwhile by another thread
+        // inherits them. The child still gets the slave as its stdio, which `dup2` leaves open.
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+        if master == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let master = unsafe { OwnedFd::from_raw_fd(master) };
+        if unsafe { libc::grantpt(master.as_raw_fd()) } == -1
+            || unsafe { libc::unlockpt(master.as_raw_fd()) } == -1
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut name = [0 as libc::c_char; 64];
+        let error = unsafe { libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+        if error != 0 {
+            return Err(std::io::Error::from_raw_os_error(error));
+        }
+        let slave = unsafe {
+            libc::open(
+                name.as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,
+            )
+        };
+        if slave == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let slave = unsafe { OwnedFd::from_raw_fd(slave) };
+        set_pty_size(master.as_raw_fd(), rows, cols)?;
+
+        // `env -i` clears the environment, so the terminal type goes along with the command
+        let mut command = ArchProcess {
+            command: format!("export TERM={}; {}", PTY_TERM, self.command),
+            user: self.user.clone(),
             process: None,
-            panic_on_error: false,
         }
-        .spawn()
+        .command();
+        command
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        unsafe {
+            command.pre_exec(|| {
+                // A session of its own, which the pseudo-terminal controls, so that Ctrl+C and resizes reach it
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(PtyProcess {
+            child: command.spawn()?,
+            master: File::from(master),
+        })
+    }
+
+    /// What `command()` runs, taken apart for the diagnostics page: the Proot options and bind mounts,
+    /// then the environment the shell command starts with
+    pub fn describe(&self) -> Value {
+        let command = self.command();
+        let args = command
+            .get_args()
+            .map(|it| it.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        let (proot_args, rest) = args
+            .iter()
+            .position(|it| it == "/usr/bin/env")
+            .map_or((&args[..], &[][..]), |at| args.split_at(at));
+        let (binds, options): (Vec<_>, Vec<_>) =
+            proot_args.iter().partition(|it| it.starts_with("--bind="));
+        let binds = binds
+            .iter()
+            .map(|it| it.trim_start_matches("--bind="))
+            .collect::<Vec<_>>();
+        // `env -i` clears the Android environment, and sets what follows
+        let environment = rest
+            .iter()
+            .skip(2)
+            .take_while(|it| it.contains('='))
+            .cloned()
+            .collect::<Vec<_>>();
+        let proot_environment = command
+            .get_envs()
+            .filter_map(|(key, value)| {
+                Some(format!(
+                    "{}={}",
+                    key.to_string_lossy(),
+                    value?.to_string_lossy()
+                ))
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "user": self.user,
+            "proot": command.get_program().to_string_lossy(),
+            "proot options": options.iter().map(|it| it.as_str()).collect::<Vec<_>>().join(" "),
+            "proot environment": proot_environment.join("\n"),
+            "bind mounts": binds.join("\n"),
+            "environment": environment.join("\n"),
+            "command": self.command,
+        })
+    }
+
+    pub fn exec(command: &str) -> Result<Self, PolarBearError> {
+        Self::exec_as(command, "root")
     }
 
-    pub fn exec_as(command: &str, user: &str) -> Self {
+    pub fn exec_as(command: &str, user: &str) -> Result<Self, PolarBearError> {
         ArchProcess {
             command: command.to_string(),
             user: user.to_string(),
             process: None,
-            panic_on_error: false,
         }
         .spawn()
     }
 
+    /// Pass the stdout lines to `log`, and log the stderr ones as warnings
     pub fn with_log(self, mut log: impl FnMut(String)) {
-        if let Some(child) = self.process {
-            let reader = BufReader::new(child.stdout.unwrap());
-            for line in reader.lines() {
-                let line = line.unwrap();
-                log(line);
-            }
-        }
+        let _ = self.with_output(|line, stream| match stream {
+            OutputStream::Stdout => log(line),
+            OutputStream::Stderr => log::warn!("{}", line),
+        });
     }
 
-    pub fn wait_with_output(self) -> std::io::Result<std::process::Output> {
-        if let Some(child) = self.process {
-            child.wait_with_output()
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Process not spawned",
-            ))
+    /// Pass every line the process outputs to `output` as it comes, telling its progress from its errors, then wait
+    /// for the process to exit
+    pub fn with_output(
+        self,
+        mut output: impl FnMut(String, OutputStream),
+    ) -> Result<ExitStatus, PolarBearError> {
+        let Some(mut child) = self.process else {
+            return Err(PolarBearError::NotSpawned(self.command));
+        };
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward_lines(stdout, OutputStream::Stdout, sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_lines(stderr, OutputStream::Stderr, sender);
         }
+        // Both streams are over once the senders are dropped
+        for (line, stream) in receiver {
+            output(line, stream);
+        }
+        child.wait().map_err(|source| PolarBearError::Io {
+            context: format!("Failed to wait for `{}`", self.command),
+            source,
+        })
     }
 
-    pub fn wait(self) -> std::io::Result<std::process::ExitStatus> {
-        if let Some(mut child) = self.process {
-            child.wait()
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Process not spawned",
-            ))
-        }
+    pub fn wait_with_output(self) -> Result<std::process::Output, PolarBearError> {
+        let Some(child) = self.process else {
+            return Err(PolarBearError::NotSpawned(self.command));
+        };
+        child
+            .wait_with_output()
+            .map_err(|source| PolarBearError::Io {
+                context: format!("Failed to wait for `{}`", self.command),
+                source,
+            })
     }
 
-    pub fn exec_with_panic_on_error(command: &str) {
-        if let Some(child) = (ArchProcess {
-            command: command.to_string(),
-            user: "root".to_string(),
-            process: None,
-            panic_on_error: true,
+    /// Wait for the process to exit, its output being discarded meanwhile
+    pub fn wait(self) -> Result<ExitStatus, PolarBearError> {
+        let Some(mut child) = self.process else {
+            return Err(PolarBearError::NotSpawned(self.command));
+        };
+        // Reading the output keeps the process from blocking on a full pipe, without holding it in memory
+        let drain = |pipe: Option<Box<dyn Read + Send>>| {
+            pipe.map(|mut pipe| thread::spawn(move || io::copy(&mut pipe, &mut io::sink())))
+        };
+        let drains = [
+            drain(child.stdout.take().map(|it| Box::new(it) as _)),
+            drain(child.stderr.take().map(|it| Box::new(it) as _)),
+        ];
+        let status = child.wait();
+        for drain in drains.into_iter().flatten() {
+            let _ = drain.join();
         }
-        .spawn()
-        .process)
-        {
-            // What is the best way to get full stderr as a string?
-            if let Some(stderr) = child.stderr {
-                let mut error_output = String::new();
-                let mut reader = BufReader::new(stderr);
-                reader.read_to_string(&mut error_output).unwrap();
-                if error_output.contains("fatal error: see `libproot.so --help`") {
-                    panic!("PRoot error: {}", error_output);
-                }
-            }
+        status.map_err(|source| PolarBearError::Io {
+            context: format!("Failed to wait for `{}`", self.command),
+            source,
+        })
+    }
+
+    /// Install the packages that are missing, for the features that are not worth installing up front.
+    /// Returns `true` once they are all installed.
+    pub fn install_packages(packages: &str) -> bool {
+        let _guard = INSTALL.lock().unwrap_or_else(|it| it.into_inner());
+        let command = format!(
+            "pacman -Q {0} >/dev/null 2>&1 || stdbuf -oL pacman -S --needed --noconfirm --noprogressbar {0} 2>&1",
+            packages
+        );
+        ArchProcess::exec(&command)
+            .and_then(ArchProcess::wait_with_output)
+            .is_ok_and(|it| it.status.success())
+    }
+
+    /// Run `command` as root, and fail if Proot itself could not run it, e.g. as the Arch FS is broken
+    pub fn exec_checked(command: &str) -> Result<(), PolarBearError> {
+        let output = ArchProcess::exec(command)?.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("fatal error: see `libproot.so --help`") {
+            return Err(PolarBearError::Proot(stderr.trim().to_string()));
         }
+        Ok(())
     }
 }
 
@@ -176,14 +415,14 @@ mod tests {
 
     #[test]
     fn should_echoable() {
-        let process = ArchProcess::exec("echo hello");
+        let process = ArchProcess::exec("echo hello").expect("Failed to run command");
         let output = process.wait_with_output().expect("Failed to read output");
         assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
     }
 
     #[test]
     fn should_output_uname() {
-        let process = ArchProcess::exec("uname -a");
+        let process = ArchProcess::exec("uname -a").expect("Failed to run command");
         let output = process.wait_with_output().expect("Failed to read output");
         log::info!("Output: {}", String::from_utf8_lossy(&output.stdout));
         assert!(String::from_utf8_lossy(&output.stdout)
@@ -198,25 +437,54 @@ mod tests {
             command: "echo hello".to_string(),
             user: "root".to_string(),
             process: None,
-            panic_on_error: true,
         }
         .spawn()
+        .expect("Failed to run command")
         .with_log(|log| {
             logs.push_back(log.to_string());
         });
         assert!(logs.iter().any(|log| log.contains("hello")));
     }
 
+    #[test]
+    fn should_tell_stderr_from_stdout() {
+        let mut lines = vec![];
+        let status = ArchProcess::exec("echo progress; echo error >&2; exit 3")
+            .expect("Failed to run command")
+            .with_output(|line, stream| lines.push((line, stream)))
+            .expect("Failed to read output");
+        assert_eq!(status.code(), Some(3));
+        assert!(lines.contains(&("progress".to_string(), OutputStream::Stdout)));
+        assert!(lines.contains(&("error".to_string(), OutputStream::Stderr)));
+    }
+
+    #[test]
+    fn should_run_in_a_terminal() {
+        let mut process = ArchProcess {
+            command: "stty size; tty".to_string(),
+            user: "root".to_string(),
+            process: None,
+        }
+        .spawn_pty(24, 80)
+        .expect("Failed to open a pseudo-terminal");
+        let mut output = String::new();
+        // Reading ends with an error once the terminal is closed
+        let _ = process.reader().unwrap().read_to_string(&mut output);
+        assert!(process.wait().unwrap().success());
+        assert!(output.contains("24 80"));
+        assert!(output.contains("/dev/pts/"));
+    }
+
     #[test]
     fn should_exit_with_success_code() {
-        let process = ArchProcess::exec("pacman -Ss chrome");
+        let process = ArchProcess::exec("pacman -Ss chrome").expect("Failed to run command");
         let status = process.wait().expect("Failed to wait for process");
         assert_eq!(status.success(), true);
     }
 
     #[test]
     fn should_exit_with_fail_code() {
-        let process = ArchProcess::exec("pacman -Qg plasmma");
+        let process = ArchProcess::exec("pacman -Qg plasmma").expect("Failed to run command");
         let status = process.wait().expect("Failed to wait for process");
         assert_ne!(status.success(), true);
     }
@@ -1,30 +1,46 @@
-use super::process::ArchProcess;
+use super::{
+    packages::apply_shims,
+    process::{ArchProcess, OutputStream},
+    users::{create, grant_sudo, sudo_granted},
+};
 use crate::{
     android::{
         app::build::PolarBearBackend,
         backend::{
-            wayland::{Compositor, WaylandBackend},
+            wayland::{
+                Compositor, ExtraKeys, FocusArbiter, GestureRecognizer, InjectedInputs,
+                MotionCoalescer, PresentationFeedbacks, SoftKeyboard, Splash, Tablet, Wallpaper,
+                WaylandBackend, Zoom,
+            },
             webview::WebviewBackend,
         },
-        utils::application_context::get_application_context,
+        utils::{
+            application_context::{get_application_context, update_local_config},
+            choreographer::Choreographer,
+            ndk::{get_intent_data, get_intent_string_extra, open_content_uri, run_in_jvm},
+        },
     },
     core::{
-        config::{CommandConfig, ARCH_FS_ARCHIVE, ARCH_FS_ROOT},
-        logging::PolarBearExpectation,
+        config::{save_config, CommandConfig, ARCH_FS_ROOT},
+        error::{Context, PolarBearError},
+        key_repeat::KeyRepeat,
+        power::ScreenPower,
+        sanitizer::{sanitize, RULES},
+        startup,
     },
 };
-use pathdiff::diff_paths;
-use smithay::utils::Clock;
+use smithay::reexports::rustix::fs::statvfs;
 use std::{
     fs::{self, File},
-    io::{Read, Write},
-    os::unix::fs::{symlink, PermissionsExt},
+    io::{self, Cursor, Read, Write},
+    os::unix::fs::PermissionsExt,
     path::Path,
     sync::{
-        mpsc::{self, Sender},
+        mpsc::{self, Receiver, Sender},
         Arc, Mutex,
     },
     thread::{self, JoinHandle},
+    time::Instant,
 };
 use tar::Archive;
 use winit::platform::android::activity::AndroidApp;
@@ -34,11 +50,15 @@ use xz2::read::XzDecoder;
 pub enum SetupMessage {
     Progress(String),
     Error(String),
+    /// Ask the user which account to use, the setup page answers through `SetupOptions::usernames`
+    AskUsername,
 }
 
 pub struct SetupOptions {
     pub android_app: AndroidApp,
     pub mpsc_sender: Sender<SetupMessage>,
+    /// The usernames typed on the setup page
+    pub usernames: Arc<Mutex<Receiver<String>>>,
 }
 
 /// Setup is a process that should be done **only once** when the user installed the app.
@@ -49,7 +69,156 @@ type SetupStage = Box<dyn Fn(&SetupOptions) -> StageOutput + Send>;
 /// Each stage should indicate whether the associated task is done previously or not.
 /// Thus, it should return a finished status if the task is done, so that the setup process can move on to the next stage.
 /// Otherwise, it should return a `JoinHandle`, so that the setup process can wait for the task to finish, but not block the main thread so that the setup progress can be reported to the user.
-type StageOutput = Option<JoinHandle<()>>;
+/// The error a task ends with is shown on the setup page, and the stages after it are not run.
+type StageOutput = Option<JoinHandle<Result<(), PolarBearError>>>;
+
+/// Where the Arch FS is installed from: the archive a file manager opened the app with, the `rootfs` intent extra,
+/// or `[command] archive`
+fn archive_source(android_app: &AndroidApp) -> String {
+    let mut source = None;
+    run_in_jvm(
+        |env, app| {
+            let from_intent = get_intent_data(env, app)
+                .transpose()
+                .or_else(|| get_intent_string_extra(env, app, "rootfs").transpose());
+            match from_intent {
+                Some(Ok(it)) => source = Some(it),
+                Some(Err(e)) => {
+                    let _ = env.exception_clear();
+                    log::warn!("Failed to read the rootfs archive from the intent: {:?}", e);
+                }
+                None => {}
+            }
+        },
+        android_app.clone(),
+    );
+    source.unwrap_or_else(|| get_application_context().local_config.command.archive)
+}
+
+/// Open an archive on the phone, given as a path, a `file://` URI or a `content://` URI
+fn open_local_archive(android_app: &AndroidApp, source: &str) -> Result<File, String> {
+    if source.starts_with("content://") {
+        let mut result = Err("the content resolver was not reached".to_string());
+        run_in_jvm(
+            |env, app| {
+                result = open_content_uri(env, app, source)
+                    .map(File::from)
+                    .map_err(|e| {
+                        let _ = env.exception_clear();
+                        format!("{:?}", e)
+                    })
+            },
+            android_app.clone(),
+        );
+        result
+    } else {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        File::open(path).map_err(|e| e.to_string())
+    }
+}
+
+/// About what the Arch Linux FS archive takes to download
+const ARCHIVE_SIZE: u64 = 1536 * 1024 * 1024;
+
+/// About what the Arch Linux FS takes once extracted
+const EXTRACTED_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Fail before the download or the extraction runs out of space halfway, which would leave a broken Arch FS
+fn check_disk_space(data_dir: &Path, required: u64) -> Result<(), PolarBearError> {
+    let available = match statvfs(data_dir) {
+        Ok(stats) => stats.f_bavail * stats.f_frsize,
+        Err(e) => {
+            log::warn!(
+                "Failed to query the free space of {}: {}",
+                data_dir.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+    log::info!(
+        "Setting up needs {} bytes, {} bytes are available",
+        required,
+        available
+    );
+    if available < required {
+        return Err(PolarBearError::NotEnoughStorage {
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// The zstd frame magic number, which the Arch FS backups start with
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decompress an archive: either an Arch Linux FS release (xz), or a backup of the Arch FS (zstd)
+fn decompress(mut file: File, mpsc_sender: &Sender<SetupMessage>) -> io::Result<Box<dyn Read>> {
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    let file = Cursor::new(magic).chain(file);
+    if magic == ZSTD_MAGIC {
+        mpsc_sender
+            .send(SetupMessage::Progress(
+                "Restoring the Arch FS from a backup...".to_string(),
+            ))
+            .unwrap_or(());
+        Ok(Box::new(zstd::Decoder::new(file)?))
+    } else {
+        Ok(Box::new(XzDecoder::new(file)))
+    }
+}
+
+fn download_arch_fs(
+    url: &str,
+    temp_file: &Path,
+    mpsc_sender: &Sender<SetupMessage>,
+) -> Result<(), PolarBearError> {
+    mpsc_sender
+        .send(SetupMessage::Progress(
+            "Downloading Arch Linux FS...".to_string(),
+        ))
+        .unwrap_or(());
+
+    let response = reqwest::blocking::get(url)?;
+
+    let total_size = response.content_length().unwrap_or(0);
+    let mut file =
+        File::create(temp_file).context("Failed to create temp file for Arch Linux FS")?;
+
+    let mut downloaded = 0u64;
+    let mut buffer = [0u8; 8192];
+    let mut reader = response;
+    let mut last_percent = 0;
+
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .context("Failed to read the Arch Linux FS download")?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buffer[..n])
+            .context("Failed to write the Arch Linux FS download")?;
+        downloaded += n as u64;
+        if total_size > 0 {
+            let percent = (downloaded * 100 / total_size).min(100) as u8;
+            if percent != last_percent {
+                let downloaded_mb = downloaded as f64 / 1024.0 / 1024.0;
+                let total_mb = total_size as f64 / 1024.0 / 1024.0;
+                mpsc_sender
+                    .send(SetupMessage::Progress(format!(
+                        "Downloading Arch Linux FS... {}% ({:.2} MB / {:.2} MB)",
+                        percent, downloaded_mb, total_mb
+                    )))
+                    .unwrap_or(());
+                last_percent = percent;
+            }
+        }
+    }
+    Ok(())
+}
 
 fn setup_arch_fs(options: &SetupOptions) -> StageOutput {
     let context = get_application_context();
@@ -57,79 +226,64 @@ fn setup_arch_fs(options: &SetupOptions) -> StageOutput {
     let fs_root = Path::new(ARCH_FS_ROOT);
     let extracted_dir = context.data_dir.join("archlinux-aarch64");
     let mpsc_sender = options.mpsc_sender.clone();
+    let android_app = options.android_app.clone();
 
     // Only run if the fs_root is missing or empty
     // TODO: Setup integration test to make sure on clean install, the fs_root is either non existent or empty
     let need_setup = fs_root.read_dir().map_or(true, |mut d| d.next().is_none());
     if need_setup {
         return Some(thread::spawn(move || {
-            // Download if the archive doesn't exist
+            let source = archive_source(&android_app);
+            let download = source.starts_with("http://") || source.starts_with("https://");
+            // The download is only deleted once extracted, and may already be there
+            let to_download = if download {
+                ARCHIVE_SIZE.saturating_sub(fs::metadata(&temp_file).map_or(0, |it| it.len()))
+            } else {
+                0
+            };
+            check_disk_space(&context.data_dir, EXTRACTED_SIZE + to_download)?;
             loop {
-                if !temp_file.exists() {
-                    mpsc_sender
-                        .send(SetupMessage::Progress(
-                            "Downloading Arch Linux FS...".to_string(),
-                        ))
-                        .pb_expect("Failed to send log message");
-
-                    let response = reqwest::blocking::get(ARCH_FS_ARCHIVE)
-                        .pb_expect("Failed to download Arch Linux FS");
-
-                    let total_size = response.content_length().unwrap_or(0);
-                    let mut file = File::create(&temp_file)
-                        .pb_expect("Failed to create temp file for Arch Linux FS");
-
-                    let mut downloaded = 0u64;
-                    let mut buffer = [0u8; 8192];
-                    let mut reader = response;
-                    let mut last_percent = 0;
-
-                    loop {
-                        let n = reader
-                            .read(&mut buffer)
-                            .pb_expect("Failed to read from response");
-                        if n == 0 {
-                            break;
-                        }
-                        file.write_all(&buffer[..n])
-                            .pb_expect("Failed to write to file");
-                        downloaded += n as u64;
-                        if total_size > 0 {
-                            let percent = (downloaded * 100 / total_size).min(100) as u8;
-                            if percent != last_percent {
-                                let downloaded_mb = downloaded as f64 / 1024.0 / 1024.0;
-                                let total_mb = total_size as f64 / 1024.0 / 1024.0;
-                                mpsc_sender
-                                    .send(SetupMessage::Progress(format!(
-                                        "Downloading Arch Linux FS... {}% ({:.2} MB / {:.2} MB)",
-                                        percent, downloaded_mb, total_mb
-                                    )))
-                                    .unwrap_or(());
-                                last_percent = percent;
-                            }
-                        }
+                let tar_file = if download {
+                    // Download if the archive doesn't exist
+                    if !temp_file.exists() {
+                        download_arch_fs(&source, &temp_file, &mpsc_sender)?;
                     }
-                }
+                    File::open(&temp_file)
+                        .context("Failed to open downloaded Arch Linux FS file")?
+                } else {
+                    log::info!("Installing the Arch Linux FS from {}", source);
+                    open_local_archive(&android_app, &source).map_err(|reason| {
+                        PolarBearError::Archive {
+                            archive: source.clone(),
+                            reason,
+                        }
+                    })?
+                };
 
                 mpsc_sender
                     .send(SetupMessage::Progress(
                         "Extracting Arch Linux FS...".to_string(),
                     ))
-                    .pb_expect("Failed to send log message");
+                    .unwrap_or(());
 
                 // Ensure the extracted directory is clean
                 let _ = fs::remove_dir_all(&extracted_dir);
 
                 // Extract tar file directly to the final destination
-                let tar_file = File::open(&temp_file)
-                    .pb_expect("Failed to open downloaded Arch Linux FS file");
-                let tar = XzDecoder::new(tar_file);
-                let mut archive = Archive::new(tar);
+                let extracted = decompress(tar_file, &mpsc_sender)
+                    .and_then(|tar| Archive::new(tar).unpack(context.data_dir.clone()));
 
                 // Try to extract, if it fails, remove temp file and restart download
-                if let Err(e) = archive.unpack(context.data_dir.clone()) {
+                if let Err(e) = extracted {
                     // Clean up the failed extraction
                     let _ = fs::remove_dir_all(&extracted_dir);
+                    if !download {
+                        // Downloading again would not fix a local archive
+                        return Err(PolarBearError::Archive {
+                            archive: source,
+                            reason: e.to_string(),
+                        });
+                    }
                     let _ = fs::remove_file(&temp_file);
 
                     mpsc_sender
@@ -149,10 +303,13 @@ fn setup_arch_fs(options: &SetupOptions) -> StageOutput {
 
             // Move the extracted files to the final destination
             fs::rename(&extracted_dir, fs_root)
-                .pb_expect("Failed to rename extracted files to final destination");
+                .context("Failed to rename extracted files to final destination")?;
 
-            // Clean up the temporary file
-            fs::remove_file(&temp_file).pb_expect("Failed to remove temporary file");
+            // Clean up the temporary file, local archives are left alone
+            if download {
+                fs::remove_file(&temp_file).context("Failed to remove temporary file")?;
+            }
+            Ok(())
         }));
     }
     None
@@ -168,7 +325,7 @@ fn simulate_linux_sysdata_stage(options: &SetupOptions) -> StageOutput {
                 .send(SetupMessage::Progress(
                     "Simulating Linux system data...".to_string(),
                 ))
-                .pb_expect(&format!("Failed to send log message"));
+                .unwrap_or(());
 
             // Create necessary directories - don't fail if they already exist
             let _ = fs::create_dir_all(fs_root.join("proc"));
@@ -200,9 +357,10 @@ fn simulate_linux_sysdata_stage(options: &SetupOptions) -> StageOutput {
                 ];
 
             for (path, content) in proc_files {
-                let _ = fs::write(fs_root.join(path), content)
-                    .pb_expect(&format!("Permission denied while writing to {}", path));
+                fs::write(fs_root.join(path), content)
+                    .context(format!("Failed to write {}", path))?;
             }
+            Ok(())
         }));
     }
     None
@@ -212,6 +370,7 @@ fn install_dependencies(options: &SetupOptions) -> StageOutput {
     let SetupOptions {
         mpsc_sender,
         android_app: _,
+        usernames: _,
     } = options;
 
     let context = get_application_context();
@@ -219,16 +378,17 @@ fn install_dependencies(options: &SetupOptions) -> StageOutput {
         check,
         install,
         launch: _,
+        archive: _,
     } = context.local_config.command;
 
     let installed = move || {
         ArchProcess::exec(&check)
-            .wait()
-            .pb_expect("Failed to check whether the installation target is installed")
-            .success()
+            .and_then(ArchProcess::wait)
+            .map(|it| it.success())
     };
 
-    if installed() {
+    // A check that cannot run fails again in the stage, which tells why
+    if installed().unwrap_or(false) {
         return None;
     }
 
@@ -236,117 +396,110 @@ fn install_dependencies(options: &SetupOptions) -> StageOutput {
     return Some(thread::spawn(move || {
         // Install dependencies until `check` succeed
         loop {
-            ArchProcess::exec_with_panic_on_error("rm -f /var/lib/pacman/db.lck");
-            ArchProcess::exec(&install).with_log(|it| {
-                mpsc_sender
-                    .send(SetupMessage::Progress(it))
-                    .pb_expect("Failed to send log message");
+            ArchProcess::exec_checked("rm -f /var/lib/pacman/db.lck")?;
+            let _ = ArchProcess::exec(&install)?.with_output(|it, stream| {
+                let message = match stream {
+                    OutputStream::Stdout => SetupMessage::Progress(it),
+                    OutputStream::Stderr => SetupMessage::Error(it),
+                };
+                mpsc_sender.send(message).unwrap_or(());
             });
-            if installed() {
+            if installed()? {
                 break;
             }
         }
+        Ok(())
     }));
 }
 
-fn setup_firefox_config(_: &SetupOptions) -> StageOutput {
-    // Create the Firefox root directory if it doesn't exist
-    let firefox_root = format!("{}/usr/lib/firefox", ARCH_FS_ROOT);
-    let _ = fs::create_dir_all(&firefox_root).pb_expect("Failed to create Firefox root directory");
-
-    // Create the defaults/pref directory
-    let pref_dir = format!("{}/defaults/pref", firefox_root);
-    let _ = fs::create_dir_all(&pref_dir).pb_expect("Failed to create Firefox pref directory");
-
-    // Create autoconfig.js in defaults/pref
-    let autoconfig_js = r#"pref("general.config.filename", "localdesktop.cfg");
-pref("general.config.obscure_value", 0);
-"#;
-
-    let _ = fs::write(format!("{}/autoconfig.js", pref_dir), autoconfig_js)
-        .pb_expect("Failed to write Firefox autoconfig.js");
-
-    // Create localdesktop.cfg in the Firefox root directory
-    let firefox_cfg = r#"// Auto updated by Local Desktop on each startup, do not edit manually
-defaultPref("media.cubeb.sandbox", false);
-defaultPref("security.sandbox.content.level", 0);
-"#; // It is required that the first line of this file is a comment, even if you have nothing to comment. Docs: https://support.mozilla.org/en-US/kb/customizing-firefox-using-autoconfig
-
-    let _ = fs::write(format!("{}/localdesktop.cfg", firefox_root), firefox_cfg)
-        .pb_expect("Failed to write Firefox configuration");
-
+/// Make the applications that need it work under Proot, and tame the background services, see `compat::enabled`
+fn apply_compatibility_shims(_: &SetupOptions) -> StageOutput {
+    apply_shims();
     None
 }
 
-fn fix_xkb_symlink(options: &SetupOptions) -> StageOutput {
-    let fs_root = Path::new(ARCH_FS_ROOT);
-    let xkb_path = fs_root.join("usr/share/X11/xkb");
-    let mpsc_sender = options.mpsc_sender.clone();
-
-    if let Ok(meta) = fs::symlink_metadata(&xkb_path) {
-        if meta.file_type().is_symlink() {
-            if let Ok(target) = fs::read_link(&xkb_path) {
-                if target.is_absolute() {
-                    log::info!(
-                        "Absolute symlink target detected: {} -> {}. This is a problem because libxkbcommon is loaded in NDK, whose / is not Arch FS root!",
-                        xkb_path.display(),
-                        target.display()
-                    );
-                    // Compute the relative path from /usr/share/X11/xkb to /usr/share/xkeyboard-config-2
-                    // Both are inside the chroot, so strip the fs_root prefix
-                    let xkb_inside = Path::new("/usr/share/X11/xkb");
-                    let target_inside = Path::new("/usr/share/xkeyboard-config-2");
-                    let rel_target = diff_paths(target_inside, xkb_inside.parent().unwrap())
-                        .unwrap_or_else(|| target_inside.to_path_buf());
-                    log::info!(
-                        "Fixing with new relative symlink: {} -> {}",
-                        xkb_path.display(),
-                        rel_target.display()
-                    );
-                    // Remove the old symlink
-                    let _ = fs::remove_file(&xkb_path);
-                    // Create the new relative symlink
-                    if let Err(e) = symlink(&rel_target, &xkb_path) {
-                        mpsc_sender
-                            .send(SetupMessage::Error(format!(
-                                "Failed to create relative symlink for xkb: {}",
-                                e
-                            )))
-                            .unwrap_or(());
-                    }
+/// Have the user choose a username on first setup, rather than running everything as root: the user is created
+/// with a home and sudo rights, and its session is the one launched
+fn create_user(options: &SetupOptions) -> StageOutput {
+    if sudo_granted() {
+        return None;
+    }
+    let sender = options.mpsc_sender.clone();
+    let usernames = options.usernames.clone();
+    Some(thread::spawn(move || {
+        let username = loop {
+            sender.send(SetupMessage::AskUsername).unwrap_or(());
+            let username = usernames
+                .lock()
+                .unwrap()
+                .recv()
+                .map_err(|_| PolarBearError::SetupPageGone)?;
+            if username != "root" {
+                if let Err(e) = create(&username, None) {
+                    sender.send(SetupMessage::Error(e)).unwrap_or(());
+                    continue;
                 }
             }
-        }
-    }
+            break username;
+        };
+        grant_sudo().context("Failed to grant sudo to the users")?;
+        let config = update_local_config(|config| config.user.username = username.clone());
+        save_config(&config);
+        sender
+            .send(SetupMessage::Progress(format!(
+                "The session runs as {}",
+                username
+            )))
+            .unwrap_or(());
+        Ok(())
+    }))
+}
+
+/// Fix what does not work under Proot in the rootfs, see `sanitizer::RULES`
+fn sanitize_rootfs(_options: &SetupOptions) -> StageOutput {
+    sanitize(Path::new(ARCH_FS_ROOT), RULES);
     None
 }
 
 pub fn setup(android_app: AndroidApp) -> PolarBearBackend {
     let (sender, receiver) = mpsc::channel();
+    let (username_sender, usernames) = mpsc::channel();
     let progress = Arc::new(Mutex::new(0));
 
     let options = SetupOptions {
-        android_app,
+        android_app: android_app.clone(),
         mpsc_sender: sender.clone(),
+        usernames: Arc::new(Mutex::new(usernames)),
     };
 
     let stages: Vec<SetupStage> = vec![
         Box::new(setup_arch_fs),                // Step 1. Setup Arch FS (extract)
         Box::new(simulate_linux_sysdata_stage), // Step 2. Simulate Linux system data
         Box::new(install_dependencies),         // Step 3. Install dependencies
-        Box::new(setup_firefox_config),         // Step 4. Setup Firefox config
-        Box::new(fix_xkb_symlink),              // Step 5. Fix xkb symlink (last)
+        Box::new(create_user),                  // Step 4. Create the user
+        Box::new(apply_compatibility_shims),    // Step 5. Apply the compatibility shims
+        Box::new(sanitize_rootfs),              // Step 6. Sanitize the rootfs (last)
     ];
 
-    let handle_stage_error = |e: Box<dyn std::any::Any + Send>, sender: &Sender<SetupMessage>| {
-        let error_msg = if let Some(e) = e.downcast_ref::<String>() {
-            format!("Stage execution failed: {}", e)
-        } else if let Some(e) = e.downcast_ref::<&str>() {
-            format!("Stage execution failed: {}", e)
-        } else {
-            "Stage execution failed: Unknown error".to_string()
+    // Whether the stage succeeded, its error being shown on the setup page otherwise
+    let wait_stage = |handle: JoinHandle<Result<(), PolarBearError>>,
+                      sender: &Sender<SetupMessage>| {
+        let error_msg = match handle.join() {
+            Ok(Ok(())) => return true,
+            Ok(Err(e)) => format!("Stage execution failed: {}", e),
+            Err(e) => {
+                if let Some(e) = e.downcast_ref::<String>() {
+                    format!("Stage execution failed: {}", e)
+                } else if let Some(e) = e.downcast_ref::<&str>() {
+                    format!("Stage execution failed: {}", e)
+                } else {
+                    "Stage execution failed: Unknown error".to_string()
+                }
+            }
         };
+        log::error!("{}", error_msg);
         sender.send(SetupMessage::Error(error_msg)).unwrap_or(());
+        false
     };
 
     let fully_installed = 'outer: loop {
@@ -360,8 +513,7 @@ pub fn setup(android_app: AndroidApp) -> PolarBearBackend {
                     *progress.lock().unwrap() = progress_value;
 
                     // Wait for the current stage to finish
-                    if let Err(e) = handle.join() {
-                        handle_stage_error(e, &sender_clone);
+                    if !wait_stage(handle, &sender_clone) {
                         return;
                     }
 
@@ -370,8 +522,7 @@ pub fn setup(android_app: AndroidApp) -> PolarBearBackend {
                         let progress_value = ((j) as u16 * 100 / stages.len() as u16) as u16;
                         *progress.lock().unwrap() = progress_value;
                         if let Some(next_handle) = next_stage(&options) {
-                            if let Err(e) = next_handle.join() {
-                                handle_stage_error(e, &sender_clone);
+                            if !wait_stage(next_handle, &sender_clone) {
                                 return;
                             }
 
@@ -389,7 +540,7 @@ pub fn setup(android_app: AndroidApp) -> PolarBearBackend {
                         .send(SetupMessage::Progress(
                             "Installation finished, please restart the app".to_string(),
                         ))
-                        .pb_expect("Failed to send installation finished message");
+                        .unwrap_or(());
                 });
 
                 // Setup is still running in the background, but we need to return control
@@ -402,15 +553,64 @@ pub fn setup(android_app: AndroidApp) -> PolarBearBackend {
         break 'outer true;
     };
 
-    if fully_installed {
+    startup::mark("setup checked");
+
+    let compositor = if fully_installed {
+        match Compositor::build(android_app.clone()) {
+            Ok(compositor) => Some(compositor),
+            // The setup page tells what went wrong, rather than the app crashing on every launch
+            Err(e) => {
+                log::error!("{}", e);
+                *progress.lock().unwrap() = 100;
+                sender
+                    .send(SetupMessage::Error(e.to_string()))
+                    .unwrap_or(());
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Some(compositor) = compositor {
+        startup::mark("compositor built");
         PolarBearBackend::Wayland(WaylandBackend {
-            compositor: Compositor::build().pb_expect("Failed to build compositor"),
+            compositor,
             graphic_renderer: None,
-            clock: Clock::new(),
+            damage_tracker: None,
+            focus: FocusArbiter::default(),
+            wallpaper: Wallpaper::new(
+                &get_application_context().local_config.display,
+                &android_app,
+            ),
+            splash: Splash::new(android_app.clone()),
+            choreographer: Choreographer::new(android_app.create_waker()),
+            screenshot_requested: false,
+            screen_power: ScreenPower::new(
+                &get_application_context().local_config.power,
+                Instant::now(),
+            ),
+            screen_kept_on: None,
+            soft_keyboard: SoftKeyboard::default(),
+            extra_keys: ExtraKeys::new(android_app),
+            gestures: GestureRecognizer::new(&get_application_context().local_config.touch),
+            injected_inputs: InjectedInputs::default(),
+            touch_motions: MotionCoalescer::new(
+                get_application_context().local_config.touch.coalesce_motion,
+            ),
+            tablet: Tablet::default(),
+            zoom: Zoom::default(),
+            keyboard_inset: get_application_context()
+                .local_config
+                .display
+                .keyboard_inset(),
+            pointer_captured: false,
             key_counter: 0,
-            scale_factor: 1.0,
+            key_repeat: KeyRepeat::default(),
+            scale_factor: get_application_context().local_config.display.scale(1.0),
+            presentation_feedbacks: PresentationFeedbacks::default(),
         })
     } else {
-        PolarBearBackend::WebView(WebviewBackend::build(receiver, progress))
+        PolarBearBackend::WebView(WebviewBackend::build(receiver, progress, username_sender))
     }
 }
@@ -0,0 +1,194 @@
+use crate::{
+    android::utils::{
+        application_context::get_application_context,
+        ndk::{open_content_uri, run_in_jvm},
+        share::{describe_content_uri, shared_items, SharedItem},
+    },
+    core::{
+        config::ARCH_FS_ROOT,
+        logging::panel_log,
+        screenshot::file_name,
+        share::{shared_file_name, unique_name},
+    },
+};
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// The pipe the session reads the files to open from, inside the Arch FS
+const FIFO: &str = "/tmp/.localdesktop-share";
+
+/// Where the shared files are copied, in the home of the user
+const SHARED_DIR: &str = "Shared";
+
+/// The session may still be starting as the file is shared, e.g. when the app was not running
+const SESSION_TIMEOUT: Duration = Duration::from_secs(120);
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The intents handled already, as the activity sees the same one again when Android recreates it
+static HANDLED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// The home of `username`, as the Arch FS processes see it
+fn home(username: &str) -> String {
+    if username == "root" {
+        "/root".to_string()
+    } else {
+        format!("/home/{}", username)
+    }
+}
+
+/// The shell commands of the session opening the files the app hands over, to prefix the launch command with. The
+/// apps are started by the session, so that they live as long as it does.
+pub fn share_environment() -> String {
+    let fifo = format!("{}{}", ARCH_FS_ROOT, FIFO);
+    if let Err(e) = create_fifo(&fifo) {
+        log::warn!("The session can't open shared files: {}", e);
+        return String::new();
+    }
+    let open_with = get_application_context().local_config.share.open_with;
+    format!(
+        "(while :; do while IFS= read -r file; do DISPLAY=:1 {} \"$file\" >/dev/null 2>&1 & done < {} || sleep 1; done) & ",
+        open_with.trim(),
+        FIFO
+    )
+}
+
+fn create_fifo(path: &str) -> io::Result<()> {
+    if Path::new(path).exists() {
+        return Ok(());
+    }
+    let c_path = CString::new(path).map_err(io::Error::other)?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o666) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))
+}
+
+/// Copy what the intent of the activity shares into the `Shared` folder of the user, and have the session open it
+pub fn open_shared_files(android_app: &AndroidApp) {
+    let mut items = vec![];
+    run_in_jvm(
+        |env, app| match shared_items(env, app) {
+            Ok(it) => items = it,
+            Err(e) => {
+                let _ = env.exception_clear();
+                log::warn!("Failed to read what was shared: {:?}", e);
+            }
+        },
+        android_app.clone(),
+    );
+    {
+        let mut handled = HANDLED.lock().unwrap();
+        let handled = handled.get_or_insert_with(HashSet::new);
+        items.retain(|item| handled.insert(format!("{:?}", item)));
+    }
+    if items.is_empty() {
+        return;
+    }
+    let username = get_application_context().local_config.user.username;
+    let dir = format!("{}/{}", home(&username), SHARED_DIR);
+    let mut paths = vec![];
+    for item in items {
+        match copy_item(android_app, &item, &dir) {
+            Ok(path) => paths.push(path),
+            Err(e) => panel_log(format!("Failed to copy what was shared: {}", e), true),
+        }
+    }
+    if paths.is_empty() {
+        return;
+    }
+    thread::spawn(move || {
+        for path in paths {
+            panel_log(format!("Opening {}", path), false);
+            if let Err(e) = hand_over(&path) {
+                panel_log(format!("Failed to open {}: {}", path, e), true);
+            }
+        }
+    });
+}
+
+/// Copy a shared item into `dir`, returning the path of the copy in the Arch FS
+fn copy_item(android_app: &AndroidApp, item: &SharedItem, dir: &str) -> Result<String, String> {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |it| it.as_secs());
+    let fallback = file_name("Shared", secs, "");
+    let fallback = fallback.trim_end_matches('.');
+    let (name, mut source): (String, Box<dyn io::Read>) = match item {
+        SharedItem::Text(text) => (
+            shared_file_name(None, Some("text/plain"), fallback),
+            Box::new(io::Cursor::new(text.clone().into_bytes())),
+        ),
+        SharedItem::File(uri) if uri.starts_with("content://") => {
+            let mut result = Err("the content resolver was not reached".to_string());
+            run_in_jvm(
+                |env, app| {
+                    result = describe_content_uri(env, app, uri)
+                        .and_then(|(name, mime_type)| {
+                            let file = File::from(open_content_uri(env, app, uri)?);
+                            Ok((
+                                shared_file_name(name.as_deref(), mime_type.as_deref(), fallback),
+                                file,
+                            ))
+                        })
+                        .map_err(|e| {
+                            let _ = env.exception_clear();
+                            format!("{:?}", e)
+                        })
+                },
+                android_app.clone(),
+            );
+            let (name, file) = result?;
+            (name, Box::new(file))
+        }
+        SharedItem::File(uri) => {
+            let path = uri.strip_prefix("file://").unwrap_or(uri);
+            let name = Path::new(path)
+                .file_name()
+                .map(|it| it.to_string_lossy().to_string());
+            (
+                shared_file_name(name.as_deref(), None, fallback),
+                Box::new(File::open(path).map_err(|e| e.to_string())?),
+            )
+        }
+    };
+    let host_dir = PathBuf::from(format!("{}{}", ARCH_FS_ROOT, dir));
+    fs::create_dir_all(&host_dir).map_err(|e| e.to_string())?;
+    let name = unique_name(&name, |it| host_dir.join(it).exists());
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o644)
+        .open(host_dir.join(&name))
+        .map_err(|e| e.to_string())?;
+    io::copy(&mut source, &mut file).map_err(|e| e.to_string())?;
+    file.flush().map_err(|e| e.to_string())?;
+    Ok(format!("{}/{}", dir, name))
+}
+
+/// Hand a file over to the session, waiting for it to read the pipe
+fn hand_over(path: &str) -> io::Result<()> {
+    let fifo = format!("{}{}", ARCH_FS_ROOT, FIFO);
+    let deadline = Instant::now() + SESSION_TIMEOUT;
+    loop {
+        // Fails as long as no session reads the pipe
+        match OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&fifo)
+        {
+            Ok(mut pipe) => return pipe.write_all(format!("{}\n", path).as_bytes()),
+            Err(e) if Instant::now() > deadline => return Err(e),
+            Err(_) => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use super::process::ArchProcess;
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{
+        config::{is_valid_username, save_config, ARCH_FS_ROOT},
+        diagnostics,
+        logging::panel_log,
+    },
+};
+use serde_json::json;
+use std::{fs, io, os::unix::fs::PermissionsExt, path::Path, process::Stdio, sync::Mutex, thread};
+
+/// Regular users start from this uid, below are the system accounts
+const FIRST_USER_UID: u32 = 1000;
+
+/// The `nobody` account
+const OVERFLOW_UID: u32 = 65534;
+
+/// The sudo rule of the users created by the app, which are all in `wheel`
+const SUDOERS_FILE: &str = "/etc/sudoers.d/localdesktop";
+
+/// The users run root commands (e.g. pacman) without a password, as they may have none: the Arch FS is theirs
+/// alone, and Proot only fakes root anyway
+const SUDOERS_RULE: &str = "%wheel ALL=(ALL:ALL) NOPASSWD: ALL\n";
+
+/// The user whose session starts on the next launch, once another one than the running session has been selected
+static NEXT_SESSION_USER: Mutex<Option<String>> = Mutex::new(None);
+
+/// Run a command as root inside the Arch FS, the arguments being available as `"$@"` so that they are never
+/// interpreted by the shell
+fn run(command: &str, args: &[&str]) -> Result<(), String> {
+    let output = ArchProcess {
+        command: format!("{} 2>&1", command),
+        user: "root".to_string(),
+        process: None,
+    }
+    .command()
+    .args(args)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::inherit())
+    .output()
+    .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// The accounts one can log in as: root and the regular users, read from the `passwd` database of the Arch FS
+fn list_users() -> Vec<(String, String)> {
+    let passwd = fs::read_to_string(format!("{}/etc/passwd", ARCH_FS_ROOT)).unwrap_or_default();
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split(':').collect::<Vec<_>>();
+            let uid = fields.get(2)?.parse::<u32>().ok()?;
+            (uid == 0 || (FIRST_USER_UID..OVERFLOW_UID).contains(&uid)).then(|| {
+                (
+                    fields[0].to_string(),
+                    fields.get(5).unwrap_or(&"").to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Publish the users for the diagnostics page to manage them
+pub fn publish_users() {
+    let running = get_application_context().local_config.user.username;
+    let next = NEXT_SESSION_USER.lock().unwrap().clone();
+    let users = list_users()
+        .into_iter()
+        .map(|(name, home)| {
+            let session = if next.as_ref() == Some(&name) {
+                "next launch"
+            } else if name == running && next.is_none() {
+                "running"
+            } else if name == running {
+                "running until restart"
+            } else {
+                ""
+            };
+            json!({ "name": name, "home": home, "session": session })
+        })
+        .collect::<Vec<_>>();
+    diagnostics::publish("users", json!(users));
+}
+
+/// Create the selected user on first launch, so that the session never runs as a missing user
+pub fn ensure_user(username: &str) {
+    if username == "root" || list_users().iter().any(|(name, _)| name == username) {
+        return;
+    }
+    if let Err(e) = create(username, None) {
+        panel_log(format!("Failed to create user {}: {}", username, e), true);
+    }
+}
+
+/// Whether the users of `wheel` may use sudo, which the setup grants once the user is chosen
+pub(super) fn sudo_granted() -> bool {
+    Path::new(&format!("{}{}", ARCH_FS_ROOT, SUDOERS_FILE)).exists()
+}
+
+pub(super) fn grant_sudo() -> io::Result<()> {
+    let path = format!("{}{}", ARCH_FS_ROOT, SUDOERS_FILE);
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, SUDOERS_RULE)?;
+    // sudo ignores the rules anyone else can write to
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o440))
+}
+
+pub(super) fn create(username: &str, password: Option<&str>) -> Result<(), String> {
+    if !is_valid_username(username) {
+        return Err(format!("{:?} is not a valid username", username));
+    }
+    log::info!("Creating user {}", username);
+    run("useradd -m -G wheel -s /bin/bash \"$1\"", &[username])?;
+    if let Some(password) = password {
+        set_password(username, password)?;
+    }
+    Ok(())
+}
+
+/// Only the accounts the diagnostics page lists are changed, never the system ones, e.g. of the daemons
+fn ensure_listed(username: &str) -> Result<(), String> {
+    if list_users().iter().any(|(name, _)| name == username) {
+        Ok(())
+    } else {
+        Err(format!("There is no user {}", username))
+    }
+}
+
+fn set_password(username: &str, password: &str) -> Result<(), String> {
+    ensure_listed(username)?;
+    if password.is_empty() || password.contains('\n') {
+        return Err("A password cannot be empty nor span several lines".to_string());
+    }
+    run(
+        "printf '%s:%s\\n' \"$1\" \"$2\" | chpasswd",
+        &[username, password],
+    )
+}
+
+fn delete(username: &str) -> Result<(), String> {
+    if username == "root" {
+        return Err("root cannot be deleted".to_string());
+    }
+    if username == get_application_context().local_config.user.username {
+        return Err("The user of the running session cannot be deleted".to_string());
+    }
+    ensure_listed(username)?;
+    log::info!("Deleting user {}", username);
+    run("userdel -r \"$1\"", &[username])
+}
+
+/// Start the session of that user from the next launch on
+fn select(username: &str) -> Result<(), String> {
+    ensure_listed(username)?;
+    // The running session, and every process started along with it, keep their user until the app restarts
+    let mut config = get_application_context().local_config;
+    config.user.username = username.to_string();
+    save_config(&config);
+    *NEXT_SESSION_USER.lock().unwrap() = Some(username.to_string());
+    panel_log(
+        format!("The session of {} starts on the next launch", username),
+        false,
+    );
+    Ok(())
+}
+
+/// Changes requested from the diagnostics page, whose socket only takes the pages with the token of this launch,
+/// reported on the panel
+pub enum UserRequest {
+    Create { name: String, password: String },
+    Delete { name: String },
+    SetPassword { name: String, password: String },
+    Select { name: String },
+}
+
+pub fn manage_users(request: UserRequest) {
+    thread::spawn(move || {
+        let result = match &request {
+            UserRequest::Create { name, password } => {
+                create(name, (!password.is_empty()).then_some(password.as_str()))
+            }
+            UserRequest::Delete { name } => delete(name),
+            UserRequest::SetPassword { name, password } => set_password(name, password),
+            UserRequest::Select { name } => select(name),
+        };
+        if let Err(e) = result {
+            panel_log(e, true);
+        }
+        publish_users();
+    });
+}
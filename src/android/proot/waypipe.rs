@@ -0,0 +1,159 @@
+use super::process::ArchProcess;
+use crate::{
+    android::utils::application_context::get_application_context,
+    core::{config, diagnostics, logging::panel_log},
+};
+use serde_json::json;
+use std::{
+    io::{BufRead, BufReader},
+    process::Stdio,
+    sync::{Mutex, Once},
+    thread,
+};
+
+/// Both are needed to forward the Wayland connection of remote apps through SSH
+const PACKAGES: &str = "waypipe openssh";
+
+/// The key the phone authenticates with, to be added to `~/.ssh/authorized_keys` on the remote hosts
+const PRIVATE_KEY: &str = "~/.ssh/id_ed25519";
+
+static PREPARE: Once = Once::new();
+
+/// The remote apps started since launch, as published on the diagnostics page
+static SESSIONS: Mutex<Vec<RemoteSession>> = Mutex::new(Vec::new());
+
+static PUBLIC_KEY: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+struct RemoteSession {
+    host: String,
+    command: String,
+    status: String,
+}
+
+fn publish() {
+    let public_key = PUBLIC_KEY.lock().ok().and_then(|it| it.clone());
+    let sessions = SESSIONS
+        .lock()
+        .map(|sessions| {
+            sessions
+                .iter()
+                .map(|it| json!({ "host": it.host, "command": it.command, "status": it.status }))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    diagnostics::publish(
+        "remote apps",
+        json!({ "publicKey": public_key, "sessions": sessions }),
+    );
+}
+
+fn set_status(index: usize, status: &str) {
+    if let Some(session) = SESSIONS.lock().unwrap().get_mut(index) {
+        session.status = status.to_string();
+    }
+    publish();
+}
+
+/// Install waypipe and SSH if needed, then publish the public key of the user, generating it on first use
+fn install() -> bool {
+    if !ArchProcess::install_packages(PACKAGES) {
+        panel_log(format!("Failed to install {}", PACKAGES), true);
+        return false;
+    }
+
+    let username = get_application_context().local_config.user.username;
+    let command = format!(
+        "[ -f {0} ] || {{ mkdir -p -m 700 ~/.ssh; ssh-keygen -q -t ed25519 -N '' -C localdesktop -f {0}; }}; cat {0}.pub",
+        PRIVATE_KEY
+    );
+    match ArchProcess::exec_as(&command, &username).and_then(ArchProcess::wait_with_output) {
+        Ok(output) if output.status.success() => {
+            let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            *PUBLIC_KEY.lock().unwrap() = Some(key);
+            publish();
+            true
+        }
+        _ => {
+            panel_log("Failed to generate the SSH key", true);
+            false
+        }
+    }
+}
+
+/// Get waypipe ready in the background, so that the key is known before any remote app is started. Only done once
+/// the user asks for it on the diagnostics page, as it installs packages and generates a key.
+pub fn prepare() {
+    PREPARE.call_once(|| {
+        thread::spawn(install);
+    });
+}
+
+/// Run `command` on `host` over SSH, its windows showing up on the local compositor through waypipe.
+/// The host is anything `ssh` accepts, e.g. `user@desktop.lan` or a `Host` of `~/.ssh/config`.
+pub fn run_remote_app(host: String, command: String) {
+    let (host, command) = (host.trim().to_string(), command.trim().to_string());
+    if host.is_empty() || command.is_empty() || host.starts_with('-') {
+        panel_log("A remote app needs both a host and a command", true);
+        return;
+    }
+
+    let index = {
+        let mut sessions = SESSIONS.lock().unwrap();
+        sessions.push(RemoteSession {
+            host: host.clone(),
+            command: command.clone(),
+            status: "Starting".to_string(),
+        });
+        sessions.len() - 1
+    };
+    publish();
+
+    thread::spawn(move || {
+        // Installing again is a no-op, but waits for a pending installation
+        if !install() {
+            set_status(index, "Failed to install waypipe");
+            return;
+        }
+
+        // The host and the command are passed as arguments, so that they are never interpreted by the local shell.
+        // There is no terminal to answer prompts: authentication must go through the key, and new host keys are trusted.
+        let child = ArchProcess {
+            command: format!(
+                "XDG_RUNTIME_DIR=/tmp WAYLAND_DISPLAY={} exec waypipe ssh -o BatchMode=yes -o StrictHostKeyChecking=accept-new \"$1\" \"$2\" 2>&1",
+                config::WAYLAND_SOCKET_NAME
+            ),
+            user: get_application_context().local_config.user.username,
+            process: None,
+        }
+        .command()
+            .arg(&host)
+            .arg(&command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn();
+        let mut child = match child {
+            Ok(it) => it,
+            Err(e) => {
+                panel_log(format!("Failed to start waypipe: {}", e), true);
+                set_status(index, "Failed to start");
+                return;
+            }
+        };
+        set_status(index, "Running");
+
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                log::info!("{}: {}", host, line);
+            }
+        }
+        match child.wait() {
+            Ok(status) if status.success() => set_status(index, "Exited"),
+            Ok(status) => {
+                panel_log(format!("{} on {} failed ({})", command, host, status), true);
+                set_status(index, &format!("Failed ({})", status));
+            }
+            Err(e) => set_status(index, &format!("Failed ({})", e)),
+        }
+    });
+}
@@ -0,0 +1,153 @@
+use super::process::ArchProcess;
+use crate::{
+    android::{
+        app::attach::attached_app,
+        utils::{
+            application_context::get_application_context,
+            ndk::{run_in_jvm, view_uri},
+        },
+    },
+    core::{config::ARCH_FS_ROOT, logging::panel_log, xdg_open::OpenRequest},
+};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::Once,
+    thread,
+};
+
+/// The shim falls back to the `xdg-open` of xdg-utils, and asks `xdg-mime` whether the desktop has a default app
+const PACKAGES: &str = "xdg-utils";
+
+/// Found before the `xdg-open` of xdg-utils, as `/usr/local/bin` comes first in the `PATH` of the Arch FS
+const SHIM: &str = "/usr/local/bin/xdg-open";
+
+/// The socket the shim posts the links to, inside the Arch FS
+const SOCKET: &str = "/tmp/.localdesktop-open";
+
+/// `curl` comes along with pacman, so the shim needs nothing else to talk to the socket
+const SHIM_CONTENT: &str = r#"#!/bin/sh
+# Written by Local Desktop: opens the links with the apps of Android, see `[open] links_on_android`, and everything
+# else with the apps of the desktop
+case "$1" in
+*:*)
+    scheme="${1%%:*}"
+    handler="$(/usr/bin/xdg-mime query default "x-scheme-handler/$scheme" 2>/dev/null)"
+    if printf '%s' "$1" | curl -sf --max-time 10 --unix-socket /tmp/.localdesktop-open \
+        -H "X-Desktop-Handler: $handler" --data-binary @- http://localhost/open >/dev/null 2>&1; then
+        exit 0
+    fi
+    ;;
+esac
+exec /usr/bin/xdg-open "$@"
+"#;
+
+static SERVE: Once = Once::new();
+
+/// Install the `xdg-open` shim of the sessions, and open the links it posts with the apps of Android, until the app
+/// exits
+pub fn serve_xdg_open() {
+    if !ArchProcess::install_packages(PACKAGES) {
+        panel_log(
+            format!(
+                "Failed to install {}, the links of the session open in the desktop",
+                PACKAGES
+            ),
+            true,
+        );
+        return;
+    }
+    let shim = format!("{}{}", ARCH_FS_ROOT, SHIM);
+    let written = Path::new(&shim)
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&shim, SHIM_CONTENT))
+        .and_then(|_| fs::set_permissions(&shim, fs::Permissions::from_mode(0o755)));
+    if let Err(e) = written {
+        panel_log(format!("Failed to write {}: {}", SHIM, e), true);
+        return;
+    }
+    SERVE.call_once(|| {
+        let socket = format!("{}{}", ARCH_FS_ROOT, SOCKET);
+        // A socket left over by a previous process makes listening fail
+        let _ = fs::remove_file(&socket);
+        let listener = match UnixListener::bind(&socket) {
+            Ok(it) => it,
+            Err(e) => {
+                log::error!("Failed to listen on {}: {}", socket, e);
+                return;
+            }
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                if let Err(e) = answer(stream) {
+                    log::warn!("Failed to answer xdg-open: {}", e);
+                }
+            }
+        });
+    });
+}
+
+/// Open the link of a request, answering whether Android did, for the shim to fall back to the desktop otherwise
+fn answer(stream: UnixStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        head.push_str(&line);
+    }
+    let opened = match OpenRequest::parse_head(&head) {
+        Some((length, desktop_handler)) => {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            let request = OpenRequest {
+                target: String::from_utf8_lossy(&body).to_string(),
+                desktop_handler,
+            };
+            open(&request)
+        }
+        None => false,
+    };
+    let status = if opened { "200 OK" } else { "404 Not Found" };
+    (&stream).write_all(
+        format!(
+            "HTTP/1.0 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        )
+        .as_bytes(),
+    )
+}
+
+fn open(request: &OpenRequest) -> bool {
+    let mode = get_application_context()
+        .local_config
+        .open
+        .links_on_android();
+    let (Some(uri), Some(android_app)) = (request.android_uri(mode), attached_app()) else {
+        return false;
+    };
+    let mut opened = false;
+    run_in_jvm(
+        |env, app| match view_uri(env, app, uri) {
+            Ok(()) => opened = true,
+            // No app of Android opens it
+            Err(e) => {
+                let _ = env.exception_clear();
+                log::info!("Android did not open {}: {}", uri, e);
+            }
+        },
+        android_app,
+    );
+    if opened {
+        log::info!("Opened {} with Android", uri);
+    }
+    opened
+}
@@ -0,0 +1,177 @@
+use libloading::{Library, Symbol};
+use std::{error::Error, ffi::c_void, ptr, time::Duration};
+
+/// From `aaudio/AAudio.h`
+const AAUDIO_OK: i32 = 0;
+const AAUDIO_FORMAT_PCM_I16: i32 = 1;
+const AAUDIO_PERFORMANCE_MODE_LOW_LATENCY: i32 = 12;
+const AAUDIO_DIRECTION_OUTPUT: i32 = 0;
+const AAUDIO_DIRECTION_INPUT: i32 = 1;
+
+type CreateStreamBuilder = unsafe extern "C" fn(*mut *mut c_void) -> i32;
+type BuilderSetI32 = unsafe extern "C" fn(*mut c_void, i32);
+type OpenStream = unsafe extern "C" fn(*mut c_void, *mut *mut c_void) -> i32;
+type StreamCall = unsafe extern "C" fn(*mut c_void) -> i32;
+type StreamWrite = unsafe extern "C" fn(*mut c_void, *const c_void, i32, i64) -> i32;
+type StreamRead = unsafe extern "C" fn(*mut c_void, *mut c_void, i32, i64) -> i32;
+
+/// An AAudio output stream playing interleaved 16-bit samples.
+/// AAudio came with Android 8, so `libaaudio.so` is loaded at runtime rather than linked.
+pub struct AAudioOutput {
+    library: Library,
+    stream: *mut c_void,
+    channels: usize,
+}
+
+// The stream is only ever used by the thread owning it
+unsafe impl Send for AAudioOutput {}
+
+fn check(call: &str, result: i32) -> Result<(), Box<dyn Error>> {
+    if result == AAUDIO_OK {
+        Ok(())
+    } else {
+        Err(format!("{} failed with AAudio error {}", call, result).into())
+    }
+}
+
+/// Open and start a stream of interleaved 16-bit samples
+fn open_stream(
+    library: &Library,
+    direction: i32,
+    sample_rate: i32,
+    channels: i32,
+) -> Result<*mut c_void, Box<dyn Error>> {
+    unsafe {
+        let create: Symbol<CreateStreamBuilder> = library.get(b"AAudio_createStreamBuilder")?;
+        let set_direction: Symbol<BuilderSetI32> =
+            library.get(b"AAudioStreamBuilder_setDirection")?;
+        let set_format: Symbol<BuilderSetI32> = library.get(b"AAudioStreamBuilder_setFormat")?;
+        let set_channel_count: Symbol<BuilderSetI32> =
+            library.get(b"AAudioStreamBuilder_setChannelCount")?;
+        let set_sample_rate: Symbol<BuilderSetI32> =
+            library.get(b"AAudioStreamBuilder_setSampleRate")?;
+        let set_performance_mode: Symbol<BuilderSetI32> =
+            library.get(b"AAudioStreamBuilder_setPerformanceMode")?;
+        let open: Symbol<OpenStream> = library.get(b"AAudioStreamBuilder_openStream")?;
+        let delete: Symbol<StreamCall> = library.get(b"AAudioStreamBuilder_delete")?;
+        let start: Symbol<StreamCall> = library.get(b"AAudioStream_requestStart")?;
+        let close: Symbol<StreamCall> = library.get(b"AAudioStream_close")?;
+
+        let mut builder = ptr::null_mut();
+        check("AAudio_createStreamBuilder", create(&mut builder))?;
+        set_direction(builder, direction);
+        set_format(builder, AAUDIO_FORMAT_PCM_I16);
+        set_channel_count(builder, channels);
+        set_sample_rate(builder, sample_rate);
+        set_performance_mode(builder, AAUDIO_PERFORMANCE_MODE_LOW_LATENCY);
+        let mut stream = ptr::null_mut();
+        let opened = open(builder, &mut stream);
+        delete(builder);
+        check("AAudioStreamBuilder_openStream", opened)?;
+        if let Err(e) = check("AAudioStream_requestStart", start(stream)) {
+            close(stream);
+            return Err(e);
+        }
+        Ok(stream)
+    }
+}
+
+impl AAudioOutput {
+    pub fn open(sample_rate: i32, channels: i32) -> Result<Self, Box<dyn Error>> {
+        let library = unsafe { Library::new("libaaudio.so") }?;
+        let stream = open_stream(&library, AAUDIO_DIRECTION_OUTPUT, sample_rate, channels)?;
+        Ok(Self {
+            library,
+            stream,
+            channels: channels as usize,
+        })
+    }
+
+    /// Play `samples`, blocking until AAudio took them all or `timeout` passed.
+    /// Fails once the stream is disconnected, e.g. when headphones are plugged in, and must then be reopened.
+    pub fn write(&mut self, samples: &[i16], timeout: Duration) -> Result<(), Box<dyn Error>> {
+        let write: Symbol<StreamWrite> = unsafe { self.library.get(b"AAudioStream_write") }?;
+        let mut frames = &samples[..samples.len() / self.channels * self.channels];
+        while !frames.is_empty() {
+            let written = unsafe {
+                write(
+                    self.stream,
+                    frames.as_ptr() as *const c_void,
+                    (frames.len() / self.channels) as i32,
+                    timeout.as_nanos() as i64,
+                )
+            };
+            if written < 0 {
+                check("AAudioStream_write", written)?;
+            }
+            if written == 0 {
+                // Nothing played within the timeout, the rest is dropped rather than piling up
+                break;
+            }
+            frames = &frames[written as usize * self.channels..];
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AAudioOutput {
+    fn drop(&mut self) {
+        if let Ok(close) = unsafe { self.library.get::<StreamCall>(b"AAudioStream_close") } {
+            unsafe { close(self.stream) };
+        }
+    }
+}
+
+/// An AAudio input stream recording interleaved 16-bit samples, e.g. from the microphone, which needs the
+/// `RECORD_AUDIO` permission
+pub struct AAudioInput {
+    library: Library,
+    stream: *mut c_void,
+    channels: usize,
+}
+
+// The stream is only ever used by the thread owning it
+unsafe impl Send for AAudioInput {}
+
+impl AAudioInput {
+    pub fn open(sample_rate: i32, channels: i32) -> Result<Self, Box<dyn Error>> {
+        let library = unsafe { Library::new("libaaudio.so") }?;
+        let stream = open_stream(&library, AAUDIO_DIRECTION_INPUT, sample_rate, channels)?;
+        Ok(Self {
+            library,
+            stream,
+            channels: channels as usize,
+        })
+    }
+
+    /// Fill `samples` with what was recorded, blocking until they are all filled or `timeout` passed. Returns how
+    /// many samples were filled.
+    pub fn read(
+        &mut self,
+        samples: &mut [i16],
+        timeout: Duration,
+    ) -> Result<usize, Box<dyn Error>> {
+        let read: Symbol<StreamRead> = unsafe { self.library.get(b"AAudioStream_read") }?;
+        let frames = samples.len() / self.channels;
+        let read = unsafe {
+            read(
+                self.stream,
+                samples.as_mut_ptr() as *mut c_void,
+                frames as i32,
+                timeout.as_nanos() as i64,
+            )
+        };
+        if read < 0 {
+            check("AAudioStream_read", read)?;
+        }
+        Ok(read as usize * self.channels)
+    }
+}
+
+impl Drop for AAudioInput {
+    fn drop(&mut self) {
+        if let Ok(close) = unsafe { self.library.get::<StreamCall>(b"AAudioStream_close") } {
+            unsafe { close(self.stream) };
+        }
+    }
+}
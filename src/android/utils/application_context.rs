@@ -102,3 +102,15 @@ pub fn get_application_context() -> ApplicationContext {
         .clone()
         .pb_expect("ApplicationContext is not initialized. Please make sure `ApplicationContext::build(&android_app);` is called in `android_main`.");
 }
+
+/// Change the configuration of the running app, e.g. to apply a selection made at launch. Returns the new configuration.
+pub fn update_local_config(update: impl FnOnce(&mut LocalConfig)) -> LocalConfig {
+    let mut context = APPLICATION_CONTEXT
+        .write()
+        .pb_expect("Failed to write application context");
+    let context = context
+        .as_mut()
+        .pb_expect("ApplicationContext is not initialized");
+    update(&mut context.local_config);
+    context.local_config.clone()
+}
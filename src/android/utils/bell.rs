@@ -0,0 +1,122 @@
+use super::{application_context::get_application_context, ndk::run_in_jvm};
+use crate::core::bell::Throttle;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// `RingtoneManager.TYPE_NOTIFICATION`
+const TYPE_NOTIFICATION: i32 = 2;
+
+/// How long the phone vibrates for a bell
+const VIBRATION: Duration = Duration::from_millis(150);
+
+/// `VibrationEffect` came with Android 8, the older versions take the duration alone
+const VIBRATION_EFFECT_SDK: i32 = 26;
+
+static THROTTLE: Mutex<Throttle> = Mutex::new(Throttle::new());
+
+/// Ring the bell as `[bell]` says, unless it just rang. `source` is only logged.
+pub fn ring(android_app: &AndroidApp, source: &str) {
+    let config = get_application_context().local_config.bell;
+    let signal = config.signal();
+    if !signal.sound && !signal.vibrate {
+        return;
+    }
+    let cooldown = Duration::from_millis(config.cooldown);
+    if !THROTTLE.lock().unwrap().allow(Instant::now(), cooldown) {
+        return;
+    }
+    log::debug!("The bell rang in {}", source);
+    // Looking the sound up reads the settings, which the compositor should not wait for
+    let android_app = android_app.clone();
+    thread::spawn(move || {
+        run_in_jvm(
+            |env, app| {
+                let result = (|| {
+                    if signal.sound {
+                        play_notification_sound(env, app)?;
+                    }
+                    if signal.vibrate {
+                        vibrate(env, app)?;
+                    }
+                    Ok::<_, jni::errors::Error>(())
+                })();
+                if let Err(e) = result {
+                    let _ = env.exception_clear();
+                    log::warn!("Failed to ring the bell: {:?}", e);
+                }
+            },
+            android_app,
+        );
+    });
+}
+
+fn play_notification_sound(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let uri = env
+        .call_static_method(
+            "android/media/RingtoneManager",
+            "getDefaultUri",
+            "(I)Landroid/net/Uri;",
+            &[JValue::Int(TYPE_NOTIFICATION)],
+        )?
+        .l()?;
+    let ringtone = env
+        .call_static_method(
+            "android/media/RingtoneManager",
+            "getRingtone",
+            "(Landroid/content/Context;Landroid/net/Uri;)Landroid/media/Ringtone;",
+            &[JValue::Object(&activity), JValue::Object(&uri)],
+        )?
+        .l()?;
+    // No ringtone when the phone has no notification sound
+    if !ringtone.is_null() {
+        env.call_method(&ringtone, "play", "()V", &[])?;
+    }
+    Ok(())
+}
+
+fn vibrate(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let service = env.new_string("vibrator")?;
+    let vibrator = env
+        .call_method(
+            &activity,
+            "getSystemService",
+            "(Ljava/lang/String;)Ljava/lang/Object;",
+            &[JValue::Object(&service)],
+        )?
+        .l()?;
+    if vibrator.is_null() {
+        return Ok(());
+    }
+    let millis = VIBRATION.as_millis() as i64;
+    let sdk = env
+        .get_static_field("android/os/Build$VERSION", "SDK_INT", "I")?
+        .i()?;
+    if sdk >= VIBRATION_EFFECT_SDK {
+        // `VibrationEffect.DEFAULT_AMPLITUDE`
+        let effect = env
+            .call_static_method(
+                "android/os/VibrationEffect",
+                "createOneShot",
+                "(JI)Landroid/os/VibrationEffect;",
+                &[JValue::Long(millis), JValue::Int(-1)],
+            )?
+            .l()?;
+        env.call_method(
+            &vibrator,
+            "vibrate",
+            "(Landroid/os/VibrationEffect;)V",
+            &[JValue::Object(&effect)],
+        )?;
+    } else {
+        env.call_method(&vibrator, "vibrate", "(J)V", &[JValue::Long(millis)])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use libloading::{Library, Symbol};
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+use winit::platform::android::activity::AndroidAppWaker;
+
+type GetInstance = unsafe extern "C" fn() -> *mut c_void;
+/// `AChoreographer_postFrameCallback64`, from Android 10
+type PostFrameCallback64 = unsafe extern "C" fn(*mut c_void, FrameCallback64, *mut c_void);
+type FrameCallback64 = unsafe extern "C" fn(i64, *mut c_void);
+/// `AChoreographer_postFrameCallback`, from Android 7, whose frame time is a `long` (64-bit on arm64 all the same)
+type PostFrameCallback = unsafe extern "C" fn(*mut c_void, FrameCallback, *mut c_void);
+type FrameCallback = unsafe extern "C" fn(std::ffi::c_long, *mut c_void);
+
+/// A vsync came since the last frame was drawn
+static VSYNC: AtomicBool = AtomicBool::new(false);
+
+/// A frame callback is posted and did not run yet
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the event loop up on vsync, as the callbacks run while it waits and do not end its wait
+static WAKER: OnceLock<AndroidAppWaker> = OnceLock::new();
+
+unsafe extern "C" fn on_frame_64(_frame_time_nanos: i64, _data: *mut c_void) {
+    on_vsync();
+}
+
+unsafe extern "C" fn on_frame(_frame_time_nanos: std::ffi::c_long, _data: *mut c_void) {
+    on_vsync();
+}
+
+fn on_vsync() {
+    PENDING.store(false, Ordering::SeqCst);
+    VSYNC.store(true, Ordering::SeqCst);
+    if let Some(waker) = WAKER.get() {
+        waker.wake();
+    }
+}
+
+/// Paces the frames on the vsync of the display, through the `AChoreographer` of the event loop's thread.
+/// `libandroid.so` is loaded at runtime, as the Choreographer came with Android 7. Without it, every frame is due
+/// right away and `eglSwapBuffers` alone paces them.
+pub struct Choreographer {
+    /// Loaded on the first frame, as the Choreographer is bound to the thread asking for it
+    api: Option<Option<ChoreographerApi>>,
+}
+
+struct ChoreographerApi {
+    _library: Library,
+    instance: *mut c_void,
+    post: Post,
+}
+
+enum Post {
+    V64(PostFrameCallback64),
+    Legacy(PostFrameCallback),
+}
+
+impl Choreographer {
+    pub fn new(waker: AndroidAppWaker) -> Self {
+        let _ = WAKER.set(waker);
+        Self { api: None }
+    }
+
+    /// Whether a frame is due, i.e. a vsync came since the last frame. Otherwise, the event loop is woken up on the
+    /// next vsync to ask again.
+    pub fn frame_due(&mut self) -> bool {
+        let Some(api) = self.api.get_or_insert_with(load).as_ref() else {
+            return true;
+        };
+        if VSYNC.swap(false, Ordering::SeqCst) {
+            return true;
+        }
+        if !PENDING.swap(true, Ordering::SeqCst) {
+            unsafe {
+                match api.post {
+                    Post::V64(post) => post(api.instance, on_frame_64, ptr::null_mut()),
+                    Post::Legacy(post) => post(api.instance, on_frame, ptr::null_mut()),
+                }
+            }
+        }
+        false
+    }
+}
+
+fn load() -> Option<ChoreographerApi> {
+    let loaded = unsafe {
+        Library::new("libandroid.so").and_then(|library| {
+            let get_instance: Symbol<GetInstance> = library.get(b"AChoreographer_getInstance")?;
+            let post =
+                match library.get::<PostFrameCallback64>(b"AChoreographer_postFrameCallback64") {
+                    Ok(post) => Post::V64(*post),
+                    Err(_) => Post::Legacy(
+                        *library.get::<PostFrameCallback>(b"AChoreographer_postFrameCallback")?,
+                    ),
+                };
+            let instance = get_instance();
+            Ok((library, instance, post))
+        })
+    };
+    match loaded {
+        Ok((_, instance, _)) if instance.is_null() => {
+            log::warn!(
+                "No Choreographer on the event loop's thread, the frames are not paced on vsync"
+            );
+            None
+        }
+        Ok((library, instance, post)) => Some(ChoreographerApi {
+            _library: library,
+            instance,
+            post,
+        }),
+        Err(e) => {
+            log::warn!(
+                "The Choreographer is not available, the frames are not paced on vsync: {}",
+                e
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::{
+    settings, terminal,
+    webview::{accept_page, page_token, show_webview_overlay},
+};
+use crate::{
+    android::proot::{
+        backup::back_up_rootfs,
+        maintenance::{purge_package_cache, reset_rootfs},
+        mime::{publish_default_applications, set_default_application},
+        users::{manage_users, publish_users, UserRequest},
+        waypipe::{self, run_remote_app},
+    },
+    core::{
+        diagnostics,
+        logging::{panel_log, set_log_levels, PolarBearExpectation},
+    },
+};
+use serde::Deserialize;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use websocket::sync::Server;
+use websocket::OwnedMessage;
+use winit::platform::android::activity::AndroidApp;
+
+/// How often a connected diagnostics page receives a fresh snapshot
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+static DIAGNOSTICS_PORT: OnceLock<u16> = OnceLock::new();
+
+/// Actions taken from the diagnostics page, e.g. `{"setLogLevel": "debug"}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Request {
+    SetLogLevel(String),
+    /// Install waypipe and show the SSH key to copy to the remote hosts
+    PrepareRemoteApps,
+    RunRemoteApp {
+        host: String,
+        command: String,
+    },
+    CreateUser {
+        name: String,
+        password: String,
+    },
+    DeleteUser {
+        name: String,
+    },
+    SetPassword {
+        name: String,
+        password: String,
+    },
+    SelectUser {
+        name: String,
+    },
+    SetDefaultApplication {
+        category: String,
+        application: String,
+    },
+    ResetRootfs,
+    BackUpRootfs,
+    PurgePackageCache,
+}
+
+fn handle_request(text: &str) {
+    match serde_json::from_str::<Request>(text) {
+        Ok(Request::SetLogLevel(level)) => {
+            if let Err(e) = set_log_levels(&level) {
+                panel_log(e, true);
+            }
+        }
+        Ok(Request::PrepareRemoteApps) => waypipe::prepare(),
+        Ok(Request::RunRemoteApp { host, command }) => run_remote_app(host, command),
+        Ok(Request::CreateUser { name, password }) => {
+            manage_users(UserRequest::Create { name, password })
+        }
+        Ok(Request::DeleteUser { name }) => manage_users(UserRequest::Delete { name }),
+        Ok(Request::SetPassword { name, password }) => {
+            manage_users(UserRequest::SetPassword { name, password })
+        }
+        Ok(Request::SelectUser { name }) => manage_users(UserRequest::Select { name }),
+        Ok(Request::SetDefaultApplication {
+            category,
+            application,
+        }) => set_default_application(category, application),
+        Ok(Request::ResetRootfs) => reset_rootfs(),
+        Ok(Request::BackUpRootfs) => back_up_rootfs(),
+        Ok(Request::PurgePackageCache) => purge_package_cache(),
+        Err(e) => log::warn!("Invalid diagnostics request {:?}: {}", text, e),
+    }
+}
+
+/// Start (once) a websocket server streaming the diagnostics snapshot to `diagnostics.html`, and taking its requests
+fn serve() -> u16 {
+    *DIAGNOSTICS_PORT.get_or_init(|| {
+        let socket = Server::bind("127.0.0.1:0").pb_expect("Failed to bind diagnostics socket");
+        let port = socket.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for request in socket.filter_map(Result::ok) {
+                // The requests reset the Arch FS, manage the users or reach remote hosts
+                let Some(client) = accept_page(request) else {
+                    continue;
+                };
+                let Ok((mut receiver, mut sender)) = client.split() else {
+                    continue;
+                };
+
+                thread::spawn(move || {
+                    for message in receiver.incoming_messages() {
+                        match message {
+                            Ok(OwnedMessage::Text(text)) => handle_request(&text),
+                            Ok(OwnedMessage::Close(_)) | Err(_) => break,
+                            Ok(_) => {}
+                        }
+                    }
+                });
+
+                thread::spawn(move || loop {
+                    let message = OwnedMessage::Text(diagnostics::snapshot().to_string());
+                    if sender.send_message(&message).is_err() {
+                        log::info!("Diagnostics page disconnected");
+                        break;
+                    }
+                    thread::sleep(REFRESH_INTERVAL);
+                });
+            }
+        });
+
+        port
+    })
+}
+
+/// Show the diagnostics page on top of the desktop
+pub fn show_diagnostics(android_app: AndroidApp) {
+    let port = serve();
+    publish_users();
+    publish_default_applications();
+    let url = format!(
+        "file:///android_asset/diagnostics.html?port={}&terminal={}&settings={}&token={}",
+        port,
+        terminal::serve(&android_app),
+        settings::serve(),
+        page_token()
+    );
+    show_webview_overlay(android_app, url);
+}
@@ -0,0 +1,197 @@
+use jni::{
+    objects::{JByteArray, JObject, JValue},
+    JNIEnv,
+};
+use std::{error::Error, fs, io::Read, path::Path};
+
+/// The AES key of the Android Keystore wrapping the secrets of the app. It never leaves the Keystore
+/// (nor the secure hardware, on the devices that have one).
+const KEY_ALIAS: &str = "localdesktop-secrets";
+
+const TRANSFORMATION: &str = "AES/GCM/NoPadding";
+
+/// Length (in bits) of the GCM authentication tag
+const TAG_LENGTH: i32 = 128;
+
+/// `KeyProperties.PURPOSE_ENCRYPT | KeyProperties.PURPOSE_DECRYPT`
+const PURPOSE_ENCRYPT_DECRYPT: i32 = 3;
+
+/// `Cipher.ENCRYPT_MODE` and `Cipher.DECRYPT_MODE`
+const ENCRYPT_MODE: i32 = 1;
+const DECRYPT_MODE: i32 = 2;
+
+/// Length (in bytes) of the generated secrets
+const SECRET_LENGTH: usize = 32;
+
+fn string_array<'local>(
+    env: &mut JNIEnv<'local>,
+    value: &str,
+) -> jni::errors::Result<JObject<'local>> {
+    let value = env.new_string(value)?;
+    Ok(env.new_object_array(1, "java/lang/String", &value)?.into())
+}
+
+/// The key wrapping the secrets, generated in the Android Keystore on first use
+fn wrapping_key<'local>(env: &mut JNIEnv<'local>) -> jni::errors::Result<JObject<'local>> {
+    let provider = env.new_string("AndroidKeyStore")?;
+    let alias = env.new_string(KEY_ALIAS)?;
+    let key_store = env
+        .call_static_method(
+            "java/security/KeyStore",
+            "getInstance",
+            "(Ljava/lang/String;)Ljava/security/KeyStore;",
+            &[JValue::Object(&provider)],
+        )?
+        .l()?;
+    env.call_method(
+        &key_store,
+        "load",
+        "(Ljava/security/KeyStore$LoadStoreParameter;)V",
+        &[JValue::Object(&JObject::null())],
+    )?;
+
+    let exists = env
+        .call_method(
+            &key_store,
+            "containsAlias",
+            "(Ljava/lang/String;)Z",
+            &[JValue::Object(&alias)],
+        )?
+        .z()?;
+    if !exists {
+        log::info!("Generating the {} key in the Android Keystore", KEY_ALIAS);
+        let builder = env.new_object(
+            "android/security/keystore/KeyGenParameterSpec$Builder",
+            "(Ljava/lang/String;I)V",
+            &[JValue::Object(&alias), JValue::Int(PURPOSE_ENCRYPT_DECRYPT)],
+        )?;
+        let block_modes = string_array(env, "GCM")?;
+        env.call_method(
+            &builder,
+            "setBlockModes",
+            "([Ljava/lang/String;)Landroid/security/keystore/KeyGenParameterSpec$Builder;",
+            &[JValue::Object(&block_modes)],
+        )?;
+        let paddings = string_array(env, "NoPadding")?;
+        env.call_method(
+            &builder,
+            "setEncryptionPaddings",
+            "([Ljava/lang/String;)Landroid/security/keystore/KeyGenParameterSpec$Builder;",
+            &[JValue::Object(&paddings)],
+        )?;
+        let spec = env
+            .call_method(
+                &builder,
+                "build",
+                "()Landroid/security/keystore/KeyGenParameterSpec;",
+                &[],
+            )?
+            .l()?;
+
+        let algorithm = env.new_string("AES")?;
+        let generator = env
+            .call_static_method(
+                "javax/crypto/KeyGenerator",
+                "getInstance",
+                "(Ljava/lang/String;Ljava/lang/String;)Ljavax/crypto/KeyGenerator;",
+                &[JValue::Object(&algorithm), JValue::Object(&provider)],
+            )?
+            .l()?;
+        env.call_method(
+            &generator,
+            "init",
+            "(Ljava/security/spec/AlgorithmParameterSpec;)V",
+            &[JValue::Object(&spec)],
+        )?;
+        env.call_method(&generator, "generateKey", "()Ljavax/crypto/SecretKey;", &[])?;
+    }
+
+    env.call_method(
+        &key_store,
+        "getKey",
+        "(Ljava/lang/String;[C)Ljava/security/Key;",
+        &[JValue::Object(&alias), JValue::Object(&JObject::null())],
+    )?
+    .l()
+}
+
+fn cipher<'local>(env: &mut JNIEnv<'local>) -> jni::errors::Result<JObject<'local>> {
+    let transformation = env.new_string(TRANSFORMATION)?;
+    env.call_static_method(
+        "javax/crypto/Cipher",
+        "getInstance",
+        "(Ljava/lang/String;)Ljavax/crypto/Cipher;",
+        &[JValue::Object(&transformation)],
+    )?
+    .l()
+}
+
+fn do_final(env: &mut JNIEnv, cipher: &JObject, input: &[u8]) -> jni::errors::Result<Vec<u8>> {
+    let input = env.byte_array_from_slice(input)?;
+    let output = env
+        .call_method(cipher, "doFinal", "([B)[B", &[JValue::Object(&input)])?
+        .l()?;
+    env.convert_byte_array(JByteArray::from(output))
+}
+
+/// Encrypt with the Keystore key. The output starts with the length of the IV and the IV itself.
+fn encrypt(env: &mut JNIEnv, plaintext: &[u8]) -> jni::errors::Result<Vec<u8>> {
+    let key = wrapping_key(env)?;
+    let cipher = cipher(env)?;
+    env.call_method(
+        &cipher,
+        "init",
+        "(ILjava/security/Key;)V",
+        &[JValue::Int(ENCRYPT_MODE), JValue::Object(&key)],
+    )?;
+    let iv = env.call_method(&cipher, "getIV", "()[B", &[])?.l()?;
+    let iv = env.convert_byte_array(JByteArray::from(iv))?;
+    let ciphertext = do_final(env, &cipher, plaintext)?;
+
+    let mut output = vec![iv.len() as u8];
+    output.extend(iv);
+    output.extend(ciphertext);
+    Ok(output)
+}
+
+fn decrypt(env: &mut JNIEnv, input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (iv_length, input) = input.split_first().ok_or("Empty secret")?;
+    if input.len() < *iv_length as usize {
+        return Err("Truncated secret".into());
+    }
+    let (iv, ciphertext) = input.split_at(*iv_length as usize);
+
+    let key = wrapping_key(env)?;
+    let cipher = cipher(env)?;
+    let iv = env.byte_array_from_slice(iv)?;
+    let spec = env.new_object(
+        "javax/crypto/spec/GCMParameterSpec",
+        "(I[B)V",
+        &[JValue::Int(TAG_LENGTH), JValue::Object(&iv)],
+    )?;
+    env.call_method(
+        &cipher,
+        "init",
+        "(ILjava/security/Key;Ljava/security/spec/AlgorithmParameterSpec;)V",
+        &[
+            JValue::Int(DECRYPT_MODE),
+            JValue::Object(&key),
+            JValue::Object(&spec),
+        ],
+    )?;
+    Ok(do_final(env, &cipher, ciphertext)?)
+}
+
+/// A random secret, stored in `path` encrypted by the Android Keystore, and generated on first use.
+/// Returned hex encoded, to be usable as a password.
+pub fn get_or_create_secret(env: &mut JNIEnv, path: &Path) -> Result<String, Box<dyn Error>> {
+    let secret = if path.exists() {
+        decrypt(env, &fs::read(path)?)?
+    } else {
+        let mut secret = vec![0u8; SECRET_LENGTH];
+        fs::File::open("/dev/urandom")?.read_exact(&mut secret)?;
+        fs::write(path, encrypt(env, &secret)?)?;
+        secret
+    };
+    Ok(secret.iter().map(|it| format!("{:02x}", it)).collect())
+}
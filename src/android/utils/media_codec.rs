@@ -0,0 +1,451 @@
+use libloading::Library;
+use std::{
+    ffi::{c_char, c_long, c_void, CString},
+    ptr::{self, NonNull},
+    sync::LazyLock,
+    time::Duration,
+};
+
+/// From `media/NdkMediaCodec.h` and `media/NdkMediaMuxer.h`
+const AMEDIA_OK: i32 = 0;
+const CONFIGURE_FLAG_ENCODE: u32 = 1;
+const INFO_OUTPUT_FORMAT_CHANGED: isize = -2;
+const BUFFER_FLAG_CODEC_CONFIG: u32 = 2;
+const BUFFER_FLAG_END_OF_STREAM: u32 = 4;
+const OUTPUT_FORMAT_MPEG_4: i32 = 0;
+
+/// `MediaCodecInfo.CodecCapabilities.COLOR_FormatSurface`: the frames are drawn into an input surface
+const COLOR_FORMAT_SURFACE: i32 = 0x7F000789;
+
+/// `MediaCodecInfo.CodecProfileLevel.AACObjectLC`
+const AAC_PROFILE_LC: i32 = 2;
+
+/// `AMediaCodecBufferInfo`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct BufferInfo {
+    offset: i32,
+    size: i32,
+    presentation_time_us: i64,
+    flags: u32,
+}
+
+type FormatNew = unsafe extern "C" fn() -> *mut c_void;
+type FormatDelete = unsafe extern "C" fn(*mut c_void) -> i32;
+type FormatSetString = unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char);
+type FormatSetInt32 = unsafe extern "C" fn(*mut c_void, *const c_char, i32);
+type FormatSetInt64 = unsafe extern "C" fn(*mut c_void, *const c_char, i64);
+type CreateEncoder = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type Configure =
+    unsafe extern "C" fn(*mut c_void, *const c_void, *mut c_void, *mut c_void, u32) -> i32;
+type CreateInputSurface = unsafe extern "C" fn(*mut c_void, *mut *mut c_void) -> i32;
+type CodecCall = unsafe extern "C" fn(*mut c_void) -> i32;
+type DequeueInput = unsafe extern "C" fn(*mut c_void, i64) -> isize;
+type GetBuffer = unsafe extern "C" fn(*mut c_void, usize, *mut usize) -> *mut u8;
+type QueueInput = unsafe extern "C" fn(*mut c_void, usize, c_long, usize, u64, u32) -> i32;
+type DequeueOutput = unsafe extern "C" fn(*mut c_void, *mut BufferInfo, i64) -> isize;
+type GetOutputFormat = unsafe extern "C" fn(*mut c_void) -> *mut c_void;
+type ReleaseOutput = unsafe extern "C" fn(*mut c_void, usize, bool) -> i32;
+type MuxerNew = unsafe extern "C" fn(i32, i32) -> *mut c_void;
+type MuxerAddTrack = unsafe extern "C" fn(*mut c_void, *const c_void) -> isize;
+type MuxerWrite = unsafe extern "C" fn(*mut c_void, usize, *const u8, *const BufferInfo) -> i32;
+
+/// The media API of the NDK, whose encoders from surfaces came with Android 8, so `libmediandk.so` is loaded at
+/// runtime rather than linked
+struct MediaApi {
+    /// Keeps the symbols below loaded
+    _library: Library,
+    format_new: FormatNew,
+    format_delete: FormatDelete,
+    format_set_string: FormatSetString,
+    format_set_int32: FormatSetInt32,
+    format_set_int64: FormatSetInt64,
+    create_encoder: CreateEncoder,
+    configure: Configure,
+    create_input_surface: CreateInputSurface,
+    start: CodecCall,
+    stop: CodecCall,
+    delete: CodecCall,
+    signal_end_of_input_stream: CodecCall,
+    dequeue_input: DequeueInput,
+    get_input_buffer: GetBuffer,
+    queue_input: QueueInput,
+    dequeue_output: DequeueOutput,
+    get_output_buffer: GetBuffer,
+    get_output_format: GetOutputFormat,
+    release_output: ReleaseOutput,
+    muxer_new: MuxerNew,
+    muxer_add_track: MuxerAddTrack,
+    muxer_start: CodecCall,
+    muxer_stop: CodecCall,
+    muxer_delete: CodecCall,
+    muxer_write: MuxerWrite,
+}
+
+static API: LazyLock<Result<MediaApi, String>> = LazyLock::new(|| unsafe {
+    let library = Library::new("libmediandk.so").map_err(|e| e.to_string())?;
+    macro_rules! symbol {
+        ($name:literal) => {
+            *library.get($name).map_err(|e| e.to_string())?
+        };
+    }
+    Ok(MediaApi {
+        format_new: symbol!(b"AMediaFormat_new"),
+        format_delete: symbol!(b"AMediaFormat_delete"),
+        format_set_string: symbol!(b"AMediaFormat_setString"),
+        format_set_int32: symbol!(b"AMediaFormat_setInt32"),
+        format_set_int64: symbol!(b"AMediaFormat_setInt64"),
+        create_encoder: symbol!(b"AMediaCodec_createEncoderByType"),
+        configure: symbol!(b"AMediaCodec_configure"),
+        create_input_surface: symbol!(b"AMediaCodec_createInputSurface"),
+        start: symbol!(b"AMediaCodec_start"),
+        stop: symbol!(b"AMediaCodec_stop"),
+        delete: symbol!(b"AMediaCodec_delete"),
+        signal_end_of_input_stream: symbol!(b"AMediaCodec_signalEndOfInputStream"),
+        dequeue_input: symbol!(b"AMediaCodec_dequeueInputBuffer"),
+        get_input_buffer: symbol!(b"AMediaCodec_getInputBuffer"),
+        queue_input: symbol!(b"AMediaCodec_queueInputBuffer"),
+        dequeue_output: symbol!(b"AMediaCodec_dequeueOutputBuffer"),
+        get_output_buffer: symbol!(b"AMediaCodec_getOutputBuffer"),
+        get_output_format: symbol!(b"AMediaCodec_getOutputFormat"),
+        release_output: symbol!(b"AMediaCodec_releaseOutputBuffer"),
+        muxer_new: symbol!(b"AMediaMuxer_new"),
+        muxer_add_track: symbol!(b"AMediaMuxer_addTrack"),
+        muxer_start: symbol!(b"AMediaMuxer_start"),
+        muxer_stop: symbol!(b"AMediaMuxer_stop"),
+        muxer_delete: symbol!(b"AMediaMuxer_delete"),
+        muxer_write: symbol!(b"AMediaMuxer_writeSampleData"),
+        _library: library,
+    })
+});
+
+fn api() -> Result<&'static MediaApi, String> {
+    API.as_ref()
+        .map_err(|e| format!("The media API is not available: {}", e))
+}
+
+fn check(call: &str, status: i32) -> Result<(), String> {
+    if status == AMEDIA_OK {
+        Ok(())
+    } else {
+        Err(format!("{} failed with media status {}", call, status))
+    }
+}
+
+/// An `AMediaFormat`, deleted once dropped
+struct Format(*mut c_void);
+
+impl Format {
+    fn new(api: &MediaApi, mime_type: &str) -> Self {
+        let format = Self(unsafe { (api.format_new)() });
+        format.set_string(api, "mime", mime_type);
+        format
+    }
+
+    fn set_string(&self, api: &MediaApi, key: &str, value: &str) {
+        let (key, value) = (CString::new(key).unwrap(), CString::new(value).unwrap());
+        unsafe { (api.format_set_string)(self.0, key.as_ptr(), value.as_ptr()) };
+    }
+
+    fn set_i32(&self, api: &MediaApi, key: &str, value: i32) {
+        let key = CString::new(key).unwrap();
+        unsafe { (api.format_set_int32)(self.0, key.as_ptr(), value) };
+    }
+
+    fn set_i64(&self, api: &MediaApi, key: &str, value: i64) {
+        let key = CString::new(key).unwrap();
+        unsafe { (api.format_set_int64)(self.0, key.as_ptr(), value) };
+    }
+}
+
+impl Drop for Format {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe { (api.format_delete)(self.0) };
+        }
+    }
+}
+
+/// A started `AMediaCodec` encoder. Its input and its output may be used from two threads at once.
+#[derive(Debug)]
+pub struct Encoder {
+    codec: *mut c_void,
+}
+
+unsafe impl Send for Encoder {}
+unsafe impl Sync for Encoder {}
+
+/// What came out of an encoder
+pub enum Output<'a> {
+    /// Nothing within the timeout
+    Nothing,
+    /// The encoder knows its output format, which the muxer needs for the track
+    FormatChanged,
+    /// An encoded sample, to be written before the next one is dequeued
+    Sample(Sample<'a>),
+    /// The encoder is done, after the end of its input
+    End,
+}
+
+/// An output buffer of an encoder, given back to it once dropped
+pub struct Sample<'a> {
+    encoder: &'a Encoder,
+    index: usize,
+    info: BufferInfo,
+    data: &'a [u8],
+}
+
+impl Sample<'_> {
+    /// Copy the sample, e.g. to write it once the muxer started
+    pub fn keep(&self) -> OwnedSample {
+        OwnedSample {
+            info: self.info,
+            data: self.data.to_vec(),
+        }
+    }
+}
+
+impl Drop for Sample<'_> {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe { (api.release_output)(self.encoder.codec, self.index, false) };
+        }
+    }
+}
+
+/// An encoded sample kept after its buffer went back to the encoder
+pub struct OwnedSample {
+    info: BufferInfo,
+    data: Vec<u8>,
+}
+
+impl Encoder {
+    fn create(api: &MediaApi, format: &Format, mime_type: &str) -> Result<Self, String> {
+        let mime = CString::new(mime_type).unwrap();
+        let codec = unsafe { (api.create_encoder)(mime.as_ptr()) };
+        if codec.is_null() {
+            return Err(format!("No encoder for {}", mime_type));
+        }
+        let encoder = Self { codec };
+        check("AMediaCodec_configure", unsafe {
+            (api.configure)(
+                codec,
+                format.0,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                CONFIGURE_FLAG_ENCODE,
+            )
+        })?;
+        Ok(encoder)
+    }
+
+    fn start(self, api: &MediaApi) -> Result<Self, String> {
+        check("AMediaCodec_start", unsafe { (api.start)(self.codec) })?;
+        Ok(self)
+    }
+
+    /// An H.264 encoder of the frames drawn into the `ANativeWindow` returned along, which is the caller's to
+    /// release. The frames are taken when drawn, and the last one is repeated when nothing is drawn for a while.
+    pub fn video(
+        width: i32,
+        height: i32,
+        bitrate: i32,
+        frame_rate: i32,
+    ) -> Result<(Self, NonNull<c_void>), String> {
+        let api = api()?;
+        let format = Format::new(api, "video/avc");
+        format.set_i32(api, "width", width);
+        format.set_i32(api, "height", height);
+        format.set_i32(api, "color-format", COLOR_FORMAT_SURFACE);
+        format.set_i32(api, "bitrate", bitrate);
+        format.set_i32(api, "frame-rate", frame_rate);
+        format.set_i32(api, "i-frame-interval", 1);
+        format.set_i64(
+            api,
+            "repeat-previous-frame-after",
+            1_000_000 / frame_rate as i64,
+        );
+        let encoder = Self::create(api, &format, "video/avc")?;
+        let mut window = ptr::null_mut();
+        check("AMediaCodec_createInputSurface", unsafe {
+            (api.create_input_surface)(encoder.codec, &mut window)
+        })?;
+        let window = NonNull::new(window).ok_or("The encoder has no input surface")?;
+        Ok((encoder.start(api)?, window))
+    }
+
+    /// An AAC encoder of interleaved 16-bit samples
+    pub fn audio(sample_rate: i32, channels: i32, bitrate: i32) -> Result<Self, String> {
+        let api = api()?;
+        let format = Format::new(api, "audio/mp4a-latm");
+        format.set_i32(api, "sample-rate", sample_rate);
+        format.set_i32(api, "channel-count", channels);
+        format.set_i32(api, "bitrate", bitrate);
+        format.set_i32(api, "aac-profile", AAC_PROFILE_LC);
+        Self::create(api, &format, "audio/mp4a-latm")?.start(api)
+    }
+
+    /// End the input of an encoder of an input surface, after its last frame
+    pub fn end_input_surface(&self) -> Result<(), String> {
+        let api = api()?;
+        check("AMediaCodec_signalEndOfInputStream", unsafe {
+            (api.signal_end_of_input_stream)(self.codec)
+        })
+    }
+
+    /// Queue samples, at `time_us` for the first one, blocking for at most `timeout` per input buffer. Returns how
+    /// many bytes were queued. `end` marks the last samples.
+    pub fn queue_input(
+        &self,
+        samples: &[u8],
+        time_us: u64,
+        end: bool,
+        timeout: Duration,
+    ) -> Result<usize, String> {
+        let api = api()?;
+        let index = unsafe { (api.dequeue_input)(self.codec, timeout.as_micros() as i64) };
+        if index < 0 {
+            return Ok(0);
+        }
+        let mut capacity = 0;
+        let buffer = unsafe { (api.get_input_buffer)(self.codec, index as usize, &mut capacity) };
+        let size = samples.len().min(capacity);
+        if !buffer.is_null() {
+            unsafe { ptr::copy_nonoverlapping(samples.as_ptr(), buffer, size) };
+        }
+        let flags = if end && size == samples.len() {
+            BUFFER_FLAG_END_OF_STREAM
+        } else {
+            0
+        };
+        check("AMediaCodec_queueInputBuffer", unsafe {
+            (api.queue_input)(self.codec, index as usize, 0, size, time_us, flags)
+        })?;
+        Ok(size)
+    }
+
+    /// Take what the encoder output, waiting for at most `timeout`
+    pub fn dequeue_output(&self, timeout: Duration) -> Result<Output<'_>, String> {
+        let api = api()?;
+        let mut info = BufferInfo::default();
+        let index =
+            unsafe { (api.dequeue_output)(self.codec, &mut info, timeout.as_micros() as i64) };
+        if index == INFO_OUTPUT_FORMAT_CHANGED {
+            return Ok(Output::FormatChanged);
+        }
+        if index < 0 {
+            // Nothing yet, or the output buffers changed, which the NDK handles on its own
+            return Ok(Output::Nothing);
+        }
+        let mut capacity = 0;
+        let buffer = unsafe { (api.get_output_buffer)(self.codec, index as usize, &mut capacity) };
+        let data = if buffer.is_null() || info.size <= 0 {
+            &[][..]
+        } else {
+            unsafe {
+                std::slice::from_raw_parts(buffer.add(info.offset as usize), info.size as usize)
+            }
+        };
+        let end = info.flags & BUFFER_FLAG_END_OF_STREAM != 0;
+        let sample = Sample {
+            encoder: self,
+            index: index as usize,
+            info,
+            data,
+        };
+        // The codec specific data already is in the output format
+        if info.flags & BUFFER_FLAG_CODEC_CONFIG != 0 || data.is_empty() {
+            drop(sample);
+            return Ok(if end { Output::End } else { Output::Nothing });
+        }
+        if end {
+            drop(sample);
+            return Ok(Output::End);
+        }
+        Ok(Output::Sample(sample))
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe {
+                (api.stop)(self.codec);
+                (api.delete)(self.codec);
+            }
+        }
+    }
+}
+
+/// An `AMediaMuxer` writing an MP4 file, finished once dropped
+pub struct Muxer {
+    muxer: *mut c_void,
+    started: bool,
+}
+
+unsafe impl Send for Muxer {}
+
+impl Muxer {
+    /// Write into `fd`, which must be open for writing and stay open until the muxer is dropped
+    pub fn mp4(fd: i32) -> Result<Self, String> {
+        let api = api()?;
+        let muxer = unsafe { (api.muxer_new)(fd, OUTPUT_FORMAT_MPEG_4) };
+        if muxer.is_null() {
+            return Err("Failed to create the MP4 muxer".to_string());
+        }
+        Ok(Self {
+            muxer,
+            started: false,
+        })
+    }
+
+    /// Add a track of what `encoder` outputs, once its format is known. Returns the index of the track.
+    pub fn add_track(&mut self, encoder: &Encoder) -> Result<usize, String> {
+        let api = api()?;
+        let format = Format(unsafe { (api.get_output_format)(encoder.codec) });
+        let track = unsafe { (api.muxer_add_track)(self.muxer, format.0) };
+        if track < 0 {
+            return Err(format!("AMediaMuxer_addTrack failed with {}", track));
+        }
+        Ok(track as usize)
+    }
+
+    /// Start writing, once every track is added
+    pub fn start(&mut self) -> Result<(), String> {
+        let api = api()?;
+        check("AMediaMuxer_start", unsafe {
+            (api.muxer_start)(self.muxer)
+        })?;
+        self.started = true;
+        Ok(())
+    }
+
+    pub fn write(&mut self, track: usize, sample: &Sample) -> Result<(), String> {
+        self.write_data(track, sample.data, &sample.info)
+    }
+
+    pub fn write_owned(&mut self, track: usize, sample: &OwnedSample) -> Result<(), String> {
+        self.write_data(track, &sample.data, &sample.info)
+    }
+
+    fn write_data(&mut self, track: usize, data: &[u8], info: &BufferInfo) -> Result<(), String> {
+        let api = api()?;
+        let info = BufferInfo { offset: 0, ..*info };
+        check("AMediaMuxer_writeSampleData", unsafe {
+            (api.muxer_write)(self.muxer, track, data.as_ptr(), &info)
+        })
+    }
+}
+
+impl Drop for Muxer {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe {
+                if self.started {
+                    (api.muxer_stop)(self.muxer);
+                }
+                (api.muxer_delete)(self.muxer);
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::{
+    fs::{self, File},
+    io,
+    os::fd::{FromRawFd, OwnedFd},
+    path::Path,
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// The shared storage of the primary user, where the gallery looks for pictures and videos
+const SHARED_STORAGE: &str = "/storage/emulated/0";
+
+/// The folder of the collections the app saves into, e.g. `Pictures/Local Desktop`
+const APP_FOLDER: &str = "Local Desktop";
+
+/// Android 10 lets the apps add to the shared collections through the MediaStore without any permission, in the
+/// folder of their choice. Before, the files are written straight into the shared storage.
+const RELATIVE_PATH_SDK: i32 = 29;
+
+/// The collections of the shared storage the app saves captures and reports into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    Pictures,
+    Movies,
+    /// Where the crash reports are exported, for the user to send them
+    Downloads,
+}
+
+impl Collection {
+    fn folder(self) -> &'static str {
+        match self {
+            Self::Pictures => "Pictures",
+            Self::Movies => "Movies",
+            Self::Downloads => "Download",
+        }
+    }
+
+    /// The class holding the `EXTERNAL_CONTENT_URI` of the collection
+    fn media_class(self) -> &'static str {
+        match self {
+            Self::Pictures => "android/provider/MediaStore$Images$Media",
+            Self::Movies => "android/provider/MediaStore$Video$Media",
+            // Added with Android 10, before which the files are written straight into the shared storage
+            Self::Downloads => "android/provider/MediaStore$Downloads",
+        }
+    }
+}
+
+/// Save a file into a collection of the shared storage, in the folder of the app, where the gallery finds it. `write`
+/// fills the file, which the gallery only lists once it returns. Returns where the file is, as the user sees it.
+pub fn save(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    collection: Collection,
+    name: &str,
+    mime_type: &str,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> Result<String, String> {
+    let location = format!("{}/{}/{}", collection.folder(), APP_FOLDER, name);
+    let sdk = env
+        .get_static_field("android/os/Build$VERSION", "SDK_INT", "I")
+        .and_then(|it| it.i())
+        .map_err(|e| describe(env, e))?;
+    if sdk >= RELATIVE_PATH_SDK {
+        let (uri, fd) =
+            insert(env, android_app, collection, name, mime_type).map_err(|e| describe(env, e))?;
+        let written = write(&mut File::from(fd));
+        // A pending entry left behind is deleted by the MediaStore after a week
+        match written {
+            Ok(()) => publish(env, android_app, &uri).map_err(|e| describe(env, e))?,
+            Err(e) => {
+                let _ = delete(env, android_app, &uri);
+                return Err(format!("Failed to write {}: {}", location, e));
+            }
+        }
+    } else {
+        let path = Path::new(SHARED_STORAGE).join(&location);
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create(&path))
+            .and_then(|mut file| write(&mut file));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&path);
+            return Err(format!("Failed to write {}: {}", path.display(), e));
+        }
+        scan(env, android_app, &path.to_string_lossy(), mime_type).map_err(|e| describe(env, e))?;
+    }
+    Ok(location)
+}
+
+fn describe(env: &mut JNIEnv, error: jni::errors::Error) -> String {
+    let _ = env.exception_clear();
+    format!("The MediaStore failed: {:?}", error)
+}
+
+fn content_resolver<'local>(
+    env: &mut JNIEnv<'local>,
+    android_app: &AndroidApp,
+) -> jni::errors::Result<JObject<'local>> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    env.call_method(
+        activity,
+        "getContentResolver",
+        "()Landroid/content/ContentResolver;",
+        &[],
+    )?
+    .l()
+}
+
+fn put_string(
+    env: &mut JNIEnv,
+    values: &JObject,
+    key: &str,
+    value: &str,
+) -> jni::errors::Result<()> {
+    let key = env.new_string(key)?;
+    let value = env.new_string(value)?;
+    env.call_method(
+        values,
+        "put",
+        "(Ljava/lang/String;Ljava/lang/String;)V",
+        &[JValue::Object(&key), JValue::Object(&value)],
+    )?;
+    Ok(())
+}
+
+fn put_int(env: &mut JNIEnv, values: &JObject, key: &str, value: i32) -> jni::errors::Result<()> {
+    let key = env.new_string(key)?;
+    let value = env
+        .call_static_method(
+            "java/lang/Integer",
+            "valueOf",
+            "(I)Ljava/lang/Integer;",
+            &[JValue::Int(value)],
+        )?
+        .l()?;
+    env.call_method(
+        values,
+        "put",
+        "(Ljava/lang/String;Ljava/lang/Integer;)V",
+        &[JValue::Object(&key), JValue::Object(&value)],
+    )?;
+    Ok(())
+}
+
+/// Add a pending entry to the collection, and open its file for writing
+fn insert<'local>(
+    env: &mut JNIEnv<'local>,
+    android_app: &AndroidApp,
+    collection: Collection,
+    name: &str,
+    mime_type: &str,
+) -> jni::errors::Result<(JObject<'local>, OwnedFd)> {
+    let resolver = content_resolver(env, android_app)?;
+    let values = env.new_object("android/content/ContentValues", "()V", &[])?;
+    put_string(env, &values, "_display_name", name)?;
+    put_string(env, &values, "mime_type", mime_type)?;
+    put_string(
+        env,
+        &values,
+        "relative_path",
+        &format!("{}/{}", collection.folder(), APP_FOLDER),
+    )?;
+    put_int(env, &values, "is_pending", 1)?;
+    let collection_uri = env
+        .get_static_field(
+            collection.media_class(),
+            "EXTERNAL_CONTENT_URI",
+            "Landroid/net/Uri;",
+        )?
+        .l()?;
+    let uri = env
+        .call_method(
+            &resolver,
+            "insert",
+            "(Landroid/net/Uri;Landroid/content/ContentValues;)Landroid/net/Uri;",
+            &[JValue::Object(&collection_uri), JValue::Object(&values)],
+        )?
+        .l()?;
+    if uri.is_null() {
+        return Err(jni::errors::Error::NullPtr("ContentResolver.insert"));
+    }
+    let mode = env.new_string("w")?;
+    let descriptor = env
+        .call_method(
+            &resolver,
+            "openFileDescriptor",
+            "(Landroid/net/Uri;Ljava/lang/String;)Landroid/os/ParcelFileDescriptor;",
+            &[JValue::Object(&uri), JValue::Object(&mode)],
+        )?
+        .l()?;
+    let fd = env.call_method(&descriptor, "detachFd", "()I", &[])?.i()?;
+    // Detached, so the file descriptor is ours to close
+    Ok((uri, unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+/// Show a written entry in the collection
+fn publish(env: &mut JNIEnv, android_app: &AndroidApp, uri: &JObject) -> jni::errors::Result<()> {
+    let resolver = content_resolver(env, android_app)?;
+    let values = env.new_object("android/content/ContentValues", "()V", &[])?;
+    put_int(env, &values, "is_pending", 0)?;
+    env.call_method(
+        &resolver,
+        "update",
+        "(Landroid/net/Uri;Landroid/content/ContentValues;Ljava/lang/String;[Ljava/lang/String;)I",
+        &[
+            JValue::Object(uri),
+            JValue::Object(&values),
+            JValue::Object(&JObject::null()),
+            JValue::Object(&JObject::null()),
+        ],
+    )?;
+    Ok(())
+}
+
+fn delete(env: &mut JNIEnv, android_app: &AndroidApp, uri: &JObject) -> jni::errors::Result<()> {
+    let resolver = content_resolver(env, android_app)?;
+    let result = env.call_method(
+        &resolver,
+        "delete",
+        "(Landroid/net/Uri;Ljava/lang/String;[Ljava/lang/String;)I",
+        &[
+            JValue::Object(uri),
+            JValue::Object(&JObject::null()),
+            JValue::Object(&JObject::null()),
+        ],
+    );
+    if result.is_err() {
+        let _ = env.exception_clear();
+    }
+    result.map(|_| ())
+}
+
+/// Tell the MediaStore about a file written straight into the shared storage
+fn scan(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    path: &str,
+    mime_type: &str,
+) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let path = env.new_string(path)?;
+    let paths = env.new_object_array(1, "java/lang/String", &path)?;
+    let mime_type = env.new_string(mime_type)?;
+    let mime_types = env.new_object_array(1, "java/lang/String", &mime_type)?;
+    env.call_static_method(
+        "android/media/MediaScannerConnection",
+        "scanFile",
+        "(Landroid/content/Context;[Ljava/lang/String;[Ljava/lang/String;Landroid/media/MediaScannerConnection$OnScanCompletedListener;)V",
+        &[
+            JValue::Object(&activity),
+            JValue::Object(&paths),
+            JValue::Object(&mime_types),
+            JValue::Object(&JObject::null()),
+        ],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use super::{application_context::get_application_context, ndk::run_in_jvm};
+use crate::android::{
+    app::attach::{attached_app, wake_event_loop},
+    proot::packages::pause_package_manager,
+};
+use crate::core::{
+    logging::{compact_panel_log, panel_log},
+    memory::MemoryPressure,
+};
+use jni::objects::JValue;
+use jni::JNIEnv;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often the trim level is read, as NativeActivity does not forward `onTrimMemory` and there is no Java class
+/// of our own to register for it
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The latest pressure reported
+static PRESSURE: Mutex<MemoryPressure> = Mutex::new(MemoryPressure::None);
+
+/// Whether the pressure changed since the main thread last gave memory back
+static CHANGED: AtomicBool = AtomicBool::new(false);
+
+static WATCH: Once = Once::new();
+
+/// Watch (once) how hard Android asks for memory, waking the event loop up for the main thread to give some back.
+/// pacman is stopped meanwhile with `[memory] pause_pacman`.
+pub fn watch_memory_pressure() {
+    WATCH.call_once(|| {
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            let Some(android_app) = attached_app() else {
+                continue;
+            };
+            let mut level = None;
+            run_in_jvm(
+                |env, _| match trim_level(env) {
+                    Ok(it) => level = Some(it),
+                    Err(_) => {
+                        let _ = env.exception_clear();
+                    }
+                },
+                android_app,
+            );
+            if let Some(level) = level {
+                if report(MemoryPressure::from_trim_level(level)) {
+                    wake_event_loop();
+                }
+            }
+        });
+    });
+}
+
+/// Android called `onLowMemory`, the last warning before killing the apps in the foreground
+pub fn report_low_memory() {
+    report(MemoryPressure::Critical);
+}
+
+/// The pressure, if it changed since the last call, for the main thread to give memory back
+pub fn take_memory_pressure() -> Option<MemoryPressure> {
+    CHANGED
+        .swap(false, Ordering::SeqCst)
+        .then(|| *PRESSURE.lock().unwrap())
+}
+
+/// Give back what the other threads can at `pressure`, returning whether it changed
+fn report(pressure: MemoryPressure) -> bool {
+    let mut current = PRESSURE.lock().unwrap();
+    let pause_pacman = get_application_context().local_config.memory.pause_pacman;
+    if *current == pressure {
+        // The transactions started meanwhile are stopped too
+        if pause_pacman && pressure.pauses_pacman() {
+            pause_package_manager(true);
+        }
+        return false;
+    }
+    if pressure > *current && pressure >= MemoryPressure::Low {
+        panel_log(
+            format!(
+                "Android runs low on memory ({:?}), giving some back",
+                pressure
+            ),
+            true,
+        );
+    } else {
+        log::info!("The memory pressure is now {:?}", pressure);
+    }
+    if pause_pacman && pressure.pauses_pacman() != current.pauses_pacman() {
+        pause_package_manager(pressure.pauses_pacman());
+    }
+    if let Some(keep) = pressure.panel_log_entries() {
+        compact_panel_log(keep);
+    }
+    *current = pressure;
+    CHANGED.store(true, Ordering::SeqCst);
+    true
+}
+
+/// The level of the last `onTrimMemory` of the app, which Android keeps even without a callback registered
+fn trim_level(env: &mut JNIEnv) -> jni::errors::Result<i32> {
+    let info = env.new_object(
+        "android/app/ActivityManager$RunningAppProcessInfo",
+        "()V",
+        &[],
+    )?;
+    env.call_static_method(
+        "android/app/ActivityManager",
+        "getMyMemoryState",
+        "(Landroid/app/ActivityManager$RunningAppProcessInfo;)V",
+        &[JValue::Object(&info)],
+    )?;
+    env.get_field(&info, "lastTrimLevel", "I")?.i()
+}
@@ -1,6 +1,12 @@
 use crate::core::logging::PolarBearExpectation;
+use jni::objects::{JObject, JString, JValue};
 use jni::sys::JNIInvokeInterface_;
 use jni::{JNIEnv, JavaVM};
+use std::{
+    ffi::CString,
+    io::Read,
+    os::fd::{FromRawFd, OwnedFd},
+};
 use winit::platform::android::activity::AndroidApp;
 
 /// A higher-order function to run a provided JNI function within the JVM context.
@@ -23,3 +29,255 @@ where
     // Detach the current thread from the JVM
     unsafe { vm.detach_current_thread() };
 }
+
+/// Read a whole file bundled in the APK assets
+pub fn read_asset(android_app: &AndroidApp, name: &str) -> Result<Vec<u8>, String> {
+    let filename = CString::new(name).map_err(|e| e.to_string())?;
+    let mut asset = android_app
+        .asset_manager()
+        .open(&filename)
+        .ok_or_else(|| format!("Asset {} not found", name))?;
+    let mut bytes = Vec::new();
+    asset
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read asset {}: {}", name, e))?;
+    Ok(bytes)
+}
+
+fn clipboard_manager<'local>(
+    env: &mut JNIEnv<'local>,
+    android_app: &AndroidApp,
+) -> jni::errors::Result<JObject<'local>> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let service = env.new_string("clipboard")?;
+    env.call_method(
+        activity,
+        "getSystemService",
+        "(Ljava/lang/String;)Ljava/lang/Object;",
+        &[JValue::Object(&service)],
+    )?
+    .l()
+}
+
+/// The text of the Android primary clip, if any.
+/// Android only lets the focused app read the clipboard, so call this when the activity gains focus.
+pub fn get_clipboard_text(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+) -> jni::errors::Result<Option<String>> {
+    let clipboard = clipboard_manager(env, android_app)?;
+    let clip = env
+        .call_method(
+            &clipboard,
+            "getPrimaryClip",
+            "()Landroid/content/ClipData;",
+            &[],
+        )?
+        .l()?;
+    if clip.is_null() || env.call_method(&clip, "getItemCount", "()I", &[])?.i()? == 0 {
+        return Ok(None);
+    }
+    let item = env
+        .call_method(
+            &clip,
+            "getItemAt",
+            "(I)Landroid/content/ClipData$Item;",
+            &[JValue::Int(0)],
+        )?
+        .l()?;
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let text = env
+        .call_method(
+            &item,
+            "coerceToText",
+            "(Landroid/content/Context;)Ljava/lang/CharSequence;",
+            &[JValue::Object(&activity)],
+        )?
+        .l()?;
+    if text.is_null() {
+        return Ok(None);
+    }
+    let text = env
+        .call_method(&text, "toString", "()Ljava/lang/String;", &[])?
+        .l()?;
+    Ok(Some(env.get_string(&JString::from(text))?.into()))
+}
+
+/// Replace the Android primary clip with some plain text
+pub fn set_clipboard_text(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    text: &str,
+) -> jni::errors::Result<()> {
+    let clipboard = clipboard_manager(env, android_app)?;
+    let label = env.new_string("Local Desktop")?;
+    let text = env.new_string(text)?;
+    let clip = env
+        .call_static_method(
+            "android/content/ClipData",
+            "newPlainText",
+            "(Ljava/lang/CharSequence;Ljava/lang/CharSequence;)Landroid/content/ClipData;",
+            &[JValue::Object(&label), JValue::Object(&text)],
+        )?
+        .l()?;
+    env.call_method(
+        &clipboard,
+        "setPrimaryClip",
+        "(Landroid/content/ClipData;)V",
+        &[JValue::Object(&clip)],
+    )?;
+    Ok(())
+}
+
+/// A string extra of the intent that started the activity, e.g. given with `adb shell am start --es <name> <value>`
+pub fn get_intent_string_extra(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    name: &str,
+) -> jni::errors::Result<Option<String>> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let intent = env
+        .call_method(activity, "getIntent", "()Landroid/content/Intent;", &[])?
+        .l()?;
+    if intent.is_null() {
+        return Ok(None);
+    }
+    let name = env.new_string(name)?;
+    let value = env
+        .call_method(
+            &intent,
+            "getStringExtra",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+            &[JValue::Object(&name)],
+        )?
+        .l()?;
+    if value.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(env.get_string(&JString::from(value))?.into()))
+}
+
+/// The data URI of the intent that started the activity, e.g. `content://...` when a file manager opened a file with the app
+pub fn get_intent_data(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+) -> jni::errors::Result<Option<String>> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let intent = env
+        .call_method(activity, "getIntent", "()Landroid/content/Intent;", &[])?
+        .l()?;
+    if intent.is_null() {
+        return Ok(None);
+    }
+    let data = env
+        .call_method(&intent, "getDataString", "()Ljava/lang/String;", &[])?
+        .l()?;
+    if data.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(env.get_string(&JString::from(data))?.into()))
+}
+
+/// Open a `content://` URI for reading through the content resolver, as the Storage Access Framework hands them out
+pub fn open_content_uri(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    uri: &str,
+) -> jni::errors::Result<OwnedFd> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let resolver = env
+        .call_method(
+            activity,
+            "getContentResolver",
+            "()Landroid/content/ContentResolver;",
+            &[],
+        )?
+        .l()?;
+    let uri = env.new_string(uri)?;
+    let uri = env
+        .call_static_method(
+            "android/net/Uri",
+            "parse",
+            "(Ljava/lang/String;)Landroid/net/Uri;",
+            &[JValue::Object(&uri)],
+        )?
+        .l()?;
+    let mode = env.new_string("r")?;
+    let descriptor = env
+        .call_method(
+            &resolver,
+            "openFileDescriptor",
+            "(Landroid/net/Uri;Ljava/lang/String;)Landroid/os/ParcelFileDescriptor;",
+            &[JValue::Object(&uri), JValue::Object(&mode)],
+        )?
+        .l()?;
+    let fd = env.call_method(&descriptor, "detachFd", "()I", &[])?.i()?;
+    // Detached, so the file descriptor is ours to close
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Whether the app holds a runtime permission, e.g. `android.permission.RECORD_AUDIO`
+pub fn has_permission(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    permission: &str,
+) -> jni::errors::Result<bool> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let permission = env.new_string(permission)?;
+    let result = env
+        .call_method(
+            activity,
+            "checkSelfPermission",
+            "(Ljava/lang/String;)I",
+            &[JValue::Object(&permission)],
+        )?
+        .i()?;
+    // PackageManager.PERMISSION_GRANTED
+    Ok(result == 0)
+}
+
+/// Ask for a runtime permission. The answer is not waited for, so check the permission again when it is next needed.
+pub fn request_permission(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    permission: &str,
+) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let permission = env.new_string(permission)?;
+    let permissions = env.new_object_array(1, "java/lang/String", &permission)?;
+    env.call_method(
+        activity,
+        "requestPermissions",
+        "([Ljava/lang/String;I)V",
+        &[JValue::Object(&permissions), JValue::Int(0)],
+    )?;
+    Ok(())
+}
+
+/// Open a URI with the app of Android the user picked for it, e.g. the browser for a web page. Fails when no app
+/// handles it.
+pub fn view_uri(env: &mut JNIEnv, android_app: &AndroidApp, uri: &str) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let uri = env.new_string(uri)?;
+    let uri = env
+        .call_static_method(
+            "android/net/Uri",
+            "parse",
+            "(Ljava/lang/String;)Landroid/net/Uri;",
+            &[JValue::Object(&uri)],
+        )?
+        .l()?;
+    let action = env.new_string("android.intent.action.VIEW")?;
+    let intent = env.new_object(
+        "android/content/Intent",
+        "(Ljava/lang/String;Landroid/net/Uri;)V",
+        &[JValue::Object(&action), JValue::Object(&uri)],
+    )?;
+    env.call_method(
+        activity,
+        "startActivity",
+        "(Landroid/content/Intent;)V",
+        &[JValue::Object(&intent)],
+    )?;
+    Ok(())
+}
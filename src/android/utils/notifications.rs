@@ -0,0 +1,235 @@
+use super::ndk::{has_permission, request_permission};
+use crate::core::notifications::GuestNotification;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::sync::atomic::{AtomicBool, Ordering};
+use winit::platform::android::activity::AndroidApp;
+
+/// The notifications of the session are tagged, so that their ids never collide with those of the app
+const TAG: &str = "session";
+
+const CHANNEL_ID: &str = "session";
+const CHANNEL_NAME: &str = "Desktop apps";
+
+/// `NotificationManager.IMPORTANCE_DEFAULT`
+const IMPORTANCE_DEFAULT: i32 = 3;
+
+/// The notification channels came with Android 8, before which the priority of each notification applies
+const CHANNELS_SDK: i32 = 26;
+
+/// `PendingIntent.FLAG_IMMUTABLE`
+const FLAG_IMMUTABLE: i32 = 0x0400_0000;
+
+const PERMISSION: &str = "android.permission.POST_NOTIFICATIONS";
+
+/// The permission is only asked for once per process, on the first notification
+static PERMISSION_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Show a notification of the session, replacing the one of the same id. Tapping it brings the desktop back.
+pub fn post(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    notification: &GuestNotification,
+) -> jni::errors::Result<()> {
+    // Android 13 drops the notifications until the user allows them
+    if !has_permission(env, android_app, PERMISSION)?
+        && !PERMISSION_REQUESTED.swap(true, Ordering::SeqCst)
+    {
+        request_permission(env, android_app, PERMISSION)?;
+    }
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let manager = notification_manager(env, &activity)?;
+    let sdk = env
+        .get_static_field("android/os/Build$VERSION", "SDK_INT", "I")?
+        .i()?;
+    let builder = if sdk >= CHANNELS_SDK {
+        create_channel(env, &manager)?;
+        let channel = env.new_string(CHANNEL_ID)?;
+        env.new_object(
+            "android/app/Notification$Builder",
+            "(Landroid/content/Context;Ljava/lang/String;)V",
+            &[JValue::Object(&activity), JValue::Object(&channel)],
+        )?
+    } else {
+        env.new_object(
+            "android/app/Notification$Builder",
+            "(Landroid/content/Context;)V",
+            &[JValue::Object(&activity)],
+        )?
+    };
+
+    let info = env
+        .call_method(
+            &activity,
+            "getApplicationInfo",
+            "()Landroid/content/pm/ApplicationInfo;",
+            &[],
+        )?
+        .l()?;
+    let icon = env.get_field(&info, "icon", "I")?.i()?;
+    env.call_method(
+        &builder,
+        "setSmallIcon",
+        "(I)Landroid/app/Notification$Builder;",
+        &[JValue::Int(icon)],
+    )?;
+    for (setter, text) in [
+        ("setContentTitle", notification.title()),
+        ("setContentText", notification.body.as_str()),
+        ("setSubText", notification.app_name.as_str()),
+    ] {
+        if text.is_empty() {
+            continue;
+        }
+        let text = env.new_string(text)?;
+        env.call_method(
+            &builder,
+            setter,
+            "(Ljava/lang/CharSequence;)Landroid/app/Notification$Builder;",
+            &[JValue::Object(&text)],
+        )?;
+    }
+    // The whole body shows once the notification is expanded
+    let body = env.new_string(&notification.body)?;
+    let style = env.new_object("android/app/Notification$BigTextStyle", "()V", &[])?;
+    env.call_method(
+        &style,
+        "bigText",
+        "(Ljava/lang/CharSequence;)Landroid/app/Notification$BigTextStyle;",
+        &[JValue::Object(&body)],
+    )?;
+    env.call_method(
+        &builder,
+        "setStyle",
+        "(Landroid/app/Notification$Style;)Landroid/app/Notification$Builder;",
+        &[JValue::Object(&style)],
+    )?;
+    env.call_method(
+        &builder,
+        "setPriority",
+        "(I)Landroid/app/Notification$Builder;",
+        &[JValue::Int(notification.priority())],
+    )?;
+    env.call_method(
+        &builder,
+        "setAutoCancel",
+        "(Z)Landroid/app/Notification$Builder;",
+        &[JValue::Bool(1)],
+    )?;
+    let intent = launch_intent(env, &activity)?;
+    if !intent.is_null() {
+        env.call_method(
+            &builder,
+            "setContentIntent",
+            "(Landroid/app/PendingIntent;)Landroid/app/Notification$Builder;",
+            &[JValue::Object(&intent)],
+        )?;
+    }
+
+    let built = env
+        .call_method(&builder, "build", "()Landroid/app/Notification;", &[])?
+        .l()?;
+    let tag = env.new_string(TAG)?;
+    env.call_method(
+        &manager,
+        "notify",
+        "(Ljava/lang/String;ILandroid/app/Notification;)V",
+        &[
+            JValue::Object(&tag),
+            JValue::Int(notification.id as i32),
+            JValue::Object(&built),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Remove a notification of the session, once its app closed it
+pub fn cancel(env: &mut JNIEnv, android_app: &AndroidApp, id: u32) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let manager = notification_manager(env, &activity)?;
+    let tag = env.new_string(TAG)?;
+    env.call_method(
+        &manager,
+        "cancel",
+        "(Ljava/lang/String;I)V",
+        &[JValue::Object(&tag), JValue::Int(id as i32)],
+    )?;
+    Ok(())
+}
+
+fn notification_manager<'local>(
+    env: &mut JNIEnv<'local>,
+    activity: &JObject,
+) -> jni::errors::Result<JObject<'local>> {
+    let service = env.new_string("notification")?;
+    env.call_method(
+        activity,
+        "getSystemService",
+        "(Ljava/lang/String;)Ljava/lang/Object;",
+        &[JValue::Object(&service)],
+    )?
+    .l()
+}
+
+/// Creating the channel again is a no-op, and keeps the importance the user chose for it
+fn create_channel(env: &mut JNIEnv, manager: &JObject) -> jni::errors::Result<()> {
+    let id = env.new_string(CHANNEL_ID)?;
+    let name = env.new_string(CHANNEL_NAME)?;
+    let channel = env.new_object(
+        "android/app/NotificationChannel",
+        "(Ljava/lang/String;Ljava/lang/CharSequence;I)V",
+        &[
+            JValue::Object(&id),
+            JValue::Object(&name),
+            JValue::Int(IMPORTANCE_DEFAULT),
+        ],
+    )?;
+    env.call_method(
+        manager,
+        "createNotificationChannel",
+        "(Landroid/app/NotificationChannel;)V",
+        &[JValue::Object(&channel)],
+    )?;
+    Ok(())
+}
+
+/// What opens the app, if it has a launcher activity
+fn launch_intent<'local>(
+    env: &mut JNIEnv<'local>,
+    activity: &JObject,
+) -> jni::errors::Result<JObject<'local>> {
+    let package_manager = env
+        .call_method(
+            activity,
+            "getPackageManager",
+            "()Landroid/content/pm/PackageManager;",
+            &[],
+        )?
+        .l()?;
+    let package = env
+        .call_method(activity, "getPackageName", "()Ljava/lang/String;", &[])?
+        .l()?;
+    let intent = env
+        .call_method(
+            &package_manager,
+            "getLaunchIntentForPackage",
+            "(Ljava/lang/String;)Landroid/content/Intent;",
+            &[JValue::Object(&package)],
+        )?
+        .l()?;
+    if intent.is_null() {
+        return Ok(intent);
+    }
+    env.call_static_method(
+        "android/app/PendingIntent",
+        "getActivity",
+        "(Landroid/content/Context;ILandroid/content/Intent;I)Landroid/app/PendingIntent;",
+        &[
+            JValue::Object(activity),
+            JValue::Int(0),
+            JValue::Object(&intent),
+            JValue::Int(FLAG_IMMUTABLE),
+        ],
+    )?
+    .l()
+}
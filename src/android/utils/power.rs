@@ -0,0 +1,90 @@
+use super::{application_context::get_application_context, ndk::run_in_jvm};
+use crate::android::{app::attach::attached_app, proot::packages::package_manager_running};
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::JNIEnv;
+use std::{sync::Once, thread, time::Duration};
+use winit::platform::android::activity::AndroidApp;
+
+/// How often pacman is looked for
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `PowerManager.PARTIAL_WAKE_LOCK`: the CPU runs on with the screen off
+const PARTIAL_WAKE_LOCK: i32 = 1;
+
+const WAKE_LOCK_TAG: &str = "localdesktop:pacman";
+
+static WATCH: Once = Once::new();
+
+/// Keep the CPU running (once) while pacman does, with `[power] wake_lock_during_pacman`, so that a long upgrade
+/// goes on as the screen turns off rather than being left halfway
+pub fn hold_wake_lock_for_pacman() {
+    WATCH.call_once(|| {
+        thread::spawn(move || {
+            let mut wake_lock: Option<GlobalRef> = None;
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let wanted = get_application_context()
+                    .local_config
+                    .power
+                    .wake_lock_during_pacman
+                    && package_manager_running();
+                if wanted == wake_lock.is_some() {
+                    continue;
+                }
+                let Some(android_app) = attached_app() else {
+                    continue;
+                };
+                run_in_jvm(
+                    |env, app| {
+                        let result = match wake_lock.take() {
+                            Some(lock) => release(env, &lock),
+                            None => acquire(env, app).map(|it| {
+                                wake_lock = Some(it);
+                            }),
+                        };
+                        match result {
+                            Ok(()) if wake_lock.is_some() => {
+                                log::info!("pacman runs, keeping the CPU awake")
+                            }
+                            Ok(()) => log::info!("pacman is done, letting the CPU sleep"),
+                            Err(e) => {
+                                let _ = env.exception_clear();
+                                log::warn!("Failed to set the wake lock: {}", e);
+                            }
+                        }
+                    },
+                    android_app,
+                );
+            }
+        });
+    });
+}
+
+fn acquire(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<GlobalRef> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let service = env.new_string("power")?;
+    let power_manager = env
+        .call_method(
+            &activity,
+            "getSystemService",
+            "(Ljava/lang/String;)Ljava/lang/Object;",
+            &[JValue::Object(&service)],
+        )?
+        .l()?;
+    let tag = env.new_string(WAKE_LOCK_TAG)?;
+    let wake_lock = env
+        .call_method(
+            &power_manager,
+            "newWakeLock",
+            "(ILjava/lang/String;)Landroid/os/PowerManager$WakeLock;",
+            &[JValue::Int(PARTIAL_WAKE_LOCK), JValue::Object(&tag)],
+        )?
+        .l()?;
+    env.call_method(&wake_lock, "acquire", "()V", &[])?;
+    env.new_global_ref(wake_lock)
+}
+
+fn release(env: &mut JNIEnv, wake_lock: &GlobalRef) -> jni::errors::Result<()> {
+    env.call_method(wake_lock, "release", "()V", &[])?;
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use super::{ndk::run_in_jvm, session_service};
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::JNIEnv;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use winit::platform::android::activity::AndroidApp;
+
+/// `DialogInterface.BUTTON_POSITIVE` and `BUTTON_NEGATIVE`
+const BUTTON_POSITIVE: i32 = -1;
+const BUTTON_NEGATIVE: i32 = -2;
+
+/// The `what` of the messages the dialog sends as it is answered
+const QUIT: i32 = 1;
+const DISMISSED: i32 = 2;
+
+/// How often the dialog is checked for an answer
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the dialog is shown, so that pressing Back again does not stack another one
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Ask whether to close the app, and close it on Quit, without blocking the caller.
+///
+/// No Java class of the app listens to the buttons. Instead, they send messages to a `Handler` whose `Looper` never
+/// runs, where the messages stay queued until the thread of that Looper finds them.
+pub fn confirm_quit(android_app: AndroidApp) {
+    if SHOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || {
+        run_in_jvm(
+            |env, app| {
+                if let Err(e) = wait_for_answer(env, app) {
+                    let _ = env.exception_clear();
+                    log::error!("Failed to ask whether to quit: {:?}", e);
+                }
+            },
+            android_app,
+        );
+        SHOWN.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Show the dialog on a thread of its own, and close the app once Quit is pressed
+fn wait_for_answer(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    env.call_static_method("android/os/Looper", "prepare", "()V", &[])?;
+    let looper = my_looper(env)?;
+    let handler = env.new_object(
+        "android/os/Handler",
+        "(Landroid/os/Looper;)V",
+        &[(&looper).into()],
+    )?;
+    let handler = env.new_global_ref(handler)?;
+
+    // The dialog needs a Looper of its own, which blocks its thread until the dialog is gone
+    let (sender, receiver) = mpsc::channel();
+    let app = android_app.clone();
+    let answers = handler.clone();
+    thread::spawn(move || {
+        run_in_jvm(
+            |env, app| {
+                if let Err(e) = show_dialog(env, app, &answers, &sender) {
+                    let _ = env.exception_clear();
+                    log::error!("Failed to show the quit dialog: {:?}", e);
+                }
+            },
+            app,
+        );
+    });
+    let Ok(dialog_looper) = receiver.recv() else {
+        return Ok(());
+    };
+
+    let has_message = |env: &mut JNIEnv, what: i32| {
+        env.call_method(
+            handler.as_obj(),
+            "hasMessages",
+            "(I)Z",
+            &[JValue::Int(what)],
+        )?
+        .z()
+    };
+    while !has_message(env, DISMISSED)? {
+        thread::sleep(POLL_INTERVAL);
+    }
+    env.call_method(dialog_looper.as_obj(), "quit", "()V", &[])?;
+    let quit = has_message(env, QUIT)?;
+    // Drop the answers along with the Looper they were queued on
+    env.call_method(&looper, "quit", "()V", &[])?;
+    if quit {
+        log::info!("Quitting, as the Back button was confirmed");
+        // The session ends along with the activity
+        session_service::stop(env, android_app)?;
+        let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+        env.call_method(&activity, "finish", "()V", &[])?;
+    }
+    Ok(())
+}
+
+/// Build the dialog, whose buttons send their messages to `handler`, and run it until `wait_for_answer` quits its
+/// Looper
+fn show_dialog(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    handler: &GlobalRef,
+    sender: &mpsc::Sender<GlobalRef>,
+) -> jni::errors::Result<()> {
+    env.call_static_method("android/os/Looper", "prepare", "()V", &[])?;
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+
+    let builder = env.new_object(
+        "android/app/AlertDialog$Builder",
+        "(Landroid/content/Context;)V",
+        &[(&activity).into()],
+    )?;
+    let title = env.new_string("Quit Local Desktop?")?;
+    env.call_method(
+        &builder,
+        "setTitle",
+        "(Ljava/lang/CharSequence;)Landroid/app/AlertDialog$Builder;",
+        &[(&title).into()],
+    )?;
+    let message = env.new_string("The desktop stops, along with the apps running in it.")?;
+    env.call_method(
+        &builder,
+        "setMessage",
+        "(Ljava/lang/CharSequence;)Landroid/app/AlertDialog$Builder;",
+        &[(&message).into()],
+    )?;
+    let dialog = env
+        .call_method(&builder, "create", "()Landroid/app/AlertDialog;", &[])?
+        .l()?;
+
+    let quit = obtain_message(env, handler, QUIT)?;
+    let label = env.new_string("Quit")?;
+    env.call_method(
+        &dialog,
+        "setButton",
+        "(ILjava/lang/CharSequence;Landroid/os/Message;)V",
+        &[
+            JValue::Int(BUTTON_POSITIVE),
+            (&label).into(),
+            (&quit).into(),
+        ],
+    )?;
+    // Cancel only dismisses the dialog, as does tapping outside of it or pressing Back again
+    let label = env.new_string("Cancel")?;
+    env.call_method(
+        &dialog,
+        "setButton",
+        "(ILjava/lang/CharSequence;Landroid/os/Message;)V",
+        &[
+            JValue::Int(BUTTON_NEGATIVE),
+            (&label).into(),
+            (&JObject::null()).into(),
+        ],
+    )?;
+    let dismissed = obtain_message(env, handler, DISMISSED)?;
+    env.call_method(
+        &dialog,
+        "setDismissMessage",
+        "(Landroid/os/Message;)V",
+        &[(&dismissed).into()],
+    )?;
+    env.call_method(&dialog, "show", "()V", &[])?;
+
+    let looper = my_looper(env)?;
+    let _ = sender.send(env.new_global_ref(looper)?);
+    env.call_static_method("android/os/Looper", "loop", "()V", &[])?;
+    Ok(())
+}
+
+fn obtain_message<'local>(
+    env: &mut JNIEnv<'local>,
+    handler: &GlobalRef,
+    what: i32,
+) -> jni::errors::Result<JObject<'local>> {
+    env.call_static_method(
+        "android/os/Message",
+        "obtain",
+        "(Landroid/os/Handler;I)Landroid/os/Message;",
+        &[handler.as_obj().into(), JValue::Int(what)],
+    )?
+    .l()
+}
+
+fn my_looper<'local>(env: &mut JNIEnv<'local>) -> jni::errors::Result<JObject<'local>> {
+    env.call_static_method(
+        "android/os/Looper",
+        "myLooper",
+        "()Landroid/os/Looper;",
+        &[],
+    )?
+    .l()
+}
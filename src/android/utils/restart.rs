@@ -0,0 +1,101 @@
+use super::ndk::run_in_jvm;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use winit::platform::android::activity::AndroidApp;
+
+/// How long after asking the app starts again, for the process to be gone by then
+const RESTART_DELAY_MS: i64 = 500;
+
+/// `AlarmManager.RTC`
+const RTC: i32 = 1;
+
+/// `Intent.FLAG_ACTIVITY_NEW_TASK | Intent.FLAG_ACTIVITY_CLEAR_TASK`
+const NEW_TASK_FLAGS: i32 = 0x1000_0000 | 0x0000_8000;
+
+/// `PendingIntent.FLAG_IMMUTABLE | PendingIntent.FLAG_CANCEL_CURRENT`
+const PENDING_INTENT_FLAGS: i32 = 0x0400_0000 | 0x1000_0000;
+
+/// Have Android start the app again shortly, through an alarm that outlives the process
+pub fn schedule_restart(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let package_manager = env
+        .call_method(
+            &activity,
+            "getPackageManager",
+            "()Landroid/content/pm/PackageManager;",
+            &[],
+        )?
+        .l()?;
+    let package_name = env
+        .call_method(&activity, "getPackageName", "()Ljava/lang/String;", &[])?
+        .l()?;
+    let intent = env
+        .call_method(
+            &package_manager,
+            "getLaunchIntentForPackage",
+            "(Ljava/lang/String;)Landroid/content/Intent;",
+            &[JValue::Object(&package_name)],
+        )?
+        .l()?;
+    if intent.is_null() {
+        return Err(jni::errors::Error::NullPtr("getLaunchIntentForPackage"));
+    }
+    env.call_method(
+        &intent,
+        "addFlags",
+        "(I)Landroid/content/Intent;",
+        &[JValue::Int(NEW_TASK_FLAGS)],
+    )?;
+    let pending_intent = env
+        .call_static_method(
+            "android/app/PendingIntent",
+            "getActivity",
+            "(Landroid/content/Context;ILandroid/content/Intent;I)Landroid/app/PendingIntent;",
+            &[
+                JValue::Object(&activity),
+                JValue::Int(0),
+                JValue::Object(&intent),
+                JValue::Int(PENDING_INTENT_FLAGS),
+            ],
+        )?
+        .l()?;
+
+    let service = env.new_string("alarm")?;
+    let alarm_manager = env
+        .call_method(
+            &activity,
+            "getSystemService",
+            "(Ljava/lang/String;)Ljava/lang/Object;",
+            &[JValue::Object(&service)],
+        )?
+        .l()?;
+    let now = env
+        .call_static_method("java/lang/System", "currentTimeMillis", "()J", &[])?
+        .j()?;
+    env.call_method(
+        &alarm_manager,
+        "set",
+        "(IJLandroid/app/PendingIntent;)V",
+        &[
+            JValue::Int(RTC),
+            JValue::Long(now + RESTART_DELAY_MS),
+            JValue::Object(&pending_intent),
+        ],
+    )?;
+    Ok(())
+}
+
+/// End the process and start the app again, e.g. once the recovery screen dealt with a crash. The user starts it
+/// again if the alarm could not be set.
+pub fn restart_app(android_app: &AndroidApp) -> ! {
+    run_in_jvm(
+        |env, app| {
+            if let Err(e) = schedule_restart(env, app) {
+                let _ = env.exception_clear();
+                log::error!("Failed to schedule the restart of the app: {:?}", e);
+            }
+        },
+        android_app.clone(),
+    );
+    std::process::exit(0);
+}
@@ -0,0 +1,79 @@
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::sync::atomic::{AtomicBool, Ordering};
+use winit::platform::android::activity::AndroidApp;
+
+/// The foreground service of `kotlin/SessionService.kt`, declared in `manifest.yaml`
+const SERVICE_CLASS: &str = "app.polarbear.SessionService";
+
+/// `Context.startForegroundService` came with Android 8, before which a started service may go foreground as well
+const FOREGROUND_SERVICE_SDK: i32 = 26;
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+fn service_intent<'local>(
+    env: &mut JNIEnv<'local>,
+    activity: &JObject,
+) -> jni::errors::Result<JObject<'local>> {
+    let package_name = env
+        .call_method(activity, "getPackageName", "()Ljava/lang/String;", &[])?
+        .l()?;
+    let class_name = env.new_string(SERVICE_CLASS)?;
+    let intent = env.new_object("android/content/Intent", "()V", &[])?;
+    env.call_method(
+        &intent,
+        "setClassName",
+        "(Ljava/lang/String;Ljava/lang/String;)Landroid/content/Intent;",
+        &[JValue::Object(&package_name), JValue::Object(&class_name)],
+    )?;
+    Ok(intent)
+}
+
+/// Keep the process in the foreground from now on, so that the sessions outlive the activity, e.g. once the app is
+/// swiped away. Starting it again is a no-op.
+pub fn start(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let intent = service_intent(env, &activity)?;
+    let sdk = env
+        .get_static_field("android/os/Build$VERSION", "SDK_INT", "I")?
+        .i()?;
+    let method = if sdk >= FOREGROUND_SERVICE_SDK {
+        "startForegroundService"
+    } else {
+        "startService"
+    };
+    let result = env.call_method(
+        &activity,
+        method,
+        "(Landroid/content/Intent;)Landroid/content/ComponentName;",
+        &[JValue::Object(&intent)],
+    );
+    if result.is_err() {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+    result.map(|_| ())
+}
+
+/// Let the process go along with the activity, e.g. as the user quits
+pub fn stop(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    if !RUNNING.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let intent = service_intent(env, &activity)?;
+    env.call_method(
+        &activity,
+        "stopService",
+        "(Landroid/content/Intent;)Z",
+        &[JValue::Object(&intent)],
+    )?;
+    Ok(())
+}
+
+/// Whether the sessions are meant to outlive the activity
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
@@ -0,0 +1,133 @@
+use super::{
+    application_context::{get_application_context, update_local_config},
+    webview::{accept_page, page_token, show_webview_overlay},
+};
+use crate::android::backend::wayland::{
+    EDGE_SWIPE_DISTANCE, EDGE_WIDTH, MULTI_TAP_DURATION, TAP_SLOP,
+};
+use crate::core::{
+    config::{
+        is_valid_username, save_config, BackAction, BellSignal, GestureAction, GpuRenderer,
+        KeepScreenOn, LinksOnAndroid, LocalConfig,
+    },
+    logging::{panel_log, PolarBearExpectation},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::thread;
+use websocket::sync::Server;
+use websocket::OwnedMessage;
+use winit::platform::android::activity::AndroidApp;
+
+static SETTINGS_PORT: OnceLock<u16> = OnceLock::new();
+
+/// What `settings.html` sends, e.g. `{"save": {"user": {"username": "alice", ...}, ...}}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Request {
+    /// The whole config, as edited on the page
+    Save(Value),
+}
+
+/// The config as the page shows it, along with the outcome of the last save if any
+fn config_message(error: Option<String>, saved: bool) -> OwnedMessage {
+    let config =
+        serde_json::to_value(get_application_context().local_config).unwrap_or(Value::Null);
+    // Every text of `[gestures]` is an action, picked from a list
+    let mut actions: serde_json::Map<String, Value> = config["gestures"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, value)| value.is_string())
+        .map(|(key, _)| (key.clone(), json!(GestureAction::NAMES)))
+        .collect();
+    actions.insert("back".to_string(), json!(BackAction::NAMES));
+    OwnedMessage::Text(
+        json!({
+            "config": config,
+            "choices": {
+                "gestures": actions,
+                "bell": { "signal": BellSignal::NAMES },
+                "gpu": { "renderer": GpuRenderer::NAMES },
+                "power": { "keep_screen_on": KeepScreenOn::NAMES },
+                "open": { "links_on_android": LinksOnAndroid::NAMES },
+            },
+            // How the compositor tells the gestures apart, for the page to preview them
+            "gestures": {
+                "edgeWidth": EDGE_WIDTH,
+                "swipeDistance": EDGE_SWIPE_DISTANCE,
+                "tapDuration": MULTI_TAP_DURATION.as_millis() as u64,
+                "tapSlop": TAP_SLOP,
+            },
+            "error": error,
+            "saved": saved,
+        })
+        .to_string(),
+    )
+}
+
+fn save(value: Value) -> Result<(), String> {
+    let config = serde_json::from_value::<LocalConfig>(value).map_err(|e| e.to_string())?;
+    if !is_valid_username(&config.user.username) {
+        return Err(format!("Invalid username {:?}", config.user.username));
+    }
+    save_config(&config);
+    update_local_config(|it| *it = config);
+    panel_log("Saved the settings", false);
+    Ok(())
+}
+
+/// Start (once) a websocket server letting `settings.html` read and write the config file
+pub fn serve() -> u16 {
+    *SETTINGS_PORT.get_or_init(|| {
+        let socket = Server::bind("127.0.0.1:0").pb_expect("Failed to bind settings socket");
+        let port = socket.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for request in socket.filter_map(Result::ok) {
+                // Saving runs `[command] launch` on the next session, which no other app may set
+                let Some(mut client) = accept_page(request) else {
+                    continue;
+                };
+                thread::spawn(move || {
+                    if client.send_message(&config_message(None, false)).is_err() {
+                        return;
+                    }
+                    while let Ok(message) = client.recv_message() {
+                        let text = match message {
+                            OwnedMessage::Text(text) => text,
+                            OwnedMessage::Close(_) => break,
+                            _ => continue,
+                        };
+                        let reply = match serde_json::from_str::<Request>(&text) {
+                            Ok(Request::Save(value)) => match save(value) {
+                                Ok(()) => config_message(None, true),
+                                Err(e) => config_message(Some(e), false),
+                            },
+                            Err(e) => {
+                                log::warn!("Invalid settings request {:?}: {}", text, e);
+                                continue;
+                            }
+                        };
+                        if client.send_message(&reply).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        port
+    })
+}
+
+/// Show the settings on top of the desktop
+pub fn show_settings(android_app: AndroidApp) {
+    let url = format!(
+        "file:///android_asset/settings.html?port={}&token={}",
+        serve(),
+        page_token()
+    );
+    show_webview_overlay(android_app, url);
+}
@@ -0,0 +1,181 @@
+use jni::objects::{JObject, JString, JValue};
+use jni::JNIEnv;
+use winit::platform::android::activity::AndroidApp;
+
+/// What another app shared with the activity, or opened with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharedItem {
+    /// A `content://` or `file://` URI of a file
+    File(String),
+    /// Text shared alone, e.g. a note or a link
+    Text(String),
+}
+
+const EXTRA_STREAM: &str = "android.intent.extra.STREAM";
+const EXTRA_TEXT: &str = "android.intent.extra.TEXT";
+
+/// `OpenableColumns.DISPLAY_NAME`
+const DISPLAY_NAME: &str = "_display_name";
+
+/// What the intent that started the activity shares: the data of `VIEW`, or the extras of `SEND` and
+/// `SEND_MULTIPLE`
+pub fn shared_items(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+) -> jni::errors::Result<Vec<SharedItem>> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let intent = env
+        .call_method(activity, "getIntent", "()Landroid/content/Intent;", &[])?
+        .l()?;
+    if intent.is_null() {
+        return Ok(vec![]);
+    }
+    let action = env
+        .call_method(&intent, "getAction", "()Ljava/lang/String;", &[])?
+        .l()?;
+    let action = to_string(env, action)?.unwrap_or_default();
+    let mut items = vec![];
+    match action.as_str() {
+        "android.intent.action.VIEW" => {
+            let data = env
+                .call_method(&intent, "getDataString", "()Ljava/lang/String;", &[])?
+                .l()?;
+            items.extend(to_string(env, data)?.map(SharedItem::File));
+        }
+        "android.intent.action.SEND" => {
+            let name = env.new_string(EXTRA_STREAM)?;
+            let uri = env
+                .call_method(
+                    &intent,
+                    "getParcelableExtra",
+                    "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                    &[JValue::Object(&name)],
+                )?
+                .l()?;
+            match uri_string(env, uri)? {
+                Some(uri) => items.push(SharedItem::File(uri)),
+                None => {
+                    let name = env.new_string(EXTRA_TEXT)?;
+                    let text = env
+                        .call_method(
+                            &intent,
+                            "getStringExtra",
+                            "(Ljava/lang/String;)Ljava/lang/String;",
+                            &[JValue::Object(&name)],
+                        )?
+                        .l()?;
+                    items.extend(to_string(env, text)?.map(SharedItem::Text));
+                }
+            }
+        }
+        "android.intent.action.SEND_MULTIPLE" => {
+            let name = env.new_string(EXTRA_STREAM)?;
+            let uris = env
+                .call_method(
+                    &intent,
+                    "getParcelableArrayListExtra",
+                    "(Ljava/lang/String;)Ljava/util/ArrayList;",
+                    &[JValue::Object(&name)],
+                )?
+                .l()?;
+            if !uris.is_null() {
+                let count = env.call_method(&uris, "size", "()I", &[])?.i()?;
+                for index in 0..count {
+                    let uri = env
+                        .call_method(&uris, "get", "(I)Ljava/lang/Object;", &[JValue::Int(index)])?
+                        .l()?;
+                    items.extend(uri_string(env, uri)?.map(SharedItem::File));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(items)
+}
+
+/// The name and the type of a shared file, as the app sharing it tells them, if it does
+pub fn describe_content_uri(
+    env: &mut JNIEnv,
+    android_app: &AndroidApp,
+    uri: &str,
+) -> jni::errors::Result<(Option<String>, Option<String>)> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let resolver = env
+        .call_method(
+            activity,
+            "getContentResolver",
+            "()Landroid/content/ContentResolver;",
+            &[],
+        )?
+        .l()?;
+    let uri = env.new_string(uri)?;
+    let uri = env
+        .call_static_method(
+            "android/net/Uri",
+            "parse",
+            "(Ljava/lang/String;)Landroid/net/Uri;",
+            &[JValue::Object(&uri)],
+        )?
+        .l()?;
+    let mime_type = env
+        .call_method(
+            &resolver,
+            "getType",
+            "(Landroid/net/Uri;)Ljava/lang/String;",
+            &[JValue::Object(&uri)],
+        )?
+        .l()?;
+    let mime_type = to_string(env, mime_type)?;
+
+    let column = env.new_string(DISPLAY_NAME)?;
+    let projection = env.new_object_array(1, "java/lang/String", &column)?;
+    let null = JObject::null();
+    let cursor = env
+        .call_method(
+            &resolver,
+            "query",
+            "(Landroid/net/Uri;[Ljava/lang/String;Ljava/lang/String;[Ljava/lang/String;Ljava/lang/String;)Landroid/database/Cursor;",
+            &[
+                JValue::Object(&uri),
+                JValue::Object(&projection),
+                JValue::Object(&null),
+                JValue::Object(&null),
+                JValue::Object(&null),
+            ],
+        )?
+        .l()?;
+    if cursor.is_null() {
+        return Ok((None, mime_type));
+    }
+    let mut name = None;
+    if env.call_method(&cursor, "moveToFirst", "()Z", &[])?.z()? {
+        let value = env
+            .call_method(
+                &cursor,
+                "getString",
+                "(I)Ljava/lang/String;",
+                &[JValue::Int(0)],
+            )?
+            .l()?;
+        name = to_string(env, value)?;
+    }
+    env.call_method(&cursor, "close", "()V", &[])?;
+    Ok((name, mime_type))
+}
+
+fn uri_string(env: &mut JNIEnv, uri: JObject) -> jni::errors::Result<Option<String>> {
+    if uri.is_null() {
+        return Ok(None);
+    }
+    let value = env
+        .call_method(&uri, "toString", "()Ljava/lang/String;", &[])?
+        .l()?;
+    to_string(env, value)
+}
+
+fn to_string(env: &mut JNIEnv, value: JObject) -> jni::errors::Result<Option<String>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(env.get_string(&JString::from(value))?.into()))
+}
@@ -0,0 +1,160 @@
+use crate::android::utils::application_context::get_application_context;
+use crate::core::diagnostics;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::{
+    fs,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use winit::platform::android::activity::AndroidApp;
+
+/// The shared storage of the primary user, where the downloads, documents and pictures are
+const SHARED_STORAGE: &str = "/storage/emulated/0";
+
+/// Marks that the user was asked for the storage access once, so that declining is not asked again on every start
+const ASKED_MARKER: &str = "storage-access-asked";
+
+/// Android 11 replaced the storage permissions with the "All files access" special permission
+const ALL_FILES_ACCESS_SDK: i32 = 30;
+
+const PERMISSIONS: [&str; 2] = [
+    "android.permission.READ_EXTERNAL_STORAGE",
+    "android.permission.WRITE_EXTERNAL_STORAGE",
+];
+
+static GRANTED: AtomicBool = AtomicBool::new(false);
+
+/// The Proot argument binding the shared storage into the Arch FS, if `[storage] shared` is on and the user
+/// granted the access to it
+pub fn shared_storage_bind() -> Option<String> {
+    if !GRANTED.load(Ordering::Relaxed) {
+        return None;
+    }
+    get_application_context()
+        .local_config
+        .storage
+        .bind_argument(SHARED_STORAGE)
+}
+
+/// Read again whether the app can access the shared storage, as the user grants it from the system settings while
+/// the app is in the background. The processes spawned from then on see the shared storage.
+pub fn refresh_shared_storage(env: &mut JNIEnv, android_app: &AndroidApp) {
+    let granted = match is_granted(env, android_app) {
+        Ok(granted) => granted,
+        Err(e) => {
+            let _ = env.exception_clear();
+            log::warn!("Failed to check the storage access: {:?}", e);
+            false
+        }
+    };
+    if GRANTED.swap(granted, Ordering::Relaxed) != granted {
+        log::info!("Shared storage access granted: {}", granted);
+    }
+    let config = get_application_context().local_config.storage;
+    diagnostics::publish(
+        "storage",
+        serde_json::json!({
+            "enabled": config.shared,
+            "granted": granted,
+            "mountPoint": config.mount_point,
+        }),
+    );
+}
+
+/// Ask for the access to the shared storage on the first start with `[storage] shared` on. Declining it leaves the
+/// shared storage out of the Arch FS, until the access is granted in the system settings of the app.
+pub fn request_shared_storage(env: &mut JNIEnv, android_app: &AndroidApp) {
+    refresh_shared_storage(env, android_app);
+    let context = get_application_context();
+    let marker = context.data_dir.join(ASKED_MARKER);
+    if !context.local_config.storage.shared || GRANTED.load(Ordering::Relaxed) || marker.exists() {
+        return;
+    }
+    if let Err(e) = fs::write(&marker, []) {
+        log::warn!("Failed to write {}: {}", marker.display(), e);
+    }
+    if let Err(e) = ask(env, android_app) {
+        let _ = env.exception_clear();
+        log::warn!("Failed to ask for the storage access: {:?}", e);
+    }
+}
+
+fn sdk_version(env: &mut JNIEnv) -> jni::errors::Result<i32> {
+    env.get_static_field("android/os/Build$VERSION", "SDK_INT", "I")?
+        .i()
+}
+
+fn is_granted(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<bool> {
+    if sdk_version(env)? >= ALL_FILES_ACCESS_SDK {
+        return env
+            .call_static_method(
+                "android/os/Environment",
+                "isExternalStorageManager",
+                "()Z",
+                &[],
+            )?
+            .z();
+    }
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let permission = env.new_string(PERMISSIONS[0])?;
+    let result = env
+        .call_method(
+            activity,
+            "checkSelfPermission",
+            "(Ljava/lang/String;)I",
+            &[JValue::Object(&permission)],
+        )?
+        .i()?;
+    // PackageManager.PERMISSION_GRANTED
+    Ok(result == 0)
+}
+
+fn ask(env: &mut JNIEnv, android_app: &AndroidApp) -> jni::errors::Result<()> {
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    if sdk_version(env)? < ALL_FILES_ACCESS_SDK {
+        let permissions = env.new_object_array(
+            PERMISSIONS.len() as i32,
+            "java/lang/String",
+            JObject::null(),
+        )?;
+        for (i, permission) in PERMISSIONS.iter().enumerate() {
+            let permission = env.new_string(permission)?;
+            env.set_object_array_element(&permissions, i as i32, permission)?;
+        }
+        env.call_method(
+            activity,
+            "requestPermissions",
+            "([Ljava/lang/String;I)V",
+            &[JValue::Object(&permissions), JValue::Int(0)],
+        )?;
+        return Ok(());
+    }
+
+    // The "All files access" screen of the app in the system settings
+    let package = env
+        .call_method(&activity, "getPackageName", "()Ljava/lang/String;", &[])?
+        .l()?;
+    let package: String = env.get_string(&package.into())?.into();
+    let uri = env.new_string(format!("package:{}", package))?;
+    let uri = env
+        .call_static_method(
+            "android/net/Uri",
+            "parse",
+            "(Ljava/lang/String;)Landroid/net/Uri;",
+            &[JValue::Object(&uri)],
+        )?
+        .l()?;
+    let action = env.new_string("android.settings.MANAGE_APP_ALL_FILES_ACCESS_PERMISSION")?;
+    let intent = env.new_object(
+        "android/content/Intent",
+        "(Ljava/lang/String;Landroid/net/Uri;)V",
+        &[JValue::Object(&action), JValue::Object(&uri)],
+    )?;
+    env.call_method(
+        activity,
+        "startActivity",
+        "(Landroid/content/Intent;)V",
+        &[JValue::Object(&intent)],
+    )?;
+    Ok(())
+}
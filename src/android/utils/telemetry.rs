@@ -0,0 +1,37 @@
+use super::application_context::get_application_context;
+use crate::core::telemetry;
+use sentry::protocol::Event;
+use sentry::Level;
+
+/// The tag of the usage reports, which are stripped of anything identifying before they are sent
+const USAGE_TAG: &str = "usage report";
+
+/// Send the features used since the last report along with the crash reports, with `[telemetry] share_usage` on.
+/// Each feature is a tag of the report, for Sentry to count the reports by option.
+pub fn report_usage() {
+    let report = telemetry::take_report();
+    if report.is_empty() || !get_application_context().local_config.telemetry.share_usage {
+        return;
+    }
+    log::debug!("Reporting the usage of {:?}", report.keys());
+    sentry::with_scope(
+        |scope| {
+            // The breadcrumbs of the sessions name the users
+            scope.clear_breadcrumbs();
+            scope.set_tag(USAGE_TAG, true);
+            for (feature, options) in report {
+                scope.set_tag(&feature, options);
+            }
+        },
+        || sentry::capture_message("Feature usage", Level::Info),
+    );
+}
+
+/// Leave out of the usage reports who sent them, which the crash reports keep to follow a user's issue up
+pub fn anonymize(mut event: Event<'static>) -> Option<Event<'static>> {
+    if event.tags.contains_key(USAGE_TAG) {
+        event.user = None;
+        event.server_name = None;
+    }
+    Some(event)
+}
@@ -0,0 +1,277 @@
+use super::{
+    bell,
+    webview::{accept_page, page_token, show_webview_overlay},
+};
+use crate::{
+    android::{
+        proot::{launch::restart_sessions, process::ArchProcess},
+        utils::application_context::get_application_context,
+    },
+    core::{bell::BellScanner, config::ARCH_FS_ROOT, logging::PolarBearExpectation},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    fs,
+    io::Read,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+};
+use websocket::{sync::Server, OwnedMessage};
+use winit::platform::android::activity::AndroidApp;
+
+/// The size of the terminal until the page measured the screen
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+
+/// A login shell of the session user, falling back to `sh` on a rootfs without bash.
+/// Bash saves every command right away, as the shell is killed along with the page rather than exiting.
+const SHELL: &str = "cd; export PROMPT_COMMAND='history -a' HISTCONTROL=ignoredups; \
+    command -v bash >/dev/null && exec bash -l || exec sh -l";
+
+/// The commands pinned on the terminal, in the app data outside the Arch FS
+const FAVORITES_FILE: &str = "terminal-favorites.json";
+
+/// How many of the latest commands of the shell history the page lists
+const MAX_HISTORY: usize = 50;
+
+/// The commands always pinned, for the users unfamiliar with pacman. Each step runs with `sudo` for the users
+/// other than root.
+const BUILTIN_FAVORITES: [(&str, &[&str]); 2] = [
+    ("Update the system", &["pacman -Syu"]),
+    (
+        "Fix the pacman keyring",
+        &["pacman-key --init", "pacman-key --populate"],
+    ),
+];
+
+static TERMINAL_PORT: OnceLock<u16> = OnceLock::new();
+
+/// What `terminal.html` sends, e.g. `{"input": "ls\r"}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Request {
+    Input(String),
+    Resize {
+        rows: u16,
+        cols: u16,
+    },
+    /// Type a command and run it
+    Run(String),
+    Pin(String),
+    Unpin(String),
+    /// Ask for the history and the favorites, which the page is also sent on connection
+    Shortcuts,
+    RestartSessions,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Favorite {
+    label: String,
+    command: String,
+    /// Pinned by the user, who can unpin it
+    pinned: bool,
+}
+
+fn favorites_file() -> PathBuf {
+    get_application_context().data_dir.join(FAVORITES_FILE)
+}
+
+fn pinned() -> Vec<String> {
+    fs::read_to_string(favorites_file())
+        .ok()
+        .and_then(|it| serde_json::from_str(&it).ok())
+        .unwrap_or_default()
+}
+
+fn set_pinned(commands: &[String]) {
+    let result = serde_json::to_string(commands)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(favorites_file(), json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::warn!("Failed to save the terminal favorites: {}", e);
+    }
+}
+
+fn favorites(user: &str) -> Vec<Favorite> {
+    let sudo = if user == "root" { "" } else { "sudo " };
+    BUILTIN_FAVORITES
+        .iter()
+        .map(|(label, steps)| Favorite {
+            label: label.to_string(),
+            command: steps
+                .iter()
+                .map(|step| format!("{}{}", sudo, step))
+                .collect::<Vec<_>>()
+                .join(" && "),
+            pinned: false,
+        })
+        .chain(pinned().into_iter().map(|command| Favorite {
+            label: command.clone(),
+            command,
+            pinned: true,
+        }))
+        .collect()
+}
+
+/// The latest commands the user ran, most recent first, which bash keeps in the Arch FS across the shells
+fn history(user: &str) -> Vec<String> {
+    let home = if user == "root" {
+        "/root".to_string()
+    } else {
+        format!("/home/{}", user)
+    };
+    let history = fs::read_to_string(format!("{}{}/.bash_history", ARCH_FS_ROOT, home));
+    let mut commands = Vec::new();
+    for line in history.unwrap_or_default().lines().rev() {
+        let line = line.trim();
+        // Bash writes the timestamps as comments when `HISTTIMEFORMAT` is set
+        if line.is_empty() || line.starts_with('#') || commands.iter().any(|it| it == line) {
+            continue;
+        }
+        commands.push(line.to_string());
+        if commands.len() == MAX_HISTORY {
+            break;
+        }
+    }
+    commands
+}
+
+/// The history and the favorites, as a text message, which the page tells apart from the binary output
+fn shortcuts(user: &str) -> OwnedMessage {
+    OwnedMessage::Text(
+        json!({ "history": history(user), "favorites": favorites(user) }).to_string(),
+    )
+}
+
+/// Start (once) a websocket server giving each `terminal.html` connecting a shell of its own in the Arch FS.
+/// The output goes as binary messages, as the escape sequences may split UTF-8 characters.
+pub fn serve(android_app: &AndroidApp) -> u16 {
+    *TERMINAL_PORT.get_or_init(|| {
+        let android_app = android_app.clone();
+        let socket = Server::bind("127.0.0.1:0").pb_expect("Failed to bind terminal socket");
+        let port = socket.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for request in socket.filter_map(Result::ok) {
+                let Some(client) = accept_page(request) else {
+                    continue;
+                };
+                let Ok((mut receiver, mut sender)) = client.split() else {
+                    continue;
+                };
+
+                let user = get_application_context().local_config.user.username;
+                let process = ArchProcess {
+                    command: SHELL.to_string(),
+                    user: user.clone(),
+                    process: None,
+                };
+                let mut shell = match process.spawn_pty(DEFAULT_ROWS, DEFAULT_COLS) {
+                    Ok(it) => it,
+                    Err(e) => {
+                        log::error!("Failed to start the terminal shell: {}", e);
+                        let message = format!("Failed to start the shell: {}\r\n", e);
+                        let _ = sender.send_message(&OwnedMessage::Binary(message.into_bytes()));
+                        continue;
+                    }
+                };
+                let Ok(mut output) = shell.reader() else {
+                    continue;
+                };
+                let _ = sender.send_message(&shortcuts(&user));
+                let sender = Arc::new(Mutex::new(sender));
+
+                let output_sender = sender.clone();
+                let android_app = android_app.clone();
+                thread::spawn(move || {
+                    let sender = output_sender;
+                    let mut buffer = [0u8; 4096];
+                    let mut bells = BellScanner::default();
+                    // Reading fails once the shell exited
+                    while let Ok(n) = output.read(&mut buffer) {
+                        if n == 0 {
+                            break;
+                        }
+                        if bells.scan(&buffer[..n]) {
+                            bell::ring(&android_app, "the terminal");
+                        }
+                        let message = OwnedMessage::Binary(buffer[..n].to_vec());
+                        if sender.lock().unwrap().send_message(&message).is_err() {
+                            break;
+                        }
+                    }
+                    let _ = sender
+                        .lock()
+                        .unwrap()
+                        .send_message(&OwnedMessage::Close(None));
+                });
+
+                thread::spawn(move || {
+                    for message in receiver.incoming_messages() {
+                        let text = match message {
+                            Ok(OwnedMessage::Text(text)) => text,
+                            Ok(OwnedMessage::Close(_)) | Err(_) => break,
+                            Ok(_) => continue,
+                        };
+                        let result = match serde_json::from_str::<Request>(&text) {
+                            Ok(Request::Input(input)) => shell.write(input.as_bytes()),
+                            Ok(Request::Resize { rows, cols }) => shell.resize(rows, cols),
+                            Ok(Request::Run(command)) => {
+                                shell.write(format!("{}\r", command).as_bytes())
+                            }
+                            Ok(Request::Pin(command)) => {
+                                let mut commands = pinned();
+                                if !commands.contains(&command) {
+                                    commands.push(command);
+                                    set_pinned(&commands);
+                                }
+                                sender.lock().unwrap().send_message(&shortcuts(&user)).ok();
+                                Ok(())
+                            }
+                            Ok(Request::Unpin(command)) => {
+                                let mut commands = pinned();
+                                commands.retain(|it| *it != command);
+                                set_pinned(&commands);
+                                sender.lock().unwrap().send_message(&shortcuts(&user)).ok();
+                                Ok(())
+                            }
+                            Ok(Request::Shortcuts) => {
+                                sender.lock().unwrap().send_message(&shortcuts(&user)).ok();
+                                Ok(())
+                            }
+                            Ok(Request::RestartSessions) => {
+                                restart_sessions();
+                                Ok(())
+                            }
+                            Err(e) => {
+                                log::warn!("Invalid terminal request {:?}: {}", text, e);
+                                Ok(())
+                            }
+                        };
+                        if let Err(e) = result {
+                            log::warn!("Failed to reach the terminal shell: {}", e);
+                        }
+                    }
+                    // The page went away, and the shell along with it
+                    let _ = shell.child.kill();
+                    let _ = shell.wait();
+                    log::info!("Terminal closed");
+                });
+            }
+        });
+
+        port
+    })
+}
+
+/// Show a terminal on top of the desktop, which works even when the graphical session does not
+pub fn show_terminal(android_app: AndroidApp) {
+    let url = format!(
+        "file:///android_asset/terminal.html?port={}&token={}",
+        serve(&android_app),
+        page_token()
+    );
+    show_webview_overlay(android_app, url);
+}
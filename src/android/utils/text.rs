@@ -0,0 +1,95 @@
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+
+/// Premultiplied RGBA pixels of a single line of text
+pub struct TextBitmap {
+    pub pixels: Vec<u8>,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Draw a line of text with the Android system font, so that the native GLES path can show text without bundling a font
+pub fn rasterize_text(
+    env: &mut JNIEnv,
+    text: &str,
+    text_size: f32,
+    argb_color: u32,
+) -> jni::errors::Result<TextBitmap> {
+    // Paint.ANTI_ALIAS_FLAG
+    let paint = env.new_object("android/graphics/Paint", "(I)V", &[JValue::Int(1)])?;
+    env.call_method(&paint, "setTextSize", "(F)V", &[JValue::Float(text_size)])?;
+    env.call_method(
+        &paint,
+        "setColor",
+        "(I)V",
+        &[JValue::Int(argb_color as i32)],
+    )?;
+
+    let text = env.new_string(text)?;
+    let width = env
+        .call_method(
+            &paint,
+            "measureText",
+            "(Ljava/lang/String;)F",
+            &[JValue::Object(&text)],
+        )?
+        .f()?;
+    // The ascent is negative, as it goes up from the baseline
+    let ascent = env.call_method(&paint, "ascent", "()F", &[])?.f()?;
+    let descent = env.call_method(&paint, "descent", "()F", &[])?.f()?;
+    let width = (width.ceil() as i32).max(1);
+    let height = ((descent - ascent).ceil() as i32).max(1);
+
+    let config = env
+        .get_static_field(
+            "android/graphics/Bitmap$Config",
+            "ARGB_8888",
+            "Landroid/graphics/Bitmap$Config;",
+        )?
+        .l()?;
+    let bitmap = env
+        .call_static_method(
+            "android/graphics/Bitmap",
+            "createBitmap",
+            "(IILandroid/graphics/Bitmap$Config;)Landroid/graphics/Bitmap;",
+            &[
+                JValue::Int(width),
+                JValue::Int(height),
+                JValue::Object(&config),
+            ],
+        )?
+        .l()?;
+    let canvas = env.new_object(
+        "android/graphics/Canvas",
+        "(Landroid/graphics/Bitmap;)V",
+        &[JValue::Object(&bitmap)],
+    )?;
+    env.call_method(
+        &canvas,
+        "drawText",
+        "(Ljava/lang/String;FFLandroid/graphics/Paint;)V",
+        &[
+            JValue::Object(&text),
+            JValue::Float(0.0),
+            JValue::Float(-ascent),
+            JValue::Object(&paint),
+        ],
+    )?;
+
+    // An ARGB_8888 bitmap is laid out as RGBA bytes in memory
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    let buffer = unsafe { env.new_direct_byte_buffer(pixels.as_mut_ptr(), pixels.len())? };
+    env.call_method(
+        &bitmap,
+        "copyPixelsToBuffer",
+        "(Ljava/nio/Buffer;)V",
+        &[JValue::Object(&JObject::from(buffer))],
+    )?;
+    env.call_method(&bitmap, "recycle", "()V", &[])?;
+
+    Ok(TextBitmap {
+        pixels,
+        width,
+        height,
+    })
+}
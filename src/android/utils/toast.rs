@@ -0,0 +1,77 @@
+use super::ndk::run_in_jvm;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+use std::{thread, time::Duration};
+use winit::platform::android::activity::AndroidApp;
+
+/// `Toast.LENGTH_SHORT`
+const LENGTH_SHORT: i32 = 0;
+
+/// How long the Looper of a toast runs for, which outlasts the 2 seconds the toast is shown
+const TOAST_LOOPER_LIFETIME: Duration = Duration::from_secs(4);
+
+/// Show a short message at the bottom of the screen, without blocking the caller.
+///
+/// A toast needs a `Looper` on the thread that shows it, so each toast gets a thread of its own, whose Looper is
+/// stopped once the toast is gone.
+pub fn show_toast(android_app: &AndroidApp, text: String) {
+    let android_app = android_app.clone();
+    thread::spawn(move || {
+        run_in_jvm(
+            |env, app| {
+                if let Err(e) = show(env, app, &text) {
+                    let _ = env.exception_clear();
+                    log::warn!("Failed to show the toast {:?}: {:?}", text, e);
+                }
+            },
+            android_app,
+        );
+    });
+}
+
+fn show(env: &mut JNIEnv, android_app: &AndroidApp, text: &str) -> jni::errors::Result<()> {
+    env.call_static_method("android/os/Looper", "prepare", "()V", &[])?;
+    let looper = env
+        .call_static_method(
+            "android/os/Looper",
+            "myLooper",
+            "()Landroid/os/Looper;",
+            &[],
+        )?
+        .l()?;
+    let looper = env.new_global_ref(looper)?;
+
+    let activity = unsafe { JObject::from_raw(android_app.activity_as_ptr() as *mut _) };
+    let text = env.new_string(text)?;
+    let toast = env
+        .call_static_method(
+            "android/widget/Toast",
+            "makeText",
+            "(Landroid/content/Context;Ljava/lang/CharSequence;I)Landroid/widget/Toast;",
+            &[
+                JValue::Object(&activity),
+                JValue::Object(&text),
+                JValue::Int(LENGTH_SHORT),
+            ],
+        )?
+        .l()?;
+    env.call_method(&toast, "show", "()V", &[])?;
+
+    let app = android_app.clone();
+    thread::spawn(move || {
+        thread::sleep(TOAST_LOOPER_LIFETIME);
+        run_in_jvm(
+            |env, _| {
+                if env
+                    .call_method(looper.as_obj(), "quitSafely", "()V", &[])
+                    .is_err()
+                {
+                    let _ = env.exception_clear();
+                }
+            },
+            app,
+        );
+    });
+    env.call_static_method("android/os/Looper", "loop", "()V", &[])?;
+    Ok(())
+}
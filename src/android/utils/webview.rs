@@ -1,10 +1,79 @@
-use jni::objects::{JObject, JValue};
+use super::ndk::run_in_jvm;
+use crate::core::{logging::PolarBearExpectation, token};
+use jni::objects::{GlobalRef, JObject, JValue};
 use jni::sys::_jobject;
 use jni::JNIEnv;
+use std::net::TcpStream;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use websocket::sync::{server::upgrade::Upgrade, Client};
 use winit::platform::android::activity::AndroidApp;
 
+/// The Looper of the thread hosting the current overlay, if an overlay is shown
+static OVERLAY_LOOPER: Mutex<Option<GlobalRef>> = Mutex::new(None);
+
+static PAGE_TOKEN: OnceLock<String> = OnceLock::new();
+
+/// The secret of this launch, which the pages get in their URL as `token` and send back to reach the websockets of
+/// the app. The other apps of the phone can connect to `127.0.0.1` too, but never see it.
+pub fn page_token() -> &'static str {
+    PAGE_TOKEN.get_or_init(|| token::generate().pb_expect("Failed to generate the page token"))
+}
+
+/// Accept a page connecting to one of the websockets of the app, as `ws://127.0.0.1:<port>/?token=<page_token()>`
+/// with the `rust-websocket` protocol. Any other connection is rejected.
+pub fn accept_page(request: Upgrade<TcpStream>) -> Option<Client<TcpStream>> {
+    if !request.protocols().contains(&"rust-websocket".to_string()) {
+        let _ = request.reject();
+        return None;
+    }
+    if !token::uri_has_token(&request.uri(), page_token()) {
+        log::warn!("Rejecting a websocket connection without the page token");
+        let _ = request.reject();
+        return None;
+    }
+    request.use_protocol("rust-websocket").accept().ok()
+}
+
 /// A function that can be passed into `run_in_jvm` to show a WebView popup.
 pub fn show_webview_popup(env: &mut JNIEnv, android_app: &AndroidApp, url: &str) {
+    show_popup(env, android_app, url, false);
+}
+
+/// Show a WebView popup on top of the running desktop, without blocking the caller.
+/// Unlike the setup popup, an overlay can be dismissed with the Back button.
+pub fn show_webview_overlay(android_app: AndroidApp, url: String) {
+    if OVERLAY_LOOPER.lock().unwrap().is_some() {
+        log::info!("An overlay is already shown, ignoring {}", url);
+        return;
+    }
+    // The popup needs a Looper of its own, which blocks the thread until the overlay is closed
+    thread::spawn(move || {
+        run_in_jvm(
+            move |env, app| {
+                show_popup(env, app, &url, true);
+            },
+            android_app,
+        );
+    });
+}
+
+/// Release the thread of the overlay, once it has been dismissed
+pub fn close_webview_overlay(android_app: AndroidApp) {
+    if let Some(looper) = OVERLAY_LOOPER.lock().unwrap().take() {
+        run_in_jvm(
+            move |env, _| {
+                // `Looper.quit` is safe to call from any thread
+                if let Err(e) = env.call_method(looper.as_obj(), "quit", "()V", &[]) {
+                    log::error!("Failed to quit overlay Looper: {:?}", e);
+                }
+            },
+            android_app,
+        );
+    }
+}
+
+fn show_popup(env: &mut JNIEnv, android_app: &AndroidApp, url: &str, dismissable: bool) {
     // Convert URL to JNI String
     let jurl = env.new_string(url).expect("Failed to create JNI string");
 
@@ -83,6 +152,21 @@ pub fn show_webview_popup(env: &mut JNIEnv, android_app: &AndroidApp, url: &str)
         )
         .unwrap();
 
+    if dismissable {
+        // A focusable popup gets the key events, so that the Back button dismisses it
+        env.call_method(&popup, "setFocusable", "(Z)V", &[JValue::Bool(1)])
+            .unwrap();
+        // The WebView takes the keys right away, so that a keyboard or a d-pad can navigate the page (see
+        // `navigation.js`) without touching it first
+        env.call_method(
+            &webview,
+            "setFocusableInTouchMode",
+            "(Z)V",
+            &[JValue::Bool(1)],
+        )
+        .unwrap();
+    }
+
     // 3. Show PopupWindow
     env.call_method(
         popup,
@@ -97,6 +181,27 @@ pub fn show_webview_popup(env: &mut JNIEnv, android_app: &AndroidApp, url: &str)
     )
     .unwrap();
 
+    if dismissable {
+        if let Err(e) = env.call_method(&webview, "requestFocus", "()Z", &[]) {
+            let _ = env.exception_clear();
+            log::warn!("Failed to focus the overlay: {:?}", e);
+        }
+        let looper = env
+            .call_static_method(
+                "android/os/Looper",
+                "myLooper",
+                "()Landroid/os/Looper;",
+                &[],
+            )
+            .unwrap()
+            .l()
+            .unwrap();
+        OVERLAY_LOOPER
+            .lock()
+            .unwrap()
+            .replace(env.new_global_ref(looper).unwrap());
+    }
+
     // Start the Looper
     env.call_static_method("android/os/Looper", "loop", "()V", &[])
         .expect("Failed to start Looper");
@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+/// Restarts are delayed exponentially while a process keeps crashing, up to this long
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// Once a process ran for this long, its next exit is not counted as a crash loop
+const HEALTHY_UPTIME: Duration = Duration::from_secs(60);
+
+/// Paces the restarts of a supervised process
+#[derive(Debug, Default)]
+pub struct Backoff {
+    started_at: Option<Instant>,
+    crashes: u32,
+}
+
+impl Backoff {
+    pub fn started(&mut self) {
+        self.started_at = Some(Instant::now());
+    }
+
+    /// The process exited or failed to start: how long to wait before restarting it
+    pub fn exited(&mut self) -> Duration {
+        let uptime = self.started_at.take().map(|at| at.elapsed());
+        self.delay_after(uptime)
+    }
+
+    /// How many times in a row the process exited before it ran for long
+    pub fn crashes(&self) -> u32 {
+        self.crashes
+    }
+
+    fn delay_after(&mut self, uptime: Option<Duration>) -> Duration {
+        let healthy = uptime.is_some_and(|it| it >= HEALTHY_UPTIME);
+        self.crashes = if healthy { 0 } else { self.crashes + 1 };
+        Duration::from_secs(1 << self.crashes.min(5)).min(MAX_RESTART_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_back_off_while_crashing() {
+        let mut backoff = Backoff::default();
+        let delays = (0..7)
+            .map(|_| backoff.delay_after(Some(Duration::from_secs(1))).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [2, 4, 8, 16, 30, 30, 30]);
+        assert_eq!(backoff.crashes(), 7);
+
+        // Failing to start counts as a crash
+        assert_eq!(backoff.delay_after(None), MAX_RESTART_DELAY);
+
+        assert_eq!(
+            backoff.delay_after(Some(HEALTHY_UPTIME)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(backoff.crashes(), 0);
+    }
+}
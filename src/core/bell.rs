@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+const BEL: u8 = 0x07;
+const ESC: u8 = 0x1b;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    #[default]
+    Text,
+    /// Right after an `ESC`
+    Escape,
+    /// Within an OSC, DCS, SOS, PM or APC string, which BEL may end instead of `ESC \`
+    String,
+    /// An `ESC` within such a string, which may start its terminator
+    StringEscape,
+}
+
+/// Finds the bells in the output of a terminal, as it comes in chunks.
+/// A BEL ending an escape string, e.g. the title set by `PROMPT_COMMAND`, is not a bell.
+#[derive(Debug, Default)]
+pub struct BellScanner {
+    scan: Scan,
+}
+
+impl BellScanner {
+    /// Whether the chunk rings the bell
+    pub fn scan(&mut self, bytes: &[u8]) -> bool {
+        let mut rang = false;
+        for &byte in bytes {
+            self.scan = match (self.scan, byte) {
+                (Scan::Text, BEL) => {
+                    rang = true;
+                    Scan::Text
+                }
+                (Scan::Text, ESC) => Scan::Escape,
+                (Scan::Text, _) => Scan::Text,
+                (Scan::Escape, b']' | b'P' | b'X' | b'^' | b'_') => Scan::String,
+                (Scan::Escape, ESC) => Scan::Escape,
+                (Scan::Escape, _) => Scan::Text,
+                (Scan::String | Scan::StringEscape, BEL) => Scan::Text,
+                (Scan::String | Scan::StringEscape, ESC) => Scan::StringEscape,
+                (Scan::StringEscape, b'\\') => Scan::Text,
+                (Scan::String | Scan::StringEscape, _) => Scan::String,
+            };
+        }
+        rang
+    }
+}
+
+/// Lets a signal through at most once per cooldown
+#[derive(Debug, Default)]
+pub struct Throttle {
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// Whether the signal goes through at `now`, which then starts the cooldown
+    pub fn allow(&mut self, now: Instant, cooldown: Duration) -> bool {
+        if self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) < cooldown)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_bells_outside_escape_strings() {
+        let mut scanner = BellScanner::default();
+        assert!(scanner.scan(b"done\x07"));
+        assert!(!scanner.scan(b"\x1b]0;user@host: ~\x07$ "));
+        assert!(!scanner.scan(b"\x1b]8;;file:///etc\x1b\\etc\x1b]8;;\x1b\\"));
+        assert!(scanner.scan(b"\x1b[1mbold\x1b[0m\x07"));
+        // The string goes on in the next chunk
+        assert!(!scanner.scan(b"\x1b]2;make"));
+        assert!(!scanner.scan(b" all\x07"));
+        assert!(scanner.scan(b"\x07"));
+    }
+
+    #[test]
+    fn should_throttle() {
+        let mut throttle = Throttle::new();
+        let cooldown = Duration::from_secs(1);
+        let start = Instant::now();
+        assert!(throttle.allow(start, cooldown));
+        assert!(!throttle.allow(start + Duration::from_millis(500), cooldown));
+        assert!(throttle.allow(start + Duration::from_millis(1000), cooldown));
+        assert!(!throttle.allow(start + Duration::from_millis(1999), cooldown));
+        assert!(throttle.allow(start, Duration::ZERO));
+    }
+}
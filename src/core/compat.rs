@@ -0,0 +1,427 @@
+use crate::core::config::SetupConfig;
+use std::{
+    collections::HashSet,
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Where the executables are looked up, and where the wrappers go, ahead of it in the `PATH`
+const EXECUTABLES_DIR: &str = "/usr/bin";
+const WRAPPERS_DIR: &str = "/usr/local/bin";
+
+/// Where the desktop entries are looked up, and where their copies go, which take precedence in `XDG_DATA_DIRS`
+const APPLICATIONS_DIR: &str = "/usr/share/applications";
+const OVERRIDES_DIR: &str = "/usr/local/share/applications";
+
+/// Where the GSettings defaults are overridden, which `glib-compile-schemas` has to compile again
+pub const SCHEMAS_DIR: &str = "/usr/share/glib-2.0/schemas";
+
+/// Marks the wrappers and desktop entries written here, so that those of removed packages are removed too
+const MARKER: &str = "Written by Local Desktop";
+
+/// Touched by the pacman hook after every transaction changing executables or desktop entries, so that the app
+/// applies the shims again, e.g. for a browser installed from the terminal
+pub const PACKAGES_CHANGED: &str = "/var/lib/localdesktop/packages-changed";
+
+/// A change making an application work under Proot. The paths are inside the rootfs.
+#[derive(Debug)]
+pub enum Shim {
+    /// A file written as is, e.g. a configuration the application reads on start
+    File {
+        path: &'static str,
+        contents: &'static str,
+    },
+    /// Run the executables of `/usr/bin` with extra arguments, through a wrapper of the same name ahead in the `PATH`
+    /// and copies of their desktop entries launching it. A `*` ending a name matches any suffix, e.g. `electron*`.
+    Arguments {
+        executables: &'static [&'static str],
+        arguments: &'static [&'static str],
+    },
+}
+
+/// The shims of an application
+#[derive(Debug)]
+pub struct Compat {
+    pub application: &'static str,
+    pub shims: &'static [Shim],
+}
+
+/// The applications applied on every start, new ones simply go here
+pub const APPLICATIONS: &[Compat] = &[
+    Compat {
+        application: "pacman",
+        shims: &[Shim::File {
+            path: "/etc/pacman.d/hooks/localdesktop.hook",
+            contents: "# Auto updated by Local Desktop on each startup, do not edit manually
+[Trigger]
+Operation = Install
+Operation = Upgrade
+Operation = Remove
+Type = Path
+Target = usr/bin/*
+Target = usr/share/applications/*
+Target = usr/share/X11/*
+Target = etc/fonts/*
+Target = etc/ca-certificates/*
+Target = usr/share/ca-certificates/*
+
+[Action]
+Description = Notifying Local Desktop of the package changes...
+When = PostTransaction
+Exec = /usr/bin/touch /var/lib/localdesktop/packages-changed
+",
+        }],
+    },
+    Compat {
+        application: "Firefox",
+        // The sandboxes need syscalls Proot does not emulate. Docs: https://support.mozilla.org/en-US/kb/customizing-firefox-using-autoconfig
+        shims: &[
+            Shim::File {
+                path: "/usr/lib/firefox/defaults/pref/autoconfig.js",
+                contents: "pref(\"general.config.filename\", \"localdesktop.cfg\");\npref(\"general.config.obscure_value\", 0);\n",
+            },
+            Shim::File {
+                path: "/usr/lib/firefox/localdesktop.cfg",
+                // The first line must be a comment
+                contents: "// Auto updated by Local Desktop on each startup, do not edit manually\ndefaultPref(\"media.cubeb.sandbox\", false);\ndefaultPref(\"security.sandbox.content.level\", 0);\n",
+            },
+        ],
+    },
+    Compat {
+        application: "Chromium",
+        shims: &[Shim::Arguments {
+            executables: &["chromium", "google-chrome-stable", "brave"],
+            arguments: &["--no-sandbox", "--no-zygote"],
+        }],
+    },
+    Compat {
+        application: "Electron",
+        shims: &[Shim::Arguments {
+            executables: &["electron*", "code", "codium", "obsidian", "signal-desktop"],
+            arguments: &["--no-sandbox"],
+        }],
+    },
+];
+
+/// The desktop search indexers, which crawl the whole disk after install and peg the CPU for hours on a phone.
+/// Searching by file name still works, only the contents are not indexed.
+pub const INDEXERS: &[Compat] = &[
+    Compat {
+        application: "Baloo",
+        shims: &[Shim::File {
+            path: "/etc/xdg/baloofilerc",
+            contents: "# Auto updated by Local Desktop on each startup, see [setup] tame_indexers\n[Basic Settings]\nIndexing-Enabled=false\n",
+        }],
+    },
+    Compat {
+        application: "Tracker",
+        shims: &[Shim::File {
+            path: "/usr/share/glib-2.0/schemas/90_localdesktop-tracker.gschema.override",
+            contents: "# Auto updated by Local Desktop on each startup, see [setup] tame_indexers\n[org.freedesktop.Tracker3.Miner.Files]\ncrawling-interval=-2\nenable-monitors=false\nindex-on-battery=false\nindex-on-battery-first-time=false\nindex-removable-devices=false\nindex-recursive-directories=@as []\nindex-single-directories=@as []\n",
+        }],
+    },
+];
+
+/// The software centers refreshing and downloading updates in the background, which pacman does better on demand
+pub const BACKGROUND_UPDATES: &[Compat] = &[Compat {
+    application: "GNOME Software",
+    shims: &[Shim::File {
+        path: "/usr/share/glib-2.0/schemas/90_localdesktop-gnome-software.gschema.override",
+        contents: "# Auto updated by Local Desktop on each startup, see [setup] tame_updates\n[org.gnome.software]\ndownload-updates=false\ndownload-updates-notify=false\nrefresh-when-metered=false\n",
+    }],
+}];
+
+/// The shims applied on every start given `[setup]`. Turning an option off later leaves the files written so far.
+pub fn enabled(config: &SetupConfig) -> Vec<&'static Compat> {
+    let mut enabled = APPLICATIONS.iter().collect::<Vec<_>>();
+    if config.tame_indexers {
+        enabled.extend(INDEXERS);
+    }
+    if config.tame_updates {
+        enabled.extend(BACKGROUND_UPDATES);
+    }
+    enabled
+}
+
+/// Whether the changes made include GSettings defaults
+pub fn schemas_changed(changes: &[String]) -> bool {
+    changes.iter().any(|it| it.contains(SCHEMAS_DIR))
+}
+
+/// Apply the shims of the applications to the rootfs at `root`, returning the changes made. The wrappers of the
+/// executables that went away are removed. A shim failing does not stop the others.
+pub fn apply<'a>(root: &Path, applications: impl IntoIterator<Item = &'a Compat>) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut written = HashSet::new();
+    for compat in applications {
+        for shim in compat.shims {
+            let result = match *shim {
+                Shim::File { path, contents } => {
+                    write_file(root, path, contents, 0o644, &mut changes)
+                }
+                Shim::Arguments {
+                    executables,
+                    arguments,
+                } => wrap(
+                    root,
+                    compat.application,
+                    executables,
+                    arguments,
+                    &mut written,
+                    &mut changes,
+                ),
+            };
+            if let Err(e) = result {
+                log::warn!(
+                    "Failed to apply {:?} of {}: {}",
+                    shim,
+                    compat.application,
+                    e
+                );
+            }
+        }
+    }
+    for dir in [WRAPPERS_DIR, OVERRIDES_DIR] {
+        if let Err(e) = remove_stale(root, dir, &written, &mut changes) {
+            log::warn!("Failed to clean {}: {}", dir, e);
+        }
+    }
+    for change in &changes {
+        log::info!("Applied the compatibility shims: {}", change);
+    }
+    changes
+}
+
+fn outside(root: &Path, inside: &str) -> PathBuf {
+    root.join(inside.trim_start_matches('/'))
+}
+
+/// Write the file unless it has the contents already
+fn write_file(
+    root: &Path,
+    path: &str,
+    contents: &str,
+    mode: u32,
+    changes: &mut Vec<String>,
+) -> io::Result<()> {
+    let file = outside(root, path);
+    if fs::read_to_string(&file).is_ok_and(|it| it == contents) {
+        return Ok(());
+    }
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&file, contents)?;
+    fs::set_permissions(&file, fs::Permissions::from_mode(mode))?;
+    changes.push(format!("wrote {}", path));
+    Ok(())
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+fn wrap(
+    root: &Path,
+    application: &str,
+    executables: &[&str],
+    arguments: &[&str],
+    written: &mut HashSet<PathBuf>,
+    changes: &mut Vec<String>,
+) -> io::Result<()> {
+    let Ok(entries) = fs::read_dir(outside(root, EXECUTABLES_DIR)) else {
+        return Ok(());
+    };
+    let mut names = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| executables.iter().any(|it| matches(it, name)))
+        .collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let wrapper = format!("{}/{}", WRAPPERS_DIR, name);
+        let script = format!(
+            "#!/bin/sh\n# {} for {}, do not edit manually\nexec {}/{} {} \"$@\"\n",
+            MARKER,
+            application,
+            EXECUTABLES_DIR,
+            name,
+            arguments.join(" ")
+        );
+        write_file(root, &wrapper, &script, 0o755, changes)?;
+        written.insert(outside(root, &wrapper));
+        override_desktop_entries(root, &name, written, changes)?;
+    }
+    Ok(())
+}
+
+/// Copy the desktop entries launching the executable, so that the menus launch its wrapper instead
+fn override_desktop_entries(
+    root: &Path,
+    name: &str,
+    written: &mut HashSet<PathBuf>,
+    changes: &mut Vec<String>,
+) -> io::Result<()> {
+    let Ok(entries) = fs::read_dir(outside(root, APPLICATIONS_DIR)) else {
+        return Ok(());
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let id = entry.file_name().to_string_lossy().to_string();
+        if !id.ends_with(".desktop") {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        if let Some(content) = rewrite_desktop_entry(&content, name) {
+            let path = format!("{}/{}", OVERRIDES_DIR, id);
+            write_file(root, &path, &content, 0o644, changes)?;
+            written.insert(outside(root, &path));
+        }
+    }
+    Ok(())
+}
+
+/// The desktop entry with its `Exec` and `TryExec` keys launching the wrapper, if any of them launched the executable
+fn rewrite_desktop_entry(content: &str, name: &str) -> Option<String> {
+    let executable = format!("{}/{}", EXECUTABLES_DIR, name);
+    let wrapper = format!("{}/{}", WRAPPERS_DIR, name);
+    let mut rewritten = false;
+    let lines = content
+        .lines()
+        .map(|line| {
+            for key in ["Exec=", "TryExec="] {
+                let Some(value) = line.strip_prefix(key) else {
+                    continue;
+                };
+                let (command, rest) = value.split_once(' ').unwrap_or((value, ""));
+                if command == name || command == executable {
+                    rewritten = true;
+                    let separator = if rest.is_empty() { "" } else { " " };
+                    return format!("{}{}{}{}", key, wrapper, separator, rest);
+                }
+            }
+            line.to_string()
+        })
+        .collect::<Vec<_>>();
+    rewritten.then(|| format!("# {}, do not edit manually\n{}\n", MARKER, lines.join("\n")))
+}
+
+/// Remove the files written here that were not written this time, as their executable went away
+fn remove_stale(
+    root: &Path,
+    dir: &str,
+    written: &HashSet<PathBuf>,
+    changes: &mut Vec<String>,
+) -> io::Result<()> {
+    let Ok(entries) = fs::read_dir(outside(root, dir)) else {
+        return Ok(());
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if written.contains(&path) {
+            continue;
+        }
+        if fs::read_to_string(&path).is_ok_and(|it| it.contains(MARKER)) {
+            fs::remove_file(&path)?;
+            changes.push(format!(
+                "removed {}/{}",
+                dir,
+                entry.file_name().to_string_lossy()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_apply_compatibility_shims() {
+        let root = tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        for name in ["chromium", "electron34", "ls"] {
+            fs::write(root.join("usr/bin").join(name), "").unwrap();
+        }
+        fs::create_dir_all(root.join("usr/share/applications")).unwrap();
+        fs::write(
+            root.join("usr/share/applications/chromium.desktop"),
+            "[Desktop Entry]\nName=Chromium\nExec=/usr/bin/chromium %U\n\n[Desktop Action new-window]\nExec=/usr/bin/chromium\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("usr/share/applications/ls.desktop"),
+            "[Desktop Entry]\nExec=ls\n",
+        )
+        .unwrap();
+
+        let changes = apply(root, APPLICATIONS);
+        assert_eq!(changes.len(), 6, "{:?}", changes);
+        assert!(
+            fs::read_to_string(root.join("etc/pacman.d/hooks/localdesktop.hook"))
+                .unwrap()
+                .contains(&format!("Exec = /usr/bin/touch {}\n", PACKAGES_CHANGED))
+        );
+        assert!(
+            fs::read_to_string(root.join("usr/lib/firefox/localdesktop.cfg"))
+                .unwrap()
+                .starts_with("//")
+        );
+        assert!(fs::read_to_string(root.join("usr/local/bin/chromium"))
+            .unwrap()
+            .ends_with("exec /usr/bin/chromium --no-sandbox --no-zygote \"$@\"\n"));
+        assert!(fs::read_to_string(root.join("usr/local/bin/electron34"))
+            .unwrap()
+            .ends_with("exec /usr/bin/electron34 --no-sandbox \"$@\"\n"));
+        assert!(!root.join("usr/local/bin/ls").exists());
+        let entry =
+            fs::read_to_string(root.join("usr/local/share/applications/chromium.desktop")).unwrap();
+        assert!(entry.contains("\nExec=/usr/local/bin/chromium %U\n"));
+        assert!(entry.contains("\nExec=/usr/local/bin/chromium\n"));
+        assert!(!root
+            .join("usr/local/share/applications/ls.desktop")
+            .exists());
+
+        // Everything is applied already
+        assert!(apply(root, APPLICATIONS).is_empty());
+
+        // Chromium was uninstalled
+        fs::remove_file(root.join("usr/bin/chromium")).unwrap();
+        fs::remove_file(root.join("usr/share/applications/chromium.desktop")).unwrap();
+        assert_eq!(apply(root, APPLICATIONS).len(), 2);
+        assert!(!root.join("usr/local/bin/chromium").exists());
+        assert!(!root
+            .join("usr/local/share/applications/chromium.desktop")
+            .exists());
+        assert!(root.join("usr/local/bin/electron34").exists());
+    }
+
+    #[test]
+    fn should_tame_background_services() {
+        let root = tempdir().unwrap();
+        let root = root.path();
+        let changes = apply(root, enabled(&SetupConfig::default()));
+        assert!(schemas_changed(&changes));
+        assert!(fs::read_to_string(root.join("etc/xdg/baloofilerc"))
+            .unwrap()
+            .contains("Indexing-Enabled=false"));
+        assert!(!schemas_changed(&apply(
+            root,
+            enabled(&SetupConfig::default())
+        )));
+
+        let config = SetupConfig {
+            tame_indexers: false,
+            tame_updates: false,
+        };
+        assert_eq!(enabled(&config).len(), APPLICATIONS.len());
+    }
+}
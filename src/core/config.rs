@@ -1,10 +1,13 @@
-use super::logging::PolarBearExpectation;
+use super::logging::{panel_log, PolarBearExpectation};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::{self, OpenOptions},
     io::Write,
     path::Path,
 };
+use toml::{Table, Value};
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -15,75 +18,1317 @@ pub const ARCH_FS_ROOT: &str = "/data/local/tmp/arch";
 
 pub const ARCH_FS_ARCHIVE: &str = "https://github.com/termux/proot-distro/releases/download/v4.22.1/archlinux-aarch64-pd-v4.22.1.tar.xz";
 
+/// The socket of the session of `[user] username`, the other sessions get `wayland-1`, `wayland-2`...
 pub const WAYLAND_SOCKET_NAME: &str = "wayland-0";
 
+/// The Wayland socket the clients of a session connect to, see `LocalConfig::session_users`
+pub fn wayland_socket_name(session: usize) -> String {
+    format!("wayland-{}", session)
+}
+
 pub const MAX_PANEL_LOG_ENTRIES: usize = 100;
 
-pub const SENTRY_DSN: &str = "https://38b0318da81ccc308c2c75686371ddda@o4509548388417536.ingest.de.sentry.io/4509548392480848";
+pub const SENTRY_DSN: &str = "https://38b0318da81ccc308c2c75686371ddda@o4509548388417536.ingest.de.sentry.io/4509548392480848";
+
+/// Make sure the config keys are all lowercase, and config values are single-line. Use \n for multi-line config values if needed
+/// If a key exists multiple time, the first entry is applied
+/// If a `try_` config exsists multiple time, the last entry is applied
+/// But in general, it is **invalid** to have duplicated config keys inside a TOML file
+pub const CONFIG_FILE: &str = "/etc/localdesktop/localdesktop.toml";
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct LocalConfig {
+    #[serde(default)]
+    pub user: UserConfig,
+
+    /// What happens if we don't assign this `#[serde(default)]` attribute?
+    /// The answer: If the user omits the `[command]` group, the WHOLE config fails to parse
+    /// => The default `[user]` group is applied (with `username=root`) even if the `[user]` settings are completely valid.
+    /// => So make sure that every config group has a `#[serde(default)]` attribute to avoid invalid sections breaking unrelated parts of the config.
+    #[serde(default)]
+    pub command: CommandConfig,
+
+    #[serde(default)]
+    pub display: DisplayConfig,
+
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    #[serde(default)]
+    pub remote: RemoteConfig,
+
+    #[serde(default)]
+    pub keyring: KeyringConfig,
+
+    #[serde(default)]
+    pub touch: TouchConfig,
+
+    #[serde(default)]
+    pub keyboard: KeyboardConfig,
+
+    #[serde(default)]
+    pub gamepad: GamepadConfig,
+
+    #[serde(default)]
+    pub gestures: GesturesConfig,
+
+    #[serde(default)]
+    pub audio: AudioConfig,
+
+    #[serde(default)]
+    pub gpu: GpuConfig,
+
+    #[serde(default)]
+    pub recording: RecordingConfig,
+
+    #[serde(default)]
+    pub bell: BellConfig,
+
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+
+    #[serde(default)]
+    pub open: OpenConfig,
+
+    #[serde(default)]
+    pub share: ShareConfig,
+
+    #[serde(default)]
+    pub priority: PriorityConfig,
+
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    #[serde(default)]
+    pub setup: SetupConfig,
+
+    #[serde(default)]
+    pub limits: LimitsConfig,
+
+    #[serde(default)]
+    pub memory: MemoryConfig,
+
+    #[serde(default)]
+    pub power: PowerConfig,
+
+    #[serde(default)]
+    pub health: HealthConfig,
+
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// The launch commands of the users whose session differs from `[command] launch`, keyed by username,
+    /// e.g. `bob = "... startplasma-x11"`. The session of `[user] username` is started, along with
+    /// those of `[user] concurrent_sessions`. There is a single X display, `:1`, which the session of
+    /// `[user] username` owns: the concurrent sessions must run Wayland apps or services only.
+    #[serde(default)]
+    pub sessions: BTreeMap<String, String>,
+
+    /// Extra directories bound into the Arch FS, keyed by where they show up inside it,
+    /// e.g. `"/mnt/music" = "/storage/emulated/0/Music"`
+    #[serde(default)]
+    pub mounts: BTreeMap<String, String>,
+
+    /// Extra environment variables of every process started in the Arch FS, e.g. `MOZ_ENABLE_WAYLAND = "1"`.
+    /// They take precedence over the ones set by the app.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl LocalConfig {
+    /// The command starting the session of the selected user
+    pub fn launch_command(&self) -> &str {
+        self.session_command(&self.user.username)
+    }
+
+    /// The command starting the session of any user
+    pub fn session_command(&self, user: &str) -> &str {
+        self.sessions.get(user).unwrap_or(&self.command.launch)
+    }
+
+    /// The users whose session runs, the selected user first. The session at index N gets the `wayland-N` socket.
+    /// A concurrent session needs a command of its own in `[sessions]`, as the desktop of `[command] launch`
+    /// takes the only X display.
+    pub fn session_users(&self) -> Vec<String> {
+        let mut users = vec![self.user.username.clone()];
+        for user in &self.user.concurrent_sessions {
+            if !is_valid_username(user) {
+                log::warn!("Ignoring the session of the invalid user {:?}", user);
+            } else if !users.contains(user) && !self.sessions.contains_key(user) {
+                log::warn!(
+                    "Ignoring the session of {}, which has no command in [sessions]",
+                    user
+                );
+            } else if !users.contains(user) {
+                users.push(user.clone());
+            }
+        }
+        users
+    }
+
+    /// The Proot arguments of `[mounts]`, leaving out the invalid ones
+    pub fn mount_args(&self) -> Vec<String> {
+        self.mounts
+            .iter()
+            .filter_map(|(inside, outside)| {
+                let inside = inside.trim_end_matches('/');
+                let outside = outside.trim_end_matches('/');
+                if !is_valid_bind_path(inside) || !is_valid_bind_path(outside) {
+                    log::warn!("Ignoring the invalid mount {:?} = {:?}", inside, outside);
+                    return None;
+                }
+                Some(format!("--bind={}:{}", outside, inside))
+            })
+            .collect()
+    }
+
+    /// The `NAME=value` arguments of `[env]` for `env`, leaving out the invalid ones
+    pub fn env_args(&self) -> Vec<String> {
+        self.env
+            .iter()
+            .filter_map(|(name, value)| {
+                let mut chars = name.chars();
+                let valid_name = chars
+                    .next()
+                    .is_some_and(|it| it.is_ascii_alphabetic() || it == '_')
+                    && chars.all(|it| it.is_ascii_alphanumeric() || it == '_');
+                if !valid_name || value.contains('\0') {
+                    log::warn!("Ignoring the invalid environment variable {:?}", name);
+                    return None;
+                }
+                Some(format!("{}={}", name, value))
+            })
+            .collect()
+    }
+}
+
+/// An absolute path other than the root which Proot can take in `--bind=<path>:<path>`
+fn is_valid_bind_path(path: &str) -> bool {
+    path.starts_with('/') && path.len() > 1 && !path.contains([':', '\0'])
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserConfig {
+    pub username: String,
+    /// The users whose session runs alongside the one of `username`, each connecting to its own Wayland socket,
+    /// e.g. a headless session running services as another user. They share the X display `:1` of the session of
+    /// `username`, so each needs a Wayland-only or headless command in `[sessions]`.
+    #[serde(default)]
+    pub concurrent_sessions: Vec<String>,
+}
+
+impl Default for UserConfig {
+    fn default() -> Self {
+        Self {
+            username: "root".to_string(),
+            concurrent_sessions: Vec::new(),
+        }
+    }
+}
+
+/// Same rules as `useradd` with the default `NAME_REGEX`, so that the name is also safe to pass around in commands
+pub fn is_valid_username(name: &str) -> bool {
+    let mut chars = name.chars();
+    name.len() <= 32
+        && chars
+            .next()
+            .is_some_and(|it| it.is_ascii_lowercase() || it == '_')
+        && chars.all(|it| it.is_ascii_lowercase() || it.is_ascii_digit() || it == '_' || it == '-')
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommandConfig {
+    #[serde(default = "default_check")]
+    pub check: String,
+    #[serde(default = "default_install")]
+    pub install: String,
+    #[serde(default = "default_launch")]
+    pub launch: String,
+    /// Where the Arch FS is installed from when it is missing: an URL, or an archive on the phone
+    /// (`file:///sdcard/Download/archlinux-aarch64.tar.xz` or a `content://` URI).
+    /// This file lives in the Arch FS, so this only applies to reinstalls: for the first install, open the archive
+    /// with Local Desktop from a file manager, or pass it with `adb shell am start ... --es rootfs <path>`.
+    #[serde(default = "default_archive")]
+    pub archive: String,
+}
+
+/// Former defaults of `[command] check` and `install`, replaced by the current ones so that existing installs get the
+/// packages added since. Values the user changed are left alone.
+const STALE_CHECKS: &[&str] = &[
+    "pacman -Q xorg-xwayland && pacman -Qg xfce4 && pacman -Q onboard",
+    "pacman -Q xorg-xwayland && pacman -Qg xfce4 && pacman -Q onboard waypipe openssh",
+    "pacman -Q xorg-xwayland && pacman -Qg xfce4 && pacman -Q onboard waypipe openssh gnome-keyring libsecret",
+    "pacman -Q xorg-xwayland && pacman -Qg xfce4 && pacman -Q onboard waypipe openssh gnome-keyring libsecret sudo",
+];
+const STALE_INSTALLS: &[&str] = &[
+    "stdbuf -oL pacman -Syu xorg-xwayland xfce4 onboard --noconfirm --noprogressbar",
+    "stdbuf -oL pacman -Syu xorg-xwayland xfce4 onboard waypipe openssh --noconfirm --noprogressbar",
+    "stdbuf -oL pacman -Syu xorg-xwayland xfce4 onboard waypipe openssh gnome-keyring libsecret --noconfirm --noprogressbar",
+    "stdbuf -oL pacman -Syu xorg-xwayland xfce4 onboard waypipe openssh gnome-keyring libsecret sudo --noconfirm --noprogressbar",
+];
+
+fn default_check() -> String {
+    "pacman -Q xorg-xwayland xorg-xrdb && pacman -Qg xfce4 && pacman -Q onboard waypipe openssh gnome-keyring libsecret sudo".to_string()
+}
+
+fn default_install() -> String {
+    "stdbuf -oL pacman -Syu xorg-xwayland xorg-xrdb xfce4 onboard waypipe openssh gnome-keyring libsecret sudo --noconfirm --noprogressbar"
+        .to_string()
+}
+
+fn default_archive() -> String {
+    ARCH_FS_ARCHIVE.to_string()
+}
+
+fn default_launch() -> String {
+    "while [ ! -e /tmp/.X11-unix/X1 ]; do sleep 0.1; done; XDG_SESSION_TYPE=x11 DISPLAY=:1 dbus-launch startxfce4 2>&1"
+                .to_string()
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            check: default_check(),
+            install: default_install(),
+            launch: default_launch(),
+            archive: default_archive(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DisplayConfig {
+    /// Painted behind the client surfaces, either a color (`#rrggbb` or `#rrggbbaa`) or an image.
+    /// Images are looked up inside the Arch FS (e.g. `/usr/share/backgrounds/xfce/xfce-leaves.png`),
+    /// or among the app assets with the `asset://` prefix.
+    #[serde(default = "default_background")]
+    pub background: String,
+    /// How many display refreshes a frame waits for when swapped: 1 for vsync, or 0 so that a frame never blocks
+    /// and replaces the one still queued, trading a few dropped frames for latency
+    #[serde(default = "default_swap_interval")]
+    pub swap_interval: u32,
+    /// How many buffers the window queues frames in: 3 for triple buffering, so that rendering goes on while a frame
+    /// waits for vsync, or 2 for double buffering. 0 keeps what Android picks.
+    #[serde(default)]
+    pub buffers: u32,
+    /// How many physical pixels a logical pixel of the desktop spans, e.g. `1.5`, or 0 to follow the density of the
+    /// screen. It scales the clients through the output, and the GTK, Qt and X11 apps through their environment.
+    #[serde(default)]
+    pub scale: f64,
+    /// Have Xwayland scale the X11 apps that are not HiDPI aware. Always on with a `scale` above 1.
+    #[serde(default)]
+    pub xwayland_hidpi: bool,
+    /// X extensions to turn on (`GLX` or `+GLX`) or off (`-XINERAMA`)
+    #[serde(default)]
+    pub xwayland_extensions: Vec<String>,
+    /// Keep the X server as it is when its last client disconnects, rather than resetting it
+    #[serde(default)]
+    pub xwayland_noreset: bool,
+    /// Transports the X server listens on besides its local socket, e.g. `tcp`
+    #[serde(default)]
+    pub xwayland_listen: Vec<String>,
+    /// Any other Xwayland arguments, separated by spaces
+    #[serde(default)]
+    pub xwayland_args: String,
+    /// How the desktop makes room for the soft keyboard: `pan` moves it up as far as the text being typed needs,
+    /// `shrink` resizes the output to the area above the keyboard, and `overlap` leaves it under the keyboard
+    #[serde(default = "default_keyboard_inset")]
+    pub keyboard_inset: String,
+    /// Where the desktop is drawn: `native` in the window of the activity, or `surface_view` or `texture_view` in a
+    /// view shown over it, for the devices whose native window misbehaves
+    #[serde(default = "default_presentation")]
+    pub presentation: String,
+    /// What a display plugged into the device (e.g. over USB-C) shows: `mirror` the desktop, `span` it on the right
+    /// of the screen as a second output, or `off`
+    #[serde(default = "default_external_display")]
+    pub external_display: String,
+}
+
+fn default_external_display() -> String {
+    "mirror".to_string()
+}
+
+fn default_background() -> String {
+    "#1a0000".to_string()
+}
+
+fn default_presentation() -> String {
+    "native".to_string()
+}
+
+fn default_keyboard_inset() -> String {
+    "pan".to_string()
+}
+
+fn default_swap_interval() -> u32 {
+    1
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            background: default_background(),
+            swap_interval: default_swap_interval(),
+            buffers: 0,
+            scale: 0.0,
+            xwayland_hidpi: false,
+            xwayland_extensions: vec![],
+            xwayland_noreset: false,
+            xwayland_listen: vec![],
+            xwayland_args: String::new(),
+            keyboard_inset: default_keyboard_inset(),
+            presentation: default_presentation(),
+            external_display: default_external_display(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoggingConfig {
+    /// Either a level for all logs (`error`, `warn`, `info`, `debug` or `trace`),
+    /// or a comma-separated list that also overrides some modules, e.g. `info,smithay=debug`
+    #[serde(default = "default_log_level")]
+    pub level: String,
+}
+
+fn default_log_level() -> String {
+    // Verbose logging in debug builds
+    if cfg!(debug_assertions) {
+        "trace".to_string()
+    } else {
+        "info".to_string()
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteConfig {
+    /// Let Wayland clients of other machines show up on this display, forwarded by waypipe over TCP
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_remote_port")]
+    pub port: u16,
+    /// Sent by the remote side as the first line of each connection, nothing is accepted while it is empty
+    #[serde(default)]
+    pub token: String,
+}
+
+fn default_remote_port() -> u16 {
+    7700
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_remote_port(),
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeyringConfig {
+    /// Unlock the login keyring of the session with a secret held by the Android Keystore,
+    /// so that apps can store their passwords without asking for a keyring password.
+    /// Not named `enabled`, which `[remote]` uses already: keys are unique across sections.
+    #[serde(default = "default_unlock_keyring")]
+    pub unlock: bool,
+}
+
+fn default_unlock_keyring() -> bool {
+    true
+}
+
+impl Default for KeyringConfig {
+    fn default() -> Self {
+        Self {
+            unlock: default_unlock_keyring(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TouchConfig {
+    /// Holding a finger still right-clicks where it is, e.g. to open context menus
+    #[serde(default = "default_true")]
+    pub long_press_right_click: bool,
+    /// How long (in milliseconds) a finger is held before it right-clicks
+    #[serde(default = "default_long_press_delay")]
+    pub long_press_delay: u64,
+    /// Tapping, then touching again and dragging, drags with the left button, e.g. to select text
+    #[serde(default = "default_true")]
+    pub double_tap_drag: bool,
+    /// Only forward the latest position of each finger once per frame, rather than every sample of the touchscreen.
+    /// Drawing apps may want every sample, at the cost of flooding the clients with motions.
+    #[serde(default = "default_true")]
+    pub coalesce_motion: bool,
+    /// A stylus draws through the tablet protocol, with its pressure and tilt, rather than moving the mouse. The apps
+    /// that don't support tablets only get the mouse.
+    #[serde(default = "default_true")]
+    pub stylus_tablet: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_long_press_delay() -> u64 {
+    500
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        Self {
+            long_press_right_click: true,
+            long_press_delay: default_long_press_delay(),
+            double_tap_drag: true,
+            coalesce_motion: true,
+            stylus_tablet: true,
+        }
+    }
+}
+
+/// The XKB keymap of the keyboards, which the apps and Xwayland get from the compositor. The empty values are the
+/// XKB defaults, i.e. a US keyboard.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct KeyboardConfig {
+    /// The keyboard model, e.g. `pc105`
+    #[serde(default)]
+    pub model: String,
+    /// The layouts, comma separated, e.g. `us,de`. Ctrl+Space switches to the next one.
+    #[serde(default)]
+    pub layout: String,
+    /// The variant of each layout, comma separated, e.g. `,nodeadkeys`
+    #[serde(default)]
+    pub variant: String,
+    /// The XKB options, comma separated, e.g. `caps:escape,compose:ralt`
+    #[serde(default)]
+    pub options: String,
+    /// Leave the volume keys to Android, changing the volume of the phone rather than that of the desktop
+    #[serde(default)]
+    pub android_volume_keys: bool,
+}
+
+impl KeyboardConfig {
+    /// Whether there are layouts to switch between. Ctrl+Space is left to the apps otherwise, e.g. for completion.
+    pub fn has_several_layouts(&self) -> bool {
+        self.layout
+            .split(',')
+            .filter(|it| !it.trim().is_empty())
+            .count()
+            > 1
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GamepadConfig {
+    /// Stream the buttons and the sticks of the gamepads to `/tmp/.localdesktop-gamepad` in the Arch FS, as evdev
+    /// events. Running `localdesktop-gamepad` turns them into a controller for the games and the emulators, where
+    /// `/dev/uinput` is writable, i.e. on rooted devices.
+    #[serde(default = "default_true")]
+    pub forward_gamepads: bool,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            forward_gamepads: true,
+        }
+    }
+}
+
+/// The gestures handled by the compositor rather than the apps. The long press and the double tap are in `[touch]`.
+/// The actions are `diagnostics`, `settings`, `terminal`, `keyboard` (showing or hiding the soft keyboard),
+/// `screenshot` (saving the screen into Pictures), `record` (starting or stopping a recording of the desktop) or
+/// `none`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GesturesConfig {
+    /// Dragging two fingers along scrolls the window under them
+    #[serde(default = "default_true")]
+    pub two_finger_scroll: bool,
+    /// Moving two fingers apart or together zooms the desktop in or out
+    #[serde(default = "default_true")]
+    pub pinch_zoom: bool,
+    /// What tapping with three fingers at once does
+    #[serde(default = "default_gesture_action")]
+    pub three_finger_tap: String,
+    /// What tapping with four fingers at once does
+    #[serde(default = "default_gesture_action")]
+    pub four_finger_tap: String,
+    /// What swiping from an edge of the screen towards its middle does. The touches starting along an edge with an
+    /// action no longer reach the apps.
+    #[serde(default = "default_gesture_action")]
+    pub swipe_from_left: String,
+    #[serde(default = "default_gesture_action")]
+    pub swipe_from_right: String,
+    #[serde(default = "default_gesture_action")]
+    pub swipe_from_top: String,
+    #[serde(default = "default_gesture_action")]
+    pub swipe_from_bottom: String,
+    /// What the Back button or gesture of Android does: `escape` presses Esc in the focused window, `keyboard`
+    /// toggles the soft keyboard and `quit` asks whether to close the app
+    #[serde(default = "default_back_action")]
+    pub back: String,
+}
+
+fn default_gesture_action() -> String {
+    "none".to_string()
+}
+
+fn default_back_action() -> String {
+    "escape".to_string()
+}
+
+impl Default for GesturesConfig {
+    fn default() -> Self {
+        Self {
+            two_finger_scroll: true,
+            pinch_zoom: true,
+            three_finger_tap: default_gesture_action(),
+            four_finger_tap: default_gesture_action(),
+            swipe_from_left: default_gesture_action(),
+            swipe_from_right: default_gesture_action(),
+            swipe_from_top: default_gesture_action(),
+            swipe_from_bottom: default_gesture_action(),
+            back: default_back_action(),
+        }
+    }
+}
+
+/// See `GesturesConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GestureAction {
+    None,
+    ShowDiagnostics,
+    ShowSettings,
+    ShowTerminal,
+    ToggleSoftKeyboard,
+    Screenshot,
+    ToggleRecording,
+}
+
+impl GestureAction {
+    /// The names of the actions in the config, in the order the settings page lists them
+    pub const NAMES: [&str; 7] = [
+        "none",
+        "diagnostics",
+        "settings",
+        "terminal",
+        "keyboard",
+        "screenshot",
+        "record",
+    ];
+
+    /// Falls back to doing nothing if the action is unknown
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "none" => Self::None,
+            "diagnostics" => Self::ShowDiagnostics,
+            "settings" => Self::ShowSettings,
+            "terminal" => Self::ShowTerminal,
+            "keyboard" => Self::ToggleSoftKeyboard,
+            "screenshot" => Self::Screenshot,
+            "record" => Self::ToggleRecording,
+            other => {
+                log::warn!("Invalid gesture action {:?}, doing nothing instead", other);
+                Self::None
+            }
+        }
+    }
+}
+
+/// See `GesturesConfig::back`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackAction {
+    Escape,
+    ToggleSoftKeyboard,
+    ConfirmQuit,
+}
+
+impl BackAction {
+    /// The names of the actions in the config, in the order the settings page lists them
+    pub const NAMES: [&str; 3] = ["escape", "keyboard", "quit"];
+
+    /// Falls back to Esc if the action is unknown
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "escape" => Self::Escape,
+            "keyboard" => Self::ToggleSoftKeyboard,
+            "quit" => Self::ConfirmQuit,
+            other => {
+                log::warn!("Invalid back action {:?}, pressing Esc instead", other);
+                Self::Escape
+            }
+        }
+    }
+}
+
+/// An edge of the screen, which a swipe starts from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl GesturesConfig {
+    /// What tapping with that many fingers at once does
+    pub fn tap_action(&self, fingers: usize) -> GestureAction {
+        match fingers {
+            3 => GestureAction::parse(&self.three_finger_tap),
+            4 => GestureAction::parse(&self.four_finger_tap),
+            _ => GestureAction::None,
+        }
+    }
+
+    /// What swiping from that edge does
+    pub fn swipe_action(&self, edge: Edge) -> GestureAction {
+        GestureAction::parse(match edge {
+            Edge::Left => &self.swipe_from_left,
+            Edge::Right => &self.swipe_from_right,
+            Edge::Top => &self.swipe_from_top,
+            Edge::Bottom => &self.swipe_from_bottom,
+        })
+    }
+
+    /// What the Back button does
+    pub fn back_action(&self) -> BackAction {
+        BackAction::parse(&self.back)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AudioConfig {
+    /// Play the sound of the session on the phone, through a PulseAudio server installed on first use
+    #[serde(default = "default_true")]
+    pub playback: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self { playback: true }
+    }
+}
+
+/// How the OpenGL apps of the session render, which is in software (llvmpipe) unless the GPU of the phone is
+/// bridged into the Arch FS
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GpuConfig {
+    /// `llvmpipe`, `virgl` to forward OpenGL to a renderer running on the Android side, or `zink` to run it over
+    /// the Vulkan driver of the GPU, which needs Turnip (Mesa for the Adreno GPUs) built for KGSL in the Arch FS
+    #[serde(default = "default_renderer")]
+    pub renderer: String,
+}
+
+fn default_renderer() -> String {
+    "llvmpipe".to_string()
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            renderer: default_renderer(),
+        }
+    }
+}
+
+/// See `GpuConfig::renderer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuRenderer {
+    Llvmpipe,
+    Virgl,
+    Zink,
+}
+
+impl GpuRenderer {
+    /// The names of the renderers in the config, in the order the settings page lists them
+    pub const NAMES: [&str; 3] = ["llvmpipe", "virgl", "zink"];
+}
+
+impl GpuConfig {
+    /// Falls back to llvmpipe if the renderer is unknown, which always works
+    pub fn renderer(&self) -> GpuRenderer {
+        match self.renderer.trim() {
+            "llvmpipe" => GpuRenderer::Llvmpipe,
+            "virgl" => GpuRenderer::Virgl,
+            "zink" => GpuRenderer::Zink,
+            other => {
+                log::warn!("Invalid GPU renderer {:?}, using llvmpipe instead", other);
+                GpuRenderer::Llvmpipe
+            }
+        }
+    }
+}
+
+/// The recordings of the desktop, started and stopped with Ctrl+Alt+Shift+R or a gesture, and saved into
+/// `Movies/Local Desktop`. They carry the sound of the session when `[audio] playback` is on.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecordingConfig {
+    /// Mix the microphone of the phone into the sound, e.g. to comment a demo
+    #[serde(default)]
+    pub microphone: bool,
+    /// In kilobits per second
+    #[serde(default = "default_video_bitrate")]
+    pub video_bitrate: u32,
+    /// The desktop is recorded at most that many times per second, and only when something changed on it
+    #[serde(default = "default_frame_rate")]
+    pub frame_rate: u32,
+}
+
+fn default_video_bitrate() -> u32 {
+    8000
+}
+
+fn default_frame_rate() -> u32 {
+    30
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            microphone: false,
+            video_bitrate: default_video_bitrate(),
+            frame_rate: default_frame_rate(),
+        }
+    }
+}
+
+/// The bell of the apps (`xdg_system_bell`, which Xwayland forwards the X11 bell to) and of the terminal,
+/// so that a long command can tell it finished even with `[audio] playback` off
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BellConfig {
+    /// What a bell does: play the notification `sound` of the phone, `vibrate` it, `both`, or `off`
+    #[serde(default = "default_signal")]
+    pub signal: String,
+    /// The bells within that many milliseconds of the last one are ignored, e.g. for a `cat` of a binary file
+    #[serde(default = "default_cooldown")]
+    pub cooldown: u64,
+}
+
+fn default_signal() -> String {
+    "sound".to_string()
+}
+
+fn default_cooldown() -> u64 {
+    1000
+}
+
+impl Default for BellConfig {
+    fn default() -> Self {
+        Self {
+            signal: default_signal(),
+            cooldown: default_cooldown(),
+        }
+    }
+}
+
+/// See `BellConfig::signal`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BellSignal {
+    pub sound: bool,
+    pub vibrate: bool,
+}
+
+impl BellSignal {
+    /// The names of the signals in the config, in the order the settings page lists them
+    pub const NAMES: [&str; 4] = ["sound", "vibrate", "both", "off"];
+}
+
+impl BellConfig {
+    /// Falls back to the sound if the signal is unknown
+    pub fn signal(&self) -> BellSignal {
+        let (sound, vibrate) = match self.signal.trim() {
+            "sound" => (true, false),
+            "vibrate" => (false, true),
+            "both" => (true, true),
+            "off" => (false, false),
+            other => {
+                log::warn!("Invalid bell signal {:?}, playing a sound instead", other);
+                (true, false)
+            }
+        };
+        BellSignal { sound, vibrate }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationsConfig {
+    /// Show what the apps of the session notify (`notify-send`, mail clients, long builds...) as Android
+    /// notifications, through an `org.freedesktop.Notifications` service installed on first use
+    #[serde(default = "default_true")]
+    pub forward_notifications: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            forward_notifications: true,
+        }
+    }
+}
+
+/// What `xdg-open` opens the links of the session with, e.g. as they are clicked in a terminal. The files are
+/// always opened with the apps of the desktop.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OpenConfig {
+    /// `auto` to open the links with Android when no app of the desktop is the default for them, `always` to
+    /// always open them with Android, or `never`
+    #[serde(default = "default_links_on_android")]
+    pub links_on_android: String,
+}
+
+fn default_links_on_android() -> String {
+    "auto".to_string()
+}
+
+impl Default for OpenConfig {
+    fn default() -> Self {
+        Self {
+            links_on_android: default_links_on_android(),
+        }
+    }
+}
+
+/// See `OpenConfig::links_on_android`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinksOnAndroid {
+    Auto,
+    Always,
+    Never,
+}
+
+impl LinksOnAndroid {
+    /// The names of the modes in the config, in the order the settings page lists them
+    pub const NAMES: [&str; 3] = ["auto", "always", "never"];
+}
+
+impl OpenConfig {
+    /// Falls back to `auto` if the mode is unknown
+    pub fn links_on_android(&self) -> LinksOnAndroid {
+        match self.links_on_android.trim() {
+            "auto" => LinksOnAndroid::Auto,
+            "always" => LinksOnAndroid::Always,
+            "never" => LinksOnAndroid::Never,
+            other => {
+                log::warn!(
+                    "Invalid mode {:?} for the links, opening them with Android when the desktop can't",
+                    other
+                );
+                LinksOnAndroid::Auto
+            }
+        }
+    }
+}
+
+/// The files the other apps of the phone share with Local Desktop, or open with it. They are copied into the
+/// `Shared` folder of the home of `[user] username`, and opened in the desktop.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShareConfig {
+    /// The command opening them, e.g. `evince`, or `xdg-open` for the default app of their type. The changes apply
+    /// on the next launch of the session.
+    #[serde(default = "default_open_with")]
+    pub open_with: String,
+}
+
+fn default_open_with() -> String {
+    "xdg-open".to_string()
+}
+
+impl Default for ShareConfig {
+    fn default() -> Self {
+        Self {
+            open_with: default_open_with(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriorityConfig {
+    /// Give the processes of the focused window more CPU and I/O than those of the other windows,
+    /// e.g. so that a compile in a terminal behind does not make the desktop stutter
+    #[serde(default = "default_true")]
+    pub boost_focused: bool,
+    /// The nice value of the processes of the focused window, from -20 (most CPU) to 19
+    #[serde(default = "default_focused_nice")]
+    pub focused_nice: i32,
+    /// The nice value of the processes of the other windows
+    #[serde(default = "default_background_nice")]
+    pub background_nice: i32,
+}
+
+fn default_focused_nice() -> i32 {
+    -4
+}
+
+fn default_background_nice() -> i32 {
+    10
+}
+
+impl Default for PriorityConfig {
+    fn default() -> Self {
+        Self {
+            boost_focused: true,
+            focused_nice: default_focused_nice(),
+            background_nice: default_background_nice(),
+        }
+    }
+}
+
+/// Keeps a runaway session, e.g. a build eating all the memory, from taking Android down along with it.
+/// Each session gets the limits of its own, 0 meaning no limit.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LimitsConfig {
+    /// The memory all the processes of a session may use together, in MiB, before the largest one is killed
+    #[serde(default)]
+    pub max_memory_mb: u64,
+    /// The CPU a session may use, in percent of all the cores, over which its processes are paused for a while
+    #[serde(default)]
+    pub max_cpu_percent: u32,
+    /// The files each process may have open at once
+    #[serde(default)]
+    pub max_open_files: u64,
+}
+
+/// How the app gives memory back when Android runs low, rather than being killed without a word
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemoryConfig {
+    /// Stop pacman while the memory is low, and let it go on once it is over
+    #[serde(default = "default_true")]
+    pub pause_pacman: bool,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self { pause_pacman: true }
+    }
+}
+
+/// When the phone stays awake for the desktop. The screen is kept on, or let go, until the next launch with
+/// Ctrl+Alt+Shift+W.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PowerConfig {
+    /// `always`, `fullscreen` to only keep the screen on while a window covers it whole, e.g. a video, or `never`
+    /// to let it turn off as Android says
+    #[serde(default = "default_keep_screen_on")]
+    pub keep_screen_on: String,
+    /// Let the screen turn off after that many minutes without touching it, typing or moving the mouse, 0 meaning
+    /// never
+    #[serde(default)]
+    pub idle_timeout_minutes: u32,
+    /// Keep the CPU running while pacman runs, even with the screen off, so that a long upgrade does not stall
+    #[serde(default = "default_true")]
+    pub wake_lock_during_pacman: bool,
+}
+
+fn default_keep_screen_on() -> String {
+    "always".to_string()
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            keep_screen_on: default_keep_screen_on(),
+            idle_timeout_minutes: 0,
+            wake_lock_during_pacman: true,
+        }
+    }
+}
+
+/// See `PowerConfig::keep_screen_on`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepScreenOn {
+    Always,
+    Fullscreen,
+    Never,
+}
 
-/// Make sure the config keys are all lowercase, and config values are single-line. Use \n for multi-line config values if needed
-/// If a key exists multiple time, the first entry is applied
-/// If a `try_` config exsists multiple time, the last entry is applied
-/// But in general, it is **invalid** to have duplicated config keys inside a TOML file
-pub const CONFIG_FILE: &str = "/etc/localdesktop/localdesktop.toml";
+impl KeepScreenOn {
+    /// The names of the modes in the config, in the order the settings page lists them
+    pub const NAMES: [&str; 3] = ["always", "fullscreen", "never"];
+}
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-pub struct LocalConfig {
+impl PowerConfig {
+    /// Falls back to keeping the screen on if the mode is unknown, as the app always did
+    pub fn keep_screen_on(&self) -> KeepScreenOn {
+        match self.keep_screen_on.trim() {
+            "always" => KeepScreenOn::Always,
+            "fullscreen" => KeepScreenOn::Fullscreen,
+            "never" => KeepScreenOn::Never,
+            other => {
+                log::warn!(
+                    "Invalid keep_screen_on mode {:?}, keeping the screen on instead",
+                    other
+                );
+                KeepScreenOn::Always
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HealthConfig {
+    /// Check the Arch FS once a day while the app is open: the `[command] check`, then an xterm started in a
+    /// headless X server, warning on the log panel as soon as a check fails after passing
     #[serde(default)]
-    pub user: UserConfig,
+    pub nightly: bool,
+    /// Install what the xterm check needs (Xvfb, xterm, xwd) when it is missing, instead of skipping that check
+    #[serde(default)]
+    pub install_smoke_test: bool,
+}
 
-    /// What happens if we don't assign this `#[serde(default)]` attribute?
-    /// The answer: If the user omits the `[command]` group, the WHOLE config fails to parse
-    /// => The default `[user]` group is applied (with `username=root`) even if the `[user]` settings are completely valid.
-    /// => So make sure that every config group has a `#[serde(default)]` attribute to avoid invalid sections breaking unrelated parts of the config.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TelemetryConfig {
+    /// Send which features are used along with the crash reports, e.g. the desktop, the input devices or the
+    /// audio bridge, without anything identifying the user, so that the work goes where it matters
     #[serde(default)]
-    pub command: CommandConfig,
+    pub share_usage: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct UserConfig {
-    pub username: String,
+pub struct SetupConfig {
+    /// Keep the desktop search indexers (Baloo, Tracker) from crawling the whole disk
+    #[serde(default = "default_true")]
+    pub tame_indexers: bool,
+    /// Keep the software centers (GNOME Software) from refreshing and downloading updates in the background
+    #[serde(default = "default_true")]
+    pub tame_updates: bool,
 }
 
-impl Default for UserConfig {
+impl Default for SetupConfig {
     fn default() -> Self {
         Self {
-            username: "root".to_string(),
+            tame_indexers: true,
+            tame_updates: true,
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct CommandConfig {
-    #[serde(default = "default_check")]
-    pub check: String,
-    #[serde(default = "default_install")]
-    pub install: String,
-    #[serde(default = "default_launch")]
-    pub launch: String,
+pub struct StorageConfig {
+    /// Bind the shared storage of the phone (downloads, documents, pictures...) into the Arch FS,
+    /// once the app is granted the access to all files
+    #[serde(default = "default_true")]
+    pub shared: bool,
+    /// Where the shared storage shows up in the Arch FS
+    #[serde(default = "default_mount_point")]
+    pub mount_point: String,
 }
 
-fn default_check() -> String {
-    "pacman -Q xorg-xwayland && pacman -Qg xfce4 && pacman -Q onboard".to_string()
+fn default_mount_point() -> String {
+    "/sdcard".to_string()
 }
 
-fn default_install() -> String {
-    "stdbuf -oL pacman -Syu xorg-xwayland xfce4 onboard --noconfirm --noprogressbar".to_string()
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            shared: true,
+            mount_point: default_mount_point(),
+        }
+    }
 }
 
-fn default_launch() -> String {
-    "XDG_RUNTIME_DIR=/tmp Xwayland -hidpi :1 2>&1 & while [ ! -e /tmp/.X11-unix/X1 ]; do sleep 0.1; done; XDG_SESSION_TYPE=x11 DISPLAY=:1 dbus-launch startxfce4 2>&1"
-                .to_string()
+impl StorageConfig {
+    /// The Proot argument binding the shared storage at `source` into the Arch FS, if it is shared
+    pub fn bind_argument(&self, source: &str) -> Option<String> {
+        if !self.shared {
+            return None;
+        }
+        let mount_point = self.mount_point.trim_end_matches('/');
+        if !is_valid_bind_path(mount_point) {
+            log::warn!(
+                "Ignoring the invalid [storage] mount_point {:?}",
+                self.mount_point
+            );
+            return None;
+        }
+        Some(format!("--bind={}:{}", source, mount_point))
+    }
 }
 
-impl Default for CommandConfig {
-    fn default() -> Self {
-        Self {
-            check: default_check(),
-            install: default_install(),
-            launch: default_launch(),
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    /// RGBA, each component in `0.0..=1.0`
+    Color([f32; 4]),
+    /// Absolute path inside the Arch FS
+    File(String),
+    /// File name inside the app assets
+    Asset(String),
+}
+
+/// See `DisplayConfig::keyboard_inset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardInset {
+    Pan,
+    Shrink,
+    Overlap,
+}
+
+/// See `DisplayConfig::external_display`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalDisplay {
+    Off,
+    Mirror,
+    Span,
+}
+
+/// See `DisplayConfig::presentation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presentation {
+    NativeWindow,
+    SurfaceView,
+    TextureView,
+}
+
+/// The range `[display] scale` is kept in, beyond which the desktop is unusable
+const MIN_SCALE: f64 = 0.5;
+const MAX_SCALE: f64 = 4.0;
+
+impl DisplayConfig {
+    /// The configured scale, or `auto` (the one following the density of the screen) if there is none
+    pub fn scale(&self, auto: f64) -> f64 {
+        if self.scale > 0.0 {
+            self.scale.clamp(MIN_SCALE, MAX_SCALE)
+        } else {
+            auto
+        }
+    }
+
+    /// Where to draw. Falls back to the native window if the configured presentation is unknown.
+    pub fn presentation(&self) -> Presentation {
+        match self.presentation.trim() {
+            "native" => Presentation::NativeWindow,
+            "surface_view" => Presentation::SurfaceView,
+            "texture_view" => Presentation::TextureView,
+            other => {
+                log::warn!(
+                    "Invalid presentation {:?}, drawing in the native window",
+                    other
+                );
+                Presentation::NativeWindow
+            }
+        }
+    }
+
+    /// Falls back to the default color if the configured background is neither a valid color nor a path
+    pub fn background(&self) -> Background {
+        let value = self.background.trim();
+        if let Some(hex) = value.strip_prefix('#') {
+            if let Some(color) = parse_hex_color(hex) {
+                return Background::Color(color);
+            }
+        } else if let Some(name) = value.strip_prefix("asset://") {
+            return Background::Asset(name.to_string());
+        } else if value.starts_with('/') {
+            return Background::File(value.to_string());
+        }
+        log::warn!("Invalid background {:?}, using the default one", value);
+        Background::Color(
+            parse_hex_color(&default_background()[1..]).unwrap_or([0.0, 0.0, 0.0, 1.0]),
+        )
+    }
+
+    /// Falls back to mirroring if the configured mode is unknown
+    pub fn external_display(&self) -> ExternalDisplay {
+        match self.external_display.trim() {
+            "mirror" => ExternalDisplay::Mirror,
+            "span" => ExternalDisplay::Span,
+            "off" => ExternalDisplay::Off,
+            other => {
+                log::warn!("Invalid external_display {:?}, mirroring instead", other);
+                ExternalDisplay::Mirror
+            }
+        }
+    }
+
+    /// Falls back to panning if the configured mode is unknown
+    pub fn keyboard_inset(&self) -> KeyboardInset {
+        match self.keyboard_inset.trim() {
+            "pan" => KeyboardInset::Pan,
+            "shrink" => KeyboardInset::Shrink,
+            "overlap" => KeyboardInset::Overlap,
+            other => {
+                log::warn!("Invalid keyboard_inset {:?}, panning instead", other);
+                KeyboardInset::Pan
+            }
+        }
+    }
+
+    /// The arguments Xwayland gets on top of the ones the compositor needs to run it
+    pub fn xwayland_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if self.xwayland_hidpi || self.scale > 1.0 {
+            args.push("-hidpi".to_string());
+        }
+        for extension in &self.xwayland_extensions {
+            let extension = extension.trim();
+            let (toggle, name) = match extension.strip_prefix('-') {
+                Some(name) => ("-extension", name),
+                None => ("+extension", extension.trim_start_matches('+')),
+            };
+            if !name.is_empty() {
+                args.extend([toggle.to_string(), name.to_string()]);
+            }
+        }
+        if self.xwayland_noreset {
+            args.push("-noreset".to_string());
         }
+        for transport in &self.xwayland_listen {
+            args.extend(["-listen".to_string(), transport.trim().to_string()]);
+        }
+        args.extend(self.xwayland_args.split_whitespace().map(str::to_string));
+        args
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Option<[f32; 4]> {
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
     }
+    let mut color = [1.0; 4];
+    for (i, component) in color.iter_mut().enumerate().take(hex.len() / 2) {
+        let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        *component = byte as f32 / 255.0;
+    }
+    Some(color)
+}
+
+/// Drop the `Xwayland ... &` the launch command used to start with: the compositor runs Xwayland itself now, and a
+/// second one races it for the display
+fn strip_legacy_xwayland(launch: &str) -> Option<String> {
+    let (head, rest) = launch.split_once(" & ")?;
+    if head.contains(['|', ';']) || head.contains("&&") {
+        return None;
+    }
+    let program = head.split_whitespace().find(|word| !word.contains('='))?;
+    (program == "Xwayland" || program.ends_with("/Xwayland")).then(|| rest.trim_start().to_string())
+}
+
+/// Bring a value written by an older version up to date, if it needs to be
+fn migrate_value(section: &str, key: &str, value: &str) -> Option<String> {
+    match (section, key) {
+        ("command", "launch") => strip_legacy_xwayland(value),
+        ("command", "check") if STALE_CHECKS.contains(&value) => Some(default_check()),
+        ("command", "install") if STALE_INSTALLS.contains(&value) => Some(default_install()),
+        // There is no device known to need a view, which `auto` picked from
+        ("display", "presentation") if value == "auto" => Some(default_presentation()),
+        _ => None,
+    }
+}
+
+/// The `key = value` line with the value migrated, if the value is a string that needs it
+fn migrate_line(section: &str, key: &str, value: &str) -> Option<String> {
+    let table = format!("value = {}", value).parse::<Table>().ok()?;
+    let migrated = migrate_value(section, key, table.get("value")?.as_str()?)?;
+    Some(format!("{} = {}", key, Value::String(migrated)))
 }
 
 /// This function does 2 major tasks:
@@ -94,10 +1339,25 @@ impl Default for CommandConfig {
 fn process_config_file(full_config_path: String) -> Vec<String> {
     let mut write_back_lines: Vec<String> = vec![];
     let mut effective_config: Vec<String> = vec![];
+    let mut section = String::new();
 
     if let Ok(content) = fs::read_to_string(&full_config_path) {
         for line in content.lines() {
-            let trimmed = line.trim();
+            let mut trimmed = line.trim();
+            if let Some(header) = trimmed
+                .strip_prefix('[')
+                .and_then(|it| it.strip_suffix(']'))
+            {
+                section = header.trim().to_string();
+            }
+
+            let migrated = trimmed
+                .split_once('=')
+                .and_then(|(key, value)| migrate_line(&section, key.trim(), value.trim()));
+            if let Some(migrated) = &migrated {
+                log::info!("Migrated the config line {:?} to {:?}", trimmed, migrated);
+                trimmed = migrated;
+            }
 
             if let Some((key, value)) = trimmed.split_once('=') {
                 let key = key.trim();
@@ -193,15 +1453,122 @@ pub fn save_config(config: &LocalConfig) {
 }
 
 pub fn parse_config(full_config_path: String) -> LocalConfig {
+    let original = fs::read_to_string(&full_config_path).unwrap_or_default();
     let lines = process_config_file(full_config_path);
-    let content = lines.join("\n");
-    if let Ok(config) = toml::from_str::<LocalConfig>(&content) {
-        return config;
+    let (config, errors) = validate_config(&original, &lines.join("\n"));
+    // The file is left as the user wrote it, so that they can fix it
+    for error in errors {
+        panel_log(format!("Invalid config: {}", error), true);
+    }
+    config
+}
+
+/// A problem found in the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// Counted from 1, if known
+    pub line: Option<usize>,
+    /// The `section.key` whose value is invalid, if the file could be parsed
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, "{}: ", field)?;
+        }
+        write!(f, "{}", self.message.trim())
+    }
+}
+
+/// Parse the effective config, keeping every valid value of it: an invalid value falls back to its default alone,
+/// while a syntax error falls back to the default config. The lines of the errors are found in `original`, the file
+/// as the user wrote it.
+pub fn validate_config(original: &str, effective: &str) -> (LocalConfig, Vec<ConfigError>) {
+    let syntax_error = |content: &str, e: toml::de::Error| ConfigError {
+        line: e
+            .span()
+            .map(|span| content[..span.start].matches('\n').count() + 1),
+        field: None,
+        message: format!("{}, using the default config", e.message()),
+    };
+    if let Err(e) = original.parse::<Table>() {
+        return (LocalConfig::default(), vec![syntax_error(original, e)]);
+    }
+    let table = match effective.parse::<Table>() {
+        Ok(table) => table,
+        Err(e) => return (LocalConfig::default(), vec![syntax_error(effective, e)]),
+    };
+    if let Ok(config) = Value::Table(table.clone()).try_into::<LocalConfig>() {
+        return (config, vec![]);
+    }
+
+    // Put the values one by one over the defaults, leaving out the ones that do not fit
+    let Ok(Value::Table(mut valid)) = Value::try_from(LocalConfig::default()) else {
+        return (LocalConfig::default(), vec![]);
+    };
+    let mut errors = vec![];
+    for (section, value) in table {
+        let keys = match value {
+            Value::Table(keys) if valid.get(&section).is_some_and(Value::is_table) => keys,
+            value => Table::from_iter([(String::new(), value)]),
+        };
+        for (key, value) in keys {
+            let mut candidate = valid.clone();
+            if key.is_empty() {
+                candidate.insert(section.clone(), value);
+            } else if let Some(Value::Table(values)) = candidate.get_mut(&section) {
+                values.insert(key.clone(), value);
+            }
+            match Value::Table(candidate.clone()).try_into::<LocalConfig>() {
+                Ok(_) => valid = candidate,
+                Err(e) => errors.push(ConfigError {
+                    line: find_line(original, &section, &key),
+                    field: Some(if key.is_empty() {
+                        section.clone()
+                    } else {
+                        format!("{}.{}", section, key)
+                    }),
+                    message: format!("{}, using the default value", e.message()),
+                }),
+            }
+        }
+    }
+    let config = Value::Table(valid).try_into().unwrap_or_default();
+    (config, errors)
+}
+
+/// The line of a key, or of the section itself if `key` is empty. `try_*` keys take precedence, as in `parse_config`.
+fn find_line(content: &str, section: &str, key: &str) -> Option<usize> {
+    let mut current = "";
+    let mut found = None;
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|it| it.strip_suffix(']')) {
+            current = header.trim();
+            if key.is_empty() && current == section {
+                return Some(index + 1);
+            }
+            continue;
+        }
+        let Some((name, _)) = line.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        if current == section && name == format!("try_{}", key) {
+            return Some(index + 1);
+        }
+        if key.is_empty() && current.is_empty() && name == section
+            || !key.is_empty() && current == section && name == key && found.is_none()
+        {
+            found = Some(index + 1);
+        }
     }
-    // Config malformed, giving back the default config so that the user can modify it again
-    let default_config = LocalConfig::default();
-    save_config(&default_config);
-    default_config
+    found
 }
 
 #[cfg(test)]
@@ -210,7 +1577,7 @@ mod tests {
     use std::fs;
     use tempfile::tempdir;
 
-    fn with_config_file(content: &str, f: impl Fn(String)) -> () {
+    fn with_config_file(content: &str, f: impl Fn(String)) {
         let dir = tempdir().unwrap();
         let base_dir = dir.path().to_str().unwrap();
         let path = format!("{}/etc/localdesktop", base_dir);
@@ -242,6 +1609,93 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_migrate_legacy_commands() {
+        with_config_file(
+            r#"
+                [command]
+                check = "pacman -Q xorg-xwayland && pacman -Qg xfce4 && pacman -Q onboard"
+                install = "stdbuf -oL pacman -Syu xorg-xwayland xfce4 onboard --noconfirm --noprogressbar"
+                launch = "XDG_RUNTIME_DIR=/tmp Xwayland -hidpi :1 2>&1 & while [ ! -e /tmp/.X11-unix/X1 ]; do sleep 0.1; done; XDG_SESSION_TYPE=x11 DISPLAY=:1 dbus-launch startxfce4 2>&1"
+            "#,
+            |full_config_path| {
+                let config = parse_config(full_config_path.clone());
+                assert_eq!(config.command.check, default_check());
+                assert_eq!(config.command.install, default_install());
+                assert_eq!(config.command.launch, default_launch());
+                // Written back, so that the file shows what runs
+                let config = parse_config(full_config_path.clone());
+                assert_eq!(config.command.launch, default_launch());
+                assert!(!fs::read_to_string(full_config_path)
+                    .unwrap()
+                    .contains("Xwayland -hidpi"));
+            },
+        );
+
+        // Commands the user wrote are left alone
+        with_config_file(
+            r#"
+                [command]
+                check = "pacman -Q onboard"
+                install = "pacman -S onboard"
+                launch = "Xwayland :1 && startxfce4 & sleep 1"
+            "#,
+            |full_config_path| {
+                let config = parse_config(full_config_path);
+                assert_eq!(config.command.check, "pacman -Q onboard");
+                assert_eq!(config.command.install, "pacman -S onboard");
+                assert_eq!(config.command.launch, "Xwayland :1 && startxfce4 & sleep 1");
+            },
+        );
+    }
+
+    #[test]
+    fn should_keep_the_valid_values_of_invalid_configs() {
+        let content = r##"
+[user]
+username = "alice"
+
+[display]
+swap_interval = "fast"
+try_buffers = -1
+background = "#000000"
+
+[touch]
+long_press_delay = 300
+"##;
+        with_config_file(content, |full_config_path| {
+            let config = parse_config(full_config_path.clone());
+            assert_eq!(config.user.username, "alice");
+            assert_eq!(config.display.background, "#000000");
+            assert_eq!(config.display.swap_interval, 1);
+            assert_eq!(config.display.buffers, 0);
+            assert_eq!(config.touch.long_press_delay, 300);
+            // Only the `try_*` keys were commented out
+            assert!(fs::read_to_string(full_config_path)
+                .unwrap()
+                .contains("swap_interval = \"fast\""));
+        });
+
+        let (_, errors) = validate_config(content, &content.replace("try_buffers", "buffers"));
+        let fields = errors
+            .iter()
+            .map(|it| (it.line, it.field.as_deref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                (Some(7), "display.buffers"),
+                (Some(6), "display.swap_interval")
+            ]
+        );
+
+        let broken = "[user]\nusername = \"alice\"\n[display\n";
+        let (config, errors) = validate_config(broken, broken);
+        assert_eq!(config.user.username, LocalConfig::default().user.username);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, Some(3));
+    }
+
     #[test]
     fn should_handle_configs_with_try() {
         with_config_file(
@@ -265,6 +1719,330 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_launch_the_session_of_the_user() {
+        with_config_file(
+            r#"
+                [user]
+                username = "bob"
+
+                [command]
+                launch = "launch-cmd"
+
+                [sessions]
+                bob = "bob-session"
+            "#,
+            |full_config_path| {
+                let mut config = parse_config(full_config_path);
+                assert_eq!(config.launch_command(), "bob-session");
+                config.user.username = "alice".to_string();
+                assert_eq!(config.launch_command(), "launch-cmd");
+            },
+        );
+    }
+
+    #[test]
+    fn should_list_concurrent_sessions() {
+        with_config_file(
+            r#"
+                [user]
+                username = "bob"
+                concurrent_sessions = ["services", "Bad user", "bob", "services", "alice"]
+
+                [sessions]
+                services = "services-session"
+            "#,
+            |full_config_path| {
+                let config = parse_config(full_config_path);
+                assert_eq!(config.session_users(), ["bob", "services"]);
+                assert_eq!(config.session_command("services"), "services-session");
+                assert_eq!(wayland_socket_name(1), "wayland-1");
+            },
+        );
+    }
+
+    #[test]
+    fn should_validate_mounts_and_env() {
+        with_config_file(
+            r#"
+                [mounts]
+                "/mnt/music/" = "/storage/emulated/0/Music"
+                "/" = "/storage/emulated/0"
+                "relative" = "/data"
+                "/mnt/bad" = "/a:/b"
+
+                [env]
+                MOZ_ENABLE_WAYLAND = "1"
+                _GREETING = "hello world"
+                "1ST" = "x"
+                "BAD-NAME" = "x"
+            "#,
+            |full_config_path| {
+                let config = parse_config(full_config_path);
+                assert_eq!(
+                    config.mount_args(),
+                    ["--bind=/storage/emulated/0/Music:/mnt/music"]
+                );
+                assert_eq!(
+                    config.env_args(),
+                    ["MOZ_ENABLE_WAYLAND=1", "_GREETING=hello world"]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn should_validate_usernames() {
+        assert!(is_valid_username("alice"));
+        assert!(is_valid_username("_build-1"));
+        assert!(!is_valid_username(""));
+        assert!(!is_valid_username("1alice"));
+        assert!(!is_valid_username("Alice"));
+        assert!(!is_valid_username("alice; rm -rf /"));
+        assert!(!is_valid_username(&"a".repeat(33)));
+    }
+
+    #[test]
+    fn should_parse_background() {
+        let background = |value: &str| {
+            DisplayConfig {
+                background: value.to_string(),
+                ..Default::default()
+            }
+            .background()
+        };
+        assert_eq!(
+            background("#ff000080"),
+            Background::Color([1.0, 0.0, 0.0, 128.0 / 255.0])
+        );
+        assert_eq!(
+            background("#00ff00"),
+            Background::Color([0.0, 1.0, 0.0, 1.0])
+        );
+        assert_eq!(
+            background("/usr/share/backgrounds/wallpaper.png"),
+            Background::File("/usr/share/backgrounds/wallpaper.png".to_string())
+        );
+        assert_eq!(
+            background("asset://wallpaper.png"),
+            Background::Asset("wallpaper.png".to_string())
+        );
+        assert_eq!(background("#nothex"), DisplayConfig::default().background());
+    }
+
+    #[test]
+    fn should_build_xwayland_args() {
+        let display = DisplayConfig {
+            xwayland_hidpi: true,
+            xwayland_extensions: vec!["GLX".to_string(), "-XINERAMA".to_string()],
+            xwayland_noreset: true,
+            xwayland_listen: vec!["tcp".to_string()],
+            xwayland_args: " -ac  -dpi 96".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            display.xwayland_args(),
+            [
+                "-hidpi",
+                "+extension",
+                "GLX",
+                "-extension",
+                "XINERAMA",
+                "-noreset",
+                "-listen",
+                "tcp",
+                "-ac",
+                "-dpi",
+                "96"
+            ]
+        );
+        assert!(DisplayConfig::default().xwayland_args().is_empty());
+    }
+
+    #[test]
+    fn should_parse_keyboard_inset() {
+        let inset = |value: &str| {
+            DisplayConfig {
+                keyboard_inset: value.to_string(),
+                ..Default::default()
+            }
+            .keyboard_inset()
+        };
+        assert_eq!(inset("shrink"), KeyboardInset::Shrink);
+        assert_eq!(inset(" overlap "), KeyboardInset::Overlap);
+        assert_eq!(inset("scroll"), KeyboardInset::Pan);
+        assert_eq!(
+            DisplayConfig::default().keyboard_inset(),
+            KeyboardInset::Pan
+        );
+    }
+
+    #[test]
+    fn should_bind_gestures() {
+        let gestures = GesturesConfig {
+            three_finger_tap: "terminal".to_string(),
+            four_finger_tap: " diagnostics ".to_string(),
+            swipe_from_left: "keyboard".to_string(),
+            swipe_from_top: "launcher".to_string(),
+            swipe_from_bottom: "screenshot".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(gestures.tap_action(2), GestureAction::None);
+        assert_eq!(gestures.tap_action(3), GestureAction::ShowTerminal);
+        assert_eq!(gestures.tap_action(4), GestureAction::ShowDiagnostics);
+        assert_eq!(gestures.tap_action(5), GestureAction::None);
+        assert_eq!(
+            gestures.swipe_action(Edge::Left),
+            GestureAction::ToggleSoftKeyboard
+        );
+        assert_eq!(gestures.swipe_action(Edge::Top), GestureAction::None);
+        assert_eq!(
+            gestures.swipe_action(Edge::Bottom),
+            GestureAction::Screenshot
+        );
+        for name in GestureAction::NAMES {
+            assert!(name == "none" || GestureAction::parse(name) != GestureAction::None);
+        }
+        assert_eq!(gestures.back_action(), BackAction::Escape);
+        let back = |action: &str| GesturesConfig {
+            back: action.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(back(" quit").back_action(), BackAction::ConfirmQuit);
+        assert_eq!(back("launcher").back_action(), BackAction::Escape);
+    }
+
+    #[test]
+    fn should_tell_several_layouts() {
+        let keyboard = |layout: &str| KeyboardConfig {
+            layout: layout.to_string(),
+            ..Default::default()
+        };
+        assert!(!keyboard("").has_several_layouts());
+        assert!(!keyboard("de").has_several_layouts());
+        assert!(!keyboard("de,").has_several_layouts());
+        assert!(keyboard("us, ru").has_several_layouts());
+    }
+
+    #[test]
+    fn should_parse_bell_signal() {
+        let signal = |value: &str| {
+            BellConfig {
+                signal: value.to_string(),
+                ..Default::default()
+            }
+            .signal()
+        };
+        assert_eq!(
+            signal("both"),
+            BellSignal {
+                sound: true,
+                vibrate: true
+            }
+        );
+        assert_eq!(
+            signal(" vibrate"),
+            BellSignal {
+                sound: false,
+                vibrate: true
+            }
+        );
+        assert_eq!(
+            signal("off"),
+            BellSignal {
+                sound: false,
+                vibrate: false
+            }
+        );
+        assert_eq!(signal("beep"), signal("sound"));
+        for name in BellSignal::NAMES {
+            assert!(name == "sound" || signal(name) != signal("sound"));
+        }
+    }
+
+    #[test]
+    fn should_pick_gpu_renderer() {
+        let renderer = |name: &str| {
+            GpuConfig {
+                renderer: name.to_string(),
+            }
+            .renderer()
+        };
+        assert_eq!(GpuConfig::default().renderer(), GpuRenderer::Llvmpipe);
+        assert_eq!(renderer(" virgl "), GpuRenderer::Virgl);
+        assert_eq!(renderer("zink"), GpuRenderer::Zink);
+        assert_eq!(renderer("vulkan"), GpuRenderer::Llvmpipe);
+    }
+
+    #[test]
+    fn should_pick_scale() {
+        let scale = |value: f64| DisplayConfig {
+            scale: value,
+            ..Default::default()
+        };
+        assert_eq!(scale(0.0).scale(2.75), 2.75);
+        assert_eq!(scale(-1.0).scale(2.0), 2.0);
+        assert_eq!(scale(1.5).scale(2.75), 1.5);
+        assert_eq!(scale(10.0).scale(1.0), MAX_SCALE);
+        assert_eq!(scale(0.1).scale(1.0), MIN_SCALE);
+        assert_eq!(scale(2.0).xwayland_args(), ["-hidpi"]);
+        assert!(scale(1.0).xwayland_args().is_empty());
+    }
+
+    #[test]
+    fn should_pick_presentation() {
+        let presentation = |value: &str| {
+            DisplayConfig {
+                presentation: value.to_string(),
+                ..Default::default()
+            }
+            .presentation()
+        };
+        assert_eq!(
+            DisplayConfig::default().presentation(),
+            Presentation::NativeWindow
+        );
+        assert_eq!(presentation("native"), Presentation::NativeWindow);
+        assert_eq!(presentation(" surface_view "), Presentation::SurfaceView);
+        assert_eq!(presentation("texture_view"), Presentation::TextureView);
+        assert_eq!(presentation("opengl"), Presentation::NativeWindow);
+
+        // `auto`, the former default, picked from devices none of which was known
+        with_config_file(
+            r#"
+                [display]
+                presentation = "auto"
+            "#,
+            |full_config_path| {
+                let config = parse_config(full_config_path);
+                assert_eq!(config.display.presentation, "native");
+            },
+        );
+    }
+
+    #[test]
+    fn should_bind_shared_storage() {
+        let bind = |shared: bool, mount_point: &str| {
+            StorageConfig {
+                shared,
+                mount_point: mount_point.to_string(),
+            }
+            .bind_argument("/storage/emulated/0")
+        };
+        assert_eq!(
+            StorageConfig::default().bind_argument("/storage/emulated/0"),
+            Some("--bind=/storage/emulated/0:/sdcard".to_string())
+        );
+        assert_eq!(
+            bind(true, "/mnt/phone/"),
+            Some("--bind=/storage/emulated/0:/mnt/phone".to_string())
+        );
+        assert_eq!(bind(false, "/sdcard"), None);
+        assert_eq!(bind(true, "sdcard"), None);
+        assert_eq!(bind(true, "/"), None);
+        assert_eq!(bind(true, "/a:/b"), None);
+    }
+
     #[test]
     fn should_comment_out_try_configs() {
         with_config_file(
@@ -0,0 +1,51 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// Every subsystem publishes its own section (e.g. `clients` for the Wayland client statistics),
+/// and the diagnostics page renders whatever sections are available at the time it is opened.
+static SECTIONS: RwLock<BTreeMap<String, Value>> = RwLock::new(BTreeMap::new());
+
+/// Replace the content of a diagnostics section
+pub fn publish(section: &str, value: Value) {
+    if let Ok(mut sections) = SECTIONS.write() {
+        sections.insert(section.to_string(), value);
+    }
+}
+
+/// All published sections, as a single JSON object keyed by section name
+pub fn snapshot() -> Value {
+    SECTIONS
+        .read()
+        .map(|sections| {
+            Value::Object(
+                sections
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            )
+        })
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_replace_published_section() {
+        publish("test_replace", json!({ "value": 1 }));
+        publish("test_replace", json!({ "value": 2 }));
+        assert_eq!(snapshot()["test_replace"], json!({ "value": 2 }));
+    }
+
+    #[test]
+    fn should_keep_other_sections() {
+        publish("test_first", json!([1, 2]));
+        publish("test_second", json!("hello"));
+        let snapshot = snapshot();
+        assert_eq!(snapshot["test_first"], json!([1, 2]));
+        assert_eq!(snapshot["test_second"], json!("hello"));
+    }
+}
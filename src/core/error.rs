@@ -0,0 +1,90 @@
+use std::io;
+use thiserror::Error;
+
+/// What goes wrong while setting up the Arch FS, running its processes or starting the compositor. These are shown
+/// to the user, on the setup page or the log panel, rather than crashing the app.
+#[derive(Debug, Error)]
+pub enum PolarBearError {
+    #[error("Failed to start `{command}`: {source}")]
+    Spawn {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("`{0}` was never started")]
+    NotSpawned(String),
+
+    /// Proot itself failed, before running the command
+    #[error("PRoot failed: {0}")]
+    Proot(String),
+
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to download the Arch Linux FS: {0}")]
+    Download(#[from] reqwest::Error),
+
+    #[error(
+        "Not enough storage: the Arch Linux FS needs {:.2} GB ({} bytes), but only {:.2} GB ({} bytes) are available. Free up {:.2} GB and restart the app.",
+        gigabytes(*required),
+        required,
+        gigabytes(*available),
+        available,
+        gigabytes(required.saturating_sub(*available))
+    )]
+    NotEnoughStorage { required: u64, available: u64 },
+
+    #[error("Failed to install the Arch Linux FS from {archive}: {reason}")]
+    Archive { archive: String, reason: String },
+
+    #[error("The setup page went away before a user was chosen")]
+    SetupPageGone,
+
+    #[error("Failed to build the compositor: {0}")]
+    Compositor(String),
+}
+
+fn gigabytes(bytes: u64) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0 / 1024.0
+}
+
+/// Tell what was being done when an I/O operation failed
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T, PolarBearError>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context(self, context: impl Into<String>) -> Result<T, PolarBearError> {
+        self.map_err(|source| PolarBearError::Io {
+            context: context.into(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_describe_errors() {
+        let error = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("Failed to write proc/.version")
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Failed to write proc/.version: "));
+
+        let error = PolarBearError::NotEnoughStorage {
+            required: 4 * 1024 * 1024 * 1024,
+            available: 1024 * 1024 * 1024,
+        };
+        assert!(error.to_string().contains("needs 4.00 GB"));
+        assert!(error.to_string().contains("Free up 3.00 GB"));
+    }
+}
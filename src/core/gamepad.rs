@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// evdev event types
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+
+/// The size of a `struct input_event` on 64-bit Linux: the time as two `long`, the type, the code and the value
+pub const INPUT_EVENT_SIZE: usize = 24;
+
+/// The buttons of a gamepad, from the Android key codes to the evdev codes the Linux drivers of the Xbox
+/// controllers report
+const BUTTONS: [(u32, u16); 15] = [
+    (96, 0x130),  // BUTTON_A: BTN_A
+    (97, 0x131),  // BUTTON_B: BTN_B
+    (98, 0x132),  // BUTTON_C: BTN_C
+    (99, 0x133),  // BUTTON_X: BTN_X
+    (100, 0x134), // BUTTON_Y: BTN_Y
+    (101, 0x135), // BUTTON_Z: BTN_Z
+    (102, 0x136), // BUTTON_L1: BTN_TL
+    (103, 0x137), // BUTTON_R1: BTN_TR
+    (104, 0x138), // BUTTON_L2: BTN_TL2
+    (105, 0x139), // BUTTON_R2: BTN_TR2
+    (109, 0x13a), // BUTTON_SELECT: BTN_SELECT
+    (108, 0x13b), // BUTTON_START: BTN_START
+    (110, 0x13c), // BUTTON_MODE: BTN_MODE
+    (106, 0x13d), // BUTTON_THUMBL: BTN_THUMBL
+    (107, 0x13e), // BUTTON_THUMBR: BTN_THUMBR
+];
+
+/// The range of the evdev axes, as the Xbox controllers have them
+const STICK_MAX: f64 = 32767.0;
+const TRIGGER_MAX: f64 = 255.0;
+
+/// How an Android axis of a gamepad is reported to evdev
+#[derive(Debug, Clone, Copy)]
+enum AxisRange {
+    /// From -1 to 1
+    Stick,
+    /// From 0 to 1
+    Trigger,
+    /// -1, 0 or 1
+    Hat,
+}
+
+/// The axes of a gamepad, from the Android axis ids to the evdev codes
+const AXES: [(u32, u16, AxisRange); 8] = [
+    (0, 0x00, AxisRange::Stick),    // AXIS_X: ABS_X
+    (1, 0x01, AxisRange::Stick),    // AXIS_Y: ABS_Y
+    (11, 0x03, AxisRange::Stick),   // AXIS_Z: ABS_RX
+    (14, 0x04, AxisRange::Stick),   // AXIS_RZ: ABS_RY
+    (17, 0x02, AxisRange::Trigger), // AXIS_LTRIGGER: ABS_Z
+    (18, 0x05, AxisRange::Trigger), // AXIS_RTRIGGER: ABS_RZ
+    (15, 0x10, AxisRange::Hat),     // AXIS_HAT_X: ABS_HAT0X
+    (16, 0x11, AxisRange::Hat),     // AXIS_HAT_Y: ABS_HAT0Y
+];
+
+/// The evdev code of a button of a gamepad, by its Android key code
+pub fn button_code(keycode: u32) -> Option<u16> {
+    BUTTONS
+        .iter()
+        .find_map(|(android, evdev)| (*android == keycode).then_some(*evdev))
+}
+
+/// Every button and axis reported, with the `(min, max)` range of the axes, which a virtual device is set up with
+pub fn capabilities() -> (Vec<u16>, Vec<(u16, i32, i32)>) {
+    let buttons = BUTTONS.iter().map(|(_, code)| *code).collect();
+    let axes = AXES
+        .iter()
+        .map(|(_, code, range)| {
+            let (min, max) = match range {
+                AxisRange::Stick => (-STICK_MAX as i32 - 1, STICK_MAX as i32),
+                AxisRange::Trigger => (0, TRIGGER_MAX as i32),
+                AxisRange::Hat => (-1, 1),
+            };
+            (*code, min, max)
+        })
+        .collect();
+    (buttons, axes)
+}
+
+/// A gamepad as a stream of evdev events, i.e. what a Linux driver would report for it
+#[derive(Debug, Default)]
+pub struct Gamepad {
+    /// The last value of each evdev axis, as most motions only move some of them
+    axes: BTreeMap<u16, i32>,
+}
+
+impl Gamepad {
+    /// The events of a button pressed or released
+    pub fn button(&mut self, keycode: u32, pressed: bool, time: Duration) -> Option<Vec<u8>> {
+        let code = button_code(keycode)?;
+        Some(encode(&[(EV_KEY, code, pressed as i32)], time))
+    }
+
+    /// The events of the axes that moved, out of the `(axis, value)` of the Android axes
+    pub fn motion(&mut self, axes: &[(u32, f64)], time: Duration) -> Option<Vec<u8>> {
+        let moved = axes
+            .iter()
+            .filter_map(|(axis, value)| {
+                let (_, code, range) = AXES.iter().find(|(android, ..)| android == axis)?;
+                let value = match range {
+                    AxisRange::Stick => (value.clamp(-1.0, 1.0) * STICK_MAX).round() as i32,
+                    AxisRange::Trigger => (value.clamp(0.0, 1.0) * TRIGGER_MAX).round() as i32,
+                    AxisRange::Hat => value.round().clamp(-1.0, 1.0) as i32,
+                };
+                let previous = self.axes.insert(*code, value);
+                (previous != Some(value)).then_some((EV_ABS, *code, value))
+            })
+            .collect::<Vec<_>>();
+        (!moved.is_empty()).then(|| encode(&moved, time))
+    }
+}
+
+/// The `struct input_event` of each event, followed by the report that ends them
+fn encode(events: &[(u16, u16, i32)], time: Duration) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity((events.len() + 1) * INPUT_EVENT_SIZE);
+    for (kind, code, value) in events.iter().chain([(EV_SYN, SYN_REPORT, 0)].iter()) {
+        bytes.extend_from_slice(&(time.as_secs() as i64).to_ne_bytes());
+        bytes.extend_from_slice(&(time.subsec_micros() as i64).to_ne_bytes());
+        bytes.extend_from_slice(&kind.to_ne_bytes());
+        bytes.extend_from_slice(&code.to_ne_bytes());
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Vec<(u16, u16, i32)> {
+        bytes
+            .chunks(INPUT_EVENT_SIZE)
+            .map(|it| {
+                (
+                    u16::from_ne_bytes([it[16], it[17]]),
+                    u16::from_ne_bytes([it[18], it[19]]),
+                    i32::from_ne_bytes([it[20], it[21], it[22], it[23]]),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_report_the_buttons() {
+        let mut gamepad = Gamepad::default();
+        let time = Duration::from_millis(1500);
+        let bytes = gamepad.button(96, true, time).unwrap();
+        assert_eq!(decode(&bytes), [(EV_KEY, 0x130, 1), (EV_SYN, 0, 0)]);
+        assert_eq!(bytes[..8], 1i64.to_ne_bytes());
+        assert_eq!(bytes[8..16], 500_000i64.to_ne_bytes());
+        // Not a button of a gamepad
+        assert!(gamepad.button(29, true, time).is_none());
+    }
+
+    #[test]
+    fn should_only_report_the_axes_that_moved() {
+        let mut gamepad = Gamepad::default();
+        let time = Duration::ZERO;
+        let bytes = gamepad
+            .motion(&[(0, -1.0), (17, 0.5), (15, 1.0)], time)
+            .unwrap();
+        assert_eq!(
+            decode(&bytes),
+            [
+                (EV_ABS, 0x00, -32767),
+                (EV_ABS, 0x02, 128),
+                (EV_ABS, 0x10, 1),
+                (EV_SYN, 0, 0)
+            ]
+        );
+        let bytes = gamepad.motion(&[(0, -1.0), (1, 0.25)], time).unwrap();
+        assert_eq!(decode(&bytes), [(EV_ABS, 0x01, 8192), (EV_SYN, 0, 0)]);
+        assert!(gamepad.motion(&[(0, -1.0), (42, 1.0)], time).is_none());
+    }
+}
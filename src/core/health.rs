@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How often the health check runs
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How many runs the history keeps
+const MAX_RUNS: usize = 30;
+
+/// The outcome of one health check of the Arch FS
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthRun {
+    /// Seconds since the Unix epoch
+    pub at: u64,
+    /// The step that failed and why, if any
+    pub failure: Option<String>,
+    /// The step that could not run and why, e.g. as its packages are missing, which does not fail the check
+    #[serde(default)]
+    pub skipped: Option<String>,
+}
+
+impl HealthRun {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// The outcome in a few words, for the diagnostics page and the logs
+    pub fn summary(&self) -> String {
+        match (&self.failure, &self.skipped) {
+            (Some(failure), _) => failure.clone(),
+            (None, Some(skipped)) => format!("passed, {}", skipped),
+            (None, None) => "passed".to_string(),
+        }
+    }
+}
+
+/// The past health checks, oldest first
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HealthHistory {
+    pub runs: Vec<HealthRun>,
+}
+
+impl HealthHistory {
+    /// Whether the last check is old enough at `now`, in seconds since the Unix epoch
+    pub fn is_due(&self, now: u64) -> bool {
+        self.runs.last().is_none_or(|run| {
+            now.saturating_sub(run.at) >= HEALTH_CHECK_INTERVAL.as_secs() || now < run.at
+        })
+    }
+
+    pub fn record(&mut self, run: HealthRun) {
+        self.runs.push(run);
+        let excess = self.runs.len().saturating_sub(MAX_RUNS);
+        self.runs.drain(..excess);
+    }
+
+    /// Whether the last check failed while the one before passed, i.e. something since broke the environment.
+    /// A check that keeps failing is only reported the first time.
+    pub fn just_broke(&self) -> bool {
+        match self.runs.as_slice() {
+            [.., previous, last] => previous.passed() && !last.passed(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn run(at: u64, failure: Option<&str>) -> HealthRun {
+        HealthRun {
+            at,
+            failure: failure.map(str::to_string),
+            skipped: None,
+        }
+    }
+
+    #[test]
+    fn should_run_once_a_day() {
+        let mut history = HealthHistory::default();
+        assert!(history.is_due(1000));
+        history.record(run(1000, None));
+        assert!(!history.is_due(1000 + DAY - 1));
+        assert!(history.is_due(1000 + DAY));
+        // The clock went back
+        assert!(history.is_due(999));
+    }
+
+    #[test]
+    fn should_report_the_first_failure_after_a_pass() {
+        let mut history = HealthHistory::default();
+        history.record(run(0, Some("check: exit status 1")));
+        assert!(!history.just_broke());
+        history.record(run(DAY, None));
+        assert!(!history.just_broke());
+        history.record(run(2 * DAY, Some("smoke test: exit status 1")));
+        assert!(history.just_broke());
+        history.record(run(3 * DAY, Some("smoke test: exit status 1")));
+        assert!(!history.just_broke());
+    }
+
+    #[test]
+    fn should_pass_with_a_skipped_step() {
+        let mut history = HealthHistory::default();
+        history.record(run(0, None));
+        history.record(HealthRun {
+            skipped: Some("smoke test skipped".to_string()),
+            ..run(DAY, None)
+        });
+        assert!(!history.just_broke());
+        assert_eq!(history.runs[1].summary(), "passed, smoke test skipped");
+        assert_eq!(history.runs[0].summary(), "passed");
+        // Saved before any step could be skipped
+        let saved: HealthRun =
+            serde_json::from_str(r#"{"at": 0, "failure": "check: exit status 1"}"#).unwrap();
+        assert_eq!(saved.skipped, None);
+        assert_eq!(saved.summary(), "check: exit status 1");
+    }
+
+    #[test]
+    fn should_keep_the_latest_runs() {
+        let mut history = HealthHistory::default();
+        for day in 0..(MAX_RUNS as u64 + 5) {
+            history.record(run(day * DAY, None));
+        }
+        assert_eq!(history.runs.len(), MAX_RUNS);
+        assert_eq!(history.runs[0].at, 5 * DAY);
+    }
+}
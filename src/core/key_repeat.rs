@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+/// How long a key is held before it repeats
+pub const REPEAT_DELAY: Duration = Duration::from_millis(1000);
+
+/// How many times a second a held key repeats
+pub const REPEAT_RATE: u32 = 200;
+
+/// Repeats the last key pressed while it is held, the way a desktop keyboard does. The repeats of Android are
+/// dropped and the clients are told not to repeat, so that every app repeats at the same pace.
+#[derive(Debug)]
+pub struct KeyRepeat {
+    delay: Duration,
+    interval: Duration,
+    /// The key held, and when it repeats next
+    held: Option<(u32, Instant)>,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        Self::new(REPEAT_DELAY, REPEAT_RATE)
+    }
+}
+
+impl KeyRepeat {
+    pub fn new(delay: Duration, rate: u32) -> Self {
+        Self {
+            delay,
+            interval: Duration::from_secs(1) / rate.max(1),
+            held: None,
+        }
+    }
+
+    /// A key that repeats was pressed, which takes the repeat over from the one held before
+    pub fn press(&mut self, key: u32, now: Instant) {
+        self.held = Some((key, now + self.delay));
+    }
+
+    /// A key was released, which stops the repeat if it was the one repeating
+    pub fn release(&mut self, key: u32) {
+        if self.held.is_some_and(|(held, _)| held == key) {
+            self.held = None;
+        }
+    }
+
+    /// Stop repeating, e.g. as the keyboard went to another window and its release will not be seen
+    pub fn cancel(&mut self) {
+        self.held = None;
+    }
+
+    /// The key to press again, if it is time to. A late call repeats once, rather than catching up on the repeats
+    /// missed meanwhile.
+    pub fn due(&mut self, now: Instant) -> Option<u32> {
+        let (key, at) = self.held.as_mut()?;
+        if now < *at {
+            return None;
+        }
+        *at = now.max(*at) + self.interval;
+        Some(*key)
+    }
+
+    /// When the held key repeats next
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.held.map(|(_, at)| at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_repeat_the_held_key() {
+        let start = Instant::now();
+        let mut repeat = KeyRepeat::new(Duration::from_millis(500), 10);
+        repeat.press(30, start);
+        assert_eq!(repeat.due(start + Duration::from_millis(499)), None);
+        assert_eq!(repeat.due(start + Duration::from_millis(500)), Some(30));
+        assert_eq!(
+            repeat.next_repeat(),
+            Some(start + Duration::from_millis(600))
+        );
+        // A late poll does not burst
+        assert_eq!(repeat.due(start + Duration::from_secs(2)), Some(30));
+        assert_eq!(repeat.due(start + Duration::from_secs(2)), None);
+        // Releasing another key keeps the repeat going
+        repeat.release(42);
+        assert!(repeat.next_repeat().is_some());
+        repeat.release(30);
+        assert_eq!(repeat.due(start + Duration::from_secs(3)), None);
+    }
+
+    #[test]
+    fn should_repeat_the_last_key_pressed() {
+        let start = Instant::now();
+        let mut repeat = KeyRepeat::new(Duration::from_millis(500), 10);
+        repeat.press(30, start);
+        repeat.press(31, start + Duration::from_millis(400));
+        assert_eq!(repeat.due(start + Duration::from_millis(500)), None);
+        assert_eq!(repeat.due(start + Duration::from_millis(900)), Some(31));
+        repeat.cancel();
+        assert_eq!(repeat.next_repeat(), None);
+    }
+}
@@ -0,0 +1,110 @@
+/// The smallest desktop the apps are still usable on, as the `(long, short)` sides in logical pixels
+const MIN_DESKTOP: (f64, f64) = (640.0, 360.0);
+
+/// Past this, the text of the apps gets too small to read even on a desktop of the minimum size
+const MIN_COMPACT_SCALE: f64 = 0.5;
+
+/// The density of a screen at the Android scale factor 1, in pixels per inch
+const BASELINE_DPI: f64 = 160.0;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// How a picture is drawn within a target of another size: scaled as large as it fits without being cropped nor
+/// stretched, and centered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    pub scale: f64,
+    /// Where the top left corner of the picture goes on the target
+    pub offset: (f64, f64),
+}
+
+/// Fit a `source` picture within a `target`, both as `(width, height)`, e.g. the desktop mirrored on a display of
+/// another size. An empty source is not scaled.
+pub fn fit(source: (i32, i32), target: (i32, i32)) -> Fit {
+    if source.0 <= 0 || source.1 <= 0 {
+        return Fit {
+            scale: 1.0,
+            offset: (0.0, 0.0),
+        };
+    }
+    let scale = (target.0 as f64 / source.0 as f64).min(target.1 as f64 / source.1 as f64);
+    Fit {
+        scale,
+        offset: (
+            (target.0 as f64 - source.0 as f64 * scale) / 2.0,
+            (target.1 as f64 - source.1 as f64 * scale) / 2.0,
+        ),
+    }
+}
+
+/// The scale the apps render at in a window of `size` physical pixels, as `(width, height)`: `scale`, lowered in a
+/// window too small for the desktop at that scale (split screen, picture-in-picture, a freeform window) so that
+/// the apps keep the room of a small screen, only drawn smaller
+pub fn compact_scale(size: (i32, i32), scale: f64) -> f64 {
+    let (long, short) = (size.0.max(size.1), size.0.min(size.1));
+    if short <= 0 {
+        return scale;
+    }
+    let fitting = (long as f64 / MIN_DESKTOP.0).min(short as f64 / MIN_DESKTOP.1);
+    scale.min(fitting.max(MIN_COMPACT_SCALE))
+}
+
+/// How large a screen of `size` physical pixels, as `(width, height)`, is in millimeters at the Android
+/// `density` (e.g. 2.75 for 440 dpi), for the clients to tell its DPI. An unknown density gives `(0, 0)`.
+pub fn physical_size_mm(size: (i32, i32), density: f64) -> (i32, i32) {
+    if density <= 0.0 {
+        return (0, 0);
+    }
+    let mm = |pixels: i32| (pixels as f64 / (density * BASELINE_DPI) * MM_PER_INCH).round() as i32;
+    (mm(size.0), mm(size.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_fit_within_target() {
+        // A portrait phone on a landscape monitor: pillarboxed
+        assert_eq!(
+            fit((1080, 2400), (1920, 1080)),
+            Fit {
+                scale: 0.45,
+                offset: (717.0, 0.0)
+            }
+        );
+        // A landscape phone on a monitor of the same height: letterboxed
+        assert_eq!(
+            fit((2400, 1080), (1920, 1080)),
+            Fit {
+                scale: 0.8,
+                offset: (0.0, 108.0)
+            }
+        );
+        assert_eq!(fit((1280, 720), (2560, 1440)).scale, 2.0);
+        assert_eq!(fit((0, 0), (1920, 1080)).scale, 1.0);
+    }
+
+    #[test]
+    fn should_compact_small_windows() {
+        // A full screen phone keeps its scale
+        assert_eq!(compact_scale((1080, 2400), 2.75), 2.75);
+        // Half of it in split screen
+        assert_eq!(compact_scale((1080, 1200), 2.75), 1.875);
+        // Picture-in-picture, in either orientation
+        assert_eq!(compact_scale((480, 320), 2.75), 0.75);
+        assert_eq!(compact_scale((320, 480), 2.75), 0.75);
+        // A thumbnail is not shrunk past reading
+        assert_eq!(compact_scale((160, 90), 2.75), MIN_COMPACT_SCALE);
+        // A low scale is never raised
+        assert_eq!(compact_scale((480, 320), 0.5), 0.5);
+        assert_eq!(compact_scale((0, 0), 2.0), 2.0);
+    }
+
+    #[test]
+    fn should_measure_the_screen() {
+        // A 6.4" phone at 440 dpi
+        assert_eq!(physical_size_mm((1080, 2400), 2.75), (62, 139));
+        assert_eq!(physical_size_mm((1920, 1080), 0.0), (0, 0));
+    }
+}
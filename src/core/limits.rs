@@ -0,0 +1,54 @@
+use std::{collections::HashMap, time::Duration};
+
+/// The longest the processes are stopped at once, so that the desktop never looks frozen for long
+const MAX_PAUSE: Duration = Duration::from_secs(2);
+
+/// How long to stop the processes that used `used` of CPU time over `elapsed` on that many cores, so that they
+/// average `max_share` of the CPU once the pause is counted in
+pub fn pause_for(used: Duration, elapsed: Duration, cores: u32, max_share: f64) -> Duration {
+    let allowed = (elapsed * cores).mul_f64(max_share);
+    if allowed.is_zero() || used <= allowed {
+        return Duration::ZERO;
+    }
+    // Stopped, the processes use no CPU: `used / ((elapsed + pause) * cores) = max_share`
+    elapsed
+        .mul_f64(used.as_secs_f64() / allowed.as_secs_f64() - 1.0)
+        .min(MAX_PAUSE)
+}
+
+/// The process to kill once the processes of a session use more than `max` bytes of memory all together: the
+/// largest one, other than the root of the session
+pub fn memory_victim(resident: &HashMap<u32, u64>, root: u32, max: u64) -> Option<u32> {
+    if resident.values().sum::<u64>() <= max {
+        return None;
+    }
+    resident
+        .iter()
+        .filter(|(&pid, _)| pid != root)
+        .max_by_key(|(&pid, &bytes)| (bytes, pid))
+        .map(|(&pid, _)| pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_pause_to_average_the_share() {
+        let second = Duration::from_secs(1);
+        assert_eq!(pause_for(second, second, 4, 0.5), Duration::ZERO);
+        assert_eq!(
+            pause_for(second * 3, second, 4, 0.5),
+            Duration::from_millis(500)
+        );
+        assert_eq!(pause_for(second * 4, second, 4, 0.1), MAX_PAUSE);
+    }
+
+    #[test]
+    fn should_kill_the_largest_process() {
+        let resident = HashMap::from([(10, 100), (11, 500), (12, 300)]);
+        assert_eq!(memory_victim(&resident, 10, 1000), None);
+        assert_eq!(memory_victim(&resident, 10, 800), Some(11));
+        assert_eq!(memory_victim(&resident, 11, 800), Some(12));
+    }
+}
@@ -1,3 +1,19 @@
+use super::{config::MAX_PANEL_LOG_ENTRIES, diagnostics, telemetry};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fs, io,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, RwLock},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 pub fn log_format(title: &str, content: &str) -> String {
     format!(
         "\n*** *** *** [{}] *** *** ***\n{}\n*** *** *** [{}] *** *** ***\n\n",
@@ -14,12 +30,402 @@ where
     E: std::fmt::Debug,
 {
     fn pb_expect(self, msg: &str) -> T {
-        self.expect(&log_format("POLAR BEAR EXPECTATION", msg))
+        self.unwrap_or_else(|e| panic!("{}: {:?}", log_format("POLAR BEAR EXPECTATION", msg), e))
     }
 }
 
 impl<T> PolarBearExpectation<T> for Option<T> {
     fn pb_expect(self, msg: &str) -> T {
-        self.expect(&log_format("POLAR BEAR EXPECTATION", msg))
+        self.unwrap_or_else(|| panic!("{}", log_format("POLAR BEAR EXPECTATION", msg)))
+    }
+}
+
+/// An entry of the log panel, shaped like the logs of `setup-progress.html`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PanelLogEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub message: String,
+    pub is_error: bool,
+}
+
+static PANEL_LOG: Mutex<VecDeque<PanelLogEntry>> = Mutex::new(VecDeque::new());
+
+/// Record a message worth showing to the user in the log panel of the diagnostics page.
+/// Only the latest `MAX_PANEL_LOG_ENTRIES` are kept.
+pub fn panel_log(message: impl Into<String>, is_error: bool) {
+    let message = message.into();
+    if is_error {
+        log::warn!("{}", message);
+    } else {
+        log::info!("{}", message);
+    }
+
+    let Ok(mut entries) = PANEL_LOG.lock() else {
+        return;
+    };
+    entries.push_front(PanelLogEntry {
+        timestamp: now(),
+        message,
+        is_error,
+    });
+    entries.truncate(MAX_PANEL_LOG_ENTRIES);
+
+    diagnostics::publish(
+        "log",
+        serde_json::to_value(&*entries).unwrap_or(serde_json::Value::Null),
+    );
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_millis() as u64)
+}
+
+/// Keep only the latest `keep` entries of the log panel, e.g. to give memory back to Android
+pub fn compact_panel_log(keep: usize) {
+    if let Ok(mut entries) = PANEL_LOG.lock() {
+        entries.truncate(keep);
+        entries.shrink_to_fit();
+        // The published copy would keep every entry otherwise
+        diagnostics::publish(
+            "log",
+            serde_json::to_value(&*entries).unwrap_or(serde_json::Value::Null),
+        );
+    }
+}
+
+/// The log panel entries, newest first
+pub fn panel_log_entries() -> Vec<PanelLogEntry> {
+    PANEL_LOG
+        .lock()
+        .map(|entries| entries.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The verbosity of the logs, per module. Parsed from specs such as `info`, or
+/// `warn,localdesktop::android::backend=debug,smithay=error` to override the level of some modules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLevels {
+    pub default: LevelFilter,
+    /// Module paths with a level of their own
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for LogLevels {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let parse_level = |level: &str| {
+            LevelFilter::from_str(level.trim())
+                .map_err(|_| format!("Invalid log level {:?}", level))
+        };
+        let mut levels = LogLevels {
+            default: LevelFilter::Info,
+            modules: vec![],
+        };
+        for directive in spec.split(',').map(str::trim).filter(|it| !it.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => levels
+                    .modules
+                    .push((module.trim().to_string(), parse_level(level)?)),
+                None => levels.default = parse_level(directive)?,
+            }
+        }
+        Ok(levels)
+    }
+}
+
+impl LogLevels {
+    /// The level of the most specific module containing `target`
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most verbose level of all
+    pub fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+static LOG_LEVELS: RwLock<Option<LogLevels>> = RwLock::new(None);
+
+/// Change the verbosity of the logs at runtime, e.g. `debug` or `info,smithay=trace`
+pub fn set_log_levels(spec: &str) -> Result<(), String> {
+    let levels = spec.parse::<LogLevels>()?;
+    log::set_max_level(levels.max());
+    if let Ok(mut current) = LOG_LEVELS.write() {
+        *current = Some(levels);
+    }
+    log::info!("Log level set to {:?}", spec);
+    diagnostics::publish("logging", json!({ "level": spec }));
+    Ok(())
+}
+
+/// Applies the per-module levels set with `set_log_levels` to the wrapped logger
+pub struct FilteredLogger<L>(pub L);
+
+impl<L: Log> Log for FilteredLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let allowed = match LOG_LEVELS.read() {
+            Ok(levels) => levels
+                .as_ref()
+                .is_none_or(|it| metadata.level() <= it.level_for(metadata.target())),
+            Err(_) => true,
+        };
+        allowed && self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|it| it.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+    match info.location() {
+        Some(location) => format!("{} at {}", payload, location),
+        None => payload,
+    }
+}
+
+/// Record a panic as the last entry of the log panel, then write the whole panel to `crash_log`, for the next
+/// launch to show what led to it. The next launch opens the recovery screen if the panic was `fatal`.
+/// A panic that is not fatal leaves a pending crash in place, for the recovery screen to show the one that aborted.
+fn record_panic(info: &PanicHookInfo, crash_log: &Path, fatal: bool) {
+    let message = panic_message(info);
+    let backtrace = Backtrace::force_capture().to_string();
+    log::error!("Panicked: {}\n{}", message, backtrace);
+    log::logger().flush();
+    if !fatal && pending_crash(crash_log).is_some() {
+        return;
+    }
+
+    // The panic may come from a thread holding the log panel, which must not be waited for
+    let entries = match PANEL_LOG.try_lock() {
+        Ok(mut entries) => {
+            entries.push_front(PanelLogEntry {
+                timestamp: now(),
+                message: format!("PANIC {}", message),
+                is_error: true,
+            });
+            entries.iter().cloned().collect()
+        }
+        Err(_) => vec![],
+    };
+    let crash = json!({
+        "timestamp": now(),
+        "thread": thread::current().name().unwrap_or("unnamed"),
+        "panic": message,
+        "backtrace": backtrace,
+        "log": entries,
+        "fatal": fatal,
+    });
+    if let Err(e) = fs::write(crash_log, crash.to_string()) {
+        log::error!("Failed to write {}: {}", crash_log.display(), e);
+    }
+}
+
+/// Dump the log panel to `crash_log` whenever a thread panics, after the hook set so far (e.g. Sentry's) ran.
+/// A panic of the calling thread, which runs the app, then aborts: it cannot recover anyway, and unwinding
+/// through the Android glue would only lose what happened. The other threads keep unwinding, as their panics
+/// are expected, e.g. a thread of a feature failing.
+/// `on_fatal` runs just before aborting, told whether to start the app again into the recovery screen: it is not
+/// done when the previous crash is still pending, so that a crash at startup does not loop.
+pub fn install_panic_hook(crash_log: PathBuf, on_fatal: impl Fn(bool) + Send + Sync + 'static) {
+    let main_thread = thread::current().id();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let fatal = thread::current().id() == main_thread;
+        let restart = fatal && pending_crash(&crash_log).is_none();
+        record_panic(info, &crash_log, fatal);
+        previous(info);
+        if fatal {
+            on_fatal(restart);
+            std::process::abort();
+        }
+    }));
+}
+
+/// The crash `install_panic_hook` recorded that aborted the app, if the user has not been through the recovery
+/// screen for it yet
+pub fn pending_crash(crash_log: &Path) -> Option<serde_json::Value> {
+    let crash =
+        serde_json::from_str::<serde_json::Value>(&fs::read_to_string(crash_log).ok()?).ok()?;
+    (crash["fatal"] == true && crash["acknowledged"] != true).then_some(crash)
+}
+
+/// Let the next launches start normally after a crash, which stays on the diagnostics page
+pub fn acknowledge_crash(crash_log: &Path) -> io::Result<()> {
+    let Some(mut crash) = pending_crash(crash_log) else {
+        return Ok(());
+    };
+    crash["acknowledged"] = json!(true);
+    fs::write(crash_log, crash.to_string())
+}
+
+/// Publish what `install_panic_hook` recorded before the app last crashed, if it did
+pub fn publish_last_crash(crash_log: &Path) {
+    let Ok(text) = fs::read_to_string(crash_log) else {
+        return;
+    };
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(crash) => {
+            telemetry::record("crash recovery", "app");
+            diagnostics::publish("last panic", crash)
+        }
+        Err(e) => log::warn!("Ignoring {}: {}", crash_log.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Held by the tests filling the log panel, which is shared
+    static PANEL_LOG_TEST: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn should_parse_log_levels() {
+        let levels = "warn, localdesktop::android=debug ,localdesktop::android::backend=trace"
+            .parse::<LogLevels>()
+            .unwrap();
+        assert_eq!(levels.level_for("smithay::wayland"), LevelFilter::Warn);
+        assert_eq!(
+            levels.level_for("localdesktop::android::proot"),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            levels.level_for("localdesktop::android::backend::wayland"),
+            LevelFilter::Trace
+        );
+        assert_eq!(
+            levels.level_for("localdesktop::androidx"),
+            LevelFilter::Warn
+        );
+        assert_eq!(levels.max(), LevelFilter::Trace);
+        assert!("verbose".parse::<LogLevels>().is_err());
+    }
+
+    #[test]
+    fn should_keep_latest_panel_log_entries_only() {
+        let _guard = PANEL_LOG_TEST.lock().unwrap_or_else(|it| it.into_inner());
+        for i in 0..MAX_PANEL_LOG_ENTRIES + 10 {
+            panel_log(format!("entry {}", i), false);
+        }
+        let entries = panel_log_entries();
+        assert_eq!(entries.len(), MAX_PANEL_LOG_ENTRIES);
+        assert_eq!(
+            entries[0].message,
+            format!("entry {}", MAX_PANEL_LOG_ENTRIES + 9)
+        );
+
+        compact_panel_log(3);
+        let entries = panel_log_entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].message,
+            format!("entry {}", MAX_PANEL_LOG_ENTRIES + 9)
+        );
+        // The other tests may log meanwhile, but the published copy no longer holds the entries compacted away
+        let published = diagnostics::snapshot()["log"].clone();
+        let published = published.as_array().unwrap();
+        assert!(published.len() < MAX_PANEL_LOG_ENTRIES);
+        assert!(published
+            .iter()
+            .any(|entry| entry["message"] == format!("entry {}", MAX_PANEL_LOG_ENTRIES + 7)));
+        assert!(!published
+            .iter()
+            .any(|entry| entry["message"] == format!("entry {}", MAX_PANEL_LOG_ENTRIES + 6)));
+    }
+
+    #[test]
+    fn should_record_panics_of_other_threads() {
+        let _guard = PANEL_LOG_TEST.lock().unwrap_or_else(|it| it.into_inner());
+        let crash_log = tempfile::NamedTempFile::new().unwrap();
+        install_panic_hook(crash_log.path().to_path_buf(), |_| {});
+        panel_log("before the panic", false);
+        assert!(thread::spawn(|| panic!("on purpose")).join().is_err());
+        let _ = panic::take_hook();
+
+        let crash: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(crash_log.path()).unwrap()).unwrap();
+        assert!(crash["panic"]
+            .as_str()
+            .unwrap()
+            .starts_with("on purpose at "));
+        assert!(crash["log"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|entry| entry["message"] == "before the panic"));
+        // Only the panics of the thread running the app are fatal
+        assert_eq!(crash["fatal"], false);
+        assert!(pending_crash(crash_log.path()).is_none());
+    }
+
+    #[test]
+    fn should_keep_a_pending_crash_over_later_panics() {
+        let _guard = PANEL_LOG_TEST.lock().unwrap_or_else(|it| it.into_inner());
+        let crash_log = tempfile::NamedTempFile::new().unwrap();
+        fs::write(
+            crash_log.path(),
+            r#"{"panic": "the app crashed", "fatal": true}"#,
+        )
+        .unwrap();
+        install_panic_hook(crash_log.path().to_path_buf(), |_| {});
+        assert!(thread::spawn(|| panic!("a feature failed")).join().is_err());
+        let _ = panic::take_hook();
+
+        assert_eq!(
+            pending_crash(crash_log.path()).unwrap()["panic"],
+            "the app crashed"
+        );
+    }
+
+    #[test]
+    fn should_recover_from_a_crash_once() {
+        let crash_log = tempfile::NamedTempFile::new().unwrap();
+        assert!(pending_crash(crash_log.path()).is_none());
+        fs::write(
+            crash_log.path(),
+            r#"{"panic": "on purpose", "fatal": true}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pending_crash(crash_log.path()).unwrap()["panic"],
+            "on purpose"
+        );
+
+        acknowledge_crash(crash_log.path()).unwrap();
+        assert!(pending_crash(crash_log.path()).is_none());
+        let crash: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(crash_log.path()).unwrap()).unwrap();
+        assert_eq!(crash["panic"], "on purpose");
     }
 }
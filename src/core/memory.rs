@@ -0,0 +1,83 @@
+/// How hard Android asks the app to give memory back, before the low memory killer picks it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemoryPressure {
+    None,
+    Moderate,
+    Low,
+    Critical,
+}
+
+impl MemoryPressure {
+    /// The pressure of a level of `ComponentCallbacks2.onTrimMemory`, as reported by `lastTrimLevel`. The app is
+    /// never killed for running in the foreground, so its own levels weigh as much as those of the background.
+    pub fn from_trim_level(level: i32) -> Self {
+        match level {
+            // TRIM_MEMORY_COMPLETE, TRIM_MEMORY_RUNNING_CRITICAL
+            80.. | 15..=19 => Self::Critical,
+            // TRIM_MEMORY_MODERATE, TRIM_MEMORY_RUNNING_LOW
+            60..=79 | 10..=14 => Self::Low,
+            // TRIM_MEMORY_BACKGROUND, TRIM_MEMORY_RUNNING_MODERATE
+            40..=59 | 5..=9 => Self::Moderate,
+            // TRIM_MEMORY_UI_HIDDEN only tells that the app left the screen
+            _ => Self::None,
+        }
+    }
+
+    /// Whether the render elements kept between frames are dropped, to be imported again on the next frame
+    pub fn drops_render_cache(self) -> bool {
+        self >= Self::Moderate
+    }
+
+    /// How many entries of the log panel are kept, if fewer than usual
+    pub fn panel_log_entries(self) -> Option<usize> {
+        match self {
+            Self::None | Self::Moderate => None,
+            Self::Low => Some(20),
+            Self::Critical => Some(5),
+        }
+    }
+
+    /// Whether pacman is stopped until the pressure is over, with `[memory] pause_pacman`, as a transaction takes
+    /// hundreds of MiB while it checks and extracts the packages
+    pub fn pauses_pacman(self) -> bool {
+        self >= Self::Low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_weigh_trim_levels() {
+        assert_eq!(MemoryPressure::from_trim_level(0), MemoryPressure::None);
+        assert_eq!(MemoryPressure::from_trim_level(20), MemoryPressure::None);
+        assert_eq!(MemoryPressure::from_trim_level(5), MemoryPressure::Moderate);
+        assert_eq!(
+            MemoryPressure::from_trim_level(40),
+            MemoryPressure::Moderate
+        );
+        assert_eq!(MemoryPressure::from_trim_level(10), MemoryPressure::Low);
+        assert_eq!(MemoryPressure::from_trim_level(60), MemoryPressure::Low);
+        assert_eq!(
+            MemoryPressure::from_trim_level(15),
+            MemoryPressure::Critical
+        );
+        assert_eq!(
+            MemoryPressure::from_trim_level(80),
+            MemoryPressure::Critical
+        );
+    }
+
+    #[test]
+    fn should_give_more_back_as_the_pressure_grows() {
+        assert!(!MemoryPressure::None.drops_render_cache());
+        assert!(MemoryPressure::Moderate.drops_render_cache());
+        assert_eq!(MemoryPressure::Moderate.panel_log_entries(), None);
+        assert!(
+            MemoryPressure::Low.panel_log_entries() > MemoryPressure::Critical.panel_log_entries()
+        );
+        assert!(!MemoryPressure::Moderate.pauses_pacman());
+        assert!(MemoryPressure::Critical.pauses_pacman());
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+/// What the `org.freedesktop.Notifications` service of a session writes, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestMessage {
+    /// A notification to show, or to update if its id is shown already
+    Notify(GuestNotification),
+    /// The notification of that id was closed by its app
+    Close(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GuestNotification {
+    pub id: u32,
+    #[serde(default)]
+    pub app_name: String,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub body: String,
+    /// 0 for low, 1 for normal and 2 for critical
+    #[serde(default = "default_urgency")]
+    pub urgency: u8,
+}
+
+fn default_urgency() -> u8 {
+    1
+}
+
+impl GuestNotification {
+    /// The summary, or the name of the app for the notifications without one
+    pub fn title(&self) -> &str {
+        if self.summary.trim().is_empty() {
+            &self.app_name
+        } else {
+            &self.summary
+        }
+    }
+
+    /// The `Notification.PRIORITY_*` of the urgency, which Android only follows before the channels of Android 8
+    pub fn priority(&self) -> i32 {
+        match self.urgency {
+            0 => -1,
+            1 => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Parse a line of the service, ignoring what it cannot make sense of
+pub fn parse_message(line: &str) -> Option<GuestMessage> {
+    match serde_json::from_str(line) {
+        Ok(message) => Some(message),
+        Err(e) => {
+            log::warn!("Invalid notification {:?}: {}", line, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_messages() {
+        let line = r#"{"notify": {"id": 3, "app_name": "notify-send", "summary": "Build", "body": "Done", "urgency": 2}}"#;
+        let Some(GuestMessage::Notify(notification)) = parse_message(line) else {
+            panic!("Expected a notification");
+        };
+        assert_eq!(notification.id, 3);
+        assert_eq!(notification.title(), "Build");
+        assert_eq!(notification.body, "Done");
+        assert_eq!(notification.priority(), 1);
+
+        assert_eq!(
+            parse_message(r#"{"close": 3}"#),
+            Some(GuestMessage::Close(3))
+        );
+        assert_eq!(parse_message("not json"), None);
+    }
+
+    #[test]
+    fn should_fall_back_to_the_app_name() {
+        let Some(GuestMessage::Notify(notification)) =
+            parse_message(r#"{"notify": {"id": 1, "app_name": "Thunderbird", "summary": " "}}"#)
+        else {
+            panic!("Expected a notification");
+        };
+        assert_eq!(notification.title(), "Thunderbird");
+        assert_eq!(notification.priority(), 0);
+    }
+}
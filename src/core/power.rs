@@ -0,0 +1,112 @@
+use super::config::{KeepScreenOn, PowerConfig};
+use std::time::{Duration, Instant};
+
+/// Whether the screen is kept on, from `[power]`, the input of the user and the Ctrl+Alt+Shift+W toggle
+#[derive(Debug, Clone)]
+pub struct ScreenPower {
+    mode: KeepScreenOn,
+    /// How long without input the screen is kept on for, if not forever
+    idle_timeout: Option<Duration>,
+    /// Set by the toggle, over the mode
+    forced: Option<bool>,
+    last_input: Instant,
+}
+
+impl ScreenPower {
+    pub fn new(config: &PowerConfig, now: Instant) -> Self {
+        Self {
+            mode: config.keep_screen_on(),
+            idle_timeout: (config.idle_timeout_minutes > 0)
+                .then(|| Duration::from_secs(u64::from(config.idle_timeout_minutes) * 60)),
+            forced: None,
+            last_input: now,
+        }
+    }
+
+    /// The user touched the screen, typed or moved the mouse
+    pub fn input(&mut self, now: Instant) {
+        self.last_input = now;
+    }
+
+    /// Keep the screen on, or let it turn off, whatever the mode says. Returns what now applies.
+    pub fn toggle(&mut self, now: Instant, fullscreen: bool) -> bool {
+        let keep_on = !self.keep_on(now, fullscreen);
+        self.forced = Some(keep_on);
+        self.last_input = now;
+        keep_on
+    }
+
+    /// Whether the screen is kept on at `now`, while a window covers it whole if `fullscreen`
+    pub fn keep_on(&self, now: Instant, fullscreen: bool) -> bool {
+        if let Some(forced) = self.forced {
+            return forced;
+        }
+        let wanted = match self.mode {
+            KeepScreenOn::Always => true,
+            KeepScreenOn::Fullscreen => fullscreen,
+            KeepScreenOn::Never => false,
+        };
+        wanted
+            && self
+                .idle_timeout
+                .is_none_or(|timeout| now < self.last_input + timeout)
+    }
+
+    /// When the screen stops being kept on for lack of input, if later than `now`, for the event loop to wake up then
+    pub fn idle_deadline(&self, now: Instant) -> Option<Instant> {
+        if self.forced.is_some() || self.mode == KeepScreenOn::Never {
+            return None;
+        }
+        Some(self.last_input + self.idle_timeout?).filter(|deadline| *deadline > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn power(keep_screen_on: &str, idle_timeout_minutes: u32, now: Instant) -> ScreenPower {
+        let config = PowerConfig {
+            keep_screen_on: keep_screen_on.to_string(),
+            idle_timeout_minutes,
+            ..Default::default()
+        };
+        ScreenPower::new(&config, now)
+    }
+
+    #[test]
+    fn should_keep_the_screen_on_per_mode() {
+        let now = Instant::now();
+        assert!(power("always", 0, now).keep_on(now, false));
+        assert!(!power("fullscreen", 0, now).keep_on(now, false));
+        assert!(power("fullscreen", 0, now).keep_on(now, true));
+        assert!(!power("never", 0, now).keep_on(now, true));
+        // Unknown modes keep the former behavior
+        assert!(power("sometimes", 0, now).keep_on(now, false));
+    }
+
+    #[test]
+    fn should_let_the_screen_turn_off_when_idle() {
+        let start = Instant::now();
+        let mut power = power("always", 5, start);
+        let minute = Duration::from_secs(60);
+        assert_eq!(power.idle_deadline(start), Some(start + minute * 5));
+        assert!(power.keep_on(start + minute * 4, false));
+        assert!(!power.keep_on(start + minute * 6, false));
+        assert_eq!(power.idle_deadline(start + minute * 6), None);
+
+        power.input(start + minute * 6);
+        assert!(power.keep_on(start + minute * 7, false));
+    }
+
+    #[test]
+    fn should_toggle_over_the_mode() {
+        let now = Instant::now();
+        let mut power = power("never", 5, now);
+        assert!(power.toggle(now, false));
+        assert!(power.keep_on(now + Duration::from_secs(3600), false));
+        assert_eq!(power.idle_deadline(now), None);
+        assert!(!power.toggle(now, false));
+        assert!(!power.keep_on(now, true));
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+/// How much a process gets scheduled, following the window it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// Part of the focused window
+    Focused,
+    /// Part of another window
+    Background,
+}
+
+/// The tier of the processes of the windows, given the parent of every process: a window is backed by the
+/// process of its client along with every descendant of it, e.g. the compile started in a terminal.
+/// A process under both the focused window and another window is focused, as the user is waiting for it.
+pub fn plan(parents: &HashMap<u32, u32>, focused: &[u32], others: &[u32]) -> HashMap<u32, Tier> {
+    let mut tiers = HashMap::new();
+    for (tier, roots) in [(Tier::Background, others), (Tier::Focused, focused)] {
+        for &pid in parents.keys() {
+            if descends_from(parents, pid, roots) {
+                tiers.insert(pid, tier);
+            }
+        }
+    }
+    tiers
+}
+
+/// The process and every descendant of it
+pub fn process_tree(parents: &HashMap<u32, u32>, root: u32) -> Vec<u32> {
+    let mut tree = parents
+        .keys()
+        .copied()
+        .filter(|&pid| descends_from(parents, pid, &[root]))
+        .collect::<Vec<_>>();
+    if !tree.contains(&root) {
+        tree.push(root);
+    }
+    tree
+}
+
+fn descends_from(parents: &HashMap<u32, u32>, pid: u32, roots: &[u32]) -> bool {
+    let mut current = pid;
+    // The parents are read one process at a time, so a bound keeps a recycled pid from looping forever
+    for _ in 0..parents.len() + 1 {
+        if roots.contains(&current) {
+            return true;
+        }
+        match parents.get(&current) {
+            Some(&parent) if parent != current => current = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_plan_process_trees() {
+        // 1 ─ 10 (session) ─┬─ 20 (terminal) ── 21 (shell) ── 22 (make) ── 23 (cc)
+        //                   ├─ 30 (browser) ── 31 (renderer)
+        //                   └─ 40 (launcher) ── 41 (editor)
+        let parents = HashMap::from([
+            (10, 1),
+            (20, 10),
+            (21, 20),
+            (22, 21),
+            (23, 22),
+            (30, 10),
+            (31, 30),
+            (40, 10),
+            (41, 40),
+        ]);
+        let tiers = plan(&parents, &[30], &[20, 40, 41]);
+        assert_eq!(tiers.get(&31), Some(&Tier::Focused));
+        assert_eq!(tiers.get(&23), Some(&Tier::Background));
+        assert_eq!(tiers.get(&41), Some(&Tier::Background));
+        assert_eq!(tiers.get(&10), None);
+
+        // The editor was launched from another window, and is focused now
+        let tiers = plan(&parents, &[41], &[20, 30, 40]);
+        assert_eq!(tiers.get(&41), Some(&Tier::Focused));
+        assert_eq!(tiers.get(&40), Some(&Tier::Background));
+
+        let mut tree = process_tree(&parents, 20);
+        tree.sort();
+        assert_eq!(tree, [20, 21, 22, 23]);
+    }
+
+    #[test]
+    fn should_not_loop_on_cycles() {
+        let parents = HashMap::from([(2, 3), (3, 2), (4, 4)]);
+        assert!(plan(&parents, &[5], &[]).is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::{Duration, Instant};
+
+/// The size of the video recording a desktop of `desktop` physical pixels: as large as the desktop, but within
+/// `max_side` on its longest side, which the hardware encoders of most phones cannot go beyond. The encoders only
+/// take even sizes.
+pub fn video_size(desktop: (i32, i32), max_side: i32) -> (i32, i32) {
+    let (width, height) = (desktop.0.max(2), desktop.1.max(2));
+    let scale = (max_side as f64 / width.max(height) as f64).min(1.0);
+    let even = |side: i32| ((side as f64 * scale) as i32 / 2 * 2).max(2);
+    (even(width), even(height))
+}
+
+/// Lets the frames through at most at a frame rate
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / frame_rate.max(1),
+            last: None,
+        }
+    }
+
+    /// Whether a frame is due at `now`, which then counts as the last frame
+    pub fn due(&mut self, now: Instant) -> bool {
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// Add the samples of `other` to `samples`, saturating rather than wrapping around on loud sounds
+pub fn mix(samples: &mut [i16], other: &[i16]) {
+    for (sample, other) in samples.iter_mut().zip(other) {
+        *sample = sample.saturating_add(*other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_fit_the_video_within_the_encoder_limits() {
+        assert_eq!(video_size((1080, 2400), 1920), (864, 1920));
+        assert_eq!(video_size((1280, 800), 1920), (1280, 800));
+        // Odd sizes, e.g. on a split screen
+        assert_eq!(video_size((1001, 667), 1920), (1000, 666));
+        assert_eq!(video_size((0, 0), 1920), (2, 2));
+    }
+
+    #[test]
+    fn should_pace_frames() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(30);
+        assert!(pacer.due(start));
+        assert!(!pacer.due(start + Duration::from_millis(10)));
+        assert!(pacer.due(start + Duration::from_millis(34)));
+        assert!(!pacer.due(start + Duration::from_millis(60)));
+        assert!(pacer.due(start + Duration::from_millis(70)));
+    }
+
+    #[test]
+    fn should_mix_without_wrapping_around() {
+        let mut samples = [100, i16::MAX - 10, i16::MIN + 10, 7];
+        mix(&mut samples, &[-50, 100, -100]);
+        assert_eq!(samples, [50, i16::MAX, i16::MIN, 7]);
+    }
+}
@@ -0,0 +1,194 @@
+use pathdiff::diff_paths;
+use std::{
+    fs, io,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Something of the rootfs that does not work under Proot, and how to fix it. The paths are inside the rootfs.
+#[derive(Debug, Clone, Copy)]
+pub enum Rule {
+    /// The absolute symlinks at or below the path are made relative: the libraries loaded in the app itself
+    /// (e.g. libxkbcommon reading the keymaps) resolve them against the Android root, not the rootfs
+    RelativeSymlinks(&'static str),
+    /// The owner can read and write everything at or below the path, as Proot only fakes root: pacman cannot
+    /// update the read-only directories of a package, nor can a reset remove them
+    OwnerWritable(&'static str),
+    /// The exact mode of the path, e.g. the sticky bit of `/tmp`, which some archives lose on extraction
+    Mode { path: &'static str, mode: u32 },
+}
+
+/// The fixes applied on every start, new ones simply go here
+pub const RULES: &[Rule] = &[
+    Rule::RelativeSymlinks("/usr/share/X11"),
+    Rule::RelativeSymlinks("/etc/fonts"),
+    Rule::OwnerWritable("/etc/ca-certificates"),
+    Rule::OwnerWritable("/usr/share/ca-certificates"),
+    Rule::Mode {
+        path: "/tmp",
+        mode: 0o1777,
+    },
+    Rule::Mode {
+        path: "/var/tmp",
+        mode: 0o1777,
+    },
+];
+
+/// Apply the rules to the rootfs at `root`, returning the changes made. A rule failing does not stop the others.
+pub fn sanitize(root: &Path, rules: &[Rule]) -> Vec<String> {
+    let mut changes = Vec::new();
+    for rule in rules {
+        let result = match *rule {
+            Rule::RelativeSymlinks(path) => walk(root, path, &mut |inside, outside| {
+                relative_symlink(inside, outside, &mut changes)
+            }),
+            Rule::OwnerWritable(path) => walk(root, path, &mut |inside, outside| {
+                owner_writable(inside, outside, &mut changes)
+            }),
+            Rule::Mode { path, mode } => set_mode(Path::new(path), &outside(root, path), mode)
+                .map(|change| changes.extend(change)),
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to apply {:?}: {}", rule, e);
+        }
+    }
+    for change in &changes {
+        log::info!("Sanitized the rootfs: {}", change);
+    }
+    changes
+}
+
+fn outside(root: &Path, inside: &str) -> PathBuf {
+    root.join(inside.trim_start_matches('/'))
+}
+
+/// Visit the path and everything below it, without following symlinks. A missing path has nothing to fix.
+fn walk(
+    root: &Path,
+    path: &str,
+    visit: &mut impl FnMut(&Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    fn visit_all(
+        inside: &Path,
+        outside: &Path,
+        visit: &mut impl FnMut(&Path, &Path) -> io::Result<()>,
+    ) -> io::Result<()> {
+        visit(inside, outside)?;
+        if fs::symlink_metadata(outside)?.is_dir() {
+            for entry in fs::read_dir(outside)? {
+                let name = entry?.file_name();
+                visit_all(&inside.join(&name), &outside.join(&name), visit)?;
+            }
+        }
+        Ok(())
+    }
+    let outside = outside(root, path);
+    if fs::symlink_metadata(&outside).is_err() {
+        return Ok(());
+    }
+    visit_all(Path::new(path), &outside, visit)
+}
+
+fn relative_symlink(inside: &Path, outside: &Path, changes: &mut Vec<String>) -> io::Result<()> {
+    if !fs::symlink_metadata(outside)?.file_type().is_symlink() {
+        return Ok(());
+    }
+    let target = fs::read_link(outside)?;
+    if !target.is_absolute() {
+        return Ok(());
+    }
+    let Some(relative) = inside.parent().and_then(|it| diff_paths(&target, it)) else {
+        return Ok(());
+    };
+    fs::remove_file(outside)?;
+    symlink(&relative, outside)?;
+    changes.push(format!(
+        "{} -> {} became {}",
+        inside.display(),
+        target.display(),
+        relative.display()
+    ));
+    Ok(())
+}
+
+fn owner_writable(inside: &Path, outside: &Path, changes: &mut Vec<String>) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(outside)?;
+    let required = if metadata.is_dir() {
+        0o700
+    } else if metadata.is_file() {
+        0o600
+    } else {
+        return Ok(());
+    };
+    let mode = metadata.permissions().mode() & 0o7777;
+    changes.extend(set_mode(inside, outside, mode | required)?);
+    Ok(())
+}
+
+fn set_mode(inside: &Path, outside: &Path, mode: u32) -> io::Result<Option<String>> {
+    let Ok(metadata) = fs::symlink_metadata(outside) else {
+        return Ok(None);
+    };
+    let current = metadata.permissions().mode() & 0o7777;
+    if current == mode {
+        return Ok(None);
+    }
+    fs::set_permissions(outside, fs::Permissions::from_mode(mode))?;
+    Ok(Some(format!(
+        "{} mode {:o} became {:o}",
+        inside.display(),
+        current,
+        mode
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_sanitize_rootfs() {
+        let root = tempdir().unwrap();
+        let root = root.path();
+        fs::create_dir_all(root.join("usr/share/X11")).unwrap();
+        fs::create_dir_all(root.join("usr/share/xkeyboard-config-2")).unwrap();
+        symlink(
+            "/usr/share/xkeyboard-config-2",
+            root.join("usr/share/X11/xkb"),
+        )
+        .unwrap();
+        symlink("../locale", root.join("usr/share/X11/locale")).unwrap();
+        fs::create_dir_all(root.join("etc/ca-certificates/extracted")).unwrap();
+        fs::set_permissions(
+            root.join("etc/ca-certificates/extracted"),
+            fs::Permissions::from_mode(0o555),
+        )
+        .unwrap();
+        fs::create_dir(root.join("tmp")).unwrap();
+        fs::set_permissions(root.join("tmp"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let changes = sanitize(root, RULES);
+        assert_eq!(changes.len(), 3, "{:?}", changes);
+        assert_eq!(
+            fs::read_link(root.join("usr/share/X11/xkb")).unwrap(),
+            Path::new("../xkeyboard-config-2")
+        );
+        assert_eq!(
+            fs::read_link(root.join("usr/share/X11/locale")).unwrap(),
+            Path::new("../locale")
+        );
+        let mode = |path: &str| {
+            fs::symlink_metadata(root.join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        assert_eq!(mode("etc/ca-certificates/extracted"), 0o755);
+        assert_eq!(mode("tmp"), 0o1777);
+
+        // Everything is fixed already
+        assert!(sanitize(root, RULES).is_empty());
+    }
+}
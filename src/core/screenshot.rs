@@ -0,0 +1,81 @@
+/// The name of a capture taken at `secs` since the epoch, the way Android names its own, e.g.
+/// `Screenshot_20261016-142301.png`. The time is in UTC, as the app does not know the time zone of the phone.
+pub fn file_name(prefix: &str, secs: u64, extension: &str) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "{}_{:04}{:02}{:02}-{:02}{:02}{:02}.{}",
+        prefix,
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        extension
+    )
+}
+
+/// The `(year, month, day)` of a number of days since 1970-01-01, in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Put the rows of pixels read from OpenGL, which start at the bottom of the picture, in the order of the image
+/// formats, which start at the top
+pub fn flip_rows(pixels: &mut [u8], stride: usize) {
+    if stride == 0 {
+        return;
+    }
+    let rows = pixels.len() / stride;
+    for row in 0..rows / 2 {
+        let (top, bottom) = pixels.split_at_mut((rows - 1 - row) * stride);
+        top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_name_captures_after_their_date() {
+        assert_eq!(
+            file_name("Screenshot", 0, "png"),
+            "Screenshot_19700101-000000.png"
+        );
+        // 2024-02-29T23:59:59Z, on a leap day
+        assert_eq!(
+            file_name("Screenshot", 1709251199, "png"),
+            "Screenshot_20240229-235959.png"
+        );
+        assert_eq!(
+            file_name("Recording", 1792163011, "mp4"),
+            "Recording_20261016-150331.mp4"
+        );
+    }
+
+    #[test]
+    fn should_flip_rows() {
+        let mut pixels = vec![1, 1, 2, 2, 3, 3];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, [3, 3, 2, 2, 1, 1]);
+        let mut pixels = vec![1, 2, 3, 4];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, [3, 4, 1, 2]);
+    }
+}
@@ -0,0 +1,91 @@
+/// The extensions of the common types shared without a name, for the apps of the desktop to recognize them
+const EXTENSIONS: [(&str, &str); 12] = [
+    ("application/pdf", "pdf"),
+    ("application/zip", "zip"),
+    ("application/epub+zip", "epub"),
+    ("application/vnd.oasis.opendocument.text", "odt"),
+    ("application/vnd.oasis.opendocument.spreadsheet", "ods"),
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("audio/mpeg", "mp3"),
+    ("video/mp4", "mp4"),
+    ("text/plain", "txt"),
+];
+
+/// The name a shared file is saved under: the one the sharing app gave, without what could escape the folder, or
+/// `fallback` with the extension of its type
+pub fn shared_file_name(
+    display_name: Option<&str>,
+    mime_type: Option<&str>,
+    fallback: &str,
+) -> String {
+    let name = display_name
+        .map(|it| {
+            it.chars()
+                .map(|c| if c == '/' || c.is_control() { '_' } else { c })
+                .collect::<String>()
+        })
+        .map(|it| it.trim().trim_start_matches('.').to_string())
+        .filter(|it| !it.is_empty());
+    if let Some(name) = name {
+        return name;
+    }
+    let extension = mime_type.and_then(|mime_type| {
+        EXTENSIONS
+            .iter()
+            .find(|(it, _)| it.eq_ignore_ascii_case(mime_type))
+            .map(|(_, extension)| *extension)
+    });
+    match extension {
+        Some(extension) => format!("{}.{}", fallback, extension),
+        None => fallback.to_string(),
+    }
+}
+
+/// `name`, or `name (2)`, `name (3)`... before the extension, whichever is not `taken` yet
+pub fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (2..)
+        .map(|index| format!("{} ({}){}", stem, index, extension))
+        .find(|it| !taken(it))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_name_shared_files() {
+        assert_eq!(
+            shared_file_name(Some("report.pdf"), Some("application/pdf"), "Shared"),
+            "report.pdf"
+        );
+        assert_eq!(
+            shared_file_name(Some("../../etc/passwd"), None, "Shared"),
+            "_.._etc_passwd"
+        );
+        assert_eq!(
+            shared_file_name(None, Some("application/pdf"), "Shared"),
+            "Shared.pdf"
+        );
+        assert_eq!(shared_file_name(Some(" "), Some("x/y"), "Shared"), "Shared");
+    }
+
+    #[test]
+    fn should_not_overwrite_files() {
+        let taken = ["report.pdf", "report (2).pdf", "notes"];
+        let taken = |name: &str| taken.contains(&name);
+        assert_eq!(unique_name("photo.jpg", taken), "photo.jpg");
+        assert_eq!(unique_name("report.pdf", taken), "report (3).pdf");
+        assert_eq!(unique_name("notes", taken), "notes (2)");
+    }
+}
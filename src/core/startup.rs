@@ -0,0 +1,58 @@
+use crate::core::diagnostics;
+use serde_json::json;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// When the first stage was reached, which the others are timed from
+static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+/// The stages reached so far, in order, with the time since the start
+static STAGES: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// Record that the boot reached `stage`, and publish the breakdown so far on the diagnostics page.
+/// Only the first time counts, so that stages reached on every frame can be marked unconditionally.
+pub fn mark(stage: &str) {
+    let elapsed = STARTED_AT.get_or_init(Instant::now).elapsed();
+    let Ok(mut stages) = STAGES.lock() else {
+        return;
+    };
+    if stages.iter().any(|(name, _)| name == stage) {
+        return;
+    }
+    log::info!("Startup: {} after {} ms", stage, elapsed.as_millis());
+    stages.push((stage.to_string(), elapsed));
+    diagnostics::publish("startup", breakdown(&stages));
+}
+
+fn breakdown(stages: &[(String, Duration)]) -> serde_json::Value {
+    let mut previous = Duration::ZERO;
+    stages
+        .iter()
+        .map(|(stage, elapsed)| {
+            let took = elapsed.saturating_sub(previous);
+            previous = *elapsed;
+            json!({
+                "stage": stage,
+                "since start (ms)": elapsed.as_millis() as u64,
+                "took (ms)": took.as_millis() as u64,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_time_each_stage_from_the_previous_one() {
+        let stages = vec![
+            ("first".to_string(), Duration::from_millis(10)),
+            ("second".to_string(), Duration::from_millis(25)),
+        ];
+        let breakdown = breakdown(&stages);
+        assert_eq!(breakdown[0]["took (ms)"], 10);
+        assert_eq!(breakdown[1]["since start (ms)"], 25);
+        assert_eq!(breakdown[1]["took (ms)"], 15);
+    }
+}
@@ -0,0 +1,35 @@
+/// The tilt of a stylus as the tablet protocol has it, i.e. the angles (in degrees) it leans to the right and
+/// towards the user, from the way Android has it: the angle from the normal of the screen (`tilt`) and the direction
+/// it leans towards, clockwise from up (`orientation`), both in radians.
+pub fn tilt_degrees(tilt: f64, orientation: f64) -> (f64, f64) {
+    let (sin, cos) = tilt.sin_cos();
+    (
+        (orientation.sin() * sin).atan2(cos).to_degrees(),
+        (-orientation.cos() * sin).atan2(cos).to_degrees(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4, PI};
+
+    fn assert_tilt(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn should_tilt_the_stylus_the_way_it_leans() {
+        assert_tilt(tilt_degrees(0.0, 1.0), (0.0, 0.0));
+        // Leaning up is away from the user
+        assert_tilt(tilt_degrees(FRAC_PI_4, 0.0), (0.0, -45.0));
+        assert_tilt(tilt_degrees(FRAC_PI_4, FRAC_PI_2), (45.0, 0.0));
+        assert_tilt(tilt_degrees(FRAC_PI_4, PI), (0.0, 45.0));
+        assert_tilt(tilt_degrees(FRAC_PI_3, -FRAC_PI_2), (-60.0, 0.0));
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+/// The options of the features used since the last report, by feature
+static USAGE: Mutex<BTreeMap<&'static str, BTreeSet<&'static str>>> = Mutex::new(BTreeMap::new());
+
+/// The desktops told apart in the reports, by the program starting them. Any other launch command is `other`.
+const DESKTOPS: [(&str, &str); 20] = [
+    ("startxfce4", "xfce"),
+    ("xfce4-session", "xfce"),
+    ("startplasma", "plasma"),
+    ("plasmashell", "plasma"),
+    ("gnome-session", "gnome"),
+    ("startlxqt", "lxqt"),
+    ("lxqt-session", "lxqt"),
+    ("startlxde", "lxde"),
+    ("lxsession", "lxde"),
+    ("mate-session", "mate"),
+    ("cinnamon-session", "cinnamon"),
+    ("budgie-desktop", "budgie"),
+    ("enlightenment_start", "enlightenment"),
+    ("openbox", "openbox"),
+    ("fluxbox", "fluxbox"),
+    ("icewm", "icewm"),
+    ("i3", "i3"),
+    ("sway", "sway"),
+    ("labwc", "labwc"),
+    ("weston", "weston"),
+];
+
+/// Record that `feature` was used with `option`, e.g. the `touch` input. Both are names fixed in the code, so that
+/// nothing the user typed ends up in the reports.
+pub fn record(feature: &'static str, option: &'static str) {
+    if let Ok(mut usage) = USAGE.lock() {
+        usage.entry(feature).or_default().insert(option);
+    }
+}
+
+/// The features used since the last report, with the options used joined by commas, e.g.
+/// `{"input": "keyboard,touch"}`. The next report starts over.
+pub fn take_report() -> BTreeMap<String, String> {
+    let Ok(mut usage) = USAGE.lock() else {
+        return BTreeMap::new();
+    };
+    report(&std::mem::take(&mut *usage))
+}
+
+fn report(usage: &BTreeMap<&'static str, BTreeSet<&'static str>>) -> BTreeMap<String, String> {
+    usage
+        .iter()
+        .map(|(feature, options)| {
+            let options = options.iter().copied().collect::<Vec<_>>();
+            (feature.to_string(), options.join(","))
+        })
+        .collect()
+}
+
+/// The desktop a launch command starts, as one of a few known names
+pub fn desktop_of(command: &str) -> &'static str {
+    command
+        .split(|c: char| c.is_whitespace() || ";&|()".contains(c))
+        .filter_map(|word| word.rsplit('/').next())
+        .find_map(|program| {
+            DESKTOPS.iter().find_map(|(name, desktop)| {
+                let variant = program
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'));
+                variant.then_some(*desktop)
+            })
+        })
+        .unwrap_or("other")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_tell_the_desktop_apart() {
+        let launch = "while [ ! -e /tmp/.X11-unix/X1 ]; do sleep 0.1; done; DISPLAY=:1 dbus-launch startxfce4 2>&1";
+        assert_eq!(desktop_of(launch), "xfce");
+        assert_eq!(
+            desktop_of("dbus-run-session /usr/bin/startplasma-x11"),
+            "plasma"
+        );
+        assert_eq!(desktop_of("exec i3"), "i3");
+        // Only whole programs count
+        assert_eq!(desktop_of("i3status; swaybg"), "other");
+        assert_eq!(desktop_of("/home/alice/my-session.sh"), "other");
+    }
+
+    #[test]
+    fn should_join_the_options_of_each_feature() {
+        let usage = BTreeMap::from([
+            ("input", BTreeSet::from(["touch", "keyboard"])),
+            ("desktop", BTreeSet::from(["xfce"])),
+        ]);
+        assert_eq!(
+            report(&usage),
+            BTreeMap::from([
+                ("desktop".to_string(), "xfce".to_string()),
+                ("input".to_string(), "keyboard,touch".to_string()),
+            ])
+        );
+    }
+}
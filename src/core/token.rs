@@ -0,0 +1,46 @@
+use std::{fs::File, io::Read};
+
+/// Random bytes in a token, written as twice as many hex digits
+const TOKEN_BYTES: usize = 32;
+
+/// A random secret, as hex digits that URLs carry as they are
+pub fn generate() -> std::io::Result<String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|it| format!("{:02x}", it)).collect())
+}
+
+/// Compare without returning early, so that the response time does not leak how much of the token was right
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Whether the URI a page connected to, e.g. `/?token=…`, carries `token`
+pub fn uri_has_token(uri: &str, token: &str) -> bool {
+    let Some((_, query)) = uri.split_once('?') else {
+        return false;
+    };
+    query
+        .split('&')
+        .filter_map(|it| it.strip_prefix("token="))
+        .any(|it| constant_time_eq(it.as_bytes(), token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_check_the_token_of_a_uri() {
+        let token = generate().unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert_ne!(token, generate().unwrap());
+
+        assert!(uri_has_token(&format!("/?token={}", token), &token));
+        assert!(uri_has_token(&format!("/?a=b&token={}", token), &token));
+        assert!(!uri_has_token("/", &token));
+        assert!(!uri_has_token("/?token=", &token));
+        assert!(!uri_has_token(&format!("/?token={}0", token), &token));
+        assert!(!uri_has_token(&format!("/?tokens={}", token), &token));
+    }
+}
@@ -0,0 +1,111 @@
+/// The part of the desktop the window shows: the desktop is laid out in the physical pixels of the window, which the
+/// input events report, whatever the scale the apps render at. Only the zoom and the pan move it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    /// How much larger the desktop is drawn
+    pub scale: f64,
+    /// The desktop location shown at the top left corner of the window
+    pub origin: (f64, f64),
+    /// How far up the window is moved, in window pixels
+    pub pan: f64,
+}
+
+impl View {
+    /// Where a location of the window is on the desktop
+    pub fn to_desktop(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            x / self.scale + self.origin.0,
+            (y + self.pan) / self.scale + self.origin.1,
+        )
+    }
+
+    /// Where a location of the desktop is on the window
+    pub fn to_window(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            (x - self.origin.0) * self.scale,
+            (y - self.origin.1) * self.scale - self.pan,
+        )
+    }
+}
+
+/// Where an output is on the desktop, as its `location` and `size` in physical pixels. This is not the geometry
+/// the outputs have in the space, which is divided by the scale the apps render at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputArea {
+    pub location: (i32, i32),
+    pub size: (i32, i32),
+}
+
+/// Keep a location of the desktop on the outputs, side by side from left to right: within their width, and the
+/// height of the output it is on. Without outputs, the location is kept as is.
+pub fn clamp_to_outputs((x, y): (f64, f64), outputs: &[OutputArea]) -> (f64, f64) {
+    let Some(left) = outputs.iter().map(|it| it.location.0).min() else {
+        return (x, y);
+    };
+    let right = outputs
+        .iter()
+        .map(|it| it.location.0 + it.size.0)
+        .max()
+        .unwrap_or(left);
+    let x = x.clamp(left as f64, right as f64);
+    let output = outputs
+        .iter()
+        .find(|it| (it.location.0 as f64..=(it.location.0 + it.size.0) as f64).contains(&x));
+    match output {
+        Some(output) => {
+            let top = output.location.1 as f64;
+            (x, y.clamp(top, top + output.size.1 as f64))
+        }
+        None => (x, y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_map_the_window_to_the_desktop() {
+        let unzoomed = View {
+            scale: 1.0,
+            origin: (0.0, 0.0),
+            pan: 0.0,
+        };
+        assert_eq!(unzoomed.to_desktop((540.0, 1200.0)), (540.0, 1200.0));
+
+        // Zoomed in twice on the bottom right quarter, moved up for the soft keyboard
+        let zoomed = View {
+            scale: 2.0,
+            origin: (540.0, 1200.0),
+            pan: 300.0,
+        };
+        assert_eq!(zoomed.to_desktop((0.0, 0.0)), (540.0, 1350.0));
+        assert_eq!(zoomed.to_desktop((1080.0, 2100.0)), (1080.0, 2400.0));
+        for location in [(0.0, 0.0), (123.0, 456.0), (1080.0, 2100.0)] {
+            assert_eq!(zoomed.to_window(zoomed.to_desktop(location)), location);
+        }
+    }
+
+    #[test]
+    fn should_keep_the_locations_on_the_outputs() {
+        // The phone, and a shorter external display on its right
+        let outputs = [
+            OutputArea {
+                location: (0, 0),
+                size: (1080, 2400),
+            },
+            OutputArea {
+                location: (1080, 0),
+                size: (1920, 1080),
+            },
+        ];
+        assert_eq!(clamp_to_outputs((500.0, 2000.0), &outputs), (500.0, 2000.0));
+        assert_eq!(clamp_to_outputs((-10.0, 3000.0), &outputs), (0.0, 2400.0));
+        assert_eq!(
+            clamp_to_outputs((2000.0, 2000.0), &outputs),
+            (2000.0, 1080.0)
+        );
+        assert_eq!(clamp_to_outputs((4000.0, -5.0), &outputs), (3000.0, 0.0));
+        assert_eq!(clamp_to_outputs((7.0, 8.0), &[]), (7.0, 8.0));
+    }
+}
@@ -0,0 +1,126 @@
+use super::config::LinksOnAndroid;
+
+/// The longest link handed over to Android, past which it is more likely garbage than a link
+const MAX_LENGTH: usize = 8192;
+
+/// What the `xdg-open` of the session asks to open, as it posts it to the socket of the app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenRequest {
+    /// The link, as given to `xdg-open`
+    pub target: String,
+    /// Whether an app of the desktop is the default for the scheme of the link
+    pub desktop_handler: bool,
+}
+
+impl OpenRequest {
+    /// Read a request from the head of the HTTP request `curl` sends, returning it along with the length of the link
+    /// in the body that follows
+    pub fn parse_head(head: &str) -> Option<(usize, bool)> {
+        let mut lines = head.lines();
+        if !lines.next()?.starts_with("POST /open ") {
+            return None;
+        }
+        let mut length = None;
+        let mut desktop_handler = false;
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => length = value.parse::<usize>().ok(),
+                "x-desktop-handler" => desktop_handler = !value.is_empty(),
+                _ => {}
+            }
+        }
+        length
+            .filter(|it| *it <= MAX_LENGTH)
+            .map(|it| (it, desktop_handler))
+    }
+
+    /// The link to hand over to Android with `[open] links_on_android`, if any. The files of the Arch FS are left to
+    /// the desktop, as the apps of Android can't read them.
+    pub fn android_uri(&self, mode: LinksOnAndroid) -> Option<&str> {
+        let opens = match mode {
+            LinksOnAndroid::Auto => !self.desktop_handler,
+            LinksOnAndroid::Always => true,
+            LinksOnAndroid::Never => false,
+        };
+        let target = self.target.trim();
+        let (scheme, rest) = target.split_once(':')?;
+        let valid_scheme = scheme
+            .chars()
+            .next()
+            .is_some_and(|it| it.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|it| it.is_ascii_alphanumeric() || "+-.".contains(it));
+        (opens
+            && valid_scheme
+            && !scheme.eq_ignore_ascii_case("file")
+            && !rest.is_empty()
+            && !target
+                .chars()
+                .any(|it| it.is_control() || it.is_whitespace()))
+        .then_some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(target: &str, desktop_handler: bool) -> OpenRequest {
+        OpenRequest {
+            target: target.to_string(),
+            desktop_handler,
+        }
+    }
+
+    #[test]
+    fn should_parse_the_head() {
+        let head = "POST /open HTTP/1.1\r\nHost: localhost\r\nContent-Length: 19\r\nX-Desktop-Handler: firefox.desktop\r\n";
+        assert_eq!(OpenRequest::parse_head(head), Some((19, true)));
+        let head = "POST /open HTTP/1.1\r\nContent-Length: 19\r\nX-Desktop-Handler:\r\n";
+        assert_eq!(OpenRequest::parse_head(head), Some((19, false)));
+        assert_eq!(OpenRequest::parse_head("GET / HTTP/1.1\r\n"), None);
+        assert_eq!(
+            OpenRequest::parse_head("POST /open HTTP/1.1\r\nContent-Length: 99999\r\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn should_only_hand_links_over() {
+        let link = "https://example.com/a?b=c";
+        assert_eq!(
+            request(link, false).android_uri(LinksOnAndroid::Auto),
+            Some(link)
+        );
+        assert_eq!(request(link, true).android_uri(LinksOnAndroid::Auto), None);
+        assert_eq!(
+            request(link, true).android_uri(LinksOnAndroid::Always),
+            Some(link)
+        );
+        assert_eq!(
+            request(link, false).android_uri(LinksOnAndroid::Never),
+            None
+        );
+        assert_eq!(
+            request("mailto:me@example.com", false).android_uri(LinksOnAndroid::Auto),
+            Some("mailto:me@example.com")
+        );
+        for target in [
+            "file:///etc/hosts",
+            "/etc/hosts",
+            "notes.txt",
+            "https://a b",
+            ":x",
+        ] {
+            assert_eq!(
+                request(target, false).android_uri(LinksOnAndroid::Always),
+                None
+            );
+        }
+    }
+}
@@ -1,6 +1,30 @@
 pub mod core {
+    pub mod backoff;
+    pub mod bell;
+    pub mod compat;
     pub mod config;
+    pub mod diagnostics;
+    pub mod error;
+    pub mod gamepad;
+    pub mod health;
+    pub mod key_repeat;
+    pub mod layout;
+    pub mod limits;
     pub mod logging;
+    pub mod memory;
+    pub mod notifications;
+    pub mod power;
+    pub mod priority;
+    pub mod recording;
+    pub mod sanitizer;
+    pub mod screenshot;
+    pub mod share;
+    pub mod startup;
+    pub mod stylus;
+    pub mod telemetry;
+    pub mod token;
+    pub mod transform;
+    pub mod xdg_open;
 }
 
 #[cfg(target_os = "android")]
@@ -8,22 +32,61 @@ pub mod android {
 
     pub mod main;
     pub mod app {
+        pub mod attach;
         pub mod build;
         pub mod run;
+        pub mod saved_state;
     }
     pub mod backend {
+        pub mod recovery;
         pub mod wayland;
         pub mod webview;
     }
     pub mod proot {
+        pub mod audio;
+        pub mod backup;
+        pub mod gamepad;
+        pub mod gpu;
+        pub mod health;
+        pub mod keyring;
         pub mod launch;
+        pub mod limits;
+        pub mod maintenance;
+        pub mod mime;
+        pub mod notifications;
+        pub mod packages;
+        pub mod priority;
         pub mod process;
         pub mod setup;
+        pub mod share;
+        pub mod users;
+        pub mod waypipe;
+        pub mod xdg_open;
     }
     pub mod utils {
+        pub mod aaudio;
         pub mod application_context;
+        pub mod bell;
+        pub mod choreographer;
+        pub mod diagnostics;
         pub mod fullscreen_immersive;
+        pub mod keystore;
+        pub mod media_codec;
+        pub mod media_store;
+        pub mod memory;
         pub mod ndk;
+        pub mod notifications;
+        pub mod power;
+        pub mod quit_dialog;
+        pub mod restart;
+        pub mod session_service;
+        pub mod settings;
+        pub mod share;
+        pub mod storage;
+        pub mod telemetry;
+        pub mod terminal;
+        pub mod text;
+        pub mod toast;
         pub mod webview;
     }
 }